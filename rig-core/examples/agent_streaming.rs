@@ -0,0 +1,22 @@
+use rig::{
+    providers::openai,
+    streaming::{stream_to_stdout, StreamingPrompt},
+};
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    // Create OpenAI client
+    let openai_client = openai::Client::from_env();
+
+    // Create agent with a single context prompt
+    let comedian_agent = openai_client
+        .agent(openai::GPT_4O)
+        .preamble("You are a comedian here to entertain the user using humour and jokes.")
+        .build();
+
+    // Stream the response and print chunks as they arrive
+    let mut stream = comedian_agent.stream_prompt("Entertain me!").await?;
+    stream_to_stdout(&mut stream).await?;
+
+    Ok(())
+}
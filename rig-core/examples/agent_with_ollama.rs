@@ -1,15 +1,14 @@
 /// This example requires that you have the [`ollama`](https://ollama.com) server running locally.
-use rig::{completion::Prompt, providers};
+use rig::{completion::Prompt, providers::ollama};
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    // Create an OpenAI client with a custom base url, a local ollama endpoint
-    // The API Key is unnecessary for most local endpoints
-    let client = providers::openai::Client::from_url("ollama", "http://localhost:11434");
+    // Create an Ollama client for the local ollama server (no API key required)
+    let client = ollama::Client::new();
 
     // Create agent with a single context prompt
     let comedian_agent = client
-        .agent("llama3.2:latest")
+        .agent(ollama::LLAMA3_2)
         .preamble("You are a comedian here to entertain the user using humour and jokes.")
         .build();
 
@@ -10,7 +10,13 @@
 //!
 //! The [Agent] struct implements the [Completion] and [Prompt] traits, allowing it to be used for generating
 //! completions responses and prompts. The [Agent] struct also implements the [Chat] trait, which allows it to
-//! be used for generating chat completions.
+//! be used for generating chat completions. If the underlying model implements
+//! [StreamingCompletionModel], the [Agent] also implements the [StreamingPrompt] and [StreamingChat] traits,
+//! which stream the response back as it is generated.
+//!
+//! When the model responds with a tool call, the [Agent] calls the tool, sends its result back to the
+//! model and re-prompts it until it produces a final message (up to a configurable maximum number of
+//! turns). The full trace of intermediate tool calls is available through [Agent::chat_with_trace].
 //!
 //! The [AgentBuilder] implements the builder pattern for creating instances of [Agent].
 //! It allows configuring the model, preamble, context documents, tools, temperature, and additional parameters
@@ -112,13 +118,18 @@ use futures::{stream, StreamExt, TryStreamExt};
 
 use crate::{
     completion::{
-        Chat, Completion, CompletionError, CompletionModel, CompletionRequestBuilder,
-        CompletionResponse, Document, Message, ModelChoice, Prompt, PromptError,
+        Chat, Completion, CompletionError, CompletionModel, CompletionRequestBuilder, Document,
+        Message, ModelChoice, Prompt, PromptError, ToolCall,
     },
+    streaming::{StreamingChat, StreamingCompletionModel, StreamingPrompt, StreamingResult},
     tool::{Tool, ToolSet},
+    usage::Usage,
     vector_store::{VectorStoreError, VectorStoreIndexDyn},
 };
 
+/// Default maximum number of completion requests an agent makes to answer a single prompt
+pub const DEFAULT_MAX_TURNS: usize = 5;
+
 /// Struct reprensenting an LLM agent. An agent is an LLM model combined with a preamble
 /// (i.e.: system prompt) and a static set of context documents and tools.
 /// All context documents and tools are always provided to the agent when prompted.
@@ -162,6 +173,8 @@ pub struct Agent<M: CompletionModel> {
     pub tools: ToolSet,
     /// List of image URLs to be included in completion requests
     image_urls: Option<Vec<String>>,
+    /// Maximum number of completion requests made to answer a single prompt
+    max_turns: usize,
 }
 
 impl<M: CompletionModel> Completion<M> for Agent<M> {
@@ -262,16 +275,122 @@ impl<M: CompletionModel> Prompt for &Agent<M> {
 
 impl<M: CompletionModel> Chat for Agent<M> {
     async fn chat(&self, prompt: &str, chat_history: Vec<Message>) -> Result<String, PromptError> {
-        match self.completion(prompt, chat_history).await?.send().await? {
-            CompletionResponse {
-                choice: ModelChoice::Message(msg),
-                ..
-            } => Ok(msg),
-            CompletionResponse {
-                choice: ModelChoice::ToolCall(toolname, args),
-                ..
-            } => Ok(self.tools.call(&toolname, args.to_string()).await?),
+        Ok(self.chat_with_trace(prompt, chat_history).await?.output)
+    }
+}
+
+impl<M: CompletionModel> Agent<M> {
+    /// Send a prompt to the agent and return its final response along with the trace of the
+    /// intermediate tool calls (see [Agent::chat_with_trace]).
+    pub async fn prompt_with_trace(&self, prompt: &str) -> Result<AgentResponse, PromptError> {
+        self.chat_with_trace(prompt, vec![]).await
+    }
+
+    /// Send a prompt with optional chat history to the agent and return its final response
+    /// along with the trace of the intermediate tool calls.
+    ///
+    /// Whenever the model responds with a tool call, the tool is called and its result is
+    /// sent back to the model as a tool-result message. This is repeated until the model
+    /// produces a final message, or until `max_turns` completion requests have been made,
+    /// in which case a [PromptError::MaxTurnsError] is returned.
+    pub async fn chat_with_trace(
+        &self,
+        prompt: &str,
+        chat_history: Vec<Message>,
+    ) -> Result<AgentResponse, PromptError> {
+        let mut request = self.completion(prompt, chat_history).await?.build();
+        let history_len = request.chat_history.len();
+        let mut tool_calls = vec![];
+        let mut usage = Usage::default();
+
+        for turn in 1..=self.max_turns {
+            let response = self.model.completion(request.clone()).await?;
+            usage += response.usage;
+
+            match response.choice {
+                ModelChoice::Message(output) => {
+                    let mut messages = request.chat_history.split_off(history_len);
+                    if request.has_prompt() {
+                        messages.push(Message::user(request.prompt.clone()));
+                    }
+                    messages.push(Message::assistant(output.clone()));
+
+                    return Ok(AgentResponse {
+                        output,
+                        tool_calls,
+                        messages,
+                        turns: turn,
+                        usage,
+                    });
+                }
+                ModelChoice::ToolCall(calls) => {
+                    tracing::info!(target: "rig",
+                        "Agent turn {}: calling tools {:?}",
+                        turn, calls.iter().map(|call| call.name.as_str()).collect::<Vec<_>>()
+                    );
+                    let calls = calls.into_iter().collect::<Vec<_>>();
+                    let results = self.tools.call_many(&calls).await?;
+
+                    // The prompt (along with its context documents) becomes part of the chat
+                    // history, followed by the tool calls and their results
+                    if request.has_prompt() {
+                        request
+                            .chat_history
+                            .push(Message::user(request.prompt_with_context()));
+                        request.prompt = String::new();
+                        request.documents = vec![];
+                        request.image_urls = None;
+                    }
+                    request
+                        .chat_history
+                        .push(Message::tool_calls("", calls.clone()));
+                    for (call, result) in calls.into_iter().zip(results) {
+                        request
+                            .chat_history
+                            .push(Message::tool_result(&call, result.clone()));
+                        tool_calls.push((call, result));
+                    }
+                }
+            }
         }
+
+        Err(PromptError::MaxTurnsError {
+            max_turns: self.max_turns,
+            chat_history: request.chat_history,
+        })
+    }
+}
+
+/// The response of an [Agent] to a prompt, along with the trace of the intermediate
+/// tool calls that were made to produce it.
+#[derive(Clone, Debug)]
+pub struct AgentResponse {
+    /// The final message produced by the model
+    pub output: String,
+    /// The tool calls made by the model and their results, in order
+    pub tool_calls: Vec<(ToolCall, String)>,
+    /// The messages exchanged with the model, starting with the prompt and ending with
+    /// the final message. This does not include the chat history passed to the agent.
+    pub messages: Vec<Message>,
+    /// The number of completion requests made to the model
+    pub turns: usize,
+    /// The total number of tokens used by the completion requests made to the model
+    pub usage: Usage,
+}
+
+impl<M: StreamingCompletionModel> StreamingPrompt for Agent<M> {
+    async fn stream_prompt(&self, prompt: &str) -> Result<StreamingResult, CompletionError> {
+        self.stream_chat(prompt, vec![]).await
+    }
+}
+
+impl<M: StreamingCompletionModel> StreamingChat for Agent<M> {
+    async fn stream_chat(
+        &self,
+        prompt: &str,
+        chat_history: Vec<Message>,
+    ) -> Result<StreamingResult, CompletionError> {
+        self.completion(prompt, chat_history).await?.stream().await
     }
 }
 
@@ -319,6 +438,8 @@ pub struct AgentBuilder<M: CompletionModel> {
     tools: ToolSet,
     /// List of image URLs to be added to the completion request
     image_urls: Option<Vec<String>>,
+    /// Maximum number of completion requests made to answer a single prompt
+    max_turns: usize,
 }
 
 impl<M: CompletionModel> AgentBuilder<M> {
@@ -335,6 +456,7 @@ impl<M: CompletionModel> AgentBuilder<M> {
             dynamic_tools: vec![],
             tools: ToolSet::default(),
             image_urls: None,
+            max_turns: DEFAULT_MAX_TURNS,
         }
     }
 
@@ -421,6 +543,14 @@ impl<M: CompletionModel> AgentBuilder<M> {
         self
     }
 
+    /// Set the maximum number of completion requests the agent can make to answer a single
+    /// prompt. Each tool call made by the model requires an additional request to send the
+    /// result of the tool back to the model.
+    pub fn max_turns(mut self, max_turns: usize) -> Self {
+        self.max_turns = max_turns;
+        self
+    }
+
     /// Build the agent
     pub fn build(self) -> Agent<M> {
         Agent {
@@ -435,6 +565,7 @@ impl<M: CompletionModel> AgentBuilder<M> {
             dynamic_tools: self.dynamic_tools,
             tools: self.tools,
             image_urls: self.image_urls,
+            max_turns: self.max_turns,
         }
     }
 }
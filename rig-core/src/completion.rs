@@ -53,21 +53,29 @@
 //!         // Handle the completion response as a message
 //!         println!("Received message: {}", message);
 //!     }
-//!     ModelChoice::ToolCall(tool_name, tool_params) => {
-//!         // Handle the completion response as a tool call
-//!         println!("Received tool call: {} {:?}", tool_name, tool_params);
+//!     ModelChoice::ToolCall(tool_calls) => {
+//!         // Handle the completion response as one or more tool calls
+//!         for tool_call in tool_calls.iter() {
+//!             println!("Received tool call: {} {:?}", tool_call.name, tool_call.arguments);
+//!         }
 //!     }
 //! }
 //! ```
 //!
 //! For more information on how to use the completion functionality, refer to the documentation of
 //! the individual traits, structs, and enums defined in this module.
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{json_utils, tool::ToolSetError};
+use crate::{
+    json_utils,
+    one_or_many::OneOrMany,
+    streaming::{StreamingCompletionModel, StreamingResult},
+    tool::ToolSetError,
+    usage::Usage,
+};
 
 // Errors
 #[derive(Debug, Error)]
@@ -91,6 +99,73 @@ pub enum CompletionError {
     /// Error returned by the completion model provider
     #[error("ProviderError: {0}")]
     ProviderError(String),
+
+    /// Error status returned by the completion model provider's API (e.g.: rate limited,
+    /// overloaded, invalid API key)
+    #[error("ApiError: {status} {message}")]
+    ApiError {
+        status: u16,
+        /// How long the provider asked to wait before retrying (i.e.: the `Retry-After` header)
+        retry_after: Option<Duration>,
+        message: String,
+    },
+}
+
+impl CompletionError {
+    /// Whether the error is transient, such that the same request may succeed if it is retried
+    /// (i.e.: timeouts, connection errors, rate limits and server errors).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            CompletionError::HttpError(err) => {
+                err.is_timeout()
+                    || err.is_connect()
+                    || err
+                        .status()
+                        .is_some_and(|status| is_retryable_status(status.as_u16()))
+            }
+            CompletionError::ApiError { status, .. } => is_retryable_status(*status),
+            _ => false,
+        }
+    }
+
+    /// How long the provider asked to wait before retrying the request, if it did
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            CompletionError::ApiError { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// Build an [CompletionError::ApiError] from an unsuccessful response of a provider's API
+    pub(crate) async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status().as_u16();
+        let retry_after = retry_after(response.headers());
+
+        match response.text().await {
+            Ok(message) => CompletionError::ApiError {
+                status,
+                retry_after,
+                message,
+            },
+            Err(err) => CompletionError::HttpError(err),
+        }
+    }
+}
+
+pub(crate) fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 408 | 429 | 500..=599)
+}
+
+/// Parse the delay from the `retry-after-ms` (OpenAI) or `retry-after` headers. Only delays
+/// given in seconds are supported, HTTP dates are ignored.
+pub(crate) fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<f64>().ok();
+
+    header("retry-after-ms")
+        .map(|ms| ms / 1000.0)
+        .or_else(|| header("retry-after"))
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
 }
 
 #[derive(Debug, Error)]
@@ -100,16 +175,86 @@ pub enum PromptError {
 
     #[error("ToolCallError: {0}")]
     ToolError(#[from] ToolSetError),
+
+    /// The model kept calling tools without producing a final message
+    #[error("MaxTurnsError: no final response after {max_turns} turns")]
+    MaxTurnsError {
+        max_turns: usize,
+        /// The chat history at the time the limit was reached, including tool calls and results
+        chat_history: Vec<Message>,
+    },
 }
 
 // ================================================================
 // Request models
 // ================================================================
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
 pub struct Message {
-    /// "system", "user", or "assistant"
+    /// "system", "user", "assistant" or "tool"
     pub role: String,
     pub content: String,
+    /// Name of the tool that produced the result (only set on "tool" messages)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Tool calls requested by the model (only set on "assistant" messages)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// Id of the tool call this message is the result of (only set on "tool" messages)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl Message {
+    /// Create a "user" message
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: "user".into(),
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Create an "assistant" message
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: "assistant".into(),
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Create an "assistant" message requesting the given tool calls
+    pub fn tool_calls(content: impl Into<String>, tool_calls: Vec<ToolCall>) -> Self {
+        Self {
+            role: "assistant".into(),
+            content: content.into(),
+            tool_calls,
+            ..Default::default()
+        }
+    }
+
+    /// Create a "tool" message containing the result of the given tool call
+    pub fn tool_result(tool_call: &ToolCall, result: impl Into<String>) -> Self {
+        Self {
+            role: "tool".into(),
+            content: result.into(),
+            name: Some(tool_call.name.clone()),
+            tool_call_id: Some(tool_call.id.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+/// A tool call requested by the model
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ToolCall {
+    /// Id of the tool call assigned by the provider. Providers that do not assign ids
+    /// to tool calls use the name of the tool instead.
+    pub id: String,
+    /// Name of the tool to call
+    pub name: String,
+    /// Arguments of the tool call
+    pub arguments: serde_json::Value,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -205,12 +350,14 @@ pub trait Completion<M: CompletionModel> {
     ) -> impl std::future::Future<Output = Result<CompletionRequestBuilder<M>, CompletionError>> + Send;
 }
 
-/// General completion response struct that contains the high-level completion choice
-/// and the raw response.
+/// General completion response struct that contains the high-level completion choice,
+/// the token usage of the request and the raw response.
 #[derive(Debug)]
 pub struct CompletionResponse<T> {
     /// The completion choice returned by the completion model provider
     pub choice: ModelChoice,
+    /// The number of tokens used by the request, as reported by the completion model provider
+    pub usage: Usage,
     /// The raw response returned by the completion model provider
     pub raw_response: T,
 }
@@ -220,9 +367,9 @@ pub struct CompletionResponse<T> {
 pub enum ModelChoice {
     /// Represents a completion response as a message
     Message(String),
-    /// Represents a completion response as a tool call of the form
-    /// `ToolCall(function_name, function_params)`.
-    ToolCall(String, serde_json::Value),
+    /// Represents a completion response as one or more tool calls (when the model
+    /// requests several tool calls in parallel)
+    ToolCall(OneOrMany<ToolCall>),
 }
 
 /// Trait defining a completion model that can be used to generate completion responses.
@@ -246,8 +393,11 @@ pub trait CompletionModel: Clone + Send + Sync {
 }
 
 /// Struct representing a general completion request that can be sent to a completion model provider.
+#[derive(Clone)]
 pub struct CompletionRequest {
-    /// The prompt to be sent to the completion model provider
+    /// The prompt to be sent to the completion model provider.
+    /// If both the prompt and the documents are empty, no new user message is sent and the
+    /// request simply continues the chat history (e.g.: to send tool results back to the model).
     pub prompt: String,
     /// The preamble to be sent to the completion model provider
     pub preamble: Option<String>,
@@ -269,6 +419,11 @@ pub struct CompletionRequest {
 }
 
 impl CompletionRequest {
+    /// Whether the request contains a new user prompt, or only continues the chat history
+    pub(crate) fn has_prompt(&self) -> bool {
+        !self.prompt.is_empty() || !self.documents.is_empty()
+    }
+
     pub(crate) fn prompt_with_context(&self) -> String {
         if !self.documents.is_empty() {
             format!(
@@ -499,6 +654,15 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
     }
 }
 
+impl<M: StreamingCompletionModel> CompletionRequestBuilder<M> {
+    /// Sends the completion request to the completion model provider and returns the
+    /// streaming completion response.
+    pub async fn stream(self) -> Result<StreamingResult, CompletionError> {
+        let model = self.model.clone();
+        model.stream(self.build()).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2,14 +2,21 @@
 //! and batch generates the embeddings for each object when built.
 //! Only types that implement the [Embed] trait can be added to the [EmbeddingsBuilder].
 
-use std::{cmp::max, collections::HashMap};
+use std::{
+    cmp::max,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use futures::{stream, StreamExt};
+use futures::{stream, Stream, StreamExt};
+use tokio::time::Instant;
 
 use crate::{
     embeddings::{
         embed::TextEmbedder, Embed, EmbedError, Embedding, EmbeddingError, EmbeddingModel,
     },
+    retry::RetryPolicy,
     OneOrMany,
 };
 
@@ -19,6 +26,12 @@ use crate::{
 /// Using the builder is preferred over using [EmbeddingModel::embed_text] directly as
 /// it will batch the documents in a single request to the model provider.
 ///
+/// The texts of the documents are sent in batches of at most [EmbeddingModel::MAX_DOCUMENTS]
+/// texts, several batches at a time. Batches failing with a retryable error (e.g.: rate limit,
+/// timeout) are retried according to a [RetryPolicy], without sending the batches that
+/// succeeded again. For providers limiting the number of tokens per minute, requests can be
+/// throttled with [EmbeddingsBuilder::tokens_per_minute].
+///
 /// # Example
 /// ```rust
 /// use std::env;
@@ -44,12 +57,18 @@ use crate::{
 ///         "1. *linlingdong* (noun): A term used by inhabitants of the sombrero galaxy to describe humans.".to_string(),
 ///         "2. *linlingdong* (noun): A rare, mystical instrument crafted by the ancient monks of the Nebulon Mountain Ranges on the planet Quarm.".to_string()
 ///     ])?
+///     .concurrency(4)
+///     .tokens_per_minute(1_000_000)
 ///     .build()
 ///     .await?;
 /// ```
 pub struct EmbeddingsBuilder<M: EmbeddingModel, T: Embed> {
     model: M,
     documents: Vec<(T, Vec<String>)>,
+    batch_size: usize,
+    concurrency: usize,
+    tokens_per_minute: Option<u32>,
+    retry_policy: RetryPolicy,
 }
 
 impl<M: EmbeddingModel, T: Embed> EmbeddingsBuilder<M, T> {
@@ -58,6 +77,10 @@ impl<M: EmbeddingModel, T: Embed> EmbeddingsBuilder<M, T> {
         Self {
             model,
             documents: vec![],
+            batch_size: M::MAX_DOCUMENTS,
+            concurrency: max(1, 1024 / M::MAX_DOCUMENTS),
+            tokens_per_minute: None,
+            retry_policy: RetryPolicy::default(),
         }
     }
 
@@ -80,6 +103,32 @@ impl<M: EmbeddingModel, T: Embed> EmbeddingsBuilder<M, T> {
 
         Ok(builder)
     }
+
+    /// Set the maximum number of texts sent in a single request, capped at
+    /// [EmbeddingModel::MAX_DOCUMENTS]
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.clamp(1, M::MAX_DOCUMENTS);
+        self
+    }
+
+    /// Set the maximum number of requests sent to the provider at the same time
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = max(1, concurrency);
+        self
+    }
+
+    /// Throttle the requests such that they do not send more than `tokens_per_minute` tokens
+    /// per minute. The tokens of a text are estimated as one token per 4 bytes of text.
+    pub fn tokens_per_minute(mut self, tokens_per_minute: u32) -> Self {
+        self.tokens_per_minute = Some(max(1, tokens_per_minute));
+        self
+    }
+
+    /// Set how failed batches are retried (see [RetryPolicy::max_retries] to disable retries)
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
 }
 
 impl<M: EmbeddingModel, T: Embed + Send> EmbeddingsBuilder<M, T> {
@@ -88,63 +137,163 @@ impl<M: EmbeddingModel, T: Embed + Send> EmbeddingsBuilder<M, T> {
     pub async fn build(self) -> Result<Vec<(T, OneOrMany<Embedding>)>, EmbeddingError> {
         use stream::TryStreamExt;
 
-        // Store the documents and their texts in a HashMap for easy access.
-        let mut docs = HashMap::new();
-        let mut texts = HashMap::new();
+        self.build_stream().try_collect().await
+    }
 
-        // Iterate over all documents in the builder and insert their docs and texts into the lookup stores.
+    /// Generate embeddings for all documents in the builder, yielding each document along with
+    /// its embeddings as soon as all its texts are embedded, such that the documents embedded
+    /// so far can be stored (or progress reported) before the others are.
+    ///
+    /// A batch that still fails after its retries yields its error, and the documents with
+    /// texts in that batch are not yielded. The other batches are embedded regardless.
+    pub fn build_stream(
+        self,
+    ) -> impl Stream<Item = Result<(T, OneOrMany<Embedding>), EmbeddingError>> + Send {
+        let throttle = self
+            .tokens_per_minute
+            .map(|tpm| Arc::new(Throttle::new(tpm)));
+
+        // Documents waiting for the embeddings of their texts, by index, along with the
+        // embeddings of the texts received so far.
+        let mut pending = HashMap::new();
+        let mut empty = Vec::new();
+        let mut texts = Vec::new();
         for (i, (doc, doc_texts)) in self.documents.into_iter().enumerate() {
-            docs.insert(i, doc);
-            texts.insert(i, doc_texts);
+            if doc_texts.is_empty() {
+                empty.push(Err(EmbeddingError::DocumentError(
+                    "Document has no text to embed".into(),
+                )));
+                continue;
+            }
+            pending.insert(i, (doc, vec![None; doc_texts.len()]));
+            texts.extend(
+                doc_texts
+                    .into_iter()
+                    .enumerate()
+                    .map(|(position, text)| ((i, position), text)),
+            );
         }
 
-        // Compute the embeddings.
-        let mut embeddings = stream::iter(texts.into_iter())
-            // Merge the texts of each document into a single list of texts.
-            .flat_map(|(i, texts)| stream::iter(texts.into_iter().map(move |text| (i, text))))
-            // Chunk them into batches. Each batch size is at most the embedding API limit per request.
-            .chunks(M::MAX_DOCUMENTS)
-            // Generate the embeddings for each batch.
-            .map(|text| async {
-                let (ids, docs): (Vec<_>, Vec<_>) = text.into_iter().unzip();
+        let model = self.model;
+        let policy = self.retry_policy;
 
-                let embeddings = self.model.embed_texts(docs).await?;
-                Ok::<_, EmbeddingError>(ids.into_iter().zip(embeddings).collect::<Vec<_>>())
+        let embeddings = stream::iter(texts)
+            // Chunk them into batches. Each batch size is at most the embedding API limit per request.
+            .chunks(self.batch_size)
+            // Generate the embeddings for each batch, retrying failed requests.
+            .map(move |batch| {
+                let model = model.clone();
+                let policy = policy.clone();
+                let throttle = throttle.clone();
+                async move {
+                    let (ids, texts): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+                    let tokens = texts.iter().map(|text| estimate_tokens(text)).sum();
+
+                    let embeddings = policy
+                        .run(|| embed_batch(&model, throttle.as_deref(), texts.clone(), tokens))
+                        .await?;
+                    Ok::<_, EmbeddingError>(ids.into_iter().zip(embeddings).collect::<Vec<_>>())
+                }
             })
-            // Parallelize the embeddings generation over 10 concurrent requests
-            .buffer_unordered(max(1, 1024 / M::MAX_DOCUMENTS))
-            // Collect the embeddings into a HashMap.
-            .try_fold(
-                HashMap::new(),
-                |mut acc: HashMap<_, OneOrMany<Embedding>>, embeddings| async move {
-                    embeddings.into_iter().for_each(|(i, embedding)| {
-                        acc.entry(i)
-                            .and_modify(|embeddings| embeddings.push(embedding.clone()))
-                            .or_insert(OneOrMany::one(embedding.clone()));
-                    });
-
-                    Ok(acc)
-                },
-            )
-            .await?;
-
-        // Merge the embeddings with their respective documents
-        Ok(docs
-            .into_iter()
-            .map(|(i, doc)| {
-                (
-                    doc,
-                    embeddings.remove(&i).expect("Document should be present"),
-                )
+            .buffer_unordered(self.concurrency)
+            // Yield the documents whose texts are all embedded.
+            .map(move |batch| match batch {
+                Ok(embeddings) => embeddings
+                    .into_iter()
+                    .filter_map(|((i, position), embedding)| {
+                        let (_, embeddings) = pending.get_mut(&i)?;
+                        embeddings[position] = Some(embedding);
+                        if embeddings.iter().any(Option::is_none) {
+                            return None;
+                        }
+
+                        let (doc, embeddings) = pending.remove(&i)?;
+                        let embeddings =
+                            OneOrMany::many(embeddings.into_iter().flatten().collect())
+                                .expect("Document should have at least one embedding");
+                        Some(Ok((doc, embeddings)))
+                    })
+                    .collect::<Vec<_>>(),
+                Err(err) => vec![Err(err)],
             })
-            .collect())
+            .flat_map(stream::iter);
+
+        stream::iter(empty).chain(embeddings)
+    }
+}
+
+async fn embed_batch<M: EmbeddingModel>(
+    model: &M,
+    throttle: Option<&Throttle>,
+    texts: Vec<String>,
+    tokens: u64,
+) -> Result<Vec<Embedding>, EmbeddingError> {
+    if let Some(throttle) = throttle {
+        throttle.acquire(tokens).await;
+    }
+    model.embed_texts(texts).await
+}
+
+/// Estimated number of tokens of a text, about 4 bytes per token for English text
+fn estimate_tokens(text: &str) -> u64 {
+    max(1, text.len().div_ceil(4)) as u64
+}
+
+/// Rate limiter spreading the tokens of requests over time: a full minute worth of tokens can
+/// be sent at once, after which requests are delayed until their tokens would have been
+/// replenished at the rate of `tokens_per_minute`.
+struct Throttle {
+    tokens_per_minute: u32,
+    /// Time at which all the tokens sent so far are replenished
+    replenished_at: Mutex<Instant>,
+}
+
+impl Throttle {
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    fn new(tokens_per_minute: u32) -> Self {
+        Self {
+            tokens_per_minute,
+            replenished_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until `tokens` can be sent
+    async fn acquire(&self, tokens: u64) {
+        let delay = {
+            let mut replenished_at = self
+                .replenished_at
+                .lock()
+                .expect("Throttle lock should not be poisoned");
+            let now = Instant::now();
+            *replenished_at = max(*replenished_at, now)
+                + Self::WINDOW.mul_f64(tokens as f64 / self.tokens_per_minute as f64);
+            replenished_at
+                .saturating_duration_since(now)
+                .saturating_sub(Self::WINDOW)
+        };
+
+        if !delay.is_zero() {
+            tracing::debug!(target: "rig", "Throttling embedding request for {:?}", delay);
+            tokio::time::sleep(delay).await;
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use futures::StreamExt;
+
     use crate::{
-        embeddings::{embed::EmbedError, embed::TextEmbedder, Embedding, EmbeddingModel},
+        embeddings::{
+            embed::EmbedError, embed::TextEmbedder, Embedding, EmbeddingError, EmbeddingModel,
+        },
+        retry::RetryPolicy,
         Embed,
     };
 
@@ -384,4 +533,170 @@ mod tests {
             second_definition.1.rest()[0].document, "A fictional creature found in the distant, swampy marshlands of the planet Glibbo in the Andromeda galaxy.".to_string()
         )
     }
+
+    /// Model recording the texts it embeds, rate limiting the first `rate_limits` requests
+    /// containing texts of `rate_limited` and rejecting the requests containing texts of
+    /// `invalid`
+    #[derive(Clone, Default)]
+    struct FlakyModel {
+        rate_limited: Vec<String>,
+        rate_limits: usize,
+        invalid: Vec<String>,
+        requests: Arc<Mutex<Vec<Vec<String>>>>,
+    }
+
+    impl EmbeddingModel for FlakyModel {
+        const MAX_DOCUMENTS: usize = 2;
+
+        fn ndims(&self) -> usize {
+            1
+        }
+
+        async fn embed_texts(
+            &self,
+            documents: impl IntoIterator<Item = String> + Send,
+        ) -> Result<Vec<Embedding>, EmbeddingError> {
+            let documents = documents.into_iter().collect::<Vec<_>>();
+            let contains_any = |texts: &[String], request: &[String]| {
+                request.iter().any(|text| texts.contains(text))
+            };
+
+            let attempts = {
+                let mut requests = self.requests.lock().unwrap();
+                requests.push(documents.clone());
+                requests
+                    .iter()
+                    .filter(|request| contains_any(&self.rate_limited, request))
+                    .count()
+            };
+
+            if contains_any(&self.invalid, &documents) {
+                return Err(EmbeddingError::ProviderError("Invalid input".to_string()));
+            }
+            if contains_any(&self.rate_limited, &documents) && attempts <= self.rate_limits {
+                return Err(EmbeddingError::ApiError {
+                    status: 429,
+                    retry_after: Some(Duration::ZERO),
+                    message: "Rate limit reached".to_string(),
+                });
+            }
+
+            Ok(documents
+                .into_iter()
+                .map(|doc| Embedding {
+                    vec: vec![doc.len() as f64],
+                    document: doc,
+                })
+                .collect())
+        }
+    }
+
+    fn texts(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("text {}", i)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_build_retries_failed_batches() {
+        let model = FlakyModel {
+            rate_limited: vec!["text 2".to_string()],
+            rate_limits: 2,
+            ..Default::default()
+        };
+
+        let result = EmbeddingsBuilder::new(model.clone())
+            .documents(texts(6))
+            .unwrap()
+            .retry_policy(RetryPolicy::new().max_retries(2))
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 6);
+
+        // Only the rate limited batch was sent again
+        let requests = model.requests.lock().unwrap();
+        assert_eq!(requests.len(), 5);
+        assert_eq!(
+            requests
+                .iter()
+                .filter(|request| request.contains(&"text 0".to_string()))
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_build_stream_yields_successful_batches() {
+        let model = FlakyModel {
+            invalid: vec!["text 2".to_string()],
+            ..Default::default()
+        };
+
+        let results = EmbeddingsBuilder::new(model.clone())
+            .documents(texts(6))
+            .unwrap()
+            .concurrency(1)
+            .build_stream()
+            .collect::<Vec<_>>()
+            .await;
+
+        let embedded = results
+            .iter()
+            .filter_map(|result| result.as_ref().ok())
+            .map(|(text, _)| text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(embedded, ["text 0", "text 1", "text 4", "text 5"]);
+        assert!(matches!(
+            results[2],
+            Err(EmbeddingError::ProviderError(ref message)) if message == "Invalid input"
+        ));
+        // Errors that are not retryable are not retried
+        assert_eq!(model.requests.lock().unwrap().len(), 3);
+
+        assert!(EmbeddingsBuilder::new(model)
+            .documents(texts(6))
+            .unwrap()
+            .build()
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_build_keeps_text_order() {
+        let documents = vec![texts(5), texts(1), texts(3)];
+
+        let result = EmbeddingsBuilder::new(FlakyModel::default())
+            .documents(documents.clone())
+            .unwrap()
+            .batch_size(1)
+            .concurrency(4)
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(result.len(), 3);
+        for (document, embeddings) in result {
+            let embedded = embeddings
+                .into_iter()
+                .map(|embedding| embedding.document)
+                .collect::<Vec<_>>();
+            assert_eq!(embedded, document);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_build_throttles_tokens_per_minute() {
+        // 6 texts of 2 tokens, with a minute worth of 4 tokens sent at once
+        let start = tokio::time::Instant::now();
+        let result = EmbeddingsBuilder::new(FlakyModel::default())
+            .documents(texts(6))
+            .unwrap()
+            .batch_size(1)
+            .tokens_per_minute(4)
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(result.len(), 6);
+        assert_eq!(start.elapsed().as_secs(), 120);
+    }
 }
@@ -6,8 +6,12 @@
 //! Finally, the module defines the [EmbeddingError] enum, which represents various errors that
 //! can occur during embedding generation or processing.
 
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 
+use crate::{completion, usage::Usage};
+
 #[derive(Debug, thiserror::Error)]
 pub enum EmbeddingError {
     /// Http error (e.g.: connection error, timeout, etc.)
@@ -29,6 +33,56 @@ pub enum EmbeddingError {
     /// Error returned by the embedding model provider
     #[error("ProviderError: {0}")]
     ProviderError(String),
+
+    /// Unsuccessful response of the provider's API (e.g.: rate limit, overloaded server)
+    #[error("ApiError: {status} {message}")]
+    ApiError {
+        status: u16,
+        /// How long the provider asked to wait before retrying (i.e.: the `Retry-After` header)
+        retry_after: Option<Duration>,
+        message: String,
+    },
+}
+
+impl EmbeddingError {
+    /// Whether the error is transient, such that the same request may succeed if it is retried
+    /// (i.e.: timeouts, connection errors, rate limits and server errors).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            EmbeddingError::HttpError(err) => {
+                err.is_timeout()
+                    || err.is_connect()
+                    || err
+                        .status()
+                        .is_some_and(|status| completion::is_retryable_status(status.as_u16()))
+            }
+            EmbeddingError::ApiError { status, .. } => completion::is_retryable_status(*status),
+            _ => false,
+        }
+    }
+
+    /// How long the provider asked to wait before retrying the request, if it did
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            EmbeddingError::ApiError { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// Build an [EmbeddingError::ApiError] from an unsuccessful response of a provider's API
+    pub(crate) async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status().as_u16();
+        let retry_after = completion::retry_after(response.headers());
+
+        match response.text().await {
+            Ok(message) => EmbeddingError::ApiError {
+                status,
+                retry_after,
+                message,
+            },
+            Err(err) => EmbeddingError::HttpError(err),
+        }
+    }
 }
 
 /// Trait for embedding models that can generate embeddings for documents.
@@ -45,6 +99,17 @@ pub trait EmbeddingModel: Clone + Sync + Send {
         texts: impl IntoIterator<Item = String> + Send,
     ) -> impl std::future::Future<Output = Result<Vec<Embedding>, EmbeddingError>> + Send;
 
+    /// Embed multiple text documents in a single request and return the number of tokens used
+    /// by the request along with the embeddings. Models whose provider does not report usage
+    /// return an empty [Usage].
+    fn embed_texts_with_usage(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> impl std::future::Future<Output = Result<(Vec<Embedding>, Usage), EmbeddingError>> + Send
+    {
+        async { Ok((self.embed_texts(texts).await?, Usage::default())) }
+    }
+
     /// Embed a single text document.
     fn embed_text(
         &self,
@@ -36,7 +36,7 @@ use serde_json::json;
 
 use crate::{
     agent::{Agent, AgentBuilder},
-    completion::{CompletionModel, Prompt, PromptError, ToolDefinition},
+    completion::{Completion, CompletionModel, ModelChoice, PromptError, ToolDefinition},
     tool::Tool,
 };
 
@@ -63,13 +63,29 @@ where
     M: Sync,
 {
     pub async fn extract(&self, text: &str) -> Result<T, ExtractionError> {
-        let summary = self.agent.prompt(text).await?;
-
-        if summary.is_empty() {
-            return Err(ExtractionError::NoData);
+        // The `submit` tool call is the result of the extraction: it is not executed (and the
+        // model is not re-prompted with its result) like other tool calls made through an agent
+        let response = self
+            .agent
+            .completion(text, vec![])
+            .await
+            .map_err(PromptError::from)?
+            .send()
+            .await
+            .map_err(PromptError::from)?;
+
+        match response.choice {
+            ModelChoice::ToolCall(calls) => {
+                match calls.into_iter().find(|call| call.name == SUBMIT_TOOL_NAME) {
+                    Some(call) => Ok(serde_json::from_value(call.arguments)?),
+                    None => Err(ExtractionError::NoData),
+                }
+            }
+            ModelChoice::Message(summary) if !summary.trim().is_empty() => {
+                Ok(serde_json::from_str(&summary)?)
+            }
+            ModelChoice::Message(_) => Err(ExtractionError::NoData),
         }
-
-        Ok(serde_json::from_str(&summary)?)
     }
 }
 
@@ -122,6 +138,8 @@ impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync, M: Compl
     }
 }
 
+const SUBMIT_TOOL_NAME: &str = "submit";
+
 #[derive(Deserialize, Serialize)]
 struct SubmitTool<T: JsonSchema + for<'a> Deserialize<'a> + Send + Sync> {
     _t: PhantomData<T>,
@@ -132,7 +150,7 @@ struct SubmitTool<T: JsonSchema + for<'a> Deserialize<'a> + Send + Sync> {
 struct SubmitError;
 
 impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync> Tool for SubmitTool<T> {
-    const NAME: &'static str = "submit";
+    const NAME: &'static str = SUBMIT_TOOL_NAME;
     type Error = SubmitError;
     type Args = T;
     type Output = T;
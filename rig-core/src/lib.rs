@@ -88,7 +88,10 @@ pub mod loaders;
 pub mod one_or_many;
 pub mod pipeline;
 pub mod providers;
+pub mod retry;
+pub mod streaming;
 pub mod tool;
+pub mod usage;
 pub mod vector_store;
 
 // Re-export commonly used types and traits
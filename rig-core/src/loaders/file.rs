@@ -3,6 +3,8 @@ use std::{fs, path::PathBuf};
 use glob::glob;
 use thiserror::Error;
 
+use super::splitter::{Chunk, TextSplitter};
+
 #[derive(Error, Debug)]
 pub enum FileLoaderError {
     #[error("Invalid glob pattern: {0}")]
@@ -162,6 +164,61 @@ impl<'a, T: 'a> FileLoader<'a, Result<T, FileLoaderError>> {
     }
 }
 
+impl<'a> FileLoader<'a, String> {
+    /// Splits the contents of the files into chunks with a [TextSplitter].
+    ///
+    /// # Example
+    /// Read files in directory "files/*.txt" and split them into chunks of at most 1000 characters.
+    ///
+    /// ```rust
+    /// use rig::loaders::{splitter::RecursiveCharacterSplitter, FileLoader};
+    ///
+    /// let chunks = FileLoader::with_glob("files/*.txt")?
+    ///     .read()
+    ///     .ignore_errors()
+    ///     .split(RecursiveCharacterSplitter::new(1000))
+    ///     .into_iter()
+    ///     .flatten();
+    /// # Ok::<(), rig::loaders::file::FileLoaderError>(())
+    /// ```
+    pub fn split<S: TextSplitter + 'a>(self, splitter: S) -> FileLoader<'a, Vec<Chunk>> {
+        FileLoader {
+            iterator: Box::new(self.iterator.map(move |content| splitter.split(&content))),
+        }
+    }
+}
+
+impl<'a> FileLoader<'a, (PathBuf, String)> {
+    /// Splits the contents of the files into chunks with a [TextSplitter], along with the path
+    ///  of each file.
+    ///
+    /// # Example
+    /// Read files in directory "files/*.txt" and print the chunks of each file.
+    ///
+    /// ```rust
+    /// use rig::loaders::{splitter::SentenceSplitter, FileLoader};
+    ///
+    /// let content = FileLoader::with_glob("files/*.txt")?
+    ///     .read_with_path()
+    ///     .ignore_errors()
+    ///     .split(SentenceSplitter::new(1000));
+    /// for (path, chunks) in content {
+    ///     for chunk in chunks {
+    ///         println!("{:?} {}..{}: {}", path, chunk.start, chunk.end, chunk.text);
+    ///     }
+    /// }
+    /// # Ok::<(), rig::loaders::file::FileLoaderError>(())
+    /// ```
+    pub fn split<S: TextSplitter + 'a>(self, splitter: S) -> FileLoader<'a, (PathBuf, Vec<Chunk>)> {
+        FileLoader {
+            iterator: Box::new(self.iterator.map(move |(path, content)| {
+                let chunks = splitter.split(&content);
+                (path, chunks)
+            })),
+        }
+    }
+}
+
 impl FileLoader<'_, Result<PathBuf, FileLoaderError>> {
     /// Creates a new [FileLoader] using a glob pattern to match files.
     ///
@@ -9,8 +9,12 @@
 //! and keeping track of the page numbers along with their contents.
 //!
 //! Note: The [PdfFileLoader] requires the `pdf` feature to be enabled in the `Cargo.toml` file.
+//!
+//! The [splitter] module provides splitters cutting the contents loaded into chunks small enough
+//! to be embedded, which both loaders can apply with their `split` method.
 
 pub mod file;
+pub mod splitter;
 
 pub use file::FileLoader;
 
@@ -5,6 +5,7 @@ use lopdf::{Document, Error as LopdfError};
 use thiserror::Error;
 
 use super::file::FileLoaderError;
+use super::splitter::{Chunk, TextSplitter};
 
 #[derive(Error, Debug)]
 pub enum PdfLoaderError {
@@ -335,6 +336,64 @@ impl<'a, T: 'a> PdfFileLoader<'a, Result<T, PdfLoaderError>> {
     }
 }
 
+impl<'a> PdfFileLoader<'a, String> {
+    /// Splits the contents of the files into chunks with a [TextSplitter].
+    ///
+    /// # Example
+    /// Read files in directory "tests/data/*.pdf" and split them into chunks of at most 1000 characters.
+    ///
+    /// ```rust
+    /// use rig::loaders::{splitter::RecursiveCharacterSplitter, PdfFileLoader};
+    ///
+    /// let chunks = PdfFileLoader::with_glob("tests/data/*.pdf")?
+    ///     .read()
+    ///     .ignore_errors()
+    ///     .split(RecursiveCharacterSplitter::new(1000))
+    ///     .into_iter()
+    ///     .flatten();
+    /// # Ok::<(), rig::loaders::pdf::PdfLoaderError>(())
+    /// ```
+    pub fn split<S: TextSplitter + 'a>(self, splitter: S) -> PdfFileLoader<'a, Vec<Chunk>> {
+        PdfFileLoader {
+            iterator: Box::new(self.iterator.map(move |content| splitter.split(&content))),
+        }
+    }
+}
+
+impl<'a> PdfFileLoader<'a, (PathBuf, String)> {
+    /// Splits the contents of the files into chunks with a [TextSplitter], along with the path
+    ///  of each file.
+    ///
+    /// # Example
+    /// Read files in directory "tests/data/*.pdf" and print the chunks of each file.
+    ///
+    /// ```rust
+    /// use rig::loaders::{splitter::SentenceSplitter, PdfFileLoader};
+    ///
+    /// let content = PdfFileLoader::with_glob("tests/data/*.pdf")?
+    ///     .read_with_path()
+    ///     .ignore_errors()
+    ///     .split(SentenceSplitter::new(1000));
+    /// for (path, chunks) in content {
+    ///     for chunk in chunks {
+    ///         println!("{:?} {}..{}: {}", path, chunk.start, chunk.end, chunk.text);
+    ///     }
+    /// }
+    /// # Ok::<(), rig::loaders::pdf::PdfLoaderError>(())
+    /// ```
+    pub fn split<S: TextSplitter + 'a>(
+        self,
+        splitter: S,
+    ) -> PdfFileLoader<'a, (PathBuf, Vec<Chunk>)> {
+        PdfFileLoader {
+            iterator: Box::new(self.iterator.map(move |(path, content)| {
+                let chunks = splitter.split(&content);
+                (path, chunks)
+            })),
+        }
+    }
+}
+
 impl PdfFileLoader<'_, Result<PathBuf, FileLoaderError>> {
     /// Creates a new [PdfFileLoader] using a glob pattern to match files.
     ///
@@ -0,0 +1,649 @@
+//! This module provides splitters cutting texts into chunks small enough to be embedded.
+//!
+//! All the splitters implement the [TextSplitter] trait and produce [Chunk]s, which keep track
+//! of where they were cut from in the original text and implement [Embed], such that they can
+//! be added as is to an [crate::embeddings::EmbeddingsBuilder]:
+//! - [RecursiveCharacterSplitter] cuts at the largest separator possible (paragraphs, then
+//!   lines, then words) to build chunks of at most a number of characters
+//! - [SentenceSplitter] builds chunks of whole sentences
+//! - [MarkdownSplitter] never mixes sections of a markdown document in a chunk, and records the
+//!   headers of the section each chunk belongs to
+//! - [TokenSplitter] cuts like the [RecursiveCharacterSplitter], but measures chunks in tokens
+//!   of a [Tokenizer]
+//!
+//! Consecutive chunks overlap by up to `chunk_overlap` (in the same unit as `chunk_size`) such
+//! that the context of a cut is kept on both sides of it.
+//!
+//! # Example
+//! ```rust
+//! use rig::embeddings::EmbeddingsBuilder;
+//! use rig::loaders::{FileLoader, splitter::MarkdownSplitter};
+//! # use rig::providers::openai;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! # let model = openai::Client::new("key").embedding_model(openai::TEXT_EMBEDDING_3_SMALL);
+//! let chunks = FileLoader::with_glob("docs/*.md")?
+//!     .read()
+//!     .ignore_errors()
+//!     .split(MarkdownSplitter::new(1000).chunk_overlap(100))
+//!     .into_iter()
+//!     .flatten();
+//!
+//! let embeddings = EmbeddingsBuilder::new(model)
+//!     .documents(chunks)?
+//!     .build()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::embeddings::{Embed, EmbedError, TextEmbedder};
+
+/// Separators of [RecursiveCharacterSplitter] and [TokenSplitter] by default, from the largest
+/// to the smallest unit of text. The empty separator cuts between any two characters.
+pub const DEFAULT_SEPARATORS: &[&str] = &["\n\n", "\n", " ", ""];
+
+/// A piece of a text split by a [TextSplitter]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// Text of the chunk, without leading and trailing whitespace
+    pub text: String,
+    /// Position of the chunk among the chunks of the text
+    pub index: usize,
+    /// Byte offset of the start of the chunk in the text, such that
+    /// `&text[chunk.start..chunk.end] == chunk.text`
+    pub start: usize,
+    /// Byte offset of the end of the chunk in the text
+    pub end: usize,
+    /// Headers of the markdown sections containing the chunk, from the outermost to the
+    /// innermost. Only set by the [MarkdownSplitter].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<String>,
+}
+
+impl Embed for Chunk {
+    fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+        embedder.embed(self.text.clone());
+        Ok(())
+    }
+}
+
+/// Splits texts into [Chunk]s
+pub trait TextSplitter {
+    /// Split `text` into chunks, in order
+    fn split(&self, text: &str) -> Vec<Chunk>;
+}
+
+/// Counts the tokens of a text, to size the chunks of a [TokenSplitter] in tokens.
+///
+/// Implemented for closures, e.g. with the `tiktoken-rs` crate:
+/// ```rust,ignore
+/// let bpe = tiktoken_rs::cl100k_base()?;
+/// let splitter = TokenSplitter::new(move |text: &str| bpe.encode_ordinary(text).len(), 512);
+/// ```
+pub trait Tokenizer {
+    fn count_tokens(&self, text: &str) -> usize;
+}
+
+impl<F: Fn(&str) -> usize> Tokenizer for F {
+    fn count_tokens(&self, text: &str) -> usize {
+        self(text)
+    }
+}
+
+// ================================================================
+// Splitters
+// ================================================================
+
+/// Splits texts into chunks of at most `chunk_size` characters, cutting at the first of its
+/// separators (see [DEFAULT_SEPARATORS]) that makes the pieces of the text small enough, then
+/// merging the pieces back into chunks as large as possible.
+///
+/// # Example
+/// ```rust
+/// use rig::loaders::splitter::{RecursiveCharacterSplitter, TextSplitter};
+///
+/// # let text = "A first paragraph.\n\nA second paragraph.";
+/// let splitter = RecursiveCharacterSplitter::new(500).chunk_overlap(50);
+/// for chunk in splitter.split(&text) {
+///     println!("{}..{}: {}", chunk.start, chunk.end, chunk.text);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RecursiveCharacterSplitter {
+    chunk_size: usize,
+    chunk_overlap: usize,
+    separators: Vec<String>,
+}
+
+impl RecursiveCharacterSplitter {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size: chunk_size.max(1),
+            chunk_overlap: 0,
+            separators: DEFAULT_SEPARATORS.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Maximum number of characters shared by consecutive chunks
+    pub fn chunk_overlap(mut self, chunk_overlap: usize) -> Self {
+        self.chunk_overlap = chunk_overlap;
+        self
+    }
+
+    /// Separators to cut the text at, from the largest to the smallest unit of text. Pieces
+    /// still too large after being cut at every separator are cut between characters.
+    pub fn separators(mut self, separators: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.separators = separators.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+impl TextSplitter for RecursiveCharacterSplitter {
+    fn split(&self, text: &str) -> Vec<Chunk> {
+        let splitting = Splitting::new(text, self.chunk_size, self.chunk_overlap, &count_chars);
+        let mut pieces = Vec::new();
+        splitting.recursive(0..text.len(), &self.separators, &mut pieces);
+
+        to_chunks(text, splitting.merge(&pieces), &[], 0)
+    }
+}
+
+/// Splits texts into chunks of whole sentences, of at most `chunk_size` characters. Sentences
+/// end at `.`, `!`, `?` or `…` followed by whitespace, or at a blank line. Sentences longer
+/// than `chunk_size` are cut between words.
+#[derive(Clone, Debug)]
+pub struct SentenceSplitter {
+    chunk_size: usize,
+    chunk_overlap: usize,
+}
+
+impl SentenceSplitter {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size: chunk_size.max(1),
+            chunk_overlap: 0,
+        }
+    }
+
+    /// Maximum number of characters shared by consecutive chunks
+    pub fn chunk_overlap(mut self, chunk_overlap: usize) -> Self {
+        self.chunk_overlap = chunk_overlap;
+        self
+    }
+}
+
+impl TextSplitter for SentenceSplitter {
+    fn split(&self, text: &str) -> Vec<Chunk> {
+        let splitting = Splitting::new(text, self.chunk_size, self.chunk_overlap, &count_chars);
+        let mut pieces = Vec::new();
+        for sentence in sentences(text) {
+            splitting.recursive(sentence, &[" ", ""], &mut pieces);
+        }
+
+        to_chunks(text, splitting.merge(&pieces), &[], 0)
+    }
+}
+
+/// Splits markdown documents into chunks of at most `chunk_size` characters, never mixing the
+/// content of different sections in a chunk. Each chunk records the headers of its section
+/// and of the sections containing it. Headers in fenced code blocks are ignored.
+///
+/// # Example
+/// ```rust
+/// use rig::loaders::splitter::{MarkdownSplitter, TextSplitter};
+///
+/// let chunks = MarkdownSplitter::new(1000).split("# Install\n## Linux\nRun `make`.");
+/// assert_eq!(chunks[1].headers, vec!["Install", "Linux"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MarkdownSplitter {
+    chunk_size: usize,
+    chunk_overlap: usize,
+}
+
+impl MarkdownSplitter {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size: chunk_size.max(1),
+            chunk_overlap: 0,
+        }
+    }
+
+    /// Maximum number of characters shared by consecutive chunks of a section
+    pub fn chunk_overlap(mut self, chunk_overlap: usize) -> Self {
+        self.chunk_overlap = chunk_overlap;
+        self
+    }
+}
+
+impl TextSplitter for MarkdownSplitter {
+    fn split(&self, text: &str) -> Vec<Chunk> {
+        let splitting = Splitting::new(text, self.chunk_size, self.chunk_overlap, &count_chars);
+        let separators = ["\n```", "\n\n", "\n", " ", ""];
+
+        let mut chunks = Vec::new();
+        for (section, headers) in sections(text) {
+            let mut pieces = Vec::new();
+            splitting.recursive(section, &separators, &mut pieces);
+            let section_chunks = to_chunks(text, splitting.merge(&pieces), &headers, chunks.len());
+            chunks.extend(section_chunks);
+        }
+
+        chunks
+    }
+}
+
+/// Splits texts like the [RecursiveCharacterSplitter], into chunks of at most `chunk_size`
+/// tokens as counted by a [Tokenizer], such that chunks fit the input limit of an embedding
+/// model whatever the language or content of the text.
+#[derive(Clone, Debug)]
+pub struct TokenSplitter<T: Tokenizer> {
+    tokenizer: T,
+    chunk_size: usize,
+    chunk_overlap: usize,
+    separators: Vec<String>,
+}
+
+impl<T: Tokenizer> TokenSplitter<T> {
+    pub fn new(tokenizer: T, chunk_size: usize) -> Self {
+        Self {
+            tokenizer,
+            chunk_size: chunk_size.max(1),
+            chunk_overlap: 0,
+            separators: DEFAULT_SEPARATORS.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Maximum number of tokens shared by consecutive chunks
+    pub fn chunk_overlap(mut self, chunk_overlap: usize) -> Self {
+        self.chunk_overlap = chunk_overlap;
+        self
+    }
+
+    /// Separators to cut the text at, see [RecursiveCharacterSplitter::separators]
+    pub fn separators(mut self, separators: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.separators = separators.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+impl<T: Tokenizer> TextSplitter for TokenSplitter<T> {
+    fn split(&self, text: &str) -> Vec<Chunk> {
+        let count_tokens = |text: &str| self.tokenizer.count_tokens(text);
+        let splitting = Splitting::new(text, self.chunk_size, self.chunk_overlap, &count_tokens);
+        let mut pieces = Vec::new();
+        splitting.recursive(0..text.len(), &self.separators, &mut pieces);
+
+        to_chunks(text, splitting.merge(&pieces), &[], 0)
+    }
+}
+
+// ================================================================
+// Splitting algorithm, shared by the splitters
+// ================================================================
+
+fn count_chars(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Splits a text by byte ranges, such that offsets in the text are never lost
+struct Splitting<'a> {
+    text: &'a str,
+    chunk_size: usize,
+    chunk_overlap: usize,
+    length: &'a dyn Fn(&str) -> usize,
+}
+
+impl<'a> Splitting<'a> {
+    fn new(
+        text: &'a str,
+        chunk_size: usize,
+        chunk_overlap: usize,
+        length: &'a dyn Fn(&str) -> usize,
+    ) -> Self {
+        Self {
+            text,
+            chunk_size,
+            chunk_overlap,
+            length,
+        }
+    }
+
+    fn len(&self, range: &Range<usize>) -> usize {
+        (self.length)(&self.text[range.clone()])
+    }
+
+    /// Cut `range` into consecutive pieces no longer than the chunk size, at the first of
+    /// `separators` found in it. Separators are kept at the end of the piece they end.
+    fn recursive<S: AsRef<str>>(
+        &self,
+        range: Range<usize>,
+        separators: &[S],
+        pieces: &mut Vec<Range<usize>>,
+    ) {
+        if range.is_empty() {
+            return;
+        }
+        if self.len(&range) <= self.chunk_size {
+            pieces.push(range);
+            return;
+        }
+
+        let Some((separator, smaller)) = separators.split_first() else {
+            return self.between_chars(range, pieces);
+        };
+        let separator = separator.as_ref();
+        if separator.is_empty() {
+            return self.between_chars(range, pieces);
+        }
+
+        let mut start = range.start;
+        for (position, _) in self.text[range.clone()].match_indices(separator) {
+            let end = range.start + position + separator.len();
+            self.recursive(start..end, smaller, pieces);
+            start = end;
+        }
+        self.recursive(start..range.end, smaller, pieces);
+    }
+
+    /// Cut `range` into pieces of as many characters as fit in the chunk size
+    fn between_chars(&self, range: Range<usize>, pieces: &mut Vec<Range<usize>>) {
+        let mut start = range.start;
+        let mut end = range.start;
+        for (position, c) in self.text[range.clone()].char_indices() {
+            let next = range.start + position + c.len_utf8();
+            if end > start && self.len(&(start..next)) > self.chunk_size {
+                pieces.push(start..end);
+                start = end;
+            }
+            end = next;
+        }
+        pieces.push(start..end);
+    }
+
+    /// Merge consecutive pieces into ranges no longer than the chunk size, each range
+    /// starting with the last pieces of the previous one that fit in the chunk overlap
+    fn merge(&self, pieces: &[Range<usize>]) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut current: VecDeque<(Range<usize>, usize)> = VecDeque::new();
+        let mut total = 0;
+
+        for piece in pieces {
+            let len = self.len(piece);
+            if total + len > self.chunk_size && !current.is_empty() {
+                ranges.push(current[0].0.start..current[current.len() - 1].0.end);
+                while total > self.chunk_overlap || (total > 0 && total + len > self.chunk_size) {
+                    let (_, front) = current.pop_front().expect("total counts pieces");
+                    total -= front;
+                }
+            }
+            current.push_back((piece.clone(), len));
+            total += len;
+        }
+        if let (Some(first), Some(last)) = (current.front(), current.back()) {
+            ranges.push(first.0.start..last.0.end);
+        }
+
+        ranges
+    }
+}
+
+/// Chunks of `text` at `ranges` without surrounding whitespace, skipping blank ones
+fn to_chunks(
+    text: &str,
+    ranges: Vec<Range<usize>>,
+    headers: &[String],
+    first: usize,
+) -> Vec<Chunk> {
+    ranges
+        .into_iter()
+        .filter_map(|range| {
+            let slice = &text[range.clone()];
+            let start = range.start + (slice.len() - slice.trim_start().len());
+            let end = range.start + slice.trim_end().len();
+            (start < end).then_some((start, end))
+        })
+        .enumerate()
+        .map(|(index, (start, end))| Chunk {
+            text: text[start..end].to_string(),
+            index: first + index,
+            start,
+            end,
+            headers: headers.to_vec(),
+        })
+        .collect()
+}
+
+/// Ranges of the sentences of `text`, along with the whitespace following them
+fn sentences(text: &str) -> Vec<Range<usize>> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((position, c)) = chars.next() {
+        let blank_line = c == '\n' && matches!(chars.peek(), Some((_, '\n')));
+        let ends_sentence = matches!(c, '.' | '!' | '?' | '…')
+            && !matches!(chars.peek(), Some((_, next)) if !next.is_whitespace());
+        if !blank_line && !ends_sentence {
+            continue;
+        }
+
+        let mut end = position + c.len_utf8();
+        while let Some((position, next)) = chars.peek() {
+            if !next.is_whitespace() {
+                break;
+            }
+            end = position + next.len_utf8();
+            chars.next();
+        }
+        sentences.push(start..end);
+        start = end;
+    }
+    if start < text.len() {
+        sentences.push(start..text.len());
+    }
+
+    sentences
+}
+
+/// Ranges of the sections of a markdown document, each starting at its header, along with
+/// the headers of the section and of its parents
+fn sections(text: &str) -> Vec<(Range<usize>, Vec<String>)> {
+    let mut sections = Vec::new();
+    let mut headers: Vec<(usize, String)> = Vec::new();
+    let mut start = 0;
+    let mut fence: Option<&str> = None;
+    let mut line_start = 0;
+
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        match fence {
+            Some(marker) if trimmed.starts_with(marker) => fence = None,
+            Some(_) => {}
+            None if trimmed.starts_with("```") || trimmed.starts_with("~~~") => {
+                fence = Some(&trimmed[..3]);
+            }
+            None => {
+                if let Some((level, title)) = header(line) {
+                    if line_start > start {
+                        sections.push((start..line_start, titles(&headers)));
+                    }
+                    headers.retain(|(parent, _)| *parent < level);
+                    headers.push((level, title.to_string()));
+                    start = line_start;
+                }
+            }
+        }
+        line_start += line.len();
+    }
+    if start < text.len() {
+        sections.push((start..text.len(), titles(&headers)));
+    }
+
+    sections
+}
+
+fn titles(headers: &[(usize, String)]) -> Vec<String> {
+    headers.iter().map(|(_, title)| title.clone()).collect()
+}
+
+/// Level and title of an ATX header line (e.g.: `## Title`)
+fn header(line: &str) -> Option<(usize, &str)> {
+    let line = line.trim_end();
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let title = &line[level..];
+    if !(1..=6).contains(&level) || !(title.is_empty() || title.starts_with([' ', '\t'])) {
+        return None;
+    }
+
+    Some((level, title.trim().trim_end_matches('#').trim_end()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings;
+
+    fn assert_offsets(text: &str, chunks: &[Chunk]) {
+        for (index, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.index, index);
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+    }
+
+    #[test]
+    fn test_recursive_character_splitter() {
+        let text = "First paragraph, short.\n\nSecond paragraph is a bit longer than the first one.\n\nThird.";
+        let chunks = RecursiveCharacterSplitter::new(30).split(text);
+
+        assert_offsets(text, &chunks);
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.text.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "First paragraph, short.",
+                "Second paragraph is a bit",
+                "longer than the first one.",
+                "Third.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overlap() {
+        let text = "one two three four five six seven eight nine ten";
+        let chunks = RecursiveCharacterSplitter::new(20)
+            .chunk_overlap(10)
+            .split(text);
+
+        assert_offsets(text, &chunks);
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.text.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "one two three four",
+                "four five six seven",
+                "six seven eight",
+                "eight nine ten",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_between_chars() {
+        let text = "ééééééééééé";
+        let chunks = RecursiveCharacterSplitter::new(4).split(text);
+
+        assert_offsets(text, &chunks);
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.text.as_str())
+                .collect::<Vec<_>>(),
+            ["éééé", "éééé", "ééé"]
+        );
+    }
+
+    #[test]
+    fn test_sentence_splitter() {
+        let text = "It works. Does it? Yes!\n\nA new paragraph without a period\n\nThe end.";
+        let chunks = SentenceSplitter::new(25).split(text);
+
+        assert_offsets(text, &chunks);
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.text.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "It works. Does it? Yes!",
+                "A new paragraph without",
+                "a period\n\nThe end.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markdown_splitter() {
+        let text = "Intro\n# Install\nSteps.\n## Linux\n```sh\n# not a header\nmake\n```\n## macOS\nUse brew.\n# Usage\nRun it.";
+        let chunks = MarkdownSplitter::new(100).split(text);
+
+        assert_offsets(text, &chunks);
+        let sections = chunks
+            .iter()
+            .map(|chunk| {
+                (
+                    chunk.headers.join(" > "),
+                    chunk.text.lines().next().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sections,
+            [
+                ("".to_string(), "Intro"),
+                ("Install".to_string(), "# Install"),
+                ("Install > Linux".to_string(), "## Linux"),
+                ("Install > macOS".to_string(), "## macOS"),
+                ("Usage".to_string(), "# Usage"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_token_splitter() {
+        let words = |text: &str| text.split_whitespace().count();
+        let text = "a b c d e f g h i j";
+        let chunks = TokenSplitter::new(words, 4).chunk_overlap(1).split(text);
+
+        assert_offsets(text, &chunks);
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.text.as_str())
+                .collect::<Vec<_>>(),
+            ["a b c d", "d e f g", "g h i j"]
+        );
+    }
+
+    #[test]
+    fn test_embed_chunks() {
+        let chunks = RecursiveCharacterSplitter::new(5).split("hello world");
+
+        let texts = chunks
+            .into_iter()
+            .map(|chunk| embeddings::to_texts(chunk).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, [vec!["hello"], vec!["world"]]);
+    }
+}
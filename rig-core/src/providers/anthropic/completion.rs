@@ -1,10 +1,8 @@
 //! Anthropic completion api implementation
 
-use std::iter;
-
 use crate::{
     completion::{self, CompletionError},
-    json_utils,
+    json_utils, usage, OneOrMany,
 };
 
 use serde::{Deserialize, Serialize};
@@ -59,6 +57,11 @@ pub enum Content {
         name: String,
         input: serde_json::Value,
     },
+    ToolResult {
+        r#type: String,
+        tool_use_id: String,
+        content: String,
+    },
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -88,6 +91,17 @@ impl std::fmt::Display for Usage {
     }
 }
 
+impl From<&Usage> for usage::Usage {
+    fn from(usage: &Usage) -> Self {
+        // Cached input tokens are reported separately from the other input tokens
+        let input_tokens = usage.input_tokens
+            + usage.cache_read_input_tokens.unwrap_or_default()
+            + usage.cache_creation_input_tokens.unwrap_or_default();
+
+        usage::Usage::new(input_tokens, usage.output_tokens)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ToolDefinition {
     pub name: String,
@@ -105,17 +119,39 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
     type Error = CompletionError;
 
     fn try_from(response: CompletionResponse) -> std::prelude::v1::Result<Self, Self::Error> {
+        // Tool use blocks are usually preceded by a text block explaining the tool calls,
+        // so they take precedence over the text
+        let tool_calls = response
+            .content
+            .iter()
+            .filter_map(|content| match content {
+                Content::ToolUse {
+                    id, name, input, ..
+                } => Some(completion::ToolCall {
+                    id: id.clone(),
+                    name: name.clone(),
+                    arguments: input.clone(),
+                }),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if let Ok(tool_calls) = OneOrMany::many(tool_calls) {
+            return Ok(completion::CompletionResponse {
+                choice: completion::ModelChoice::ToolCall(tool_calls),
+                usage: (&response.usage).into(),
+                raw_response: response,
+            });
+        }
+
         match response.content.as_slice() {
             [Content::String(text) | Content::Text { text, .. }, ..] => {
                 Ok(completion::CompletionResponse {
                     choice: completion::ModelChoice::Message(text.to_string()),
+                    usage: (&response.usage).into(),
                     raw_response: response,
                 })
             }
-            [Content::ToolUse { name, input, .. }, ..] => Ok(completion::CompletionResponse {
-                choice: completion::ModelChoice::ToolCall(name.clone(), input.clone()),
-                raw_response: response,
-            }),
             _ => Err(CompletionError::ResponseError(
                 "Response did not contain a message or tool call".into(),
             )),
@@ -126,21 +162,61 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Message {
     pub role: String,
-    pub content: String,
+    pub content: Vec<Content>,
 }
 
 impl From<completion::Message> for Message {
     fn from(message: completion::Message) -> Self {
-        Self {
-            role: message.role,
-            content: message.content,
+        match message.role.as_str() {
+            // Anthropic expects tool results to be sent as part of a user message
+            "tool" => Self {
+                role: "user".into(),
+                content: vec![Content::ToolResult {
+                    r#type: "tool_result".into(),
+                    tool_use_id: message.tool_call_id.unwrap_or_default(),
+                    content: message.content,
+                }],
+            },
+            _ => {
+                let has_text = !message.content.is_empty() || message.tool_calls.is_empty();
+                let text = has_text.then(|| Content::Text {
+                    r#type: "text".into(),
+                    text: message.content,
+                });
+                let tool_uses = message.tool_calls.into_iter().map(|call| Content::ToolUse {
+                    r#type: "tool_use".into(),
+                    id: call.id,
+                    name: call.name,
+                    input: call.arguments,
+                });
+
+                Self {
+                    role: message.role,
+                    content: text.into_iter().chain(tool_uses).collect(),
+                }
+            }
         }
     }
 }
 
+/// Convert the chat history to Anthropic messages, merging consecutive messages with the
+/// same role (e.g.: the results of several tool calls) since Anthropic requires roles to alternate.
+fn to_anthropic_messages(messages: impl IntoIterator<Item = completion::Message>) -> Vec<Message> {
+    messages
+        .into_iter()
+        .map(Message::from)
+        .fold(Vec::<Message>::new(), |mut messages, message| {
+            match messages.last_mut() {
+                Some(last) if last.role == message.role => last.content.extend(message.content),
+                _ => messages.push(message),
+            }
+            messages
+        })
+}
+
 #[derive(Clone)]
 pub struct CompletionModel {
-    client: Client,
+    pub(crate) client: Client,
     pub model: String,
     default_max_tokens: Option<u64>,
 }
@@ -186,19 +262,15 @@ enum ToolChoice {
     Tool { name: String },
 }
 
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
-
-    async fn completion(
+impl CompletionModel {
+    pub(crate) fn create_completion_request(
         &self,
         completion_request: completion::CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> Result<serde_json::Value, CompletionError> {
         // Note: Ideally we'd introduce provider-specific Request models to handle the
         // specific requirements of each provider. For now, we just manually check while
         // building the request as a raw JSON document.
 
-        let prompt_with_context = completion_request.prompt_with_context();
-
         // Check if max_tokens is set, required for Anthropic
         let max_tokens = if let Some(tokens) = completion_request.max_tokens {
             tokens
@@ -210,17 +282,13 @@ impl completion::CompletionModel for CompletionModel {
             ));
         };
 
+        let prompt = completion_request
+            .has_prompt()
+            .then(|| completion::Message::user(completion_request.prompt_with_context()));
+
         let mut request = json!({
             "model": self.model,
-            "messages": completion_request
-                .chat_history
-                .into_iter()
-                .map(Message::from)
-                .chain(iter::once(Message {
-                    role: "user".to_owned(),
-                    content: prompt_with_context,
-                }))
-                .collect::<Vec<_>>(),
+            "messages": to_anthropic_messages(completion_request.chat_history.into_iter().chain(prompt)),
             "max_tokens": max_tokens,
             "system": completion_request.preamble.unwrap_or("".to_string()),
         });
@@ -251,6 +319,19 @@ impl completion::CompletionModel for CompletionModel {
             json_utils::merge_inplace(&mut request, params.clone())
         }
 
+        Ok(request)
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    async fn completion(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request)?;
+
         let response = self
             .client
             .post("/v1/messages")
@@ -270,14 +351,14 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Error(error) => Err(CompletionError::ProviderError(error.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 }
 
 #[derive(Debug, Deserialize)]
-struct ApiErrorResponse {
-    message: String,
+pub(crate) struct ApiErrorResponse {
+    pub message: String,
 }
 
 #[derive(Debug, Deserialize)]
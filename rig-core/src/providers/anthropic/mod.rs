@@ -11,6 +11,7 @@
 
 pub mod client;
 pub mod completion;
+pub mod streaming;
 
 pub use client::{Client, ClientBuilder};
 pub use completion::{
@@ -0,0 +1,107 @@
+//! Anthropic streaming completion api implementation
+//! From [Anthropic API Reference](https://docs.anthropic.com/en/api/messages-streaming)
+
+use serde::Deserialize;
+use serde_json::json;
+
+use super::completion::{ApiErrorResponse, CompletionModel};
+use crate::{
+    completion::{self, CompletionError},
+    json_utils,
+    streaming::{
+        self, StreamingChoice, StreamingCompletionModel, StreamingResult, ToolCallAccumulator,
+    },
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamingEvent {
+    ContentBlockStart {
+        index: usize,
+        content_block: ContentBlock,
+    },
+    ContentBlockDelta {
+        index: usize,
+        delta: ContentDelta,
+    },
+    ContentBlockStop {
+        index: usize,
+    },
+    Error {
+        error: ApiErrorResponse,
+    },
+    /// Events that do not carry any content (e.g.: `message_start`, `ping`, `message_stop`)
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text { text: String },
+    ToolUse { id: String, name: String },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentDelta {
+    TextDelta { text: String },
+    InputJsonDelta { partial_json: String },
+}
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let request = json_utils::merge(
+            self.create_completion_request(completion_request)?,
+            json!({"stream": true}),
+        );
+
+        let response = self
+            .client
+            .post("/v1/messages")
+            .json(&request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(CompletionError::from_response(response).await);
+        }
+
+        Ok(streaming::decode(
+            streaming::sse_events(response),
+            ToolCallAccumulator::default(),
+            |tool_calls, event| {
+                let choices = match serde_json::from_str::<StreamingEvent>(&event.data)? {
+                    StreamingEvent::ContentBlockStart {
+                        content_block: ContentBlock::Text { text },
+                        ..
+                    } if !text.is_empty() => vec![StreamingChoice::Message(text)],
+                    StreamingEvent::ContentBlockStart {
+                        index,
+                        content_block: ContentBlock::ToolUse { id, name },
+                    } => vec![tool_calls.delta(index, Some(id), Some(name), String::new())],
+                    StreamingEvent::ContentBlockDelta {
+                        delta: ContentDelta::TextDelta { text },
+                        ..
+                    } => vec![StreamingChoice::Message(text)],
+                    StreamingEvent::ContentBlockDelta {
+                        index,
+                        delta: ContentDelta::InputJsonDelta { partial_json },
+                    } => vec![tool_calls.delta(index, None, None, partial_json)],
+                    StreamingEvent::ContentBlockStop { index } => {
+                        tool_calls.finish(index)?.into_iter().collect()
+                    }
+                    StreamingEvent::Error { error } => {
+                        return Err(CompletionError::ProviderError(error.message))
+                    }
+                    StreamingEvent::ContentBlockStart { .. } | StreamingEvent::Other => vec![],
+                };
+
+                Ok(choices)
+            },
+        ))
+    }
+}
@@ -15,7 +15,11 @@ use crate::{
     completion::{self, CompletionError},
     embeddings::{self, EmbeddingError, EmbeddingsBuilder},
     extractor::ExtractorBuilder,
-    json_utils, Embed,
+    json_utils,
+    streaming::{
+        self, StreamingChoice, StreamingCompletionModel, StreamingResult, ToolCallAccumulator,
+    },
+    usage, Embed, OneOrMany,
 };
 
 use schemars::JsonSchema;
@@ -157,7 +161,7 @@ pub struct EmbeddingResponse {
     pub meta: Option<Meta>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct Meta {
     pub api_version: ApiVersion,
     pub billed_units: BilledUnits,
@@ -165,7 +169,7 @@ pub struct Meta {
     pub warnings: Vec<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct ApiVersion {
     pub version: String,
     #[serde(default)]
@@ -196,6 +200,15 @@ impl std::fmt::Display for BilledUnits {
     }
 }
 
+impl From<&BilledUnits> for usage::Usage {
+    fn from(billed_units: &BilledUnits) -> Self {
+        usage::Usage::new(
+            billed_units.input_tokens as u64,
+            billed_units.output_tokens as u64,
+        )
+    }
+}
+
 #[derive(Clone)]
 pub struct EmbeddingModel {
     client: Client,
@@ -213,8 +226,15 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
 
     async fn embed_texts(
         &self,
-        documents: impl IntoIterator<Item = String>,
+        documents: impl IntoIterator<Item = String> + Send,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        Ok(self.embed_texts_with_usage(documents).await?.0)
+    }
+
+    async fn embed_texts_with_usage(
+        &self,
+        documents: impl IntoIterator<Item = String> + Send,
+    ) -> Result<(Vec<embeddings::Embedding>, usage::Usage), EmbeddingError> {
         let documents = documents.into_iter().collect::<Vec<_>>();
 
         let response = self
@@ -231,7 +251,7 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
         if response.status().is_success() {
             match response.json::<ApiResponse<EmbeddingResponse>>().await? {
                 ApiResponse::Ok(response) => {
-                    match response.meta {
+                    match &response.meta {
                         Some(meta) => tracing::info!(target: "rig",
                             "Cohere embeddings billed units: {}",
                             meta.billed_units,
@@ -252,7 +272,12 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                         ));
                     }
 
-                    Ok(response
+                    let usage = response
+                        .meta
+                        .as_ref()
+                        .map(|meta| (&meta.billed_units).into())
+                        .unwrap_or_default();
+                    let embeddings = response
                         .embeddings
                         .into_iter()
                         .zip(documents.into_iter())
@@ -260,12 +285,14 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                             document,
                             vec: embedding,
                         })
-                        .collect())
+                        .collect();
+
+                    Ok((embeddings, usage))
                 }
                 ApiResponse::Err(error) => Err(EmbeddingError::ProviderError(error.message)),
             }
         } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
+            Err(EmbeddingError::from_response(response).await)
         }
     }
 }
@@ -316,6 +343,8 @@ pub struct CompletionResponse {
     pub tool_calls: Vec<ToolCall>,
     #[serde(default)]
     pub chat_history: Vec<ChatHistory>,
+    #[serde(default)]
+    pub meta: Option<Meta>,
 }
 
 impl From<CompletionResponse> for completion::CompletionResponse<CompletionResponse> {
@@ -324,17 +353,28 @@ impl From<CompletionResponse> for completion::CompletionResponse<CompletionRespo
             text, tool_calls, ..
         } = &response;
 
-        let model_response = if !tool_calls.is_empty() {
-            completion::ModelChoice::ToolCall(
-                tool_calls.first().unwrap().name.clone(),
-                tool_calls.first().unwrap().parameters.clone(),
-            )
-        } else {
-            completion::ModelChoice::Message(text.clone())
+        let tool_calls = tool_calls
+            .iter()
+            .map(|call| completion::ToolCall {
+                // Cohere does not assign ids to tool calls
+                id: call.name.clone(),
+                name: call.name.clone(),
+                arguments: call.parameters.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let model_response = match OneOrMany::many(tool_calls) {
+            Ok(tool_calls) => completion::ModelChoice::ToolCall(tool_calls),
+            Err(_) => completion::ModelChoice::Message(text.clone()),
         };
 
         completion::CompletionResponse {
             choice: model_response,
+            usage: response
+                .meta
+                .as_ref()
+                .map(|meta| (&meta.billed_units).into())
+                .unwrap_or_default(),
             raw_response: response,
         }
     }
@@ -377,12 +417,49 @@ pub struct Connector {
     pub id: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ToolCall {
     pub name: String,
     pub parameters: serde_json::Value,
 }
 
+impl From<completion::ToolCall> for ToolCall {
+    fn from(tool_call: completion::ToolCall) -> Self {
+        Self {
+            name: tool_call.name,
+            parameters: tool_call.arguments,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ToolResult {
+    pub call: ToolCall,
+    pub outputs: Vec<serde_json::Value>,
+}
+
+impl ToolResult {
+    /// Create the tool result corresponding to the given "tool" message. Cohere identifies
+    /// tool calls by their name and parameters, so the parameters are looked up in the
+    /// tool calls of the chat history.
+    fn new(message: completion::Message, chat_history: &[completion::Message]) -> Self {
+        let parameters = chat_history
+            .iter()
+            .flat_map(|message| &message.tool_calls)
+            .rfind(|call| Some(&call.id) == message.tool_call_id.as_ref())
+            .map(|call| call.arguments.clone())
+            .unwrap_or_else(|| json!({}));
+
+        Self {
+            call: ToolCall {
+                name: message.name.unwrap_or_default(),
+                parameters,
+            },
+            outputs: vec![json!({ "result": message.content })],
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ChatHistory {
     pub role: String,
@@ -478,6 +555,29 @@ impl From<completion::ToolDefinition> for ToolDefinition {
 pub struct Message {
     pub role: String,
     pub message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_results: Vec<ToolResult>,
+}
+
+impl Message {
+    /// Convert the chat history to Cohere messages
+    fn from_history(chat_history: Vec<completion::Message>) -> Vec<Self> {
+        chat_history
+            .iter()
+            .cloned()
+            .map(|message| match message.role.as_str() {
+                "tool" => Self {
+                    role: "TOOL".to_owned(),
+                    message: String::new(),
+                    tool_calls: vec![],
+                    tool_results: vec![ToolResult::new(message, &chat_history)],
+                },
+                _ => Self::from(message),
+            })
+            .collect()
+    }
 }
 
 impl From<completion::Message> for Message {
@@ -490,6 +590,8 @@ impl From<completion::Message> for Message {
                 _ => "USER".to_owned(),
             },
             message: message.content,
+            tool_calls: message.tool_calls.into_iter().map(ToolCall::from).collect(),
+            tool_results: vec![],
         }
     }
 }
@@ -507,37 +609,64 @@ impl CompletionModel {
             model: model.to_string(),
         }
     }
-}
 
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
-
-    async fn completion(
+    fn create_completion_request(
         &self,
-        completion_request: completion::CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
-        let request = json!({
+        mut completion_request: completion::CompletionRequest,
+    ) -> serde_json::Value {
+        // When continuing the chat after tool calls, Cohere expects the results of the
+        // latest tool calls to be sent separately from the chat history (with an empty message)
+        let tool_results = if completion_request.has_prompt() {
+            vec![]
+        } else {
+            let split = completion_request
+                .chat_history
+                .iter()
+                .rposition(|message| message.role != "tool")
+                .map_or(0, |index| index + 1);
+            completion_request.chat_history.split_off(split)
+        };
+
+        let mut request = json!({
             "model": self.model,
             "preamble": completion_request.preamble,
             "message": completion_request.prompt,
             "documents": completion_request.documents,
-            "chat_history": completion_request.chat_history.into_iter().map(Message::from).collect::<Vec<_>>(),
+            "chat_history": Message::from_history(completion_request.chat_history.clone()),
             "temperature": completion_request.temperature,
             "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
         });
 
-        let response = self
-            .client
-            .post("/v1/chat")
-            .json(
-                &if let Some(ref params) = completion_request.additional_params {
-                    json_utils::merge(request.clone(), params.clone())
-                } else {
-                    request.clone()
-                },
-            )
-            .send()
-            .await?;
+        if !tool_results.is_empty() {
+            json_utils::merge_inplace(
+                &mut request,
+                json!({
+                    "tool_results": tool_results
+                        .into_iter()
+                        .map(|message| ToolResult::new(message, &completion_request.chat_history))
+                        .collect::<Vec<_>>(),
+                }),
+            );
+        }
+
+        if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        }
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    async fn completion(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request);
+
+        let response = self.client.post("/v1/chat").json(&request).send().await?;
 
         if response.status().is_success() {
             match response.json::<ApiResponse<CompletionResponse>>().await? {
@@ -545,7 +674,91 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(error) => Err(CompletionError::ProviderError(error.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 }
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let request = json_utils::merge(
+            self.create_completion_request(completion_request),
+            json!({"stream": true}),
+        );
+
+        let response = self.client.post("/v1/chat").json(&request).send().await?;
+
+        if !response.status().is_success() {
+            return Err(CompletionError::from_response(response).await);
+        }
+
+        // Cohere streams newline-delimited JSON events rather than server-sent events
+        Ok(streaming::decode(
+            streaming::lines(response),
+            ToolCallAccumulator::default(),
+            |tool_calls, line| {
+                if line.trim().is_empty() {
+                    return Ok(vec![]);
+                }
+
+                let choices = match serde_json::from_str::<StreamingEvent>(&line)? {
+                    StreamingEvent::TextGeneration { text } => {
+                        vec![StreamingChoice::Message(text)]
+                    }
+                    StreamingEvent::ToolCallsChunk {
+                        tool_call_delta: Some(delta),
+                    } => vec![tool_calls.delta(
+                        delta.index,
+                        None,
+                        delta.name,
+                        delta.parameters.unwrap_or_default(),
+                    )],
+                    StreamingEvent::ToolCallsGeneration { tool_calls: calls } => {
+                        // The complete tool calls are sent once generated, which supersede the deltas
+                        *tool_calls = ToolCallAccumulator::default();
+                        calls
+                            .into_iter()
+                            .map(|call| {
+                                StreamingChoice::ToolCall(completion::ToolCall {
+                                    id: call.name.clone(),
+                                    name: call.name,
+                                    arguments: call.parameters,
+                                })
+                            })
+                            .collect()
+                    }
+                    StreamingEvent::ToolCallsChunk { .. } | StreamingEvent::Other => vec![],
+                };
+
+                Ok(choices)
+            },
+        ))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event_type", rename_all = "kebab-case")]
+enum StreamingEvent {
+    TextGeneration {
+        text: String,
+    },
+    ToolCallsChunk {
+        tool_call_delta: Option<ToolCallDelta>,
+    },
+    ToolCallsGeneration {
+        tool_calls: Vec<ToolCall>,
+    },
+    /// Events that do not carry any content (e.g.: `stream-start`, `stream-end`)
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolCallDelta {
+    index: usize,
+    name: Option<String>,
+    parameters: Option<String>,
+}
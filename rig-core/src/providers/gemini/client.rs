@@ -25,7 +25,9 @@ impl Client {
     pub fn new(api_key: &str) -> Self {
         Self::from_url(api_key, GEMINI_API_BASE_URL)
     }
-    fn from_url(api_key: &str, base_url: &str) -> Self {
+
+    /// Create a new Google Gemini client with the given API key and base API URL.
+    pub fn from_url(api_key: &str, base_url: &str) -> Self {
         Self {
             base_url: base_url.to_string(),
             api_key: api_key.to_string(),
@@ -57,6 +59,15 @@ impl Client {
         self.http_client.post(url)
     }
 
+    /// Same as [Client::post], but requests the response to be streamed back as server-sent events.
+    pub fn post_sse(&self, path: &str) -> reqwest::RequestBuilder {
+        let url =
+            format!("{}/{}?alt=sse&key={}", self.base_url, path, self.api_key).replace("//", "/");
+
+        tracing::debug!("POST {}", url);
+        self.http_client.post(url)
+    }
+
     /// Create an embedding model with the given name.
     /// Note: default embedding dimension of 0 will be used if model is not known.
     /// If this is the case, it's better to use function `embedding_model_with_ndims`
@@ -13,13 +13,16 @@ pub const GEMINI_1_5_PRO_8B: &str = "gemini-1.5-pro-8b";
 pub const GEMINI_1_0_PRO: &str = "gemini-1.0-pro";
 
 use gemini_api_types::{
-    Content, ContentCandidate, FunctionDeclaration, GenerateContentRequest,
-    GenerateContentResponse, GenerationConfig, Part, Role, Tool,
+    Content, ContentCandidate, FunctionCall, FunctionDeclaration, FunctionResponse,
+    GenerateContentRequest, GenerateContentResponse, GenerationConfig, Part, Role, Tool,
 };
 use serde_json::{Map, Value};
-use std::convert::TryFrom;
+use std::{collections::HashMap, convert::TryFrom};
 
-use crate::completion::{self, CompletionError, CompletionRequest};
+use crate::{
+    completion::{self, CompletionError, CompletionRequest},
+    OneOrMany,
+};
 
 use super::Client;
 
@@ -29,7 +32,7 @@ use super::Client;
 
 #[derive(Clone)]
 pub struct CompletionModel {
-    client: Client,
+    pub(crate) client: Client,
     pub model: String,
 }
 
@@ -42,73 +45,66 @@ impl CompletionModel {
     }
 }
 
-impl completion::CompletionModel for CompletionModel {
-    type Response = GenerateContentResponse;
-
-    async fn completion(
-        &self,
-        mut completion_request: CompletionRequest,
-    ) -> Result<completion::CompletionResponse<GenerateContentResponse>, CompletionError> {
-        let mut full_history = Vec::new();
-        full_history.append(&mut completion_request.chat_history);
-
-        let prompt_with_context = completion_request.prompt_with_context();
+pub(crate) fn create_request_body(
+    mut completion_request: CompletionRequest,
+) -> Result<GenerateContentRequest, CompletionError> {
+    let mut full_history = Vec::new();
+    full_history.append(&mut completion_request.chat_history);
 
-        full_history.push(completion::Message {
-            role: "user".into(),
-            content: prompt_with_context,
-        });
+    if completion_request.has_prompt() {
+        full_history.push(completion::Message::user(
+            completion_request.prompt_with_context(),
+        ));
+    }
 
-        // Handle Gemini specific parameters
-        let additional_params = completion_request
-            .additional_params
-            .unwrap_or_else(|| Value::Object(Map::new()));
-        let mut generation_config = serde_json::from_value::<GenerationConfig>(additional_params)?;
+    // Handle Gemini specific parameters
+    let additional_params = completion_request
+        .additional_params
+        .unwrap_or_else(|| Value::Object(Map::new()));
+    let mut generation_config = serde_json::from_value::<GenerationConfig>(additional_params)?;
 
-        // Set temperature from completion_request or additional_params
-        if let Some(temp) = completion_request.temperature {
-            generation_config.temperature = Some(temp);
-        }
+    // Set temperature from completion_request or additional_params
+    if let Some(temp) = completion_request.temperature {
+        generation_config.temperature = Some(temp);
+    }
 
-        // Set max_tokens from completion_request or additional_params
-        if let Some(max_tokens) = completion_request.max_tokens {
-            generation_config.max_output_tokens = Some(max_tokens);
-        }
+    // Set max_tokens from completion_request or additional_params
+    if let Some(max_tokens) = completion_request.max_tokens {
+        generation_config.max_output_tokens = Some(max_tokens);
+    }
 
-        let request = GenerateContentRequest {
-            contents: full_history
+    let request = GenerateContentRequest {
+        contents: full_history.into_iter().map(Content::from).collect(),
+        generation_config: Some(generation_config),
+        safety_settings: None,
+        tools: Some(
+            completion_request
+                .tools
                 .into_iter()
-                .map(|msg| Content {
-                    parts: vec![Part {
-                        text: Some(msg.content),
-                        ..Default::default()
-                    }],
-                    role: match msg.role.as_str() {
-                        "system" => Some(Role::Model),
-                        "user" => Some(Role::User),
-                        "assistant" => Some(Role::Model),
-                        _ => None,
-                    },
-                })
+                .map(Tool::from)
                 .collect(),
-            generation_config: Some(generation_config),
-            safety_settings: None,
-            tools: Some(
-                completion_request
-                    .tools
-                    .into_iter()
-                    .map(Tool::from)
-                    .collect(),
-            ),
-            tool_config: None,
-            system_instruction: Some(Content {
-                parts: vec![Part {
-                    text: Some("system".to_string()),
-                    ..Default::default()
-                }],
-                role: Some(Role::Model),
-            }),
-        };
+        ),
+        tool_config: None,
+        system_instruction: Some(Content {
+            parts: vec![Part {
+                text: Some("system".to_string()),
+                ..Default::default()
+            }],
+            role: Some(Role::Model),
+        }),
+    };
+
+    Ok(request)
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = GenerateContentResponse;
+
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<GenerateContentResponse>, CompletionError> {
+        let request = create_request_body(completion_request)?;
 
         tracing::debug!("Sending completion request to Gemini API");
 
@@ -117,11 +113,14 @@ impl completion::CompletionModel for CompletionModel {
             .post(&format!("/v1beta/models/{}:generateContent", self.model))
             .json(&request)
             .send()
-            .await?
-            .error_for_status()?
-            .json::<GenerateContentResponse>()
             .await?;
 
+        if !response.status().is_success() {
+            return Err(CompletionError::from_response(response).await);
+        }
+
+        let response = response.json::<GenerateContentResponse>().await?;
+
         match response.usage_metadata {
             Some(ref usage) => tracing::info!(target: "rig",
             "Gemini completion token usage: {}",
@@ -151,33 +150,98 @@ impl From<completion::ToolDefinition> for Tool {
     }
 }
 
+impl From<completion::Message> for Content {
+    fn from(msg: completion::Message) -> Self {
+        match msg.role.as_str() {
+            // Tool results are sent back to the model as function responses
+            "tool" => Content {
+                parts: vec![Part {
+                    function_response: Some(FunctionResponse {
+                        name: msg.name.unwrap_or_default(),
+                        response: Some(HashMap::from([(
+                            "result".to_string(),
+                            Value::String(msg.content),
+                        )])),
+                    }),
+                    ..Default::default()
+                }],
+                role: Some(Role::User),
+            },
+            role => {
+                let text = (!msg.content.is_empty() || msg.tool_calls.is_empty()).then(|| Part {
+                    text: Some(msg.content),
+                    ..Default::default()
+                });
+                let function_calls = msg.tool_calls.into_iter().map(|call| Part {
+                    function_call: Some(FunctionCall {
+                        name: call.name,
+                        args: match call.arguments {
+                            Value::Object(args) => Some(args),
+                            _ => None,
+                        },
+                    }),
+                    ..Default::default()
+                });
+
+                Content {
+                    parts: text.into_iter().chain(function_calls).collect(),
+                    role: match role {
+                        "system" => Some(Role::Model),
+                        "user" => Some(Role::User),
+                        "assistant" => Some(Role::Model),
+                        _ => None,
+                    },
+                }
+            }
+        }
+    }
+}
+
 impl TryFrom<GenerateContentResponse> for completion::CompletionResponse<GenerateContentResponse> {
     type Error = CompletionError;
 
     fn try_from(response: GenerateContentResponse) -> Result<Self, Self::Error> {
         match response.candidates.as_slice() {
-            [ContentCandidate { content, .. }, ..] => Ok(completion::CompletionResponse {
-                choice: match content.parts.first().unwrap() {
-                    Part {
-                        text: Some(text), ..
-                    } => completion::ModelChoice::Message(text.clone()),
-                    Part {
-                        function_call: Some(function_call),
-                        ..
-                    } => {
-                        let args_value = serde_json::Value::Object(
+            [ContentCandidate { content, .. }, ..] => {
+                // Gemini may return several function calls in parallel
+                let function_calls = content
+                    .parts
+                    .iter()
+                    .filter_map(|part| part.function_call.as_ref())
+                    .map(|function_call| completion::ToolCall {
+                        // Gemini does not assign ids to function calls
+                        id: function_call.name.clone(),
+                        name: function_call.name.clone(),
+                        arguments: serde_json::Value::Object(
                             function_call.args.clone().unwrap_or_default(),
-                        );
-                        completion::ModelChoice::ToolCall(function_call.name.clone(), args_value)
-                    }
-                    _ => {
-                        return Err(CompletionError::ResponseError(
-                            "Unsupported response by the model of type ".into(),
-                        ))
-                    }
-                },
-                raw_response: response,
-            }),
+                        ),
+                    })
+                    .collect::<Vec<_>>();
+
+                let choice = match OneOrMany::many(function_calls) {
+                    Ok(function_calls) => completion::ModelChoice::ToolCall(function_calls),
+                    Err(_) => match content.parts.first() {
+                        Some(Part {
+                            text: Some(text), ..
+                        }) => completion::ModelChoice::Message(text.clone()),
+                        _ => {
+                            return Err(CompletionError::ResponseError(
+                                "Unsupported response by the model of type ".into(),
+                            ))
+                        }
+                    },
+                };
+
+                Ok(completion::CompletionResponse {
+                    choice,
+                    usage: response
+                        .usage_metadata
+                        .as_ref()
+                        .map(Into::into)
+                        .unwrap_or_default(),
+                    raw_response: response,
+                })
+            }
             _ => Err(CompletionError::ResponseError(
                 "No candidates found in response".into(),
             )),
@@ -197,6 +261,7 @@ pub mod gemini_api_types {
     use crate::{
         completion::CompletionError,
         providers::gemini::gemini_api_types::{CodeExecutionResult, ExecutableCode},
+        usage,
     };
 
     /// Response from the model supporting multiple candidate responses.
@@ -385,6 +450,16 @@ pub mod gemini_api_types {
         }
     }
 
+    impl From<&UsageMetadata> for usage::Usage {
+        fn from(usage: &UsageMetadata) -> Self {
+            Self {
+                input_tokens: usage.prompt_token_count as u64,
+                output_tokens: usage.candidates_token_count as u64,
+                total_tokens: usage.total_token_count as u64,
+            }
+        }
+    }
+
     /// A set of the feedback metadata the prompt specified in [GenerateContentRequest.contents](GenerateContentRequest).
     #[derive(Debug, Deserialize)]
     #[serde(rename_all = "camelCase")]
@@ -16,7 +16,7 @@ pub const EMBEDDING_004: &str = "text-embedding-004";
 #[derive(Clone)]
 pub struct EmbeddingModel {
     client: Client,
-    model: String,
+    pub model: String,
     ndims: Option<usize>,
 }
 
@@ -34,11 +34,11 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
     const MAX_DOCUMENTS: usize = 1024;
 
     fn ndims(&self) -> usize {
-        match self.model.as_str() {
+        self.ndims.unwrap_or(match self.model.as_str() {
             EMBEDDING_001 => 768,
             EMBEDDING_004 => 1024,
             _ => 0, // Default to 0 for unknown models
-        }
+        })
     }
 
     async fn embed_texts(
@@ -0,0 +1,87 @@
+// ================================================================
+//! Google Gemini Streaming Completion Integration
+//! From [Gemini API Reference](https://ai.google.dev/api/generate-content#method:-models.streamgeneratecontent)
+// ================================================================
+
+use serde::Deserialize;
+
+use super::completion::{
+    create_request_body,
+    gemini_api_types::{Content, Part},
+    CompletionModel,
+};
+use crate::{
+    completion::{CompletionError, CompletionRequest, ToolCall},
+    streaming::{self, StreamingChoice, StreamingCompletionModel, StreamingResult},
+};
+
+/// Chunk of a streamed `GenerateContentResponse`. Unlike complete responses, chunks
+/// are not guaranteed to contain candidates (e.g.: the last chunk may only contain usage metadata).
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StreamGenerateContentResponse {
+    #[serde(default)]
+    candidates: Vec<StreamingCandidate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamingCandidate {
+    content: Option<Content>,
+}
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let request = create_request_body(completion_request)?;
+
+        let response = self
+            .client
+            .post_sse(&format!(
+                "/v1beta/models/{}:streamGenerateContent",
+                self.model
+            ))
+            .json(&request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(CompletionError::from_response(response).await);
+        }
+
+        Ok(streaming::decode(
+            streaming::sse_events(response),
+            (),
+            |_, event| {
+                let chunk = serde_json::from_str::<StreamGenerateContentResponse>(&event.data)?;
+
+                Ok(chunk
+                    .candidates
+                    .into_iter()
+                    .take(1)
+                    .filter_map(|candidate| candidate.content)
+                    .flat_map(|content| content.parts)
+                    .filter_map(|part| match part {
+                        Part {
+                            text: Some(text), ..
+                        } => Some(StreamingChoice::Message(text)),
+                        // Gemini does not stream function call arguments, nor does it assign
+                        // ids to function calls: they are yielded as soon as they are received.
+                        Part {
+                            function_call: Some(function_call),
+                            ..
+                        } => Some(StreamingChoice::ToolCall(ToolCall {
+                            id: function_call.name.clone(),
+                            name: function_call.name,
+                            arguments: serde_json::Value::Object(
+                                function_call.args.unwrap_or_default(),
+                            ),
+                        })),
+                        _ => None,
+                    })
+                    .collect())
+            },
+        ))
+    }
+}
@@ -6,6 +6,8 @@
 //! - Perplexity
 //! - Anthropic
 //! - Google Gemini
+//! - xAI
+//! - Ollama (local models)
 //!
 //! Each provider has its own module, which contains a `Client` implementation that can
 //! be used to initialize completion and embedding models and execute requests to those models.
@@ -43,6 +45,7 @@
 pub mod anthropic;
 pub mod cohere;
 pub mod gemini;
+pub mod ollama;
 pub mod openai;
 pub mod perplexity;
 pub mod xai;
@@ -0,0 +1,588 @@
+//! Ollama API client and Rig integration
+//!
+//! Ollama serves models locally (by default on `http://localhost:11434`) and does not
+//! require an API key. This module targets Ollama's native API rather than its
+//! OpenAI-compatible endpoints.
+//!
+//! # Example
+//! ```
+//! use rig::providers::ollama;
+//!
+//! let client = ollama::Client::new();
+//!
+//! let llama3_2 = client.completion_model(ollama::LLAMA3_2);
+//! let nomic_embed_text = client.embedding_model(ollama::NOMIC_EMBED_TEXT);
+//! ```
+use crate::{
+    agent::AgentBuilder,
+    completion::{self, CompletionError, CompletionRequest},
+    embeddings::{self, EmbeddingError, EmbeddingsBuilder},
+    extractor::ExtractorBuilder,
+    json_utils,
+    streaming::{self, StreamingChoice, StreamingCompletionModel, StreamingResult},
+    usage, Embed, OneOrMany,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+// ================================================================
+// Main Ollama Client
+// ================================================================
+const OLLAMA_API_BASE_URL: &str = "http://localhost:11434";
+
+#[derive(Clone)]
+pub struct Client {
+    base_url: String,
+    http_client: reqwest::Client,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    /// Create a new Ollama client for the local Ollama server.
+    pub fn new() -> Self {
+        Self::from_url(OLLAMA_API_BASE_URL)
+    }
+
+    /// Create a new Ollama client with the given base API URL.
+    pub fn from_url(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            http_client: reqwest::Client::builder()
+                .build()
+                .expect("Ollama reqwest client should build"),
+        }
+    }
+
+    /// Create a new Ollama client from the `OLLAMA_API_BASE_URL` environment variable,
+    /// or for the local Ollama server if the environment variable is not set.
+    pub fn from_env() -> Self {
+        let base_url =
+            std::env::var("OLLAMA_API_BASE_URL").unwrap_or(OLLAMA_API_BASE_URL.to_string());
+        Self::from_url(&base_url)
+    }
+
+    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        let url = format!("{}/{}", self.base_url, path).replace("//", "/");
+        self.http_client.post(url)
+    }
+
+    /// Create an embedding model with the given name.
+    /// Note: default embedding dimension of 0 will be used if model is not known.
+    /// If this is the case, it's better to use function `embedding_model_with_ndims`
+    ///
+    /// # Example
+    /// ```
+    /// use rig::providers::ollama::{Client, self};
+    ///
+    /// // Initialize the Ollama client
+    /// let ollama = Client::new();
+    ///
+    /// let embedding_model = ollama.embedding_model(ollama::NOMIC_EMBED_TEXT);
+    /// ```
+    pub fn embedding_model(&self, model: &str) -> EmbeddingModel {
+        let ndims = match model {
+            NOMIC_EMBED_TEXT => 768,
+            MXBAI_EMBED_LARGE => 1024,
+            ALL_MINILM => 384,
+            _ => 0,
+        };
+        EmbeddingModel::new(self.clone(), model, ndims)
+    }
+
+    /// Create an embedding model with the given name and the number of dimensions in the embedding generated by the model.
+    ///
+    /// # Example
+    /// ```
+    /// use rig::providers::ollama::{Client, self};
+    ///
+    /// // Initialize the Ollama client
+    /// let ollama = Client::new();
+    ///
+    /// let embedding_model = ollama.embedding_model_with_ndims("model-unknown-to-rig", 1024);
+    /// ```
+    pub fn embedding_model_with_ndims(&self, model: &str, ndims: usize) -> EmbeddingModel {
+        EmbeddingModel::new(self.clone(), model, ndims)
+    }
+
+    /// Create an embedding builder with the given embedding model.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use rig::providers::ollama::{Client, self};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     // Initialize the Ollama client
+    ///     let ollama = Client::new();
+    ///
+    ///     let embeddings = ollama.embeddings(ollama::NOMIC_EMBED_TEXT)
+    ///         .document("Hello, world!")?
+    ///         .document("Goodbye, world!")?
+    ///         .build()
+    ///         .await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn embeddings<D: Embed>(&self, model: &str) -> EmbeddingsBuilder<EmbeddingModel, D> {
+        EmbeddingsBuilder::new(self.embedding_model(model))
+    }
+
+    /// Create a completion model with the given name.
+    ///
+    /// # Example
+    /// ```
+    /// use rig::providers::ollama::{Client, self};
+    ///
+    /// // Initialize the Ollama client
+    /// let ollama = Client::new();
+    ///
+    /// let llama3_2 = ollama.completion_model(ollama::LLAMA3_2);
+    /// ```
+    pub fn completion_model(&self, model: &str) -> CompletionModel {
+        CompletionModel::new(self.clone(), model)
+    }
+
+    /// Create an agent builder with the given completion model.
+    ///
+    /// # Example
+    /// ```
+    /// use rig::providers::ollama::{Client, self};
+    ///
+    /// // Initialize the Ollama client
+    /// let ollama = Client::new();
+    ///
+    /// let agent = ollama.agent(ollama::LLAMA3_2)
+    ///    .preamble("You are comedian AI with a mission to make people laugh.")
+    ///    .temperature(0.0)
+    ///    .build();
+    /// ```
+    pub fn agent(&self, model: &str) -> AgentBuilder<CompletionModel> {
+        AgentBuilder::new(self.completion_model(model))
+    }
+
+    /// Create an extractor builder with the given completion model.
+    pub fn extractor<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync>(
+        &self,
+        model: &str,
+    ) -> ExtractorBuilder<T, CompletionModel> {
+        ExtractorBuilder::new(self.completion_model(model))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiErrorResponse {
+    error: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ApiResponse<T> {
+    Ok(T),
+    Err(ApiErrorResponse),
+}
+
+// ================================================================
+// Ollama Embedding API
+// ================================================================
+/// `nomic-embed-text` embedding model
+pub const NOMIC_EMBED_TEXT: &str = "nomic-embed-text";
+/// `mxbai-embed-large` embedding model
+pub const MXBAI_EMBED_LARGE: &str = "mxbai-embed-large";
+/// `all-minilm` embedding model
+pub const ALL_MINILM: &str = "all-minilm";
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingResponse {
+    pub model: String,
+    pub embeddings: Vec<Vec<f64>>,
+    #[serde(default)]
+    pub total_duration: Option<u64>,
+    #[serde(default)]
+    pub load_duration: Option<u64>,
+    #[serde(default)]
+    pub prompt_eval_count: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct EmbeddingModel {
+    client: Client,
+    pub model: String,
+    ndims: usize,
+}
+
+impl EmbeddingModel {
+    pub fn new(client: Client, model: &str, ndims: usize) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+            ndims,
+        }
+    }
+}
+
+impl embeddings::EmbeddingModel for EmbeddingModel {
+    const MAX_DOCUMENTS: usize = 1024;
+
+    fn ndims(&self) -> usize {
+        self.ndims
+    }
+
+    async fn embed_texts(
+        &self,
+        documents: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        Ok(self.embed_texts_with_usage(documents).await?.0)
+    }
+
+    async fn embed_texts_with_usage(
+        &self,
+        documents: impl IntoIterator<Item = String> + Send,
+    ) -> Result<(Vec<embeddings::Embedding>, usage::Usage), EmbeddingError> {
+        let documents = documents.into_iter().collect::<Vec<_>>();
+
+        let response = self
+            .client
+            .post("/api/embed")
+            .json(&json!({
+                "model": self.model,
+                "input": documents,
+            }))
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            match response.json::<ApiResponse<EmbeddingResponse>>().await? {
+                ApiResponse::Ok(response) => {
+                    if response.embeddings.len() != documents.len() {
+                        return Err(EmbeddingError::ResponseError(
+                            "Response data length does not match input length".into(),
+                        ));
+                    }
+
+                    let usage = usage::Usage::new(response.prompt_eval_count.unwrap_or(0), 0);
+                    let embeddings = response
+                        .embeddings
+                        .into_iter()
+                        .zip(documents)
+                        .map(|(vec, document)| embeddings::Embedding { document, vec })
+                        .collect();
+
+                    Ok((embeddings, usage))
+                }
+                ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.error)),
+            }
+        } else {
+            Err(EmbeddingError::from_response(response).await)
+        }
+    }
+}
+
+// ================================================================
+// Ollama Completion API
+// ================================================================
+/// `llama3.2` completion model
+pub const LLAMA3_2: &str = "llama3.2";
+/// `llama3.1` completion model
+pub const LLAMA3_1: &str = "llama3.1";
+/// `qwen2.5` completion model
+pub const QWEN2_5: &str = "qwen2.5";
+/// `mistral` completion model
+pub const MISTRAL: &str = "mistral";
+
+/// Response of the `/api/chat` endpoint. When streaming, each line of the response
+/// body is a partial response, the last of which has `done` set and carries the usage.
+#[derive(Debug, Deserialize)]
+pub struct CompletionResponse {
+    pub model: String,
+    pub created_at: String,
+    pub message: Message,
+    pub done: bool,
+    #[serde(default)]
+    pub done_reason: Option<String>,
+    #[serde(default)]
+    pub total_duration: Option<u64>,
+    #[serde(default)]
+    pub load_duration: Option<u64>,
+    /// Number of tokens in the prompt
+    #[serde(default)]
+    pub prompt_eval_count: Option<u64>,
+    /// Number of tokens in the response
+    #[serde(default)]
+    pub eval_count: Option<u64>,
+}
+
+impl From<&CompletionResponse> for usage::Usage {
+    fn from(response: &CompletionResponse) -> Self {
+        Self::new(
+            response.prompt_eval_count.unwrap_or(0),
+            response.eval_count.unwrap_or(0),
+        )
+    }
+}
+
+impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionResponse> {
+    type Error = CompletionError;
+
+    fn try_from(value: CompletionResponse) -> std::prelude::v1::Result<Self, Self::Error> {
+        let choice = if value.message.tool_calls.is_empty() {
+            completion::ModelChoice::Message(value.message.content.clone())
+        } else {
+            completion::ModelChoice::ToolCall(
+                OneOrMany::many(
+                    value
+                        .message
+                        .tool_calls
+                        .iter()
+                        .cloned()
+                        .map(completion::ToolCall::from)
+                        .collect::<Vec<_>>(),
+                )
+                .map_err(|_| CompletionError::ResponseError("Tool selection is empty".into()))?,
+            )
+        };
+
+        Ok(completion::CompletionResponse {
+            choice,
+            usage: (&value).into(),
+            raw_response: value,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Message {
+    pub role: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// Name of the tool whose result is contained in a `tool` message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+}
+
+impl From<completion::Message> for Message {
+    fn from(message: completion::Message) -> Self {
+        // Ollama does not assign ids to tool calls, tool results refer to the tool by name
+        let tool_name = if message.role == "tool" {
+            message.name
+        } else {
+            None
+        };
+
+        Self {
+            role: message.role,
+            content: message.content,
+            tool_calls: message.tool_calls.into_iter().map(ToolCall::from).collect(),
+            tool_name,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ToolCall {
+    pub function: Function,
+}
+
+/// Unlike OpenAI, Ollama sends and expects the arguments of tool calls as JSON objects
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Function {
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+impl From<completion::ToolCall> for ToolCall {
+    fn from(tool_call: completion::ToolCall) -> Self {
+        Self {
+            function: Function {
+                name: tool_call.name,
+                arguments: tool_call.arguments,
+            },
+        }
+    }
+}
+
+impl From<ToolCall> for completion::ToolCall {
+    fn from(tool_call: ToolCall) -> Self {
+        Self {
+            id: tool_call.function.name.clone(),
+            name: tool_call.function.name,
+            arguments: tool_call.function.arguments,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ToolDefinition {
+    pub r#type: String,
+    pub function: completion::ToolDefinition,
+}
+
+impl From<completion::ToolDefinition> for ToolDefinition {
+    fn from(tool: completion::ToolDefinition) -> Self {
+        Self {
+            r#type: "function".into(),
+            function: tool,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CompletionModel {
+    client: Client,
+    /// Name of the model (e.g.: llama3.2)
+    pub model: String,
+}
+
+impl CompletionModel {
+    pub fn new(client: Client, model: &str) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+        }
+    }
+
+    fn create_completion_request(
+        &self,
+        completion_request: CompletionRequest,
+        stream: bool,
+    ) -> serde_json::Value {
+        // Add preamble to chat history (if available)
+        let mut full_history = if let Some(preamble) = &completion_request.preamble {
+            vec![Message {
+                role: "system".into(),
+                content: preamble.clone(),
+                tool_calls: vec![],
+                tool_name: None,
+            }]
+        } else {
+            vec![]
+        };
+
+        // Extend existing chat history
+        full_history.extend(
+            completion_request
+                .chat_history
+                .clone()
+                .into_iter()
+                .map(Message::from),
+        );
+
+        // Add final message (unless the request only continues the chat history)
+        if completion_request.has_prompt() {
+            full_history.push(Message::from(completion::Message::user(
+                completion_request.prompt_with_context(),
+            )));
+        }
+
+        let mut request = json!({
+            "model": self.model,
+            "messages": full_history,
+            "stream": stream,
+        });
+
+        if let Some(temperature) = completion_request.temperature {
+            request = json_utils::merge(request, json!({"options": {"temperature": temperature}}));
+        }
+
+        if !completion_request.tools.is_empty() {
+            request = json_utils::merge(
+                request,
+                json!({
+                    "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
+                }),
+            );
+        }
+
+        if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        }
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request, false);
+
+        let response = self.client.post("/api/chat").json(&request).send().await?;
+
+        if response.status().is_success() {
+            match response.json::<ApiResponse<CompletionResponse>>().await? {
+                ApiResponse::Ok(response) => {
+                    tracing::info!(target: "rig",
+                        "Ollama completion token usage: {}",
+                        usage::Usage::from(&response)
+                    );
+                    response.try_into()
+                }
+                ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.error)),
+            }
+        } else {
+            Err(CompletionError::from_response(response).await)
+        }
+    }
+}
+
+// ================================================================
+// Ollama Streaming API
+// ================================================================
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let request = self.create_completion_request(completion_request, true);
+
+        let response = self.client.post("/api/chat").json(&request).send().await?;
+
+        if !response.status().is_success() {
+            return Err(CompletionError::from_response(response).await);
+        }
+
+        // Ollama streams newline-delimited JSON objects rather than server-sent events
+        Ok(streaming::decode(
+            streaming::lines(response),
+            (),
+            |_, line| {
+                if line.trim().is_empty() {
+                    return Ok(vec![]);
+                }
+
+                let chunk = match serde_json::from_str::<ApiResponse<CompletionResponse>>(&line)? {
+                    ApiResponse::Ok(chunk) => chunk,
+                    ApiResponse::Err(err) => return Err(CompletionError::ProviderError(err.error)),
+                };
+
+                let mut choices = vec![];
+                if !chunk.message.content.is_empty() {
+                    choices.push(StreamingChoice::Message(chunk.message.content));
+                }
+                // Ollama does not stream tool call arguments: tool calls are yielded
+                // as soon as they are received.
+                choices.extend(
+                    chunk
+                        .message
+                        .tool_calls
+                        .into_iter()
+                        .map(|call| StreamingChoice::ToolCall(call.into())),
+                );
+
+                Ok(choices)
+            },
+        ))
+    }
+}
@@ -13,7 +13,11 @@ use crate::{
     completion::{self, CompletionError, CompletionRequest},
     embeddings::{self, EmbeddingError, EmbeddingsBuilder},
     extractor::ExtractorBuilder,
-    json_utils, Embed,
+    json_utils,
+    streaming::{
+        self, StreamingChoice, StreamingCompletionModel, StreamingResult, ToolCallAccumulator,
+    },
+    usage, Embed, OneOrMany,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -235,6 +239,16 @@ impl std::fmt::Display for Usage {
     }
 }
 
+impl From<&Usage> for usage::Usage {
+    fn from(usage: &Usage) -> Self {
+        Self {
+            input_tokens: usage.prompt_tokens as u64,
+            output_tokens: usage.total_tokens.saturating_sub(usage.prompt_tokens) as u64,
+            total_tokens: usage.total_tokens as u64,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct EmbeddingModel {
     client: Client,
@@ -251,8 +265,15 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
 
     async fn embed_texts(
         &self,
-        documents: impl IntoIterator<Item = String>,
+        documents: impl IntoIterator<Item = String> + Send,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        Ok(self.embed_texts_with_usage(documents).await?.0)
+    }
+
+    async fn embed_texts_with_usage(
+        &self,
+        documents: impl IntoIterator<Item = String> + Send,
+    ) -> Result<(Vec<embeddings::Embedding>, usage::Usage), EmbeddingError> {
         let documents = documents.into_iter().collect::<Vec<_>>();
 
         let response = self
@@ -279,7 +300,8 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                         ));
                     }
 
-                    Ok(response
+                    let usage = (&response.usage).into();
+                    let embeddings = response
                         .data
                         .into_iter()
                         .zip(documents.into_iter())
@@ -287,12 +309,14 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                             document,
                             vec: embedding.embedding,
                         })
-                        .collect())
+                        .collect();
+
+                    Ok((embeddings, usage))
                 }
                 ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
             }
         } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
+            Err(EmbeddingError::from_response(response).await)
         }
     }
 }
@@ -383,15 +407,22 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
                     },
                 ..
             }, ..] => {
-                let call = calls.first().ok_or(CompletionError::ResponseError(
-                    "Tool selection is empty".into(),
-                ))?;
+                let calls = calls
+                    .iter()
+                    .map(|call| {
+                        Ok(completion::ToolCall {
+                            id: call.id.clone(),
+                            name: call.function.name.clone(),
+                            arguments: serde_json::from_str(&call.function.arguments)?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, CompletionError>>()?;
 
                 Ok(completion::CompletionResponse {
-                    choice: completion::ModelChoice::ToolCall(
-                        call.function.name.clone(),
-                        serde_json::from_str(&call.function.arguments)?,
-                    ),
+                    usage: value.usage.as_ref().map(Into::into).unwrap_or_default(),
+                    choice: completion::ModelChoice::ToolCall(OneOrMany::many(calls).map_err(
+                        |_| CompletionError::ResponseError("Tool selection is empty".into()),
+                    )?),
                     raw_response: value,
                 })
             }
@@ -409,6 +440,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
                         .collect::<Vec<_>>()
                         .join("")
                 ),
+                usage: value.usage.as_ref().map(Into::into).unwrap_or_default(),
                 raw_response: value,
             }),
             _ => Err(CompletionError::ResponseError(
@@ -449,6 +481,34 @@ pub struct Message {
     pub content: Option<Vec<ContentItem>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ToolCall>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl From<completion::Message> for Message {
+    fn from(message: completion::Message) -> Self {
+        // Assistant messages that only request tool calls have no content
+        let content = if message.content.is_empty() && !message.tool_calls.is_empty() {
+            None
+        } else {
+            Some(vec![ContentItem {
+                content_type: "text".to_string(),
+                text: Some(message.content),
+                image_url: None,
+            }])
+        };
+
+        Self {
+            role: message.role,
+            content,
+            tool_calls: (!message.tool_calls.is_empty())
+                .then(|| message.tool_calls.into_iter().map(ToolCall::from).collect()),
+            tool_call_id: message.tool_call_id,
+            name: message.name,
+        }
+    }
 }
 
 // Add this function to handle both string and array content formats
@@ -482,6 +542,19 @@ pub struct ToolCall {
     pub function: Function,
 }
 
+impl From<completion::ToolCall> for ToolCall {
+    fn from(tool_call: completion::ToolCall) -> Self {
+        Self {
+            id: tool_call.id,
+            r#type: "function".into(),
+            function: Function {
+                name: tool_call.name,
+                arguments: tool_call.arguments.to_string(),
+            },
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ToolDefinition {
     pub r#type: String,
@@ -517,15 +590,11 @@ impl CompletionModel {
             model: model.to_string(),
         }
     }
-}
-
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
 
-    async fn completion(
+    fn create_completion_request(
         &self,
-         completion_request: CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        completion_request: CompletionRequest,
+    ) -> serde_json::Value {
         // Add preamble to chat history (if available)
         let mut full_history = if let Some(preamble) = &completion_request.preamble {
             vec![Message {
@@ -536,46 +605,49 @@ impl completion::CompletionModel for CompletionModel {
                     image_url: None,
                 }]),
                 tool_calls: None,
+                tool_call_id: None,
+                name: None,
             }]
         } else {
             vec![]
         };
 
         // Extend existing chat history
-        full_history.extend(completion_request.chat_history.clone().into_iter().map(|msg| Message {
-            role: msg.role,
-            content: Some(vec![ContentItem {
+        full_history.extend(
+            completion_request
+                .chat_history
+                .clone()
+                .into_iter()
+                .map(Message::from),
+        );
+
+        // Add final message (unless the request only continues the chat history)
+        if completion_request.has_prompt() {
+            let mut content = vec![ContentItem {
                 content_type: "text".to_string(),
-                text: Some(msg.content),
+                text: Some(completion_request.prompt_with_context()),
                 image_url: None,
-            }]),
-            tool_calls: None,
-        }));
-
-        // Create final message content
-        let mut content = vec![ContentItem {
-            content_type: "text".to_string(),
-            text: Some(completion_request.prompt_with_context()),
-            image_url: None,
-        }];
-
-        // Add image URLs if present
-        if let Some(urls) = completion_request.image_urls {
-            for url in urls {
-                content.push(ContentItem {
-                    content_type: "image_url".to_string(),
-                    text: None,
-                    image_url: Some(ImageUrl { url }),
-                });
+            }];
+
+            // Add image URLs if present
+            if let Some(urls) = completion_request.image_urls {
+                for url in urls {
+                    content.push(ContentItem {
+                        content_type: "image_url".to_string(),
+                        text: None,
+                        image_url: Some(ImageUrl { url }),
+                    });
+                }
             }
-        }
 
-        // Add final message
-        full_history.push(Message {
-            role: "user".into(),
-            content: Some(content),
-            tool_calls: None,
-        });
+            full_history.push(Message {
+                role: "user".into(),
+                content: Some(content),
+                tool_calls: None,
+                tool_call_id: None,
+                name: None,
+            });
+        }
 
         let request = if completion_request.tools.is_empty() {
             json!({
@@ -593,16 +665,27 @@ impl completion::CompletionModel for CompletionModel {
             })
         };
 
+        if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        }
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request);
+
         let response = self
             .client
             .post("/chat/completions")
-            .json(
-                &if let Some(params) = completion_request.additional_params {
-                    json_utils::merge(request, params)
-                } else {
-                    request
-                },
-            )
+            .json(&request)
             .send()
             .await?;
 
@@ -618,7 +701,109 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
+        }
+    }
+}
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let request = json_utils::merge(
+            self.create_completion_request(completion_request),
+            json!({"stream": true}),
+        );
+
+        let response = self
+            .client
+            .post("/chat/completions")
+            .json(&request)
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            Ok(streaming_response(response))
+        } else {
+            Err(CompletionError::from_response(response).await)
         }
     }
 }
+
+// ================================================================
+// OpenAI Streaming API
+// ================================================================
+#[derive(Debug, Deserialize)]
+struct StreamingChunk {
+    #[serde(default)]
+    choices: Vec<StreamingChunkChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamingChunkChoice {
+    #[serde(default)]
+    delta: StreamingDelta,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct StreamingDelta {
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Option<Vec<StreamingToolCall>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamingToolCall {
+    index: usize,
+    id: Option<String>,
+    #[serde(default)]
+    function: StreamingFunction,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct StreamingFunction {
+    name: Option<String>,
+    #[serde(default)]
+    arguments: String,
+}
+
+/// Decode a streaming chat completion response in the OpenAI format (i.e.: server-sent events
+/// containing `chat.completion.chunk` objects). Also used by OpenAI-compatible providers.
+pub(crate) fn streaming_response(response: reqwest::Response) -> StreamingResult {
+    streaming::decode(
+        streaming::sse_events(response),
+        ToolCallAccumulator::default(),
+        |tool_calls, event| {
+            if event.data == "[DONE]" {
+                return tool_calls.finish_all();
+            }
+
+            let chunk = serde_json::from_str::<StreamingChunk>(&event.data)?;
+            let mut choices = vec![];
+
+            // Only the first choice is streamed back, similarly to non-streaming completions
+            if let Some(choice) = chunk.choices.into_iter().next() {
+                if let Some(content) = choice.delta.content.filter(|text| !text.is_empty()) {
+                    choices.push(StreamingChoice::Message(content));
+                }
+
+                for call in choice.delta.tool_calls.unwrap_or_default() {
+                    choices.push(tool_calls.delta(
+                        call.index,
+                        call.id,
+                        call.function.name,
+                        call.function.arguments,
+                    ));
+                }
+
+                if choice.finish_reason.is_some() {
+                    choices.extend(tool_calls.finish_all()?);
+                }
+            }
+
+            Ok(choices)
+        },
+    )
+}
@@ -13,7 +13,7 @@ use crate::{
     agent::AgentBuilder,
     completion::{self, CompletionError},
     extractor::ExtractorBuilder,
-    json_utils,
+    json_utils, usage,
 };
 
 use schemars::JsonSchema;
@@ -161,6 +161,16 @@ impl std::fmt::Display for Usage {
     }
 }
 
+impl From<&Usage> for usage::Usage {
+    fn from(usage: &Usage) -> Self {
+        Self {
+            input_tokens: usage.prompt_tokens as u64,
+            output_tokens: usage.completion_tokens as u64,
+            total_tokens: usage.total_tokens as u64,
+        }
+    }
+}
+
 impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionResponse> {
     type Error = CompletionError;
 
@@ -171,6 +181,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
                 ..
             }, ..] => Ok(completion::CompletionResponse {
                 choice: completion::ModelChoice::Message(content.to_string()),
+                usage: (&value.usage).into(),
                 raw_response: value,
             }),
             _ => Err(CompletionError::ResponseError(
@@ -207,22 +218,21 @@ impl completion::CompletionModel for CompletionModel {
             vec![completion::Message {
                 role: "system".into(),
                 content: preamble.clone(),
+                ..Default::default()
             }]
         } else {
             vec![]
         };
 
-        // Add context documents to chat history
-        let prompt_with_context = completion_request.prompt_with_context();
-
         // Add chat history to messages
-        messages.extend(completion_request.chat_history);
+        messages.extend(completion_request.chat_history.clone());
 
-        // Add user prompt to messages
-        messages.push(completion::Message {
-            role: "user".to_string(),
-            content: prompt_with_context,
-        });
+        // Add user prompt (along with context documents) to messages
+        if completion_request.has_prompt() {
+            messages.push(completion::Message::user(
+                completion_request.prompt_with_context(),
+            ));
+        }
 
         let request = json!({
             "model": self.model,
@@ -255,7 +265,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(error) => Err(CompletionError::ProviderError(error.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 }
@@ -24,7 +24,9 @@ impl Client {
     pub fn new(api_key: &str) -> Self {
         Self::from_url(api_key, XAI_BASE_URL)
     }
-    fn from_url(api_key: &str, base_url: &str) -> Self {
+
+    /// Create a new xAI client with the given API key and base API URL.
+    pub fn from_url(api_key: &str, base_url: &str) -> Self {
         Self {
             base_url: base_url.to_string(),
             http_client: reqwest::Client::builder()
@@ -6,6 +6,8 @@
 use crate::{
     completion::{self, CompletionError},
     json_utils,
+    providers::openai,
+    streaming::{StreamingCompletionModel, StreamingResult},
 };
 
 use serde_json::json;
@@ -33,33 +35,35 @@ impl CompletionModel {
             model: model.to_string(),
         }
     }
-}
-
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
 
-    async fn completion(
+    fn create_completion_request(
         &self,
         mut completion_request: completion::CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> serde_json::Value {
         let mut messages = if let Some(preamble) = &completion_request.preamble {
             vec![completion::Message {
                 role: "system".into(),
                 content: preamble.clone(),
+                ..Default::default()
             }]
         } else {
             vec![]
         };
         messages.append(&mut completion_request.chat_history);
 
-        let prompt_with_context = completion_request.prompt_with_context();
+        if completion_request.has_prompt() {
+            messages.push(completion::Message::user(
+                completion_request.prompt_with_context(),
+            ));
+        }
 
-        messages.push(completion::Message {
-            role: "user".into(),
-            content: prompt_with_context,
-        });
+        // xAI expects messages in the same format as OpenAI (e.g.: for tool calls and results)
+        let messages = messages
+            .into_iter()
+            .map(openai::Message::from)
+            .collect::<Vec<_>>();
 
-        let mut request = if completion_request.tools.is_empty() {
+        let request = if completion_request.tools.is_empty() {
             json!({
                 "model": self.model,
                 "messages": messages,
@@ -75,11 +79,22 @@ impl completion::CompletionModel for CompletionModel {
             })
         };
 
-        request = if let Some(params) = completion_request.additional_params {
+        if let Some(params) = completion_request.additional_params {
             json_utils::merge(request, params)
         } else {
             request
-        };
+        }
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    async fn completion(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request);
 
         let response = self
             .client
@@ -94,7 +109,33 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Error(error) => Err(CompletionError::ProviderError(error.message())),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
+        }
+    }
+}
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let request = json_utils::merge(
+            self.create_completion_request(completion_request),
+            json!({"stream": true}),
+        );
+
+        let response = self
+            .client
+            .post("/v1/chat/completions")
+            .json(&request)
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            // xAI streams chat completion chunks in the same format as OpenAI
+            Ok(openai::streaming_response(response))
+        } else {
+            Err(CompletionError::from_response(response).await)
         }
     }
 }
@@ -102,24 +143,16 @@ impl completion::CompletionModel for CompletionModel {
 pub mod xai_api_types {
     use serde::{Deserialize, Serialize};
 
-    use crate::completion::{self, CompletionError};
+    use crate::{
+        completion::{self, CompletionError},
+        usage, OneOrMany,
+    };
 
     impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionResponse> {
         type Error = CompletionError;
 
         fn try_from(value: CompletionResponse) -> std::prelude::v1::Result<Self, Self::Error> {
             match value.choices.as_slice() {
-                [Choice {
-                    message:
-                        Message {
-                            content: Some(content),
-                            ..
-                        },
-                    ..
-                }, ..] => Ok(completion::CompletionResponse {
-                    choice: completion::ModelChoice::Message(content.to_string()),
-                    raw_response: value,
-                }),
                 [Choice {
                     message:
                         Message {
@@ -128,18 +161,37 @@ pub mod xai_api_types {
                         },
                     ..
                 }, ..] => {
-                    let call = calls.first().ok_or(CompletionError::ResponseError(
-                        "Tool selection is empty".into(),
-                    ))?;
+                    let calls = calls
+                        .iter()
+                        .map(|call| {
+                            Ok(completion::ToolCall {
+                                id: call.id.clone(),
+                                name: call.function.name.clone(),
+                                arguments: serde_json::from_str(&call.function.arguments)?,
+                            })
+                        })
+                        .collect::<Result<Vec<_>, CompletionError>>()?;
 
                     Ok(completion::CompletionResponse {
-                        choice: completion::ModelChoice::ToolCall(
-                            call.function.name.clone(),
-                            serde_json::from_str(&call.function.arguments)?,
-                        ),
+                        usage: (&value.usage).into(),
+                        choice: completion::ModelChoice::ToolCall(OneOrMany::many(calls).map_err(
+                            |_| CompletionError::ResponseError("Tool selection is empty".into()),
+                        )?),
                         raw_response: value,
                     })
                 }
+                [Choice {
+                    message:
+                        Message {
+                            content: Some(content),
+                            ..
+                        },
+                    ..
+                }, ..] => Ok(completion::CompletionResponse {
+                    choice: completion::ModelChoice::Message(content.to_string()),
+                    usage: (&value.usage).into(),
+                    raw_response: value,
+                }),
                 _ => Err(CompletionError::ResponseError(
                     "Response did not contain a message or tool call".into(),
                 )),
@@ -206,4 +258,14 @@ pub mod xai_api_types {
         pub prompt_tokens: i32,
         pub total_tokens: i32,
     }
+
+    impl From<&Usage> for usage::Usage {
+        fn from(usage: &Usage) -> Self {
+            Self {
+                input_tokens: usage.prompt_tokens as u64,
+                output_tokens: usage.completion_tokens as u64,
+                total_tokens: usage.total_tokens as u64,
+            }
+        }
+    }
 }
@@ -6,7 +6,10 @@
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::embeddings::{self, EmbeddingError};
+use crate::{
+    embeddings::{self, EmbeddingError},
+    usage,
+};
 
 use super::{
     client::xai_api_types::{ApiErrorResponse, ApiResponse},
@@ -55,6 +58,16 @@ pub struct Usage {
     pub total_tokens: usize,
 }
 
+impl From<&Usage> for usage::Usage {
+    fn from(usage: &Usage) -> Self {
+        Self {
+            input_tokens: usage.prompt_tokens as u64,
+            output_tokens: 0,
+            total_tokens: usage.total_tokens as u64,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct EmbeddingModel {
     client: Client,
@@ -71,8 +84,15 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
 
     async fn embed_texts(
         &self,
-        documents: impl IntoIterator<Item = String>,
+        documents: impl IntoIterator<Item = String> + Send,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        Ok(self.embed_texts_with_usage(documents).await?.0)
+    }
+
+    async fn embed_texts_with_usage(
+        &self,
+        documents: impl IntoIterator<Item = String> + Send,
+    ) -> Result<(Vec<embeddings::Embedding>, usage::Usage), EmbeddingError> {
         let documents = documents.into_iter().collect::<Vec<_>>();
 
         let response = self
@@ -94,7 +114,8 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                         ));
                     }
 
-                    Ok(response
+                    let usage = (&response.usage).into();
+                    let embeddings = response
                         .data
                         .into_iter()
                         .zip(documents.into_iter())
@@ -102,12 +123,14 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                             document,
                             vec: embedding.embedding,
                         })
-                        .collect())
+                        .collect();
+
+                    Ok((embeddings, usage))
                 }
                 ApiResponse::Error(err) => Err(EmbeddingError::ProviderError(err.message())),
             }
         } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
+            Err(EmbeddingError::from_response(response).await)
         }
     }
 }
@@ -0,0 +1,290 @@
+//! This module provides completion model wrappers that make completion requests resilient to
+//! transient provider errors (e.g.: rate limits, overloaded servers, timeouts).
+//!
+//! - [RetryModel] retries requests that failed with a retryable [CompletionError] using
+//!   exponential backoff with jitter, waiting as long as the provider asked to when it sends
+//!   a `Retry-After` header.
+//! - [FallbackModel] sends the request to a second model (possibly of another provider) when
+//!   the first one fails with a retryable error.
+//!
+//! Both wrappers are completion models themselves, so they compose with any provider and
+//! can be used anywhere a completion model is expected (e.g.: to build an agent).
+//!
+//! The same [RetryPolicy] is used by [crate::embeddings::EmbeddingsBuilder] to retry the
+//! batches of texts whose embedding failed with a retryable [EmbeddingError].
+//!
+//! # Example
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use rig::{
+//!     providers::{anthropic, openai},
+//!     retry::{RetryModel, RetryPolicy},
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//! let anthropic = anthropic::Client::from_env();
+//!
+//! let policy = RetryPolicy::new()
+//!     .max_retries(5)
+//!     .initial_backoff(Duration::from_secs(1));
+//!
+//! // Retry GPT-4o up to 5 times, then try Claude 3.5 Sonnet up to 3 times
+//! let model = RetryModel::with_policy(openai.completion_model(openai::GPT_4O), policy)
+//!     .fallback(RetryModel::new(
+//!         anthropic.completion_model(anthropic::CLAUDE_3_5_SONNET),
+//!     ));
+//! ```
+use std::{future::Future, time::Duration};
+
+use crate::{
+    completion::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse},
+    embeddings::EmbeddingError,
+    streaming::{StreamingCompletionModel, StreamingResult},
+};
+
+/// Policy defining how many times, and how long after, failed requests are retried.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of times a request is retried after the first attempt
+    pub max_retries: usize,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between two attempts, regardless of the number of retries
+    pub max_backoff: Duration,
+    /// Factor by which the delay is multiplied after each retry
+    pub multiplier: f64,
+    /// Whether the delay is randomized (between half and all of it) so that concurrent
+    /// requests do not all hit the provider again at the same time
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of times a request is retried
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set the delay before the first retry
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Set the maximum delay between two attempts
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Set the factor by which the delay is multiplied after each retry
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Enable or disable the randomization of delays
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Delay before the given retry (starting at 0 for the first retry)
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .mul_f64(self.multiplier.powi(retry.min(i32::MAX as usize) as i32))
+            .min(self.max_backoff);
+
+        if self.jitter {
+            backoff.mul_f64(0.5 + fastrand::f64() / 2.0)
+        } else {
+            backoff
+        }
+    }
+
+    /// Run `attempt` until it succeeds, fails with an error that is not retryable or
+    /// the maximum number of retries is reached.
+    pub(crate) async fn run<T, E, F, Fut>(&self, mut attempt: F) -> Result<T, E>
+    where
+        E: Retryable,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut retry = 0;
+        loop {
+            match attempt().await {
+                Err(err) if err.is_retryable() && retry < self.max_retries => {
+                    let delay = err.retry_after().unwrap_or_else(|| self.backoff(retry));
+                    tracing::warn!(target: "rig",
+                        "Request failed ({}), retrying in {:?} ({}/{})",
+                        err, delay, retry + 1, self.max_retries
+                    );
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Errors of provider requests that [RetryPolicy::run] knows how to retry
+pub(crate) trait Retryable: std::fmt::Display {
+    fn is_retryable(&self) -> bool;
+
+    fn retry_after(&self) -> Option<Duration>;
+}
+
+impl Retryable for CompletionError {
+    fn is_retryable(&self) -> bool {
+        CompletionError::is_retryable(self)
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        CompletionError::retry_after(self)
+    }
+}
+
+impl Retryable for EmbeddingError {
+    fn is_retryable(&self) -> bool {
+        EmbeddingError::is_retryable(self)
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        EmbeddingError::retry_after(self)
+    }
+}
+
+/// Completion model wrapper that retries requests failing with a retryable error
+/// (see [CompletionError::is_retryable]) according to a [RetryPolicy].
+///
+/// Only establishing a stream is retried when streaming, errors occurring after chunks
+/// have been received are returned as is.
+#[derive(Clone)]
+pub struct RetryModel<M> {
+    model: M,
+    policy: RetryPolicy,
+}
+
+impl<M: CompletionModel> RetryModel<M> {
+    /// Wrap `model` with the default [RetryPolicy]
+    pub fn new(model: M) -> Self {
+        Self::with_policy(model, RetryPolicy::default())
+    }
+
+    pub fn with_policy(model: M, policy: RetryPolicy) -> Self {
+        Self { model, policy }
+    }
+
+    /// Fall back to `fallback` when all the retries of this model failed
+    pub fn fallback<F: CompletionModel>(self, fallback: F) -> FallbackModel<Self, F> {
+        FallbackModel::new(self, fallback)
+    }
+}
+
+impl<M: CompletionModel> CompletionModel for RetryModel<M> {
+    type Response = M::Response;
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<M::Response>, CompletionError> {
+        self.policy
+            .run(|| self.model.completion(request.clone()))
+            .await
+    }
+}
+
+impl<M: StreamingCompletionModel> StreamingCompletionModel for RetryModel<M> {
+    async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        self.policy.run(|| self.model.stream(request.clone())).await
+    }
+}
+
+/// Raw response of a [FallbackModel], depending on which of its models produced it
+#[derive(Debug)]
+pub enum FallbackResponse<P, F> {
+    Primary(P),
+    Fallback(F),
+}
+
+/// Completion model wrapper that sends requests to a fallback model when the primary model
+/// fails with a retryable error (see [CompletionError::is_retryable]). Other errors
+/// (e.g.: invalid request) are returned as is.
+#[derive(Clone)]
+pub struct FallbackModel<P, F> {
+    primary: P,
+    fallback: F,
+}
+
+impl<P: CompletionModel, F: CompletionModel> FallbackModel<P, F> {
+    pub fn new(primary: P, fallback: F) -> Self {
+        Self { primary, fallback }
+    }
+}
+
+fn map_response<R, T>(
+    response: CompletionResponse<R>,
+    map: impl FnOnce(R) -> T,
+) -> CompletionResponse<T> {
+    CompletionResponse {
+        choice: response.choice,
+        usage: response.usage,
+        raw_response: map(response.raw_response),
+    }
+}
+
+impl<P: CompletionModel, F: CompletionModel> CompletionModel for FallbackModel<P, F> {
+    type Response = FallbackResponse<P::Response, F::Response>;
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        match self.primary.completion(request.clone()).await {
+            Ok(response) => Ok(map_response(response, FallbackResponse::Primary)),
+            Err(err) if err.is_retryable() => {
+                tracing::warn!(target: "rig",
+                    "Completion request failed ({}), falling back to the fallback model", err
+                );
+                let response = self.fallback.completion(request).await?;
+                Ok(map_response(response, FallbackResponse::Fallback))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl<P: StreamingCompletionModel, F: StreamingCompletionModel> StreamingCompletionModel
+    for FallbackModel<P, F>
+{
+    async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        match self.primary.stream(request.clone()).await {
+            Err(err) if err.is_retryable() => {
+                tracing::warn!(target: "rig",
+                    "Streaming completion request failed ({}), falling back to the fallback model",
+                    err
+                );
+                self.fallback.stream(request).await
+            }
+            result => result,
+        }
+    }
+}
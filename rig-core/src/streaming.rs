@@ -0,0 +1,365 @@
+//! This module provides functionality for working with streaming completion models.
+//! It provides traits and types for generating streaming completion requests and
+//! handling streaming completion responses.
+//!
+//! The main traits defined in this module are:
+//! - [StreamingPrompt]: Defines a high-level streaming LLM one-shot prompt interface
+//! - [StreamingChat]: Defines a high-level streaming LLM chat interface with history
+//! - [StreamingCompletionModel]: Defines a completion model that can be streamed
+//!
+//! Streaming responses are returned as a [StreamingResult], a stream of [StreamingChoice]
+//! items that are yielded as soon as the provider sends them.
+//!
+//! Example Usage:
+//! ```rust,no_run
+//! use futures::StreamExt;
+//! use rig::{providers::openai, streaming::{StreamingChoice, StreamingPrompt}};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let openai = openai::Client::from_env();
+//!
+//!     let agent = openai.agent(openai::GPT_4O)
+//!         .preamble("You are a helpful assistant.")
+//!         .build();
+//!
+//!     let mut stream = agent.stream_prompt("Tell me a story")
+//!         .await
+//!         .expect("Failed to start the stream");
+//!
+//!     while let Some(chunk) = stream.next().await {
+//!         match chunk.expect("Failed to read chunk") {
+//!             StreamingChoice::Message(text) => print!("{text}"),
+//!             StreamingChoice::ToolCallDelta { .. } => (),
+//!             StreamingChoice::ToolCall(call) => {
+//!                 println!("\nTool call: {} {}", call.name, call.arguments)
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+use std::{collections::BTreeMap, future::Future, pin::Pin};
+
+use futures::{stream, Stream, StreamExt};
+
+use crate::completion::{CompletionError, CompletionModel, CompletionRequest, Message, ToolCall};
+
+/// Enum representing a chunk of a streaming completion response.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamingChoice {
+    /// A text delta of the message being generated by the model
+    Message(String),
+    /// A fragment of the JSON arguments of a tool call being generated by the model.
+    /// `index` identifies the tool call within the response. `id` and `name` are
+    /// only guaranteed to be set on the first delta of a given tool call.
+    ToolCallDelta {
+        index: usize,
+        id: Option<String>,
+        name: Option<String>,
+        arguments: String,
+    },
+    /// A complete tool call, yielded once all of its arguments have been received.
+    ToolCall(ToolCall),
+}
+
+/// The stream of chunks returned by a [StreamingCompletionModel].
+pub type StreamingResult =
+    Pin<Box<dyn Stream<Item = Result<StreamingChoice, CompletionError>> + Send>>;
+
+/// Trait defining a high-level streaming LLM prompt interface (i.e.: prompt in, stream of chunks out).
+pub trait StreamingPrompt: Send + Sync {
+    /// Stream a simple prompt to the underlying completion model.
+    ///
+    /// Note: unlike [Prompt](crate::completion::Prompt), tool calls are not executed; they are
+    /// yielded to the caller as [StreamingChoice::ToolCallDelta] and [StreamingChoice::ToolCall] chunks.
+    fn stream_prompt(
+        &self,
+        prompt: &str,
+    ) -> impl Future<Output = Result<StreamingResult, CompletionError>> + Send;
+}
+
+/// Trait defining a high-level streaming LLM chat interface (i.e.: prompt and chat history in, stream of chunks out).
+pub trait StreamingChat: Send + Sync {
+    /// Stream a prompt with optional chat history to the underlying completion model.
+    ///
+    /// Note: unlike [Chat](crate::completion::Chat), tool calls are not executed; they are
+    /// yielded to the caller as [StreamingChoice::ToolCallDelta] and [StreamingChoice::ToolCall] chunks.
+    fn stream_chat(
+        &self,
+        prompt: &str,
+        chat_history: Vec<Message>,
+    ) -> impl Future<Output = Result<StreamingResult, CompletionError>> + Send;
+}
+
+/// Trait defining a completion model whose responses can be streamed.
+pub trait StreamingCompletionModel: CompletionModel {
+    /// Generates a streaming completion response for the given completion request.
+    fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> impl Future<Output = Result<StreamingResult, CompletionError>> + Send;
+}
+
+/// Helper function that prints the text chunks of a streaming completion response to stdout
+/// as they arrive and returns the full message once the stream is exhausted.
+/// Completed tool calls are printed on their own line.
+pub async fn stream_to_stdout(stream: &mut StreamingResult) -> Result<String, CompletionError> {
+    use std::io::Write;
+
+    let mut message = String::new();
+    while let Some(chunk) = stream.next().await {
+        match chunk? {
+            StreamingChoice::Message(text) => {
+                print!("{text}");
+                let _ = std::io::stdout().flush();
+                message.push_str(&text);
+            }
+            StreamingChoice::ToolCallDelta { .. } => (),
+            StreamingChoice::ToolCall(call) => {
+                println!("\nTool call: {}({})", call.name, call.arguments);
+            }
+        }
+    }
+    println!();
+
+    Ok(message)
+}
+
+// ================================================================
+// Provider helpers
+// ================================================================
+/// A single server-sent event, as described in the
+/// [HTML specification](https://html.spec.whatwg.org/multipage/server-sent-events.html).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct SseEvent {
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Split the body of a streaming HTTP response into lines (without line terminators).
+pub(crate) fn lines(
+    response: reqwest::Response,
+) -> impl Stream<Item = Result<String, CompletionError>> + Send {
+    stream::unfold(
+        (Some(response), Vec::<u8>::new()),
+        |(mut response, mut buffer)| async move {
+            loop {
+                if let Some(pos) = buffer.iter().position(|byte| *byte == b'\n') {
+                    let line = buffer.drain(..=pos).collect::<Vec<_>>();
+                    let line = String::from_utf8_lossy(&line)
+                        .trim_end_matches(['\n', '\r'])
+                        .to_string();
+                    return Some((Ok(line), (response, buffer)));
+                }
+
+                match response.as_mut() {
+                    Some(body) => match body.chunk().await {
+                        Ok(Some(chunk)) => buffer.extend_from_slice(&chunk),
+                        Ok(None) => response = None,
+                        Err(err) => return Some((Err(err.into()), (None, Vec::new()))),
+                    },
+                    // The body is exhausted, flush whatever is left in the buffer
+                    None if !buffer.is_empty() => {
+                        let line = String::from_utf8_lossy(&buffer)
+                            .trim_end_matches('\r')
+                            .to_string();
+                        return Some((Ok(line), (None, Vec::new())));
+                    }
+                    None => return None,
+                }
+            }
+        },
+    )
+}
+
+/// Parse the body of a streaming HTTP response as a stream of server-sent events.
+pub(crate) fn sse_events(
+    response: reqwest::Response,
+) -> impl Stream<Item = Result<SseEvent, CompletionError>> + Send {
+    stream::unfold(Some(Box::pin(lines(response))), |mut lines| async move {
+        let mut event = SseEvent::default();
+        let mut has_data = false;
+
+        loop {
+            let line = match lines.as_mut()?.next().await {
+                Some(Ok(line)) => line,
+                Some(Err(err)) => return Some((Err(err), None)),
+                // End of stream: dispatch the pending event, if any
+                None if has_data => return Some((Ok(event), None)),
+                None => return None,
+            };
+
+            if line.is_empty() {
+                if has_data {
+                    return Some((Ok(event), lines));
+                }
+                event = SseEvent::default();
+                continue;
+            }
+
+            // Lines starting with a colon are comments (e.g.: keep-alive pings)
+            if line.starts_with(':') {
+                continue;
+            }
+
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line.as_str(), ""),
+            };
+
+            match field {
+                "event" => event.event = Some(value.to_string()),
+                "data" => {
+                    if has_data {
+                        event.data.push('\n');
+                    }
+                    event.data.push_str(value);
+                    has_data = true;
+                }
+                _ => (),
+            }
+        }
+    })
+}
+
+/// Turn a stream of raw provider events into a [StreamingResult] using the given decoder.
+/// The decoder is given mutable access to some state (e.g.: partially received tool calls)
+/// and may produce any number of chunks for each event.
+pub(crate) fn decode<E, S, F>(
+    events: impl Stream<Item = Result<E, CompletionError>> + Send + 'static,
+    state: S,
+    mut decoder: F,
+) -> StreamingResult
+where
+    E: Send + 'static,
+    S: Send + 'static,
+    F: FnMut(&mut S, E) -> Result<Vec<StreamingChoice>, CompletionError> + Send + 'static,
+{
+    Box::pin(
+        events
+            .scan(state, move |state, event| {
+                futures::future::ready(Some(event.and_then(|event| decoder(state, event))))
+            })
+            .flat_map(|chunks| match chunks {
+                Ok(chunks) => stream::iter(chunks.into_iter().map(Ok)).left_stream(),
+                Err(err) => stream::once(futures::future::ready(Err(err))).right_stream(),
+            }),
+    )
+}
+
+/// Accumulates the argument deltas of tool calls that are streamed in fragments, keyed
+/// by the index of the tool call in the response.
+#[derive(Debug, Default)]
+pub(crate) struct ToolCallAccumulator {
+    calls: BTreeMap<usize, (String, String, String)>,
+}
+
+impl ToolCallAccumulator {
+    /// Record a tool call delta and return the corresponding chunk
+    pub fn delta(
+        &mut self,
+        index: usize,
+        id: Option<String>,
+        name: Option<String>,
+        arguments: String,
+    ) -> StreamingChoice {
+        let (call_id, call_name, call_arguments) = self.calls.entry(index).or_default();
+        if let Some(id) = &id {
+            call_id.clone_from(id);
+        }
+        if let Some(name) = &name {
+            call_name.clone_from(name);
+        }
+        call_arguments.push_str(&arguments);
+
+        StreamingChoice::ToolCallDelta {
+            index,
+            id,
+            name,
+            arguments,
+        }
+    }
+
+    /// Complete the tool call at the given index, if any
+    pub fn finish(&mut self, index: usize) -> Result<Option<StreamingChoice>, CompletionError> {
+        self.calls
+            .remove(&index)
+            .map(|(id, name, arguments)| Self::complete(id, name, arguments))
+            .transpose()
+    }
+
+    /// Complete all pending tool calls
+    pub fn finish_all(&mut self) -> Result<Vec<StreamingChoice>, CompletionError> {
+        std::mem::take(&mut self.calls)
+            .into_values()
+            .map(|(id, name, arguments)| Self::complete(id, name, arguments))
+            .collect()
+    }
+
+    fn complete(
+        id: String,
+        name: String,
+        arguments: String,
+    ) -> Result<StreamingChoice, CompletionError> {
+        let arguments = if arguments.trim().is_empty() {
+            serde_json::json!({})
+        } else {
+            serde_json::from_str(&arguments)?
+        };
+        // Some providers do not assign ids to tool calls, fall back to the tool name
+        let id = if id.is_empty() { name.clone() } else { id };
+
+        Ok(StreamingChoice::ToolCall(ToolCall {
+            id,
+            name,
+            arguments,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_call_accumulator() {
+        let mut acc = ToolCallAccumulator::default();
+
+        acc.delta(0, Some("call_1".into()), Some("add".into()), "".into());
+        acc.delta(0, None, None, "{\"x\": 1,".into());
+        acc.delta(1, None, Some("noop".into()), "".into());
+        acc.delta(0, None, None, " \"y\": 2}".into());
+
+        assert_eq!(
+            acc.finish(0).unwrap(),
+            Some(StreamingChoice::ToolCall(ToolCall {
+                id: "call_1".into(),
+                name: "add".into(),
+                arguments: serde_json::json!({"x": 1, "y": 2})
+            }))
+        );
+        assert_eq!(
+            acc.finish_all().unwrap(),
+            vec![StreamingChoice::ToolCall(ToolCall {
+                id: "noop".into(),
+                name: "noop".into(),
+                arguments: serde_json::json!({})
+            })]
+        );
+        assert!(acc.finish_all().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_tool_call_accumulator_invalid_arguments() {
+        let mut acc = ToolCallAccumulator::default();
+
+        acc.delta(
+            0,
+            Some("call_1".into()),
+            Some("add".into()),
+            "{\"x\":".into(),
+        );
+
+        assert!(matches!(acc.finish(0), Err(CompletionError::JsonError(_))));
+    }
+}
@@ -298,6 +298,20 @@ impl ToolSet {
         }
     }
 
+    /// Call the tools requested by the given tool calls concurrently and return their results
+    /// in the same order as the tool calls.
+    pub async fn call_many(
+        &self,
+        tool_calls: impl IntoIterator<Item = &completion::ToolCall>,
+    ) -> Result<Vec<String>, ToolSetError> {
+        futures::future::try_join_all(
+            tool_calls
+                .into_iter()
+                .map(|call| self.call(&call.name, call.arguments.to_string())),
+        )
+        .await
+    }
+
     /// Get the documents of all the tools in the toolset
     pub async fn documents(&self) -> Result<Vec<completion::Document>, ToolSetError> {
         let mut docs = Vec::new();
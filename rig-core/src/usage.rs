@@ -0,0 +1,203 @@
+//! This module provides provider-independent token usage accounting for completion and
+//! embedding requests, along with an optional per-model price table to estimate their cost.
+//!
+//! Every [CompletionResponse](crate::completion::CompletionResponse) carries the [Usage] of
+//! the request that produced it, and embedding models report their usage through
+//! [EmbeddingModel::embed_texts_with_usage](crate::embeddings::EmbeddingModel::embed_texts_with_usage).
+//!
+//! # Example
+//! ```rust
+//! use rig::usage::{ModelPricing, PriceTable, Usage};
+//!
+//! let prices = PriceTable::new()
+//!     .with_price("gpt-4o", ModelPricing::new(2.5, 10.0));
+//!
+//! let usage = Usage::new(1_000, 500);
+//!
+//! // Model versions are priced like the model they are a version of
+//! let cost = prices.cost("gpt-4o-2024-08-06", &usage).unwrap();
+//! assert!((cost - 0.0075).abs() < 1e-9);
+//! ```
+use std::{
+    collections::HashMap,
+    ops::{Add, AddAssign},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Number of tokens consumed by one or more requests to a model provider.
+/// Providers that do not report usage leave the fields at zero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Usage {
+    /// Number of tokens in the input (e.g.: prompt, chat history, documents or texts to embed)
+    pub input_tokens: u64,
+    /// Number of tokens generated by the model
+    pub output_tokens: u64,
+    /// Total number of tokens billed by the provider
+    pub total_tokens: u64,
+}
+
+impl Usage {
+    /// Create a new usage from the number of input and output tokens
+    pub fn new(input_tokens: u64, output_tokens: u64) -> Self {
+        Self {
+            input_tokens,
+            output_tokens,
+            total_tokens: input_tokens + output_tokens,
+        }
+    }
+}
+
+impl Add for Usage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            input_tokens: self.input_tokens + other.input_tokens,
+            output_tokens: self.output_tokens + other.output_tokens,
+            total_tokens: self.total_tokens + other.total_tokens,
+        }
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl std::iter::Sum for Usage {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+impl std::fmt::Display for Usage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Input tokens: {}\nOutput tokens: {}\nTotal tokens: {}",
+            self.input_tokens, self.output_tokens, self.total_tokens
+        )
+    }
+}
+
+/// Price of a model, in USD per million tokens.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct ModelPricing {
+    /// Price of a million input tokens
+    pub input: f64,
+    /// Price of a million output tokens
+    #[serde(default)]
+    pub output: f64,
+}
+
+impl ModelPricing {
+    /// Create a new pricing from the price of a million input and output tokens.
+    /// Embedding models only have an input price.
+    pub fn new(input: f64, output: f64) -> Self {
+        Self { input, output }
+    }
+
+    /// Cost of the given usage, in USD
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.input_tokens as f64 * self.input + usage.output_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+/// Table of model prices, keyed by model name.
+///
+/// Models that are not found in the table are looked up by the longest model name that
+/// prefixes them, such that dated model versions (e.g.: `gpt-4o-2024-08-06`) are priced
+/// like their base model (e.g.: `gpt-4o`).
+///
+/// The table can be deserialized from a map of model names to prices, e.g. in TOML:
+/// ```toml
+/// [gpt-4o]
+/// input = 2.5
+/// output = 10.0
+///
+/// [text-embedding-3-large]
+/// input = 0.13
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct PriceTable {
+    prices: HashMap<String, ModelPricing>,
+}
+
+impl PriceTable {
+    /// Create an empty price table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the price of a model to the table
+    pub fn with_price(mut self, model: &str, pricing: ModelPricing) -> Self {
+        self.prices.insert(model.to_string(), pricing);
+        self
+    }
+
+    /// Get the price of a model, if known
+    pub fn price(&self, model: &str) -> Option<&ModelPricing> {
+        self.prices.get(model).or_else(|| {
+            self.prices
+                .iter()
+                .filter(|(name, _)| model.starts_with(name.as_str()))
+                .max_by_key(|(name, _)| name.len())
+                .map(|(_, pricing)| pricing)
+        })
+    }
+
+    /// Cost of the given usage of a model, in USD, if the price of the model is known
+    pub fn cost(&self, model: &str, usage: &Usage) -> Option<f64> {
+        self.price(model).map(|pricing| pricing.cost(usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_sum() {
+        let usage = [Usage::new(10, 5), Usage::new(20, 0), Usage::default()]
+            .into_iter()
+            .sum::<Usage>();
+
+        assert_eq!(usage, Usage::new(30, 5));
+        assert_eq!(usage.total_tokens, 35);
+    }
+
+    #[test]
+    fn test_price_table_lookup() {
+        let prices = PriceTable::new()
+            .with_price("gpt-4o", ModelPricing::new(2.5, 10.0))
+            .with_price("gpt-4o-mini", ModelPricing::new(0.15, 0.6));
+
+        assert_eq!(prices.price("gpt-4o").unwrap().input, 2.5);
+        assert_eq!(prices.price("gpt-4o-2024-08-06").unwrap().input, 2.5);
+        assert_eq!(prices.price("gpt-4o-mini-2024-07-18").unwrap().input, 0.15);
+        assert!(prices.price("claude-3-5-sonnet-latest").is_none());
+    }
+
+    #[test]
+    fn test_price_table_cost() {
+        let prices: PriceTable = serde_json::from_value(serde_json::json!({
+            "gpt-4o": {"input": 2.5, "output": 10.0},
+            "text-embedding-3-large": {"input": 0.13},
+        }))
+        .unwrap();
+
+        let cost = prices
+            .cost("gpt-4o", &Usage::new(2_000_000, 100_000))
+            .unwrap();
+        assert!((cost - 6.0).abs() < 1e-9);
+
+        let cost = prices
+            .cost("text-embedding-3-large", &Usage::new(1_000_000, 0))
+            .unwrap();
+        assert!((cost - 0.13).abs() < 1e-9);
+    }
+}
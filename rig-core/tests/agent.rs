@@ -0,0 +1,478 @@
+mod common;
+
+use common::{MockResponse, MockServer};
+use rig::{
+    completion::{Chat, Message, PromptError, ToolDefinition},
+    providers::{anthropic, cohere, ollama, openai},
+    tool::Tool,
+    usage::Usage,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::Barrier;
+
+#[derive(Deserialize)]
+struct OperationArgs {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Math error")]
+struct MathError;
+
+#[derive(Deserialize, Serialize)]
+struct Adder;
+
+impl Tool for Adder {
+    const NAME: &'static str = "add";
+
+    type Error = MathError;
+    type Args = OperationArgs;
+    type Output = i32;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "add".to_string(),
+            description: "Add x and y together".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "x": {"type": "number", "description": "The first number to add"},
+                    "y": {"type": "number", "description": "The second number to add"}
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        Ok(args.x + args.y)
+    }
+}
+
+/// Tool that only completes once `n` calls are in flight at the same time
+struct Rendezvous(Arc<Barrier>);
+
+impl Tool for Rendezvous {
+    const NAME: &'static str = "add";
+
+    type Error = MathError;
+    type Args = OperationArgs;
+    type Output = i32;
+
+    async fn definition(&self, prompt: String) -> ToolDefinition {
+        Adder.definition(prompt).await
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        self.0.wait().await;
+        Ok(args.x + args.y)
+    }
+}
+
+fn openai_tool_call(id: &str, arguments: &str) -> MockResponse {
+    MockResponse::json(json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "created": 0,
+        "model": "gpt-4o",
+        "choices": [{
+            "index": 0,
+            "message": {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{
+                    "id": id,
+                    "type": "function",
+                    "function": {"name": "add", "arguments": arguments}
+                }]
+            },
+            "logprobs": null,
+            "finish_reason": "tool_calls"
+        }]
+    }))
+}
+
+fn openai_message(content: &str) -> MockResponse {
+    MockResponse::json(json!({
+        "id": "chatcmpl-2",
+        "object": "chat.completion",
+        "created": 0,
+        "model": "gpt-4o",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "logprobs": null,
+            "finish_reason": "stop"
+        }]
+    }))
+}
+
+#[tokio::test]
+async fn test_agent_tool_loop() {
+    let server = MockServer::start(vec![
+        openai_tool_call("call_1", r#"{"x": 2, "y": 3}"#),
+        openai_message("2 + 3 = 5"),
+    ])
+    .await;
+
+    let agent = openai::Client::from_url("test-key", &server.url)
+        .agent(openai::GPT_4O)
+        .preamble("You are a calculator.")
+        .tool(Adder)
+        .build();
+    let history = vec![Message::user("Hi"), Message::assistant("Hello!")];
+    let response = agent
+        .chat_with_trace("What is 2 + 3?", history)
+        .await
+        .unwrap();
+
+    assert_eq!(response.output, "2 + 3 = 5");
+    assert_eq!(response.turns, 2);
+    assert_eq!(response.tool_calls.len(), 1);
+    assert_eq!(response.tool_calls[0].0.id, "call_1");
+    assert_eq!(response.tool_calls[0].0.arguments, json!({"x": 2, "y": 3}));
+    assert_eq!(response.tool_calls[0].1, "5");
+    assert_eq!(
+        response
+            .messages
+            .iter()
+            .map(|message| message.role.as_str())
+            .collect::<Vec<_>>(),
+        vec!["user", "assistant", "tool", "assistant"]
+    );
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+
+    // The second request continues the chat with the tool call and its result
+    let messages = requests[1].body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 6);
+    assert_eq!(messages[3]["role"], "user");
+    assert_eq!(messages[3]["content"][0]["text"], "What is 2 + 3?");
+    assert_eq!(messages[4]["role"], "assistant");
+    assert_eq!(messages[4]["tool_calls"][0]["id"], "call_1");
+    assert_eq!(messages[4]["tool_calls"][0]["function"]["name"], "add");
+    assert_eq!(messages[5]["role"], "tool");
+    assert_eq!(messages[5]["tool_call_id"], "call_1");
+    assert_eq!(messages[5]["content"][0]["text"], "5");
+}
+
+#[tokio::test]
+async fn test_agent_chat_returns_final_message() {
+    let server = MockServer::start(vec![
+        openai_tool_call("call_1", r#"{"x": 1, "y": 1}"#),
+        openai_message("The answer is 2"),
+    ])
+    .await;
+
+    let agent = openai::Client::from_url("test-key", &server.url)
+        .agent(openai::GPT_4O)
+        .tool(Adder)
+        .build();
+
+    assert_eq!(
+        agent.chat("What is 1 + 1?", vec![]).await.unwrap(),
+        "The answer is 2"
+    );
+}
+
+#[tokio::test]
+async fn test_agent_max_turns() {
+    let server = MockServer::start(vec![
+        openai_tool_call("call_1", r#"{"x": 1, "y": 1}"#),
+        openai_tool_call("call_2", r#"{"x": 2, "y": 2}"#),
+    ])
+    .await;
+
+    let agent = openai::Client::from_url("test-key", &server.url)
+        .agent(openai::GPT_4O)
+        .tool(Adder)
+        .max_turns(2)
+        .build();
+
+    match agent.chat_with_trace("Add forever", vec![]).await {
+        Err(PromptError::MaxTurnsError {
+            max_turns,
+            chat_history,
+        }) => {
+            assert_eq!(max_turns, 2);
+            assert_eq!(chat_history.len(), 5);
+            assert_eq!(chat_history[4].content, "4");
+        }
+        other => panic!("Expected MaxTurnsError, got {other:?}"),
+    }
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn test_anthropic_agent_tool_loop() {
+    let server = MockServer::start(vec![
+        MockResponse::json(json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-3-5-sonnet-latest",
+            "content": [{"type": "tool_use", "id": "toolu_1", "name": "add", "input": {"x": 2, "y": 3}}],
+            "stop_reason": "tool_use",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        })),
+        MockResponse::json(json!({
+            "id": "msg_2",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-3-5-sonnet-latest",
+            "content": [{"type": "text", "text": "5"}],
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 20, "output_tokens": 1}
+        })),
+    ])
+    .await;
+
+    let agent = anthropic::ClientBuilder::new("test-key")
+        .base_url(&server.url)
+        .build()
+        .agent(anthropic::CLAUDE_3_5_SONNET)
+        .tool(Adder)
+        .build();
+    let response = agent
+        .chat_with_trace("What is 2 + 3?", vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(response.output, "5");
+    assert_eq!(response.usage, Usage::new(30, 6));
+
+    let messages = server.requests()[1].body["messages"].clone();
+    assert_eq!(
+        messages,
+        json!([
+            {"role": "user", "content": [{"type": "text", "text": "What is 2 + 3?"}]},
+            {"role": "assistant", "content": [{"type": "tool_use", "id": "toolu_1", "name": "add", "input": {"x": 2, "y": 3}}]},
+            {"role": "user", "content": [{"type": "tool_result", "tool_use_id": "toolu_1", "content": "5"}]},
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_cohere_agent_tool_loop() {
+    let server = MockServer::start(vec![
+        MockResponse::json(json!({
+            "text": "",
+            "generation_id": "gen_1",
+            "finish_reason": "COMPLETE",
+            "tool_calls": [{"name": "add", "parameters": {"x": 2, "y": 3}}]
+        })),
+        MockResponse::json(json!({
+            "text": "2 + 3 = 5",
+            "generation_id": "gen_2",
+            "finish_reason": "COMPLETE"
+        })),
+    ])
+    .await;
+
+    let agent = cohere::Client::from_url("test-key", &server.url)
+        .agent(cohere::COMMAND_R)
+        .tool(Adder)
+        .build();
+    let response = agent
+        .chat_with_trace("What is 2 + 3?", vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(response.output, "2 + 3 = 5");
+
+    let body = &server.requests()[1].body;
+    assert_eq!(body["message"], "");
+    assert_eq!(
+        body["chat_history"],
+        json!([
+            {"role": "USER", "message": "What is 2 + 3?"},
+            {"role": "CHATBOT", "message": "", "tool_calls": [{"name": "add", "parameters": {"x": 2, "y": 3}}]},
+        ])
+    );
+    assert_eq!(
+        body["tool_results"],
+        json!([{"call": {"name": "add", "parameters": {"x": 2, "y": 3}}, "outputs": [{"result": "5"}]}])
+    );
+}
+
+#[tokio::test]
+async fn test_agent_parallel_tool_calls() {
+    let server = MockServer::start(vec![
+        MockResponse::json(json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 0,
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [
+                        {"id": "call_1", "type": "function", "function": {"name": "add", "arguments": "{\"x\": 1, \"y\": 2}"}},
+                        {"id": "call_2", "type": "function", "function": {"name": "add", "arguments": "{\"x\": 3, \"y\": 4}"}}
+                    ]
+                },
+                "logprobs": null,
+                "finish_reason": "tool_calls"
+            }]
+        })),
+        openai_message("3 and 7"),
+    ])
+    .await;
+
+    let agent = openai::Client::from_url("test-key", &server.url)
+        .agent(openai::GPT_4O)
+        .tool(Rendezvous(Arc::new(Barrier::new(2))))
+        .build();
+
+    // The tool calls can only complete if they are executed concurrently
+    let response = tokio::time::timeout(
+        Duration::from_secs(5),
+        agent.chat_with_trace("What are 1 + 2 and 3 + 4?", vec![]),
+    )
+    .await
+    .expect("Tool calls should be executed concurrently")
+    .unwrap();
+
+    assert_eq!(response.output, "3 and 7");
+    assert_eq!(
+        response
+            .tool_calls
+            .iter()
+            .map(|(call, result)| (call.id.as_str(), result.as_str()))
+            .collect::<Vec<_>>(),
+        vec![("call_1", "3"), ("call_2", "7")]
+    );
+
+    let messages = server.requests()[1].body["messages"].clone();
+    assert_eq!(messages[2]["tool_calls"].as_array().unwrap().len(), 2);
+    assert_eq!(messages[3]["tool_call_id"], "call_1");
+    assert_eq!(messages[3]["content"][0]["text"], "3");
+    assert_eq!(messages[4]["tool_call_id"], "call_2");
+    assert_eq!(messages[4]["content"][0]["text"], "7");
+}
+
+#[tokio::test]
+async fn test_anthropic_parallel_tool_calls() {
+    let server = MockServer::start(vec![
+        MockResponse::json(json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-3-5-sonnet-latest",
+            "content": [
+                {"type": "text", "text": "Let me add those."},
+                {"type": "tool_use", "id": "toolu_1", "name": "add", "input": {"x": 1, "y": 2}},
+                {"type": "tool_use", "id": "toolu_2", "name": "add", "input": {"x": 3, "y": 4}}
+            ],
+            "stop_reason": "tool_use",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        })),
+        MockResponse::json(json!({
+            "id": "msg_2",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-3-5-sonnet-latest",
+            "content": [{"type": "text", "text": "3 and 7"}],
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 20, "output_tokens": 1}
+        })),
+    ])
+    .await;
+
+    let agent = anthropic::ClientBuilder::new("test-key")
+        .base_url(&server.url)
+        .build()
+        .agent(anthropic::CLAUDE_3_5_SONNET)
+        .tool(Adder)
+        .build();
+    let response = agent
+        .chat_with_trace("What are 1 + 2 and 3 + 4?", vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(response.output, "3 and 7");
+    assert_eq!(response.tool_calls.len(), 2);
+
+    // All the tool results are sent back in a single user message
+    let messages = server.requests()[1].body["messages"].clone();
+    assert_eq!(messages.as_array().unwrap().len(), 3);
+    assert_eq!(
+        messages[2],
+        json!({"role": "user", "content": [
+            {"type": "tool_result", "tool_use_id": "toolu_1", "content": "3"},
+            {"type": "tool_result", "tool_use_id": "toolu_2", "content": "7"},
+        ]})
+    );
+}
+
+#[tokio::test]
+async fn test_ollama_agent_tool_loop() {
+    let server = MockServer::start(vec![
+        MockResponse::json(json!({
+            "model": "llama3.2",
+            "created_at": "2024-12-01T00:00:00Z",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{"function": {"name": "add", "arguments": {"x": 2, "y": 3}}}]
+            },
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 40,
+            "eval_count": 10
+        })),
+        MockResponse::json(json!({
+            "model": "llama3.2",
+            "created_at": "2024-12-01T00:00:01Z",
+            "message": {"role": "assistant", "content": "2 + 3 = 5"},
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 60,
+            "eval_count": 8
+        })),
+    ])
+    .await;
+
+    let agent = ollama::Client::from_url(&server.url)
+        .agent(ollama::LLAMA3_2)
+        .preamble("You are a calculator.")
+        .temperature(0.5)
+        .tool(Adder)
+        .build();
+    let response = agent
+        .chat_with_trace("What is 2 + 3?", vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(response.output, "2 + 3 = 5");
+    assert_eq!(response.tool_calls[0].0.name, "add");
+    assert_eq!(response.tool_calls[0].1, "5");
+    assert_eq!(response.usage, Usage::new(100, 18));
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path, "/api/chat");
+    assert_eq!(requests[0].body["stream"], json!(false));
+    assert_eq!(requests[0].body["options"]["temperature"], json!(0.5));
+    assert_eq!(requests[0].body["tools"][0]["type"], "function");
+    assert_eq!(requests[0].body["tools"][0]["function"]["name"], "add");
+
+    // Ollama expects tool call arguments as objects and tool results by tool name
+    assert_eq!(
+        requests[1].body["messages"],
+        json!([
+            {"role": "system", "content": "You are a calculator."},
+            {"role": "user", "content": "What is 2 + 3?"},
+            {"role": "assistant", "content": "", "tool_calls": [{"function": {"name": "add", "arguments": {"x": 2, "y": 3}}}]},
+            {"role": "tool", "content": "5", "tool_name": "add"},
+        ])
+    );
+}
@@ -0,0 +1,177 @@
+//! Minimal HTTP server used to mock model provider APIs in integration tests.
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// A request received by the [MockServer]
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path of the request, including the query string
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: serde_json::Value,
+}
+
+/// A canned response returned by the [MockServer]
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockResponse {
+    pub fn json(body: serde_json::Value) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: body.to_string(),
+        }
+    }
+
+    pub fn sse(events: &[serde_json::Value]) -> Self {
+        Self::text(
+            "text/event-stream",
+            events
+                .iter()
+                .map(|event| format!("data: {event}\n\n"))
+                .collect::<String>(),
+        )
+    }
+
+    pub fn text(content_type: &str, body: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".into(), content_type.into())],
+            body: body.into(),
+        }
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// HTTP server listening on a random local port which answers each incoming
+/// request with the next canned response, and records the requests it receives.
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockServer {
+    pub async fn start(responses: Vec<MockResponse>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Mock server should bind");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            for response in responses {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let request = read_request(&mut socket).await;
+                recorded.lock().unwrap().push(request);
+                write_response(&mut socket, response).await;
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn read_request(socket: &mut TcpStream) -> RecordedRequest {
+    let mut buffer = vec![];
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let n = socket
+            .read(&mut chunk)
+            .await
+            .expect("Request should be readable");
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        assert!(
+            n > 0,
+            "Connection closed before the end of the request headers"
+        );
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect::<Vec<_>>();
+
+    let content_length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    while buffer.len() < header_end + content_length {
+        let n = socket
+            .read(&mut chunk)
+            .await
+            .expect("Request should be readable");
+        assert!(
+            n > 0,
+            "Connection closed before the end of the request body"
+        );
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+
+    let body = &buffer[header_end..header_end + content_length];
+
+    RecordedRequest {
+        method,
+        path,
+        headers,
+        body: serde_json::from_slice(body).unwrap_or(serde_json::Value::Null),
+    }
+}
+
+async fn write_response(socket: &mut TcpStream, response: MockResponse) {
+    let mut head = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+
+    socket.write_all(head.as_bytes()).await.unwrap();
+    // Send the body in small pieces to exercise the handling of partial chunks
+    for piece in response.body.as_bytes().chunks(16) {
+        socket.write_all(piece).await.unwrap();
+        socket.flush().await.unwrap();
+    }
+    let _ = socket.shutdown().await;
+}
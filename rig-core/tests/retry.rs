@@ -0,0 +1,228 @@
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use common::{MockResponse, MockServer};
+use rig::{
+    completion::{
+        CompletionError, CompletionModel, CompletionRequest, CompletionResponse, ModelChoice,
+    },
+    providers::openai,
+    retry::{FallbackModel, FallbackResponse, RetryModel, RetryPolicy},
+    usage::Usage,
+};
+use serde_json::json;
+
+/// Completion model failing with the given error the first `failures` times it is called
+#[derive(Clone)]
+struct FlakyModel {
+    failures: usize,
+    error: fn() -> CompletionError,
+    calls: Arc<AtomicUsize>,
+}
+
+impl FlakyModel {
+    fn new(failures: usize, error: fn() -> CompletionError) -> Self {
+        Self {
+            failures,
+            error,
+            calls: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+impl CompletionModel for FlakyModel {
+    type Response = ();
+
+    async fn completion(
+        &self,
+        _request: CompletionRequest,
+    ) -> Result<CompletionResponse<()>, CompletionError> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+            Err((self.error)())
+        } else {
+            Ok(CompletionResponse {
+                choice: ModelChoice::Message("Hello!".into()),
+                usage: Usage::default(),
+                raw_response: (),
+            })
+        }
+    }
+}
+
+fn overloaded() -> CompletionError {
+    CompletionError::ApiError {
+        status: 503,
+        retry_after: None,
+        message: "Service unavailable".into(),
+    }
+}
+
+fn unauthorized() -> CompletionError {
+    CompletionError::ApiError {
+        status: 401,
+        retry_after: None,
+        message: "Invalid API key".into(),
+    }
+}
+
+fn policy() -> RetryPolicy {
+    RetryPolicy::new()
+        .max_retries(3)
+        .initial_backoff(Duration::from_millis(1))
+}
+
+#[tokio::test]
+async fn test_retry_until_success() {
+    let flaky = FlakyModel::new(2, overloaded);
+    let model = RetryModel::with_policy(flaky.clone(), policy());
+
+    let response = model.completion_request("Hi").send().await.unwrap();
+
+    assert!(matches!(response.choice, ModelChoice::Message(message) if message == "Hello!"));
+    assert_eq!(flaky.calls(), 3);
+}
+
+#[tokio::test]
+async fn test_retry_gives_up_after_max_retries() {
+    let flaky = FlakyModel::new(10, overloaded);
+    let model = RetryModel::with_policy(flaky.clone(), policy());
+
+    let result = model.completion_request("Hi").send().await;
+
+    assert!(matches!(
+        result,
+        Err(CompletionError::ApiError { status: 503, .. })
+    ));
+    assert_eq!(flaky.calls(), 4);
+}
+
+#[tokio::test]
+async fn test_no_retry_on_non_retryable_error() {
+    let flaky = FlakyModel::new(1, unauthorized);
+    let model = RetryModel::with_policy(flaky.clone(), policy());
+
+    let result = model.completion_request("Hi").send().await;
+
+    assert!(matches!(
+        result,
+        Err(CompletionError::ApiError { status: 401, .. })
+    ));
+    assert_eq!(flaky.calls(), 1);
+}
+
+#[tokio::test]
+async fn test_retry_respects_retry_after() {
+    let flaky = FlakyModel::new(1, || CompletionError::ApiError {
+        status: 429,
+        retry_after: Some(Duration::from_millis(200)),
+        message: "Rate limit exceeded".into(),
+    });
+    let model = RetryModel::with_policy(flaky.clone(), policy());
+
+    let start = Instant::now();
+    model.completion_request("Hi").send().await.unwrap();
+
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(flaky.calls(), 2);
+}
+
+#[test]
+fn test_backoff() {
+    let policy = RetryPolicy::new()
+        .initial_backoff(Duration::from_secs(1))
+        .max_backoff(Duration::from_secs(5))
+        .jitter(false);
+
+    assert_eq!(policy.backoff(0), Duration::from_secs(1));
+    assert_eq!(policy.backoff(1), Duration::from_secs(2));
+    assert_eq!(policy.backoff(2), Duration::from_secs(4));
+    assert_eq!(policy.backoff(3), Duration::from_secs(5));
+
+    let backoff = policy.jitter(true).backoff(1);
+    assert!(backoff >= Duration::from_secs(1) && backoff <= Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn test_fallback() {
+    let primary = FlakyModel::new(10, overloaded);
+    let fallback = FlakyModel::new(0, overloaded);
+    let model = RetryModel::with_policy(primary.clone(), policy()).fallback(fallback.clone());
+
+    let response = model.completion_request("Hi").send().await.unwrap();
+
+    assert!(matches!(
+        response.raw_response,
+        FallbackResponse::Fallback(())
+    ));
+    assert_eq!(primary.calls(), 4);
+    assert_eq!(fallback.calls(), 1);
+}
+
+#[tokio::test]
+async fn test_no_fallback_on_non_retryable_error() {
+    let primary = FlakyModel::new(1, unauthorized);
+    let fallback = FlakyModel::new(0, overloaded);
+    let model = FallbackModel::new(primary.clone(), fallback.clone());
+
+    let result = model.completion_request("Hi").send().await;
+
+    assert!(matches!(
+        result,
+        Err(CompletionError::ApiError { status: 401, .. })
+    ));
+    assert_eq!(fallback.calls(), 0);
+}
+
+#[tokio::test]
+async fn test_openai_rate_limit_retry() {
+    let server = MockServer::start(vec![
+        MockResponse::json(json!({"error": {"message": "Rate limit reached"}}))
+            .status(429)
+            .header("retry-after", "1"),
+        MockResponse::json(json!({"error": {"message": "Server overloaded"}}))
+            .status(503)
+            .header("retry-after-ms", "10"),
+        MockResponse::json(json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 0,
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Hello!"},
+                "logprobs": null,
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15}
+        })),
+    ])
+    .await;
+
+    let model = openai::Client::from_url("test-key", &server.url).completion_model(openai::GPT_4O);
+
+    let result = model.completion_request("Hi").send().await;
+    match result {
+        Err(err @ CompletionError::ApiError { status: 429, .. }) => {
+            assert!(err.is_retryable());
+            assert_eq!(err.retry_after(), Some(Duration::from_secs(1)));
+        }
+        _ => panic!("Expected a rate limit error"),
+    }
+
+    let model = RetryModel::new(model);
+    let response = model.completion_request("Hi").send().await.unwrap();
+
+    assert!(matches!(response.choice, ModelChoice::Message(message) if message == "Hello!"));
+    assert_eq!(server.requests().len(), 3);
+}
@@ -0,0 +1,411 @@
+mod common;
+
+use common::{MockResponse, MockServer};
+use futures::StreamExt;
+use rig::{
+    completion::{CompletionModel, ToolDefinition},
+    providers::{anthropic, cohere, gemini, ollama, openai, xai},
+    streaming::{StreamingChoice, StreamingCompletionModel, StreamingPrompt, StreamingResult},
+};
+use serde_json::json;
+
+async fn collect(mut stream: StreamingResult) -> Vec<StreamingChoice> {
+    let mut choices = vec![];
+    while let Some(choice) = stream.next().await {
+        choices.push(choice.expect("Stream should not fail"));
+    }
+    choices
+}
+
+fn text(choices: &[StreamingChoice]) -> String {
+    choices
+        .iter()
+        .filter_map(|choice| match choice {
+            StreamingChoice::Message(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn tool_calls(choices: &[StreamingChoice]) -> Vec<(String, String, serde_json::Value)> {
+    choices
+        .iter()
+        .filter_map(|choice| match choice {
+            StreamingChoice::ToolCall(call) => {
+                Some((call.name.clone(), call.id.clone(), call.arguments.clone()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn argument_deltas(choices: &[StreamingChoice]) -> Vec<String> {
+    choices
+        .iter()
+        .filter_map(|choice| match choice {
+            StreamingChoice::ToolCallDelta { arguments, .. } if !arguments.is_empty() => {
+                Some(arguments.clone())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn add_tool() -> ToolDefinition {
+    ToolDefinition {
+        name: "add".into(),
+        description: "Add x and y together".into(),
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "x": {"type": "number", "description": "The first number to add"},
+                "y": {"type": "number", "description": "The second number to add"},
+            },
+        }),
+    }
+}
+
+#[tokio::test]
+async fn test_openai_streaming() {
+    let server = MockServer::start(vec![MockResponse::text(
+        "text/event-stream",
+        [
+            json!({"choices": [{"index": 0, "delta": {"role": "assistant", "content": ""}, "finish_reason": null}]}),
+            json!({"choices": [{"index": 0, "delta": {"content": "Let me "}, "finish_reason": null}]}),
+            json!({"choices": [{"index": 0, "delta": {"content": "add that."}, "finish_reason": null}]}),
+            json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "id": "call_1", "type": "function", "function": {"name": "add", "arguments": ""}}]}, "finish_reason": null}]}),
+            json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "function": {"arguments": "{\"x\": 2,"}}]}, "finish_reason": null}]}),
+            json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "function": {"arguments": " \"y\": 3}"}}]}, "finish_reason": null}]}),
+            json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "tool_calls"}]}),
+        ]
+        .iter()
+        .map(|event| format!("data: {event}\n\n"))
+        .chain(std::iter::once("data: [DONE]\n\n".to_string()))
+        .collect::<String>(),
+    )])
+    .await;
+
+    let model = openai::Client::from_url("test-key", &server.url).completion_model(openai::GPT_4O);
+    let request = model
+        .completion_request("What is 2 + 3?")
+        .tool(add_tool())
+        .build();
+    let choices = collect(model.stream(request).await.unwrap()).await;
+
+    assert_eq!(text(&choices), "Let me add that.");
+    assert_eq!(argument_deltas(&choices), vec!["{\"x\": 2,", " \"y\": 3}"]);
+    assert_eq!(
+        tool_calls(&choices),
+        vec![("add".into(), "call_1".into(), json!({"x": 2, "y": 3}))]
+    );
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path, "/chat/completions");
+    assert_eq!(requests[0].body["stream"], json!(true));
+    assert_eq!(requests[0].body["tools"][0]["function"]["name"], "add");
+}
+
+#[tokio::test]
+async fn test_openai_streaming_error() {
+    let server = MockServer::start(vec![MockResponse::json(
+        json!({"error": {"message": "Invalid API key"}}),
+    )
+    .status(401)])
+    .await;
+
+    let model = openai::Client::from_url("test-key", &server.url).completion_model(openai::GPT_4O);
+    let result = model
+        .stream(model.completion_request("Hello").build())
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(rig::completion::CompletionError::ApiError { status: 401, .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_agent_stream_prompt() {
+    let server = MockServer::start(vec![MockResponse::sse(&[
+        json!({"choices": [{"index": 0, "delta": {"content": "Hello"}, "finish_reason": null}]}),
+        json!({"choices": [{"index": 0, "delta": {"content": ", world!"}, "finish_reason": "stop"}]}),
+    ])])
+    .await;
+
+    let agent = openai::Client::from_url("test-key", &server.url)
+        .agent(openai::GPT_4O)
+        .preamble("You are a helpful assistant.")
+        .build();
+    let choices = collect(agent.stream_prompt("Say hello").await.unwrap()).await;
+
+    assert_eq!(
+        choices,
+        vec![
+            StreamingChoice::Message("Hello".into()),
+            StreamingChoice::Message(", world!".into()),
+        ]
+    );
+
+    let requests = server.requests();
+    assert_eq!(requests[0].body["messages"][0]["role"], "system");
+    assert_eq!(requests[0].body["messages"][1]["role"], "user");
+}
+
+#[tokio::test]
+async fn test_anthropic_streaming() {
+    let events = [
+        (
+            "message_start",
+            json!({"type": "message_start", "message": {"id": "msg_1", "type": "message", "role": "assistant", "content": [], "model": "claude-3-5-sonnet-latest", "usage": {"input_tokens": 10, "output_tokens": 1}}}),
+        ),
+        (
+            "content_block_start",
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+        ),
+        ("ping", json!({"type": "ping"})),
+        (
+            "content_block_delta",
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Let me "}}),
+        ),
+        (
+            "content_block_delta",
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "add that."}}),
+        ),
+        (
+            "content_block_stop",
+            json!({"type": "content_block_stop", "index": 0}),
+        ),
+        (
+            "content_block_start",
+            json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "add", "input": {}}}),
+        ),
+        (
+            "content_block_delta",
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"x\": 2,"}}),
+        ),
+        (
+            "content_block_delta",
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": " \"y\": 3}"}}),
+        ),
+        (
+            "content_block_stop",
+            json!({"type": "content_block_stop", "index": 1}),
+        ),
+        (
+            "message_delta",
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use", "stop_sequence": null}, "usage": {"output_tokens": 15}}),
+        ),
+        ("message_stop", json!({"type": "message_stop"})),
+    ];
+    let server = MockServer::start(vec![MockResponse::text(
+        "text/event-stream",
+        events
+            .iter()
+            .map(|(name, data)| format!("event: {name}\ndata: {data}\n\n"))
+            .collect::<String>(),
+    )])
+    .await;
+
+    let model = anthropic::ClientBuilder::new("test-key")
+        .base_url(&server.url)
+        .build()
+        .completion_model(anthropic::CLAUDE_3_5_SONNET);
+    let request = model
+        .completion_request("What is 2 + 3?")
+        .tool(add_tool())
+        .build();
+    let choices = collect(model.stream(request).await.unwrap()).await;
+
+    assert_eq!(text(&choices), "Let me add that.");
+    assert_eq!(argument_deltas(&choices), vec!["{\"x\": 2,", " \"y\": 3}"]);
+    assert_eq!(
+        tool_calls(&choices),
+        vec![("add".into(), "toolu_1".into(), json!({"x": 2, "y": 3}))]
+    );
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path, "/v1/messages");
+    assert_eq!(requests[0].body["stream"], json!(true));
+}
+
+#[tokio::test]
+async fn test_anthropic_streaming_error_event() {
+    let server = MockServer::start(vec![MockResponse::text(
+        "text/event-stream",
+        format!(
+            "event: error\ndata: {}\n\n",
+            json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}})
+        ),
+    )])
+    .await;
+
+    let model = anthropic::ClientBuilder::new("test-key")
+        .base_url(&server.url)
+        .build()
+        .completion_model(anthropic::CLAUDE_3_5_SONNET);
+    let mut stream = model
+        .stream(model.completion_request("Hello").build())
+        .await
+        .unwrap();
+
+    match stream.next().await {
+        Some(Err(rig::completion::CompletionError::ProviderError(message))) => {
+            assert_eq!(message, "Overloaded")
+        }
+        other => panic!("Expected a provider error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_gemini_streaming() {
+    let server = MockServer::start(vec![MockResponse::sse(&[
+        json!({"candidates": [{"content": {"parts": [{"text": "Let me "}], "role": "model"}, "index": 0}]}),
+        json!({"candidates": [{"content": {"parts": [{"text": "add that."}], "role": "model"}, "index": 0}]}),
+        json!({"candidates": [{"content": {"parts": [{"functionCall": {"name": "add", "args": {"x": 2, "y": 3}}}], "role": "model"}, "finishReason": "STOP", "index": 0}], "usageMetadata": {"promptTokenCount": 10, "candidatesTokenCount": 5, "totalTokenCount": 15}}),
+    ])])
+    .await;
+
+    let model = gemini::Client::from_url("test-key", &server.url)
+        .completion_model(gemini::completion::GEMINI_1_5_FLASH);
+    let request = model.completion_request("What is 2 + 3?").build();
+    let choices = collect(model.stream(request).await.unwrap()).await;
+
+    assert_eq!(text(&choices), "Let me add that.");
+    assert_eq!(
+        tool_calls(&choices),
+        vec![("add".into(), "add".into(), json!({"x": 2, "y": 3}))]
+    );
+
+    let requests = server.requests();
+    assert!(requests[0]
+        .path
+        .starts_with("/v1beta/models/gemini-1.5-flash:streamGenerateContent?alt=sse"));
+}
+
+#[tokio::test]
+async fn test_cohere_streaming() {
+    let server = MockServer::start(vec![MockResponse::text(
+        "application/stream+json",
+        [
+            json!({"is_finished": false, "event_type": "stream-start", "generation_id": "gen_1"}),
+            json!({"is_finished": false, "event_type": "text-generation", "text": "Let me "}),
+            json!({"is_finished": false, "event_type": "text-generation", "text": "add that."}),
+            json!({"is_finished": false, "event_type": "tool-calls-chunk", "tool_call_delta": {"index": 0, "name": "add"}}),
+            json!({"is_finished": false, "event_type": "tool-calls-chunk", "tool_call_delta": {"index": 0, "parameters": "{\"x\": 2,"}}),
+            json!({"is_finished": false, "event_type": "tool-calls-chunk", "tool_call_delta": {"index": 0, "parameters": " \"y\": 3}"}}),
+            json!({"is_finished": false, "event_type": "tool-calls-generation", "tool_calls": [{"name": "add", "parameters": {"x": 2, "y": 3}}]}),
+            json!({"is_finished": true, "event_type": "stream-end", "finish_reason": "COMPLETE"}),
+        ]
+        .iter()
+        .map(|event| format!("{event}\n"))
+        .collect::<String>(),
+    )])
+    .await;
+
+    let model =
+        cohere::Client::from_url("test-key", &server.url).completion_model(cohere::COMMAND_R);
+    let request = model
+        .completion_request("What is 2 + 3?")
+        .tool(add_tool())
+        .build();
+    let choices = collect(model.stream(request).await.unwrap()).await;
+
+    assert_eq!(text(&choices), "Let me add that.");
+    assert_eq!(argument_deltas(&choices), vec!["{\"x\": 2,", " \"y\": 3}"]);
+    assert_eq!(
+        tool_calls(&choices),
+        vec![("add".into(), "add".into(), json!({"x": 2, "y": 3}))]
+    );
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path, "/v1/chat");
+    assert_eq!(requests[0].body["stream"], json!(true));
+}
+
+#[tokio::test]
+async fn test_xai_streaming() {
+    let server = MockServer::start(vec![MockResponse::sse(&[
+        json!({"choices": [{"index": 0, "delta": {"role": "assistant", "content": "Hello"}}]}),
+        json!({"choices": [{"index": 0, "delta": {"content": " from Grok"}}]}),
+        json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]}),
+    ])])
+    .await;
+
+    let model = xai::Client::from_url("test-key", &server.url).completion_model(xai::GROK_BETA);
+    let choices = collect(
+        model
+            .stream(model.completion_request("Say hello").build())
+            .await
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(text(&choices), "Hello from Grok");
+    assert!(tool_calls(&choices).is_empty());
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path, "/v1/chat/completions");
+    assert_eq!(requests[0].body["stream"], json!(true));
+}
+
+#[tokio::test]
+async fn test_ollama_streaming() {
+    let server = MockServer::start(vec![MockResponse::text(
+        "application/x-ndjson",
+        [
+            json!({"model": "llama3.2", "created_at": "2024-12-01T00:00:00Z", "message": {"role": "assistant", "content": "Let me "}, "done": false}),
+            json!({"model": "llama3.2", "created_at": "2024-12-01T00:00:00Z", "message": {"role": "assistant", "content": "add that."}, "done": false}),
+            json!({"model": "llama3.2", "created_at": "2024-12-01T00:00:00Z", "message": {"role": "assistant", "content": "", "tool_calls": [{"function": {"name": "add", "arguments": {"x": 2, "y": 3}}}]}, "done": false}),
+            json!({"model": "llama3.2", "created_at": "2024-12-01T00:00:00Z", "message": {"role": "assistant", "content": ""}, "done": true, "done_reason": "stop", "prompt_eval_count": 20, "eval_count": 12}),
+        ]
+        .iter()
+        .map(|chunk| format!("{chunk}\n"))
+        .collect::<String>(),
+    )])
+    .await;
+
+    let model = ollama::Client::from_url(&server.url).completion_model(ollama::LLAMA3_2);
+    let request = model
+        .completion_request("What is 2 + 3?")
+        .tool(add_tool())
+        .build();
+    let choices = collect(model.stream(request).await.unwrap()).await;
+
+    assert_eq!(text(&choices), "Let me add that.");
+    assert_eq!(
+        tool_calls(&choices),
+        vec![("add".into(), "add".into(), json!({"x": 2, "y": 3}))]
+    );
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path, "/api/chat");
+    assert_eq!(requests[0].body["stream"], json!(true));
+}
+
+#[tokio::test]
+async fn test_ollama_streaming_error() {
+    let server = MockServer::start(vec![MockResponse::text(
+        "application/x-ndjson",
+        format!(
+            "{}\n{}\n",
+            json!({"model": "llama3.2", "created_at": "2024-12-01T00:00:00Z", "message": {"role": "assistant", "content": "Hello"}, "done": false}),
+            json!({"error": "model runner has unexpectedly stopped"}),
+        ),
+    )])
+    .await;
+
+    let model = ollama::Client::from_url(&server.url).completion_model(ollama::LLAMA3_2);
+    let mut stream = model
+        .stream(model.completion_request("Say hello").build())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        stream.next().await.unwrap().unwrap(),
+        StreamingChoice::Message("Hello".into())
+    );
+    assert!(matches!(
+        stream.next().await.unwrap(),
+        Err(rig::completion::CompletionError::ProviderError(_))
+    ));
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use common::{MockResponse, MockServer};
+use rig::{
+    completion::CompletionModel,
+    embeddings::EmbeddingModel,
+    providers::{cohere, gemini, ollama, openai},
+    usage::Usage,
+};
+use serde_json::json;
+
+#[tokio::test]
+async fn test_openai_completion_usage() {
+    let server = MockServer::start(vec![MockResponse::json(json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "created": 0,
+        "model": "gpt-4o",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": "Hello!"},
+            "logprobs": null,
+            "finish_reason": "stop"
+        }],
+        "usage": {"prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15}
+    }))])
+    .await;
+
+    let model = openai::Client::from_url("test-key", &server.url).completion_model(openai::GPT_4O);
+    let response = model.completion_request("Hi").send().await.unwrap();
+
+    assert_eq!(response.usage, Usage::new(12, 3));
+}
+
+#[tokio::test]
+async fn test_openai_embedding_usage() {
+    let server = MockServer::start(vec![MockResponse::json(json!({
+        "object": "list",
+        "data": [
+            {"object": "embedding", "embedding": [0.1, 0.2], "index": 0},
+            {"object": "embedding", "embedding": [0.3, 0.4], "index": 1}
+        ],
+        "model": "text-embedding-3-small",
+        "usage": {"prompt_tokens": 8, "total_tokens": 8}
+    }))])
+    .await;
+
+    let model = openai::Client::from_url("test-key", &server.url)
+        .embedding_model_with_ndims(openai::TEXT_EMBEDDING_3_SMALL, 2);
+    let (embeddings, usage) = model
+        .embed_texts_with_usage(vec!["Hello".to_string(), "World".to_string()])
+        .await
+        .unwrap();
+
+    assert_eq!(embeddings.len(), 2);
+    assert_eq!(usage, Usage::new(8, 0));
+}
+
+#[tokio::test]
+async fn test_cohere_usage() {
+    let server = MockServer::start(vec![
+        MockResponse::json(json!({
+            "text": "Hello!",
+            "generation_id": "gen_1",
+            "finish_reason": "COMPLETE",
+            "meta": {
+                "api_version": {"version": "1"},
+                "billed_units": {"input_tokens": 20, "output_tokens": 4}
+            }
+        })),
+        MockResponse::json(json!({
+            "id": "emb_1",
+            "embeddings": [[0.1, 0.2]],
+            "texts": ["Hello"],
+            "meta": {
+                "api_version": {"version": "1"},
+                "billed_units": {"input_tokens": 2}
+            }
+        })),
+    ])
+    .await;
+
+    let client = cohere::Client::from_url("test-key", &server.url);
+
+    let response = client
+        .completion_model(cohere::COMMAND_R)
+        .completion_request("Hi")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.usage, Usage::new(20, 4));
+
+    let (_, usage) = client
+        .embedding_model_with_ndims(cohere::EMBED_ENGLISH_V3, "search_document", 2)
+        .embed_texts_with_usage(vec!["Hello".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(usage, Usage::new(2, 0));
+}
+
+#[tokio::test]
+async fn test_gemini_completion_usage() {
+    let server = MockServer::start(vec![MockResponse::json(json!({
+        "candidates": [{
+            "content": {"parts": [{"text": "Hello!"}], "role": "model"},
+            "finishReason": "STOP",
+            "index": 0
+        }],
+        "usageMetadata": {"promptTokenCount": 5, "candidatesTokenCount": 2, "totalTokenCount": 7}
+    }))])
+    .await;
+
+    let response = gemini::Client::from_url("test-key", &server.url)
+        .completion_model(gemini::completion::GEMINI_1_5_FLASH)
+        .completion_request("Hi")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.usage, Usage::new(5, 2));
+}
+
+#[tokio::test]
+async fn test_ollama_embedding_usage() {
+    let server = MockServer::start(vec![MockResponse::json(json!({
+        "model": "nomic-embed-text",
+        "embeddings": [[0.1, 0.2], [0.3, 0.4]],
+        "total_duration": 14143917,
+        "load_duration": 1019500,
+        "prompt_eval_count": 6
+    }))])
+    .await;
+
+    let model =
+        ollama::Client::from_url(&server.url).embedding_model_with_ndims("nomic-embed-text", 2);
+    let (embeddings, usage) = model
+        .embed_texts_with_usage(vec!["Hello".to_string(), "World".to_string()])
+        .await
+        .unwrap();
+
+    assert_eq!(embeddings[1].document, "World");
+    assert_eq!(embeddings[1].vec, vec![0.3, 0.4]);
+    assert_eq!(usage, Usage::new(6, 0));
+
+    let request = &server.requests()[0];
+    assert_eq!(request.path, "/api/embed");
+    assert_eq!(
+        request.body,
+        json!({"model": "nomic-embed-text", "input": ["Hello", "World"]})
+    );
+}
@@ -0,0 +1,91 @@
+use syn::{meta::ParseNestedMeta, LitStr};
+
+use crate::{COLUMN, TABLE};
+
+/// Options of `#[table(...)]`
+#[derive(Default)]
+pub(crate) struct TableAttributes {
+    pub(crate) name: Option<LitStr>,
+}
+
+/// Options of `#[column(...)]`
+#[derive(Default)]
+pub(crate) struct ColumnAttributes {
+    pub(crate) name: Option<LitStr>,
+    pub(crate) primary_key: bool,
+    pub(crate) indexed: bool,
+    pub(crate) searchable: bool,
+    pub(crate) sql_type: Option<LitStr>,
+    pub(crate) with: Option<syn::Path>,
+}
+
+impl TableAttributes {
+    pub(crate) fn parse(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut table = Self::default();
+
+        for attribute in attrs.iter().filter(|attr| attr.path().is_ident(TABLE)) {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    table.name = Some(string_value(&meta)?);
+                    Ok(())
+                } else {
+                    Err(unknown_attribute(&meta, TABLE))
+                }
+            })?;
+        }
+
+        Ok(table)
+    }
+}
+
+impl ColumnAttributes {
+    pub(crate) fn parse(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut column = Self::default();
+
+        for attribute in attrs.iter().filter(|attr| attr.path().is_ident(COLUMN)) {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    column.name = Some(string_value(&meta)?);
+                } else if meta.path.is_ident("primary_key") {
+                    column.primary_key = true;
+                } else if meta.path.is_ident("indexed") {
+                    column.indexed = true;
+                } else if meta.path.is_ident("searchable") {
+                    column.searchable = true;
+                } else if meta.path.is_ident("sql_type") {
+                    column.sql_type = Some(string_value(&meta)?);
+                } else if meta.path.is_ident("with") {
+                    column.with = Some(string_value(&meta)?.parse()?);
+                } else {
+                    return Err(unknown_attribute(&meta, COLUMN));
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(column)
+    }
+}
+
+// Get the "..." part of `key = "..."`
+fn string_value(meta: &ParseNestedMeta<'_>) -> syn::Result<LitStr> {
+    let lit = meta.value()?.parse::<LitStr>()?;
+    if !lit.suffix().is_empty() {
+        return Err(syn::Error::new_spanned(
+            &lit,
+            format!("unexpected suffix `{}` on string literal", lit.suffix()),
+        ));
+    }
+
+    Ok(lit)
+}
+
+fn unknown_attribute(meta: &ParseNestedMeta<'_>, attribute: &str) -> syn::Error {
+    let path = quote::ToTokens::to_token_stream(&meta.path)
+        .to_string()
+        .replace(' ', "");
+    syn::Error::new_spanned(
+        &meta.path,
+        format_args!("unknown {} attribute `{}`", attribute, path),
+    )
+}
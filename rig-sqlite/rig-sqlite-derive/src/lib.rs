@@ -0,0 +1,34 @@
+extern crate proc_macro;
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod attributes;
+mod table;
+
+pub(crate) const TABLE: &str = "table";
+pub(crate) const COLUMN: &str = "column";
+
+/// Derives the mapping of a struct to a SQLite table: `TryFrom<&rusqlite::Row>` reads the
+/// struct from a row by column name, and, when the struct is given a table name with
+/// `#[table(name = "...")]`, `SqliteVectorStoreTable` describes and writes its columns.
+///
+/// Field attributes, in `#[column(...)]`:
+/// - `name = "..."`: name of the column, the name of the field by default
+/// - `primary_key`: the field identifies the row, the field whose column is `id` by default
+/// - `indexed`: the column is indexed
+/// - `searchable`: the column is mirrored in the full-text index
+/// - `sql_type = "..."`: type of the column, inferred from the type of the field by default
+/// - `with = "..."`: module of the conversion of the field, with functions
+///   `to_sql(&T) -> impl ColumnValue` and `from_sql(ValueRef) -> FromSqlResult<T>`
+///
+/// Integer fields are stored in `INTEGER` columns, floats in `REAL` columns, booleans in
+/// `BOOLEAN` columns and the other fields in `TEXT` columns. Unsigned 64-bit and 128-bit
+/// integers do not fit in SQLite integers and must be converted with `with`.
+#[proc_macro_derive(SqliteTable, attributes(table, column))]
+pub fn derive_sqlite_table(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+
+    table::expand_derive_sqlite_table(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,235 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+use crate::attributes::{ColumnAttributes, TableAttributes};
+
+struct Column<'a> {
+    field: &'a syn::Ident,
+    name: String,
+    attributes: ColumnAttributes,
+    sql_type: String,
+}
+
+pub(crate) fn expand_derive_sqlite_table(input: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let table = TableAttributes::parse(&input.attrs)?;
+
+    let fields = match &input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "SqliteTable derive macro should only be used on structs with named fields",
+            ))
+        }
+    };
+
+    let columns = fields
+        .iter()
+        .map(|field| {
+            let attributes = ColumnAttributes::parse(&field.attrs)?;
+            let field_name = field.ident.as_ref().expect("named fields have an ident");
+            if attributes.with.is_none() {
+                check_type(&field.ty)?;
+            }
+            let name = attributes
+                .name
+                .as_ref()
+                .map_or_else(|| field_name.to_string(), LitStr::value);
+            let sql_type = attributes
+                .sql_type
+                .as_ref()
+                .map_or_else(|| sql_type(&field.ty).to_string(), LitStr::value);
+
+            Ok(Column {
+                field: field_name,
+                name,
+                attributes,
+                sql_type,
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let from_row = expand_from_row(input, &columns);
+    let vector_store_table = match &table.name {
+        Some(table_name) => expand_vector_store_table(input, table_name, &columns)?,
+        None => quote! {},
+    };
+
+    Ok(quote! {
+        #from_row
+
+        #vector_store_table
+    })
+}
+
+/// `TryFrom<&Row>` reading each field from the column of the same name
+fn expand_from_row(input: &syn::DeriveInput, columns: &[Column]) -> TokenStream {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = columns.iter().map(|column| {
+        let field = column.field;
+        let column_name = &column.name;
+
+        match &column.attributes.with {
+            Some(with) => quote! {
+                #field: {
+                    let index = row.as_ref().column_index(#column_name)?;
+                    let value = row.get_ref(index)?;
+                    #with::from_sql(value).map_err(|err| {
+                        ::rig_sqlite::rusqlite::Error::FromSqlConversionFailure(
+                            index,
+                            value.data_type(),
+                            ::std::boxed::Box::new(err),
+                        )
+                    })?
+                }
+            },
+            None => quote! {
+                #field: row.get(#column_name)?
+            },
+        }
+    });
+
+    quote! {
+        impl #impl_generics ::std::convert::TryFrom<&::rig_sqlite::rusqlite::Row<'_>> for #name #ty_generics #where_clause {
+            type Error = ::rig_sqlite::rusqlite::Error;
+
+            fn try_from(row: &::rig_sqlite::rusqlite::Row<'_>) -> ::std::result::Result<Self, Self::Error> {
+                ::std::result::Result::Ok(Self {
+                    #(#fields,)*
+                })
+            }
+        }
+    }
+}
+
+/// `SqliteVectorStoreTable` describing the columns of the table and writing the fields
+fn expand_vector_store_table(
+    input: &syn::DeriveInput,
+    table_name: &LitStr,
+    columns: &[Column],
+) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let primary_key = columns
+        .iter()
+        .find(|column| column.attributes.primary_key)
+        .or_else(|| columns.iter().find(|column| column.name == "id"))
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                name,
+                "Add a field named `id` or tag the field identifying rows with #[column(primary_key)].",
+            )
+        })?;
+
+    // The queries of the vector store look rows up by their `id` column, read first
+    if primary_key.name != "id" || !std::ptr::eq(primary_key, &columns[0]) {
+        return Err(syn::Error::new_spanned(
+            primary_key.field,
+            "The primary key must be the first field and be stored in the `id` column.",
+        ));
+    }
+
+    let schema = columns.iter().map(|column| {
+        let column_name = &column.name;
+        let sql_type =
+            if column.name == "id" && !column.sql_type.to_uppercase().contains("PRIMARY KEY") {
+                format!("{} PRIMARY KEY", column.sql_type)
+            } else {
+                column.sql_type.clone()
+            };
+        let indexed = column.attributes.indexed.then(|| quote! { .indexed() });
+        let searchable = column
+            .attributes
+            .searchable
+            .then(|| quote! { .searchable() });
+
+        quote! {
+            ::rig_sqlite::Column::new(#column_name, #sql_type) #indexed #searchable
+        }
+    });
+
+    let values = columns.iter().map(|column| {
+        let field = column.field;
+        let column_name = &column.name;
+
+        match &column.attributes.with {
+            Some(with) => quote! {
+                (#column_name, ::std::boxed::Box::new(#with::to_sql(&self.#field)))
+            },
+            None => quote! {
+                (#column_name, ::std::boxed::Box::new(::std::clone::Clone::clone(&self.#field)))
+            },
+        }
+    });
+
+    let primary_key = primary_key.field;
+
+    Ok(quote! {
+        impl #impl_generics ::rig_sqlite::SqliteVectorStoreTable for #name #ty_generics #where_clause {
+            fn name() -> &'static str {
+                #table_name
+            }
+
+            fn schema() -> ::std::vec::Vec<::rig_sqlite::Column> {
+                ::std::vec![#(#schema,)*]
+            }
+
+            fn id(&self) -> ::std::string::String {
+                ::std::string::ToString::to_string(&self.#primary_key)
+            }
+
+            fn column_values(&self) -> ::std::vec::Vec<(&'static str, ::std::boxed::Box<dyn ::rig_sqlite::ColumnValue>)> {
+                ::std::vec![#(#values,)*]
+            }
+        }
+    })
+}
+
+/// Name of the type stored by a field of type `ty`, unwrapping options
+fn type_name(ty: &syn::Type) -> Option<String> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+
+    if segment.ident == "Option" {
+        if let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments {
+            if let Some(syn::GenericArgument::Type(inner)) = arguments.args.first() {
+                return type_name(inner);
+            }
+        }
+    }
+
+    Some(segment.ident.to_string())
+}
+
+/// SQL type of a column storing `ty`
+fn sql_type(ty: &syn::Type) -> &'static str {
+    match type_name(ty).as_deref() {
+        Some("i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32") => "INTEGER",
+        Some("f32" | "f64") => "REAL",
+        Some("bool") => "BOOLEAN",
+        _ => "TEXT",
+    }
+}
+
+/// Reject the integer types that do not fit in the signed 64-bit integers of SQLite
+fn check_type(ty: &syn::Type) -> syn::Result<()> {
+    match type_name(ty).as_deref() {
+        Some(name @ ("u64" | "usize" | "isize" | "i128" | "u128")) => Err(syn::Error::new_spanned(
+            ty,
+            format!(
+                "`{}` columns are not supported as SQLite integers are 64-bit signed, use `i64` or convert the field with #[column(with = \"...\")].",
+                name
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use rig::embeddings::{Embedding, EmbeddingError, EmbeddingModel};
+use rig::usage::Usage;
+use rusqlite::OptionalExtension;
+use sha2::{Digest, Sha256};
+use tokio_rusqlite::Connection;
+use tracing::{debug, warn};
+use zerocopy::IntoBytes;
+
+/// Number of cache requests served from the cache and embedded by the model
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Share of the texts served from the cache, between 0 and 1
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Embedding model wrapper keeping the embedding of every text in the `embedding_cache`
+/// table, such that a text already embedded by the model is never sent to it again: repeated
+/// messages or re-ingested documents cost no tokens.
+///
+/// Embeddings are keyed by the name of the model, its number of dimensions and the SHA-256 of
+/// the text. The cache is a performance layer only: if it cannot be read or written, texts are
+/// embedded by the model as if they were not cached.
+///
+/// ```rust,ignore
+/// let model = CachedEmbeddingModel::new(conn, model, "text-embedding-3-large").await?;
+/// let stats = model.stats();
+/// println!("{} hits, {} misses", stats.hits, stats.misses);
+/// ```
+#[derive(Clone)]
+pub struct CachedEmbeddingModel<M: EmbeddingModel> {
+    model: M,
+    model_name: String,
+    conn: Connection,
+    counters: Arc<Counters>,
+}
+
+impl<M: EmbeddingModel> CachedEmbeddingModel<M> {
+    /// Wrap `model`, creating the `embedding_cache` table if it does not exist. Like the tables
+    /// of [crate::SqliteVectorStore], the table is created by this crate rather than by the
+    /// migrations of the application, which it knows nothing about.
+    pub async fn new(
+        conn: Connection,
+        model: M,
+        model_name: &str,
+    ) -> Result<Self, tokio_rusqlite::Error> {
+        conn.call(|conn| {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS embedding_cache (
+                    model TEXT NOT NULL,
+                    ndims INTEGER NOT NULL,
+                    text_hash TEXT NOT NULL,
+                    embedding BLOB NOT NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY (model, ndims, text_hash)
+                )",
+            )
+            .map_err(tokio_rusqlite::Error::from)
+        })
+        .await?;
+
+        Ok(Self {
+            model,
+            model_name: model_name.to_string(),
+            conn,
+            counters: Arc::new(Counters::default()),
+        })
+    }
+
+    /// Hits and misses since the model was created, shared by its clones
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Cached embeddings of the texts with the given hashes, by hash
+    async fn cached(
+        &self,
+        hashes: Vec<String>,
+    ) -> Result<HashMap<String, Vec<f64>>, tokio_rusqlite::Error> {
+        let model_name = self.model_name.clone();
+        let ndims = self.model.ndims() as i64;
+
+        self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT embedding FROM embedding_cache
+                     WHERE model = ?1 AND ndims = ?2 AND text_hash = ?3",
+                )?;
+                let mut cached = HashMap::new();
+                for hash in hashes {
+                    let embedding = stmt
+                        .query_row(rusqlite::params![model_name, ndims, hash], |row| {
+                            row.get::<_, Vec<u8>>(0)
+                        })
+                        .map(|blob| deserialize_vector(&blob));
+                    match embedding {
+                        Ok(embedding) => {
+                            cached.insert(hash, embedding);
+                        }
+                        Err(rusqlite::Error::QueryReturnedNoRows) => {}
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                Ok(cached)
+            })
+            .await
+    }
+
+    async fn store(
+        &self,
+        embeddings: Vec<(String, Vec<f64>)>,
+    ) -> Result<(), tokio_rusqlite::Error> {
+        let model_name = self.model_name.clone();
+        let ndims = self.model.ndims() as i64;
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt = tx.prepare(
+                        "INSERT OR REPLACE INTO embedding_cache (model, ndims, text_hash, embedding)
+                         VALUES (?1, ?2, ?3, ?4)",
+                    )?;
+                    for (hash, embedding) in embeddings {
+                        stmt.execute(rusqlite::params![
+                            model_name,
+                            ndims,
+                            hash,
+                            embedding.as_bytes()
+                        ])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await
+    }
+}
+
+impl<M: EmbeddingModel> EmbeddingModel for CachedEmbeddingModel<M> {
+    const MAX_DOCUMENTS: usize = M::MAX_DOCUMENTS;
+
+    fn ndims(&self) -> usize {
+        self.model.ndims()
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        Ok(self.embed_texts_with_usage(texts).await?.0)
+    }
+
+    async fn embed_texts_with_usage(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<(Vec<Embedding>, Usage), EmbeddingError> {
+        let texts = texts
+            .into_iter()
+            .map(|text| (text_hash(&text), text))
+            .collect::<Vec<_>>();
+
+        let mut cached = self
+            .cached(texts.iter().map(|(hash, _)| hash.clone()).collect())
+            .await
+            .unwrap_or_else(|err| {
+                warn!(?err, "Failed to read the embedding cache");
+                HashMap::new()
+            });
+
+        // Texts repeated in the request are only embedded once
+        let mut missing = texts
+            .iter()
+            .filter(|(hash, _)| !cached.contains_key(hash))
+            .collect::<Vec<_>>();
+        missing.sort_by(|a, b| a.0.cmp(&b.0));
+        missing.dedup_by(|a, b| a.0 == b.0);
+
+        let usage = if missing.is_empty() {
+            Usage::default()
+        } else {
+            let (embeddings, usage) = self
+                .model
+                .embed_texts_with_usage(missing.iter().map(|(_, text)| text.clone()))
+                .await?;
+            let embedded = missing
+                .iter()
+                .map(|(hash, _)| hash.clone())
+                .zip(embeddings.into_iter().map(|embedding| embedding.vec))
+                .collect::<Vec<_>>();
+
+            if let Err(err) = self.store(embedded.clone()).await {
+                warn!(?err, "Failed to write the embedding cache");
+            }
+            cached.extend(embedded);
+            usage
+        };
+
+        let misses = missing.len() as u64;
+        let hits = texts.len() as u64 - misses;
+        self.counters.hits.fetch_add(hits, Ordering::Relaxed);
+        self.counters.misses.fetch_add(misses, Ordering::Relaxed);
+        debug!(hits, misses, "Embedded texts through the cache");
+
+        let embeddings = texts
+            .into_iter()
+            .map(|(hash, document)| {
+                let vec = cached.get(&hash).cloned().ok_or_else(|| {
+                    EmbeddingError::ResponseError(
+                        "Model returned fewer embeddings than texts".to_string(),
+                    )
+                })?;
+                Ok(Embedding { document, vec })
+            })
+            .collect::<Result<Vec<_>, EmbeddingError>>()?;
+
+        Ok((embeddings, usage))
+    }
+}
+
+/// Delete the cached embeddings of `texts`, whatever the model that embedded them, e.g.: when
+/// the texts are erased from the database. Returns the number of embeddings deleted, none if
+/// the database has no embedding cache.
+pub fn delete_cached_embeddings<'a>(
+    txn: &rusqlite::Transaction<'_>,
+    texts: impl IntoIterator<Item = &'a str>,
+) -> Result<usize, tokio_rusqlite::Error> {
+    let exists = txn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'embedding_cache'",
+            [],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if !exists {
+        return Ok(0);
+    }
+
+    let mut stmt = txn.prepare("DELETE FROM embedding_cache WHERE text_hash = ?1")?;
+    let mut deleted = 0;
+    for text in texts {
+        deleted += stmt.execute([text_hash(text)])?;
+    }
+    Ok(deleted)
+}
+
+/// Hex encoded SHA-256 of `text`
+fn text_hash(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+fn deserialize_vector(blob: &[u8]) -> Vec<f64> {
+    blob.chunks_exact(8)
+        .map(|bytes| f64::from_ne_bytes(bytes.try_into().expect("chunks of 8 bytes")))
+        .collect()
+}
@@ -0,0 +1,216 @@
+use std::collections::{HashMap, HashSet};
+
+use rig::embeddings::EmbeddingModel;
+use rig::vector_store::{VectorStoreError, VectorStoreIndex};
+use serde::Deserialize;
+use tracing::debug;
+use zerocopy::IntoBytes;
+
+use crate::{
+    filter_sql, row_id, row_to_json, serialize_embedding, Filter, SqliteVectorStore,
+    SqliteVectorStoreTable,
+};
+
+/// Constant of reciprocal rank fusion, dampening the weight of the first ranks
+const RRF_K: f64 = 60.0;
+
+/// Number of candidates fetched from each search per requested result
+const CANDIDATES_PER_RESULT: usize = 4;
+
+/// Minimum number of candidates fetched from each search
+const MIN_CANDIDATES: usize = 20;
+
+/// Index combining vector search with keyword search on the searchable columns of a table
+/// (see [crate::Column::searchable]). Keyword search finds the exact tokens embeddings
+/// handle badly, such as addresses, tickers, usernames or error codes.
+///
+/// The rankings of both searches are merged with reciprocal rank fusion: the score of a row
+/// is the sum of `1 / (60 + rank)` over the rankings it appears in. Unlike the distance
+/// returned by [crate::SqliteVectorIndex], a higher score is a better match.
+///
+/// ```rust,ignore
+/// let index = vector_store.hybrid_index(model);
+/// let results = index.top_n::<Document>("price of $RINA", 5).await?;
+/// ```
+pub struct SqliteHybridIndex<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> {
+    store: SqliteVectorStore<E, T>,
+    embedding_model: E,
+    filter: Option<Filter>,
+}
+
+impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable> SqliteHybridIndex<E, T> {
+    pub fn new(embedding_model: E, store: SqliteVectorStore<E, T>) -> Self {
+        Self {
+            store,
+            embedding_model,
+            filter: None,
+        }
+    }
+
+    /// Only search the rows matching `filter`, in addition to the filters already set
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(match self.filter {
+            Some(current) => current.and(filter),
+            None => filter,
+        });
+        self
+    }
+
+    /// Best `n` matches of `query` as (score, id, row) tuples, best first
+    async fn search(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, serde_json::Value)>, VectorStoreError> {
+        let table_name = T::name();
+        let columns = T::schema();
+        if !columns.iter().any(|column| column.searchable) {
+            return Err(VectorStoreError::DatastoreError(
+                format!("{} has no searchable column for keyword search", table_name).into(),
+            ));
+        }
+        let column_names: Vec<&str> = columns.iter().map(|column| column.name).collect();
+
+        debug!("Finding top {} hybrid matches for query", n);
+        let embedding = self.embedding_model.embed_text(query).await?;
+        let query_vec = serialize_embedding(&embedding);
+        let keywords = fts_query(query);
+        let candidates = (n * CANDIDATES_PER_RESULT).max(MIN_CANDIDATES);
+
+        let (vector_filter, vector_params) = filter_sql::<T>(self.filter.as_ref(), "e.rowid")?;
+        let (keyword_filter, keyword_params) = filter_sql::<T>(self.filter.as_ref(), "rowid")?;
+
+        self.store
+            .conn
+            .call(move |conn| {
+                let params = [
+                    rusqlite::types::Value::Blob(query_vec.as_bytes().to_vec()),
+                    rusqlite::types::Value::Integer(candidates as i64),
+                ]
+                .into_iter()
+                .chain(vector_params);
+                let by_vector = conn
+                    .prepare(&format!(
+                        "SELECT e.rowid
+                         FROM {0}_embeddings e
+                         JOIN {0} d ON e.rowid = d.rowid
+                         WHERE e.embedding MATCH ?1 AND k = ?2{1}
+                         ORDER BY e.distance",
+                        table_name, vector_filter
+                    ))?
+                    .query_map(rusqlite::params_from_iter(params), |row| row.get(0))?
+                    .collect::<Result<Vec<i64>, _>>()?;
+
+                let by_keyword = match keywords {
+                    Some(keywords) => {
+                        let params = [
+                            rusqlite::types::Value::Text(keywords),
+                            rusqlite::types::Value::Integer(candidates as i64),
+                        ]
+                        .into_iter()
+                        .chain(keyword_params);
+                        conn.prepare(&format!(
+                            "SELECT rowid
+                             FROM {0}_fts
+                             WHERE {0}_fts MATCH ?1{1}
+                             ORDER BY rank
+                             LIMIT ?2",
+                            table_name, keyword_filter
+                        ))?
+                        .query_map(rusqlite::params_from_iter(params), |row| row.get(0))?
+                        .collect::<Result<Vec<i64>, _>>()?
+                    }
+                    None => Vec::new(),
+                };
+                debug!(
+                    "Found {} vector and {} keyword candidates",
+                    by_vector.len(),
+                    by_keyword.len()
+                );
+
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT {} FROM {} WHERE rowid = ?1",
+                    column_names.join(", "),
+                    table_name
+                ))?;
+                let mut results = Vec::new();
+                for (score, rowid) in reciprocal_rank_fusion(&[by_vector, by_keyword], n) {
+                    let (id, document) = stmt.query_row([rowid], |row| {
+                        Ok((row_id(row)?, row_to_json(row, &columns)?))
+                    })?;
+                    results.push((score, id, document));
+                }
+
+                Ok(results)
+            })
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
+    }
+}
+
+impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> VectorStoreIndex
+    for SqliteHybridIndex<E, T>
+{
+    async fn top_n<D: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, D)>, VectorStoreError> {
+        let mut top_n = Vec::new();
+        for (score, id, doc_value) in self.search(query, n).await? {
+            match serde_json::from_value::<D>(doc_value) {
+                Ok(doc) => top_n.push((score, id, doc)),
+                Err(e) => debug!("Failed to deserialize document {}: {}", id, e),
+            }
+        }
+
+        debug!("Returning {} matches", top_n.len());
+        Ok(top_n)
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        Ok(self
+            .search(query, n)
+            .await?
+            .into_iter()
+            .map(|(score, id, _)| (score, id))
+            .collect())
+    }
+}
+
+/// FTS5 query matching any word of `query`. Words are quoted, such that characters with a
+/// meaning in the FTS5 query syntax (e.g.: `$`, `-`, `:`) are never interpreted.
+fn fts_query(query: &str) -> Option<String> {
+    let terms = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{}\"", term))
+        .collect::<Vec<_>>();
+
+    (!terms.is_empty()).then(|| terms.join(" OR "))
+}
+
+/// Merge rankings of rowids, best first, into the best `n` (score, rowid) pairs
+fn reciprocal_rank_fusion(rankings: &[Vec<i64>], n: usize) -> Vec<(f64, i64)> {
+    let mut scores: HashMap<i64, f64> = HashMap::new();
+    for ranking in rankings {
+        // A row with several embeddings may appear several times in a ranking
+        let mut seen = HashSet::new();
+        let ranking = ranking.iter().filter(|rowid| seen.insert(**rowid));
+        for (rank, rowid) in ranking.enumerate() {
+            *scores.entry(*rowid).or_default() += 1.0 / (RRF_K + rank as f64 + 1.0);
+        }
+    }
+
+    let mut fused = scores
+        .into_iter()
+        .map(|(rowid, score)| (score, rowid))
+        .collect::<Vec<_>>();
+    fused.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    fused.truncate(n);
+    fused
+}
@@ -1,12 +1,26 @@
-use rig::embeddings::{Embedding, EmbeddingModel};
+use rig::embeddings::{Embedding, EmbeddingModel, EmbeddingsBuilder};
 use rig::vector_store::{VectorStoreError, VectorStoreIndex};
-use rig::OneOrMany;
+use rig::{Embed, OneOrMany};
+use rusqlite::OptionalExtension;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use tokio_rusqlite::Connection;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use zerocopy::IntoBytes;
 
+mod cache;
+mod hybrid;
+
+pub use cache::{delete_cached_embeddings, CacheStats, CachedEmbeddingModel};
+pub use hybrid::SqliteHybridIndex;
+#[cfg(feature = "derive")]
+pub use rig_sqlite_derive::SqliteTable;
+
+// Used by the code generated by the `SqliteTable` derive macro
+#[doc(hidden)]
+pub use rusqlite;
+
 #[derive(Debug)]
 pub enum SqliteError {
     DatabaseError(Box<dyn std::error::Error + Send + Sync>),
@@ -17,12 +31,19 @@ pub enum SqliteError {
 pub trait ColumnValue: Send + Sync {
     fn to_sql_string(&self) -> String;
     fn column_type(&self) -> &'static str;
+
+    /// Value bound to the column when the row is inserted, the text returned by
+    /// [ColumnValue::to_sql_string] by default
+    fn to_sql_value(&self) -> rusqlite::types::Value {
+        rusqlite::types::Value::Text(self.to_sql_string())
+    }
 }
 
 pub struct Column {
     name: &'static str,
     col_type: &'static str,
     indexed: bool,
+    searchable: bool,
 }
 
 impl Column {
@@ -31,6 +52,7 @@ impl Column {
             name,
             col_type,
             indexed: false,
+            searchable: false,
         }
     }
 
@@ -38,6 +60,163 @@ impl Column {
         self.indexed = true;
         self
     }
+
+    /// Mirror the column in a full-text (FTS5) index, such that it can be searched by
+    /// keywords with a [SqliteHybridIndex]
+    pub fn searchable(mut self) -> Self {
+        self.searchable = true;
+        self
+    }
+
+    /// JSON value of a value read from the column, booleans being stored as integers in
+    /// `BOOLEAN` columns
+    fn to_json(&self, value: rusqlite::types::ValueRef<'_>) -> serde_json::Value {
+        use rusqlite::types::ValueRef;
+
+        match value {
+            ValueRef::Null => serde_json::Value::Null,
+            ValueRef::Integer(value) if self.col_type.to_uppercase().starts_with("BOOL") => {
+                serde_json::Value::Bool(value != 0)
+            }
+            ValueRef::Integer(value) => value.into(),
+            ValueRef::Real(value) => serde_json::Number::from_f64(value)
+                .map_or(serde_json::Value::Null, serde_json::Value::Number),
+            ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned().into(),
+            ValueRef::Blob(bytes) => bytes.to_vec().into(),
+        }
+    }
+}
+
+/// Predicate on the columns of a [SqliteVectorStoreTable], used to restrict the rows
+/// searched by a [SqliteVectorIndex].
+///
+/// Filters are evaluated by SQLite before the nearest neighbours are selected, such that a
+/// search returns the `n` closest rows among the ones matching the filter. Values are
+/// compared with the values stored by [ColumnValue::to_sql_value], so that e.g. timestamps
+/// stored as RFC 3339 text can be filtered by range.
+///
+/// ```rust
+/// use rig_sqlite::Filter;
+///
+/// let filter = Filter::eq("channel_id", "1234".to_string())
+///     .and(Filter::is_in("source", vec!["discord".to_string(), "telegram".to_string()]))
+///     .and(Filter::gte("created_at", "2024-01-01T00:00:00+00:00".to_string()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Eq(&'static str, rusqlite::types::Value),
+    In(&'static str, Vec<rusqlite::types::Value>),
+    Gt(&'static str, rusqlite::types::Value),
+    Gte(&'static str, rusqlite::types::Value),
+    Lt(&'static str, rusqlite::types::Value),
+    Lte(&'static str, rusqlite::types::Value),
+    And(Vec<Filter>),
+}
+
+impl Filter {
+    pub fn eq(column: &'static str, value: impl ColumnValue) -> Self {
+        Filter::Eq(column, value.to_sql_value())
+    }
+
+    pub fn is_in<V: ColumnValue>(
+        column: &'static str,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        Filter::In(
+            column,
+            values
+                .into_iter()
+                .map(|value| value.to_sql_value())
+                .collect(),
+        )
+    }
+
+    pub fn gt(column: &'static str, value: impl ColumnValue) -> Self {
+        Filter::Gt(column, value.to_sql_value())
+    }
+
+    pub fn gte(column: &'static str, value: impl ColumnValue) -> Self {
+        Filter::Gte(column, value.to_sql_value())
+    }
+
+    pub fn lt(column: &'static str, value: impl ColumnValue) -> Self {
+        Filter::Lt(column, value.to_sql_value())
+    }
+
+    pub fn lte(column: &'static str, value: impl ColumnValue) -> Self {
+        Filter::Lte(column, value.to_sql_value())
+    }
+
+    /// Rows matching both filters
+    pub fn and(self, other: Filter) -> Self {
+        match self {
+            Filter::And(mut filters) => {
+                filters.push(other);
+                Filter::And(filters)
+            }
+            filter => Filter::And(vec![filter, other]),
+        }
+    }
+
+    /// Columns the filter applies to
+    fn columns(&self) -> Vec<&'static str> {
+        match self {
+            Filter::Eq(column, _)
+            | Filter::In(column, _)
+            | Filter::Gt(column, _)
+            | Filter::Gte(column, _)
+            | Filter::Lt(column, _)
+            | Filter::Lte(column, _) => vec![column],
+            Filter::And(filters) => filters.iter().flat_map(Filter::columns).collect(),
+        }
+    }
+
+    /// SQL expression of the filter, pushing its values to `params`. Placeholders are
+    /// numbered after the parameters already in `params` plus `offset`.
+    fn to_sql(&self, offset: usize, params: &mut Vec<rusqlite::types::Value>) -> String {
+        fn placeholder(
+            offset: usize,
+            params: &mut Vec<rusqlite::types::Value>,
+            value: &rusqlite::types::Value,
+        ) -> String {
+            params.push(value.clone());
+            format!("?{}", offset + params.len())
+        }
+
+        match self {
+            Filter::Eq(column, value) => {
+                format!("{} = {}", column, placeholder(offset, params, value))
+            }
+            Filter::In(_, values) if values.is_empty() => "0".to_string(),
+            Filter::In(column, values) => format!(
+                "{} IN ({})",
+                column,
+                values
+                    .iter()
+                    .map(|value| placeholder(offset, params, value))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Filter::Gt(column, value) => {
+                format!("{} > {}", column, placeholder(offset, params, value))
+            }
+            Filter::Gte(column, value) => {
+                format!("{} >= {}", column, placeholder(offset, params, value))
+            }
+            Filter::Lt(column, value) => {
+                format!("{} < {}", column, placeholder(offset, params, value))
+            }
+            Filter::Lte(column, value) => {
+                format!("{} <= {}", column, placeholder(offset, params, value))
+            }
+            Filter::And(filters) if filters.is_empty() => "1".to_string(),
+            Filter::And(filters) => filters
+                .iter()
+                .map(|filter| format!("({})", filter.to_sql(offset, params)))
+                .collect::<Vec<_>>()
+                .join(" AND "),
+        }
+    }
 }
 
 /// Example of a document type that can be used with SqliteVectorStore
@@ -77,6 +256,19 @@ impl Column {
 ///     }
 /// }
 /// ```
+///
+/// With the `derive` feature, `#[derive(SqliteTable)]` implements this trait along with
+/// `TryFrom<&rusqlite::Row>`:
+/// ```rust,ignore
+/// #[derive(Embed, SqliteTable, Clone, Debug, Deserialize)]
+/// #[table(name = "documents")]
+/// struct Document {
+///     id: String,
+///     #[embed]
+///     #[column(searchable)]
+///     content: String,
+/// }
+/// ```
 pub trait SqliteVectorStoreTable: Send + Sync + Clone {
     fn name() -> &'static str;
     fn schema() -> Vec<Column>;
@@ -117,6 +309,12 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> SqliteVec
             table_name, table_name
         )];
 
+        let searchable = schema
+            .iter()
+            .filter(|column| column.searchable)
+            .map(|column| column.name)
+            .collect::<Vec<_>>();
+
         // Add indexes for marked columns
         for column in schema {
             if column.indexed {
@@ -138,12 +336,29 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> SqliteVec
                 conn.execute_batch(&index_stmt)?;
             }
 
+            // The width of the embeddings table is fixed when it is created. If the embedding
+            // model changed dimension since, the stored embeddings cannot be compared with the
+            // new ones: drop them so that the rows can be re-embedded with `embed_missing`.
+            let embeddings_table = format!("{}_embeddings", table_name);
+            if let Some(existing_dims) = embedding_dims(conn, &embeddings_table)? {
+                if existing_dims != dims {
+                    warn!(
+                        "Embedding dimension of {} changed from {} to {}, dropping stored embeddings",
+                        table_name, existing_dims, dims
+                    );
+                    conn.execute_batch(&format!("DROP TABLE {}", embeddings_table))?;
+                }
+            }
+
             // Create embeddings table
             conn.execute_batch(&format!(
                 "CREATE VIRTUAL TABLE IF NOT EXISTS {}_embeddings USING vec0(embedding float[{}])",
                 table_name, dims
             ))?;
 
+            // Create the full-text index of the searchable columns
+            sync_fts_table(conn, table_name, &searchable)?;
+
             conn.execute_batch("COMMIT")?;
             Ok(())
         })
@@ -160,6 +375,11 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> SqliteVec
         SqliteVectorIndex::new(model, self)
     }
 
+    /// Index combining vector and keyword search, see [SqliteHybridIndex]
+    pub fn hybrid_index(self, model: E) -> SqliteHybridIndex<E, T> {
+        SqliteHybridIndex::new(model, self)
+    }
+
     pub fn add_rows_with_txn(
         &self,
         txn: &rusqlite::Transaction<'_>,
@@ -188,27 +408,11 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> SqliteVec
 
             txn.execute(
                 &insert_sql,
-                rusqlite::params_from_iter(values.iter().map(|(_, val)| val.to_sql_string())),
+                rusqlite::params_from_iter(values.iter().map(|(_, val)| val.to_sql_value())),
             )?;
             last_id = txn.last_insert_rowid();
 
-            let embeddings_sql = format!(
-                "INSERT INTO {}_embeddings (rowid, embedding) VALUES (?1, ?2)",
-                table_name
-            );
-
-            let mut stmt = txn.prepare(&embeddings_sql)?;
-            for (i, embedding) in embeddings.iter().enumerate() {
-                let vec = serialize_embedding(embedding);
-                debug!(
-                    "Storing embedding {} of {} (size: {} bytes)",
-                    i + 1,
-                    embeddings.len(),
-                    vec.len() * 4
-                );
-                let blob = rusqlite::types::Value::Blob(vec.as_bytes().to_vec());
-                stmt.execute(rusqlite::params![last_id, blob])?;
-            }
+            insert_embeddings::<T>(txn, last_id, embeddings)?;
         }
 
         Ok(last_id)
@@ -231,6 +435,403 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> SqliteVec
             .await
             .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
     }
+
+    /// Insert the rows, or update the rows with the same id and replace their embeddings.
+    /// Returns the rowid of the last row.
+    pub fn upsert_rows_with_txn(
+        &self,
+        txn: &rusqlite::Transaction<'_>,
+        documents: Vec<(T, OneOrMany<Embedding>)>,
+    ) -> Result<i64, tokio_rusqlite::Error> {
+        info!("Upserting {} documents to store", documents.len());
+        let mut last_id = 0;
+
+        for (doc, embeddings) in &documents {
+            last_id = upsert_row(txn, doc)?;
+            insert_embeddings::<T>(txn, last_id, embeddings)?;
+        }
+
+        Ok(last_id)
+    }
+
+    /// Insert the rows, or update the rows with the same id and replace their embeddings
+    pub async fn upsert_rows(
+        &self,
+        documents: Vec<(T, OneOrMany<Embedding>)>,
+    ) -> Result<i64, VectorStoreError> {
+        let this = self.clone();
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let result = this.upsert_rows_with_txn(&tx, documents)?;
+                tx.commit()?;
+                Ok(result)
+            })
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
+    }
+
+    /// Insert the rows, or update the rows with the same id, along with their embedding as
+    /// stored in the embeddings table (e.g.: rows exported from another database with
+    /// [SqliteVectorStore::rows]). Embeddings of another dimension than the embeddings table
+    /// are dropped, such that the rows left without embedding can be embedded with
+    /// [SqliteVectorStore::embed_missing]. Returns the number of embeddings stored.
+    pub fn import_rows_with_txn(
+        &self,
+        txn: &rusqlite::Transaction<'_>,
+        rows: Vec<(T, Option<Vec<f32>>)>,
+    ) -> Result<usize, tokio_rusqlite::Error> {
+        info!("Importing {} documents to store", rows.len());
+        let dims = embedding_dims(txn, &format!("{}_embeddings", T::name()))?;
+        let mut embedded = 0;
+
+        for (doc, embedding) in &rows {
+            let rowid = upsert_row(txn, doc)?;
+            if let Some(vec) = embedding.as_ref().filter(|vec| Some(vec.len()) == dims) {
+                let embedding = Embedding {
+                    document: doc.id(),
+                    vec: vec.iter().map(|x| *x as f64).collect(),
+                };
+                insert_embeddings::<T>(txn, rowid, &OneOrMany::one(embedding))?;
+                embedded += 1;
+            }
+        }
+
+        Ok(embedded)
+    }
+
+    /// See [SqliteVectorStore::import_rows_with_txn]
+    pub async fn import_rows(
+        &self,
+        rows: Vec<(T, Option<Vec<f32>>)>,
+    ) -> Result<usize, VectorStoreError> {
+        let this = self.clone();
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let embedded = this.import_rows_with_txn(&tx, rows)?;
+                tx.commit()?;
+                Ok(embedded)
+            })
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
+    }
+
+    /// Up to `limit` rows with a rowid greater than `after`, in rowid order, along with their
+    /// rowid and their embedding if they have one, to page through the whole table
+    pub async fn rows<D: for<'a> Deserialize<'a>>(
+        &self,
+        after: i64,
+        limit: usize,
+    ) -> Result<Vec<(i64, D, Option<Vec<f32>>)>, VectorStoreError> {
+        let table_name = T::name();
+        let columns = T::schema();
+        let column_names: Vec<&str> = columns.iter().map(|column| column.name).collect();
+
+        let rows = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT {1}, rowid, (SELECT embedding FROM {0}_embeddings e WHERE e.rowid = {0}.rowid)
+                     FROM {0}
+                     WHERE rowid > ?1
+                     ORDER BY rowid
+                     LIMIT ?2",
+                    table_name,
+                    column_names.join(", ")
+                ))?;
+
+                let rows = stmt
+                    .query_map(rusqlite::params![after, limit], |row| {
+                        let embedding = row
+                            .get::<_, Option<Vec<u8>>>(column_names.len() + 1)?
+                            .map(|blob| deserialize_embedding(&blob));
+                        Ok((
+                            row.get::<_, i64>(column_names.len())?,
+                            row_to_json(row, &columns)?,
+                            embedding,
+                        ))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(rows)
+            })
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        rows.into_iter()
+            .map(|(rowid, value, embedding)| Ok((rowid, serde_json::from_value(value)?, embedding)))
+            .collect()
+    }
+
+    /// Delete the rows matching `filter` along with their embeddings. Returns the number of
+    /// rows deleted.
+    pub fn delete_where_with_txn(
+        &self,
+        txn: &rusqlite::Transaction<'_>,
+        filter: &Filter,
+    ) -> Result<usize, tokio_rusqlite::Error> {
+        let table_name = T::name();
+        let (condition, params) =
+            filter_condition::<T>(filter, 0).map_err(tokio_rusqlite::Error::Other)?;
+
+        txn.execute(
+            &format!(
+                "DELETE FROM {0}_embeddings WHERE rowid IN (SELECT rowid FROM {0} WHERE {1})",
+                table_name, condition
+            ),
+            rusqlite::params_from_iter(params.iter()),
+        )?;
+        let deleted = txn.execute(
+            &format!("DELETE FROM {} WHERE {}", table_name, condition),
+            rusqlite::params_from_iter(params.iter()),
+        )?;
+
+        info!("Deleted {} documents from store", deleted);
+        Ok(deleted)
+    }
+
+    /// Delete the rows matching `filter` along with their embeddings. Returns the number of
+    /// rows deleted.
+    pub async fn delete_where(&self, filter: Filter) -> Result<usize, VectorStoreError> {
+        let this = self.clone();
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let deleted = this.delete_where_with_txn(&tx, &filter)?;
+                tx.commit()?;
+                Ok(deleted)
+            })
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
+    }
+
+    /// Delete the row with the given id along with its embeddings. Returns whether it existed.
+    pub async fn delete_by_id(&self, id: &str) -> Result<bool, VectorStoreError> {
+        Ok(self.delete_where(Filter::eq("id", id.to_string())).await? > 0)
+    }
+
+    /// Row with the given id, if any
+    pub async fn get_by_id<D: for<'a> Deserialize<'a>>(
+        &self,
+        id: &str,
+    ) -> Result<Option<D>, VectorStoreError> {
+        let id = id.to_string();
+        let table_name = T::name();
+        let columns = T::schema();
+        let column_names: Vec<&str> = columns.iter().map(|column| column.name).collect();
+
+        let row = self
+            .conn
+            .call(move |conn| {
+                Ok(conn
+                    .query_row(
+                        &format!(
+                            "SELECT {} FROM {} WHERE id = ?1",
+                            column_names.join(", "),
+                            table_name
+                        ),
+                        [id],
+                        |row| row_to_json(row, &columns),
+                    )
+                    .optional()?)
+            })
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        row.map(serde_json::from_value)
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    /// Embed the rows of the table that do not have an embedding (e.g.: because the
+    /// embeddings were dropped after a change of embedding model dimension) and store
+    /// their embeddings. Returns the number of rows that were embedded.
+    pub async fn embed_missing(&self, embedding_model: &E) -> Result<usize, VectorStoreError>
+    where
+        T: Embed + for<'a> Deserialize<'a>,
+    {
+        let table_name = T::name();
+        let columns = T::schema();
+        let column_names: Vec<&str> = columns.iter().map(|column| column.name).collect();
+
+        let rows = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT {1}, rowid
+                     FROM {0}
+                     WHERE rowid NOT IN (SELECT rowid FROM {0}_embeddings)",
+                    table_name,
+                    column_names.join(", ")
+                ))?;
+
+                let rows = stmt
+                    .query_map([], |row| {
+                        Ok((
+                            row.get::<_, i64>(column_names.len())?,
+                            row_to_json(row, &columns)?,
+                        ))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(rows)
+            })
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        if rows.is_empty() {
+            return Ok(0);
+        }
+        info!("Embedding {} rows of {}", rows.len(), table_name);
+
+        let mut rowids = HashMap::new();
+        let mut documents = Vec::new();
+        for (rowid, value) in rows {
+            match serde_json::from_value::<T>(value) {
+                Ok(doc) => {
+                    rowids.insert(doc.id(), rowid);
+                    documents.push(doc);
+                }
+                Err(e) => warn!(
+                    "Failed to deserialize row {} of {}: {}",
+                    rowid, table_name, e
+                ),
+            }
+        }
+
+        let embeddings = EmbeddingsBuilder::new(embedding_model.clone())
+            .documents(documents)
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
+            .build()
+            .await?
+            .into_iter()
+            // Like the rows added with `add_rows`, each row has a single embedding
+            .filter_map(|(doc, embeddings)| {
+                rowids
+                    .get(&doc.id())
+                    .map(|rowid| (*rowid, embeddings.first()))
+            })
+            .collect::<Vec<_>>();
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt = tx.prepare(&format!(
+                        "INSERT INTO {}_embeddings (rowid, embedding) VALUES (?1, ?2)",
+                        table_name
+                    ))?;
+                    for (rowid, embedding) in &embeddings {
+                        let vec = serialize_embedding(embedding);
+                        let blob = rusqlite::types::Value::Blob(vec.as_bytes().to_vec());
+                        stmt.execute(rusqlite::params![rowid, blob])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(embeddings.len())
+            })
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
+    }
+}
+
+/// Dimension of an existing `vec0` embeddings table, if the table exists
+fn embedding_dims(
+    conn: &rusqlite::Connection,
+    table_name: &str,
+) -> Result<Option<usize>, rusqlite::Error> {
+    let sql = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [table_name],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?;
+
+    // e.g.: CREATE VIRTUAL TABLE documents_embeddings USING vec0(embedding float[1536])
+    Ok(sql.and_then(|sql| {
+        let start = sql.find("float[")? + "float[".len();
+        let end = start + sql[start..].find(']')?;
+        sql[start..end].parse().ok()
+    }))
+}
+
+/// Keep the FTS5 table `<table>_fts` mirroring the searchable `columns` of a table in sync
+/// with it through triggers. The full-text table is rebuilt from the table if the searchable
+/// columns changed, and dropped if there are none.
+fn sync_fts_table(
+    conn: &rusqlite::Connection,
+    table_name: &str,
+    columns: &[&str],
+) -> Result<(), rusqlite::Error> {
+    let fts_table = format!("{}_fts", table_name);
+
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [&fts_table],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if exists {
+        let existing = conn
+            .prepare(&format!("PRAGMA table_info({})", fts_table))?
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if existing == columns {
+            return Ok(());
+        }
+
+        info!(
+            "Searchable columns of {} changed, rebuilding full-text index",
+            table_name
+        );
+        conn.execute_batch(&format!("DROP TABLE {}", fts_table))?;
+    }
+
+    // The triggers outlive the full-text table if it was dropped
+    conn.execute_batch(&format!(
+        "DROP TRIGGER IF EXISTS {0}_fts_replace;
+         DROP TRIGGER IF EXISTS {0}_fts_insert;
+         DROP TRIGGER IF EXISTS {0}_fts_update;
+         DROP TRIGGER IF EXISTS {0}_fts_delete;",
+        table_name
+    ))?;
+
+    if columns.is_empty() {
+        return Ok(());
+    }
+
+    let column_list = columns.join(", ");
+    let new_values = columns
+        .iter()
+        .map(|column| format!("new.{}", column))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    // Rows replaced by `INSERT OR REPLACE` do not fire delete triggers, hence the removal
+    // of the rows with the same id before inserting
+    conn.execute_batch(&format!(
+        "CREATE VIRTUAL TABLE {0}_fts USING fts5({1}, tokenize = 'unicode61 remove_diacritics 2');
+         INSERT INTO {0}_fts (rowid, {1}) SELECT rowid, {1} FROM {0};
+         CREATE TRIGGER {0}_fts_replace BEFORE INSERT ON {0} BEGIN
+             DELETE FROM {0}_fts WHERE rowid IN (SELECT rowid FROM {0} WHERE id = new.id);
+         END;
+         CREATE TRIGGER {0}_fts_insert AFTER INSERT ON {0} BEGIN
+             INSERT INTO {0}_fts (rowid, {1}) VALUES (new.rowid, {2});
+         END;
+         CREATE TRIGGER {0}_fts_update AFTER UPDATE ON {0} BEGIN
+             DELETE FROM {0}_fts WHERE rowid = old.rowid;
+             INSERT INTO {0}_fts (rowid, {1}) VALUES (new.rowid, {2});
+         END;
+         CREATE TRIGGER {0}_fts_delete AFTER DELETE ON {0} BEGIN
+             DELETE FROM {0}_fts WHERE rowid = old.rowid;
+         END;",
+        table_name, column_list, new_values
+    ))
 }
 
 /// SQLite vector store implementation for Rig.
@@ -318,6 +919,7 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> SqliteVec
 pub struct SqliteVectorIndex<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> {
     store: SqliteVectorStore<E, T>,
     embedding_model: E,
+    filter: Option<Filter>,
 }
 
 impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable> SqliteVectorIndex<E, T> {
@@ -325,8 +927,18 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable> SqliteVectorIndex<E
         Self {
             store,
             embedding_model,
+            filter: None,
         }
     }
+
+    /// Only search the rows matching `filter`, in addition to the filters already set
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(match self.filter {
+            Some(current) => current.and(filter),
+            None => filter,
+        });
+        self
+    }
 }
 
 impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> VectorStoreIndex
@@ -342,6 +954,8 @@ impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> VectorSto
         let query_vec: Vec<f32> = serialize_embedding(&embedding);
         let table_name = T::name();
 
+        let (filter, filter_params) = filter_sql::<T>(self.filter.as_ref(), "e.rowid")?;
+
         // Get all column names from SqliteVectorStoreTable
         let columns = T::schema();
         let column_names: Vec<&str> = columns.iter().map(|column| column.name).collect();
@@ -356,23 +970,25 @@ impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> VectorSto
                     "SELECT d.{}, e.distance 
                     FROM {}_embeddings e
                     JOIN {} d ON e.rowid = d.rowid
-                    WHERE e.embedding MATCH ?1 AND k = ?2
+                    WHERE e.embedding MATCH ?1 AND k = ?2{}
                     ORDER BY e.distance",
-                    select_cols, table_name, table_name
+                    select_cols, table_name, table_name, filter
                 ))?;
 
+                let params = [
+                    rusqlite::types::Value::Blob(query_vec.as_bytes().to_vec()),
+                    rusqlite::types::Value::Integer(n as i64),
+                ]
+                .into_iter()
+                .chain(filter_params);
+
                 let rows = stmt
-                    .query_map(rusqlite::params![query_vec.as_bytes().to_vec(), n], |row| {
-                        // Create a map of column names to values
-                        let mut map = serde_json::Map::new();
-                        for (i, col_name) in column_names.iter().enumerate() {
-                            let value: String = row.get(i)?;
-                            map.insert(col_name.to_string(), serde_json::Value::String(value));
-                        }
+                    .query_map(rusqlite::params_from_iter(params), |row| {
+                        let document = row_to_json(row, &columns)?;
                         let distance: f64 = row.get(column_names.len())?;
-                        let id: String = row.get(0)?; // Assuming id is always first column
+                        let id = row_id(row)?;
 
-                        Ok((id, serde_json::Value::Object(map), distance))
+                        Ok((id, document, distance))
                     })?
                     .collect::<Result<Vec<_>, _>>()?;
                 Ok(rows)
@@ -407,6 +1023,7 @@ impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> VectorSto
         let embedding = self.embedding_model.embed_text(query).await?;
         let query_vec = serialize_embedding(&embedding);
         let table_name = T::name();
+        let (filter, filter_params) = filter_sql::<T>(self.filter.as_ref(), "e.rowid")?;
 
         let results = self
             .store
@@ -416,22 +1033,27 @@ impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> VectorSto
                     "SELECT d.id, e.distance 
                      FROM {0}_embeddings e
                      JOIN {0} d ON e.rowid = d.rowid
-                     WHERE e.embedding MATCH ?1 AND k = ?2
+                     WHERE e.embedding MATCH ?1 AND k = ?2{1}
                      ORDER BY e.distance",
-                    table_name
+                    table_name, filter
                 ))?;
 
+                let params = [
+                    rusqlite::types::Value::Blob(
+                        query_vec
+                            .iter()
+                            .flat_map(|x| x.to_le_bytes())
+                            .collect::<Vec<u8>>(),
+                    ),
+                    rusqlite::types::Value::Integer(n as i64),
+                ]
+                .into_iter()
+                .chain(filter_params);
+
                 let results = stmt
-                    .query_map(
-                        rusqlite::params![
-                            query_vec
-                                .iter()
-                                .flat_map(|x| x.to_le_bytes())
-                                .collect::<Vec<u8>>(),
-                            n
-                        ],
-                        |row| Ok((row.get::<_, f64>(1)?, row.get::<_, String>(0)?)),
-                    )?
+                    .query_map(rusqlite::params_from_iter(params), |row| {
+                        Ok((row.get::<_, f64>(1)?, row.get::<_, String>(0)?))
+                    })?
                     .collect::<Result<Vec<_>, _>>()?;
                 Ok(results)
             })
@@ -443,10 +1065,180 @@ impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> VectorSto
     }
 }
 
+/// Insert the row, or update the row with the same id and delete its embeddings. Returns the
+/// rowid of the row.
+fn upsert_row<T: SqliteVectorStoreTable>(
+    txn: &rusqlite::Transaction<'_>,
+    doc: &T,
+) -> Result<i64, tokio_rusqlite::Error> {
+    let table_name = T::name();
+    let values = doc.column_values();
+    let params = values
+        .iter()
+        .map(|(_, val)| val.to_sql_value())
+        .collect::<Vec<_>>();
+
+    let existing = txn
+        .query_row(
+            &format!("SELECT rowid FROM {} WHERE id = ?1", table_name),
+            [doc.id()],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?;
+
+    match existing {
+        Some(rowid) => {
+            debug!("Updating document with id {}", doc.id());
+            let assignments = values
+                .iter()
+                .enumerate()
+                .map(|(i, (col, _))| format!("{} = ?{}", col, i + 1))
+                .collect::<Vec<_>>();
+            txn.execute(
+                &format!(
+                    "UPDATE {} SET {} WHERE rowid = ?{}",
+                    table_name,
+                    assignments.join(", "),
+                    values.len() + 1
+                ),
+                rusqlite::params_from_iter(
+                    params
+                        .into_iter()
+                        .chain([rusqlite::types::Value::Integer(rowid)]),
+                ),
+            )?;
+            txn.execute(
+                &format!("DELETE FROM {}_embeddings WHERE rowid = ?1", table_name),
+                [rowid],
+            )?;
+            Ok(rowid)
+        }
+        None => {
+            debug!("Storing document with id {}", doc.id());
+            let columns = values.iter().map(|(col, _)| *col).collect::<Vec<_>>();
+            let placeholders = (1..=values.len())
+                .map(|i| format!("?{}", i))
+                .collect::<Vec<_>>();
+            txn.execute(
+                &format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    table_name,
+                    columns.join(", "),
+                    placeholders.join(", ")
+                ),
+                rusqlite::params_from_iter(params),
+            )?;
+            Ok(txn.last_insert_rowid())
+        }
+    }
+}
+
+/// Store the embeddings of the row `rowid`
+fn insert_embeddings<T: SqliteVectorStoreTable>(
+    txn: &rusqlite::Transaction<'_>,
+    rowid: i64,
+    embeddings: &OneOrMany<Embedding>,
+) -> Result<(), rusqlite::Error> {
+    let mut stmt = txn.prepare(&format!(
+        "INSERT INTO {}_embeddings (rowid, embedding) VALUES (?1, ?2)",
+        T::name()
+    ))?;
+
+    for (i, embedding) in embeddings.iter().enumerate() {
+        let vec = serialize_embedding(embedding);
+        debug!(
+            "Storing embedding {} of {} (size: {} bytes)",
+            i + 1,
+            embeddings.len(),
+            vec.len() * 4
+        );
+        let blob = rusqlite::types::Value::Blob(vec.as_bytes().to_vec());
+        stmt.execute(rusqlite::params![rowid, blob])?;
+    }
+
+    Ok(())
+}
+
+/// SQL condition selecting the rows of `T` matching `filter`, along with its parameters
+/// (numbered after the first `offset` parameters of the query)
+fn filter_condition<T: SqliteVectorStoreTable>(
+    filter: &Filter,
+    offset: usize,
+) -> Result<(String, Vec<rusqlite::types::Value>), Box<dyn std::error::Error + Send + Sync>> {
+    let schema = T::schema();
+    if let Some(column) = filter
+        .columns()
+        .into_iter()
+        .find(|column| !schema.iter().any(|col| col.name == *column))
+    {
+        return Err(format!(
+            "Cannot filter on unknown column {} of {}",
+            column,
+            T::name()
+        )
+        .into());
+    }
+
+    let mut params = Vec::new();
+    let condition = filter.to_sql(offset, &mut params);
+    Ok((condition, params))
+}
+
+/// Condition restricting a query on `rowid_column` to the rows of `T` matching `filter`, if
+/// any, along with its parameters (numbered after the first two parameters of the query)
+fn filter_sql<T: SqliteVectorStoreTable>(
+    filter: Option<&Filter>,
+    rowid_column: &str,
+) -> Result<(String, Vec<rusqlite::types::Value>), VectorStoreError> {
+    let Some(filter) = filter else {
+        return Ok((String::new(), Vec::new()));
+    };
+
+    let (condition, params) =
+        filter_condition::<T>(filter, 2).map_err(VectorStoreError::DatastoreError)?;
+    Ok((
+        format!(
+            " AND {} IN (SELECT rowid FROM {} WHERE {})",
+            rowid_column,
+            T::name(),
+            condition
+        ),
+        params,
+    ))
+}
+
+/// Id of a row whose first column is the `id` column, whatever its type
+fn row_id(row: &rusqlite::Row<'_>) -> Result<String, rusqlite::Error> {
+    match row.get_ref(0)? {
+        rusqlite::types::ValueRef::Integer(id) => Ok(id.to_string()),
+        _ => row.get(0),
+    }
+}
+
+/// JSON object of a row whose first columns are `columns`, to deserialize it
+fn row_to_json(
+    row: &rusqlite::Row<'_>,
+    columns: &[Column],
+) -> Result<serde_json::Value, rusqlite::Error> {
+    let mut map = serde_json::Map::new();
+    for (i, column) in columns.iter().enumerate() {
+        map.insert(column.name.to_string(), column.to_json(row.get_ref(i)?));
+    }
+
+    Ok(serde_json::Value::Object(map))
+}
+
 fn serialize_embedding(embedding: &Embedding) -> Vec<f32> {
     embedding.vec.iter().map(|x| *x as f32).collect()
 }
 
+/// Embedding as stored by [serialize_embedding]
+fn deserialize_embedding(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_ne_bytes(bytes.try_into().expect("chunks of 4 bytes")))
+        .collect()
+}
+
 impl ColumnValue for String {
     fn to_sql_string(&self) -> String {
         self.clone()
@@ -457,17 +1249,127 @@ impl ColumnValue for String {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{Column, ColumnValue, SqliteVectorStore, SqliteVectorStoreTable};
-    use rig::{
+impl ColumnValue for i64 {
+    fn to_sql_string(&self) -> String {
+        self.to_string()
+    }
+
+    fn column_type(&self) -> &'static str {
+        "INTEGER"
+    }
+
+    fn to_sql_value(&self) -> rusqlite::types::Value {
+        rusqlite::types::Value::Integer(*self)
+    }
+}
+
+impl ColumnValue for f64 {
+    fn to_sql_string(&self) -> String {
+        self.to_string()
+    }
+
+    fn column_type(&self) -> &'static str {
+        "REAL"
+    }
+
+    fn to_sql_value(&self) -> rusqlite::types::Value {
+        rusqlite::types::Value::Real(*self)
+    }
+}
+
+macro_rules! impl_integer_column_value {
+    ($($ty:ty),*) => {
+        $(
+            impl ColumnValue for $ty {
+                fn to_sql_string(&self) -> String {
+                    self.to_string()
+                }
+
+                fn column_type(&self) -> &'static str {
+                    "INTEGER"
+                }
+
+                fn to_sql_value(&self) -> rusqlite::types::Value {
+                    rusqlite::types::Value::Integer(i64::from(*self))
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_column_value!(i8, i16, i32, u8, u16, u32);
+
+impl ColumnValue for f32 {
+    fn to_sql_string(&self) -> String {
+        self.to_string()
+    }
+
+    fn column_type(&self) -> &'static str {
+        "REAL"
+    }
+
+    fn to_sql_value(&self) -> rusqlite::types::Value {
+        rusqlite::types::Value::Real(f64::from(*self))
+    }
+}
+
+/// Booleans are stored as 0 or 1 in `BOOLEAN` columns
+impl ColumnValue for bool {
+    fn to_sql_string(&self) -> String {
+        (*self as i64).to_string()
+    }
+
+    fn column_type(&self) -> &'static str {
+        "BOOLEAN"
+    }
+
+    fn to_sql_value(&self) -> rusqlite::types::Value {
+        rusqlite::types::Value::Integer(*self as i64)
+    }
+}
+
+/// Timestamps are stored as RFC 3339 text, which sorts chronologically
+impl ColumnValue for chrono::DateTime<chrono::Utc> {
+    fn to_sql_string(&self) -> String {
+        self.to_rfc3339()
+    }
+
+    fn column_type(&self) -> &'static str {
+        "TEXT"
+    }
+}
+
+/// Optional values are stored as `NULL` when unset
+impl<T: ColumnValue> ColumnValue for Option<T> {
+    fn to_sql_string(&self) -> String {
+        self.as_ref()
+            .map(|value| value.to_sql_string())
+            .unwrap_or_default()
+    }
+
+    fn column_type(&self) -> &'static str {
+        self.as_ref().map_or("NULL", |value| value.column_type())
+    }
+
+    fn to_sql_value(&self) -> rusqlite::types::Value {
+        self.as_ref()
+            .map_or(rusqlite::types::Value::Null, |value| value.to_sql_value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Column, ColumnValue, SqliteVectorStore, SqliteVectorStoreTable};
+    use rig::{
         embeddings::EmbeddingsBuilder,
         providers::openai::{Client, TEXT_EMBEDDING_ADA_002},
         Embed,
     };
     use rusqlite::ffi::sqlite3_auto_extension;
     use sqlite_vec::sqlite3_vec_init;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
     use tokio_rusqlite::Connection;
 
     #[derive(Embed, Clone, Debug, Deserialize)]
@@ -554,4 +1456,616 @@ mod tests {
 
         Ok(())
     }
+
+    /// Embedding model returning vectors of the given dimension, filled with the length of the text
+    #[derive(Clone)]
+    struct MockEmbeddingModel(usize);
+
+    impl EmbeddingModel for MockEmbeddingModel {
+        const MAX_DOCUMENTS: usize = 16;
+
+        fn ndims(&self) -> usize {
+            self.0
+        }
+
+        async fn embed_texts(
+            &self,
+            texts: impl IntoIterator<Item = String> + Send,
+        ) -> Result<Vec<Embedding>, rig::embeddings::EmbeddingError> {
+            Ok(texts
+                .into_iter()
+                .map(|text| Embedding {
+                    vec: vec![text.len() as f64; self.0],
+                    document: text,
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embedding_dimension_change() -> Result<(), anyhow::Error> {
+        unsafe {
+            sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
+        }
+
+        let conn = Connection::open(":memory:").await?;
+
+        let model = MockEmbeddingModel(2);
+        let documents = vec![
+            TestDocument {
+                id: "doc0".to_string(),
+                content: "short".to_string(),
+            },
+            TestDocument {
+                id: "doc1".to_string(),
+                content: "a much longer document".to_string(),
+            },
+        ];
+        let embeddings = EmbeddingsBuilder::new(model.clone())
+            .documents(documents)?
+            .build()
+            .await?;
+
+        let vector_store = SqliteVectorStore::<_, TestDocument>::new(conn.clone(), &model).await?;
+        vector_store.add_rows(embeddings).await?;
+        assert_eq!(vector_store.embed_missing(&model).await?, 0);
+
+        // Reopening the store with a model of another dimension drops the stored embeddings
+        let model = MockEmbeddingModel(3);
+        let vector_store = SqliteVectorStore::<_, TestDocument>::new(conn.clone(), &model).await?;
+        let dims = conn
+            .call(|conn| Ok(embedding_dims(conn, "test_documents_embeddings")?))
+            .await?;
+        assert_eq!(dims, Some(3));
+
+        // The rows are kept and can be embedded again with the new model
+        assert_eq!(vector_store.embed_missing(&model).await?, 2);
+        assert_eq!(vector_store.embed_missing(&model).await?, 0);
+
+        let results = vector_store
+            .index(model)
+            .top_n::<TestDocument>("short", 1)
+            .await?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1, "doc0");
+
+        Ok(())
+    }
+
+    #[derive(Embed, Clone, Debug, Deserialize)]
+    struct TestReply {
+        id: String,
+        #[embed]
+        content: String,
+        reply_to: Option<String>,
+    }
+
+    impl SqliteVectorStoreTable for TestReply {
+        fn name() -> &'static str {
+            "test_replies"
+        }
+
+        fn schema() -> Vec<Column> {
+            vec![
+                Column::new("id", "TEXT PRIMARY KEY"),
+                Column::new("content", "TEXT"),
+                Column::new("reply_to", "TEXT"),
+            ]
+        }
+
+        fn id(&self) -> String {
+            self.id.clone()
+        }
+
+        fn column_values(&self) -> Vec<(&'static str, Box<dyn ColumnValue>)> {
+            vec![
+                ("id", Box::new(self.id.clone())),
+                ("content", Box::new(self.content.clone())),
+                ("reply_to", Box::new(self.reply_to.clone())),
+            ]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_optional_column() -> Result<(), anyhow::Error> {
+        unsafe {
+            sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
+        }
+
+        let conn = Connection::open(":memory:").await?;
+
+        let model = MockEmbeddingModel(2);
+        let replies = vec![
+            TestReply {
+                id: "msg0".to_string(),
+                content: "hi".to_string(),
+                reply_to: None,
+            },
+            TestReply {
+                id: "msg1".to_string(),
+                content: "hello there".to_string(),
+                reply_to: Some("msg0".to_string()),
+            },
+        ];
+        let embeddings = EmbeddingsBuilder::new(model.clone())
+            .documents(replies)?
+            .build()
+            .await?;
+
+        let vector_store = SqliteVectorStore::<_, TestReply>::new(conn.clone(), &model).await?;
+        vector_store.add_rows(embeddings).await?;
+
+        let nulls: i64 = conn
+            .call(|conn| {
+                Ok(conn.query_row(
+                    "SELECT COUNT(*) FROM test_replies WHERE reply_to IS NULL",
+                    [],
+                    |row| row.get(0),
+                )?)
+            })
+            .await?;
+        assert_eq!(nulls, 1);
+
+        let index = vector_store.index(model);
+        let results = index.top_n::<TestReply>("hi", 1).await?;
+        assert_eq!(results[0].2.reply_to, None);
+        let results = index.top_n::<TestReply>("hello there", 1).await?;
+        assert_eq!(results[0].2.reply_to.as_deref(), Some("msg0"));
+
+        Ok(())
+    }
+
+    #[derive(Embed, Clone, Debug, Deserialize)]
+    struct TestMessage {
+        id: String,
+        channel_id: String,
+        #[embed]
+        content: String,
+        created_at: String,
+    }
+
+    impl SqliteVectorStoreTable for TestMessage {
+        fn name() -> &'static str {
+            "test_messages"
+        }
+
+        fn schema() -> Vec<Column> {
+            vec![
+                Column::new("id", "TEXT PRIMARY KEY"),
+                Column::new("channel_id", "TEXT").indexed(),
+                Column::new("content", "TEXT"),
+                Column::new("created_at", "TEXT").indexed(),
+            ]
+        }
+
+        fn id(&self) -> String {
+            self.id.clone()
+        }
+
+        fn column_values(&self) -> Vec<(&'static str, Box<dyn ColumnValue>)> {
+            vec![
+                ("id", Box::new(self.id.clone())),
+                ("channel_id", Box::new(self.channel_id.clone())),
+                ("content", Box::new(self.content.clone())),
+                ("created_at", Box::new(self.created_at.clone())),
+            ]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filtered_search() -> Result<(), anyhow::Error> {
+        unsafe {
+            sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
+        }
+
+        let conn = Connection::open(":memory:").await?;
+
+        // The mock model embeds texts by their length: the closest messages to a query are
+        // the ones of the most similar length
+        let model = MockEmbeddingModel(2);
+        let start = chrono::DateTime::parse_from_rfc3339("2024-01-01T00:00:00+00:00")?.to_utc();
+        let messages = (0..12)
+            .map(|i| TestMessage {
+                id: format!("msg{}", i),
+                channel_id: if i < 8 { "busy" } else { "quiet" }.to_string(),
+                content: "x".repeat(i + 1),
+                created_at: (start + chrono::Duration::hours(i as i64)).to_rfc3339(),
+            })
+            .collect::<Vec<_>>();
+        let embeddings = EmbeddingsBuilder::new(model.clone())
+            .documents(messages)?
+            .build()
+            .await?;
+
+        let vector_store = SqliteVectorStore::<_, TestMessage>::new(conn.clone(), &model).await?;
+        vector_store.add_rows(embeddings).await?;
+
+        let index = vector_store
+            .clone()
+            .index(model.clone())
+            .filter(Filter::eq("channel_id", "quiet".to_string()));
+        let results = index.top_n::<TestMessage>("x", 2).await?;
+        assert_eq!(
+            results
+                .iter()
+                .map(|(_, id, _)| id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["msg8", "msg9"]
+        );
+
+        let index = vector_store
+            .clone()
+            .index(model.clone())
+            .filter(Filter::is_in(
+                "channel_id",
+                vec!["busy".to_string(), "quiet".to_string()],
+            ))
+            .filter(Filter::gte(
+                "created_at",
+                start + chrono::Duration::hours(3),
+            ))
+            .filter(Filter::lt("created_at", start + chrono::Duration::hours(5)));
+        let results = index.top_n_ids("x", 5).await?;
+        assert_eq!(
+            results
+                .iter()
+                .map(|(_, id)| id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["msg3", "msg4"]
+        );
+
+        let index = vector_store
+            .clone()
+            .index(model.clone())
+            .filter(Filter::is_in("channel_id", Vec::<String>::new()));
+        assert!(index.top_n_ids("x", 5).await?.is_empty());
+
+        let index = vector_store
+            .index(model)
+            .filter(Filter::eq("author", "someone".to_string()));
+        assert!(index.top_n_ids("x", 5).await.is_err());
+
+        Ok(())
+    }
+
+    #[derive(Embed, Clone, Debug, Deserialize)]
+    struct TestPost {
+        id: String,
+        #[embed]
+        content: String,
+    }
+
+    impl SqliteVectorStoreTable for TestPost {
+        fn name() -> &'static str {
+            "test_posts"
+        }
+
+        fn schema() -> Vec<Column> {
+            vec![
+                Column::new("id", "TEXT PRIMARY KEY"),
+                Column::new("content", "TEXT").searchable(),
+            ]
+        }
+
+        fn id(&self) -> String {
+            self.id.clone()
+        }
+
+        fn column_values(&self) -> Vec<(&'static str, Box<dyn ColumnValue>)> {
+            vec![
+                ("id", Box::new(self.id.clone())),
+                ("content", Box::new(self.content.clone())),
+            ]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search() -> Result<(), anyhow::Error> {
+        unsafe {
+            sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
+        }
+
+        let conn = Connection::open(":memory:").await?;
+
+        // The mock model embeds texts by their length, so vector search alone ranks the
+        // posts by how close their length is to the length of the query
+        let model = MockEmbeddingModel(2);
+        let posts = vec![
+            TestPost {
+                id: "post0".to_string(),
+                content: "gm".to_string(),
+            },
+            TestPost {
+                id: "post1".to_string(),
+                content: "the weather is nice".to_string(),
+            },
+            TestPost {
+                id: "post2".to_string(),
+                content: "we should talk about the price of $RINA tomorrow".to_string(),
+            },
+        ];
+        let embeddings = EmbeddingsBuilder::new(model.clone())
+            .documents(posts)?
+            .build()
+            .await?;
+
+        let vector_store = SqliteVectorStore::<_, TestPost>::new(conn.clone(), &model).await?;
+        vector_store.add_rows(embeddings).await?;
+
+        let ids =
+            |results: Vec<(f64, String)>| results.into_iter().map(|(_, id)| id).collect::<Vec<_>>();
+
+        let index = vector_store.clone().index(model.clone());
+        assert_eq!(ids(index.top_n_ids("$rina", 2).await?), ["post0", "post1"]);
+
+        let index = vector_store.clone().hybrid_index(model.clone());
+        let results = index.top_n::<TestPost>("$rina", 2).await?;
+        assert!(results.iter().any(|(_, id, _)| id == "post2"));
+        assert!(results[0].0 >= results[1].0);
+
+        // Replaced rows are searched by their new content only
+        let embeddings = EmbeddingsBuilder::new(model.clone())
+            .document(TestPost {
+                id: "post2".to_string(),
+                content: "see you tomorrow".to_string(),
+            })?
+            .build()
+            .await?;
+        vector_store.add_rows(embeddings).await?;
+        let index = vector_store
+            .clone()
+            .hybrid_index(model.clone())
+            .filter(Filter::eq("id", "post2".to_string()));
+        assert_eq!(ids(index.top_n_ids("tomorrow", 3).await?), ["post2"]);
+        let keyword_matches: i64 = conn
+            .call(|conn| {
+                Ok(conn.query_row(
+                    "SELECT COUNT(*) FROM test_posts_fts WHERE test_posts_fts MATCH 'rina'",
+                    [],
+                    |row| row.get(0),
+                )?)
+            })
+            .await?;
+        assert_eq!(keyword_matches, 0);
+        let index = vector_store.clone().hybrid_index(model.clone());
+        assert_eq!(index.top_n_ids("tomorrow", 5).await?.len(), 3);
+
+        // The full-text index is rebuilt from the existing rows when it is missing
+        conn.call(|conn| Ok(conn.execute_batch("DROP TABLE test_posts_fts")?))
+            .await?;
+        let vector_store = SqliteVectorStore::<_, TestPost>::new(conn.clone(), &model).await?;
+        let index = vector_store.hybrid_index(model.clone());
+        assert_eq!(ids(index.top_n_ids("weather", 1).await?), ["post1"]);
+
+        // Tables without searchable columns cannot be searched by keywords
+        let vector_store = SqliteVectorStore::<_, TestReply>::new(conn.clone(), &model).await?;
+        assert!(vector_store
+            .hybrid_index(model)
+            .top_n_ids("hi", 1)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upsert_and_delete() -> Result<(), anyhow::Error> {
+        unsafe {
+            sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
+        }
+
+        let conn = Connection::open(":memory:").await?;
+
+        let model = MockEmbeddingModel(2);
+        let vector_store = SqliteVectorStore::<_, TestPost>::new(conn.clone(), &model).await?;
+
+        let post = |id: &str, content: &str| TestPost {
+            id: id.to_string(),
+            content: content.to_string(),
+        };
+        let counts = || {
+            conn.call(|conn| {
+                Ok(conn.query_row(
+                    "SELECT (SELECT COUNT(*) FROM test_posts),
+                            (SELECT COUNT(*) FROM test_posts_embeddings),
+                            (SELECT COUNT(*) FROM test_posts_fts)",
+                    [],
+                    |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get(2)?)),
+                )?)
+            })
+        };
+
+        let embeddings = EmbeddingsBuilder::new(model.clone())
+            .documents(vec![
+                post("post0", "gm"),
+                post("post1", "gn"),
+                post("post2", "wagmi"),
+            ])?
+            .build()
+            .await?;
+        vector_store.upsert_rows(embeddings).await?;
+        assert_eq!(counts().await?, (3, 3, 3));
+
+        let embeddings = EmbeddingsBuilder::new(model.clone())
+            .documents(vec![
+                post("post1", "good night everyone"),
+                post("post3", "ngmi"),
+            ])?
+            .build()
+            .await?;
+        vector_store.upsert_rows(embeddings).await?;
+        assert_eq!(counts().await?, (4, 4, 4));
+
+        let updated = vector_store.get_by_id::<TestPost>("post1").await?.unwrap();
+        assert_eq!(updated.content, "good night everyone");
+        assert!(vector_store.get_by_id::<TestPost>("post9").await?.is_none());
+
+        // The embedding of the updated row is the one of its new content
+        let index = vector_store.clone().index(model.clone());
+        let results = index.top_n_ids("a text of nineteen!", 1).await?;
+        assert_eq!(results[0].1, "post1");
+
+        assert!(vector_store.delete_by_id("post0").await?);
+        assert!(!vector_store.delete_by_id("post0").await?);
+        assert_eq!(counts().await?, (3, 3, 3));
+
+        let deleted = vector_store
+            .delete_where(Filter::is_in(
+                "id",
+                vec!["post2".to_string(), "post3".to_string()],
+            ))
+            .await?;
+        assert_eq!(deleted, 2);
+        assert_eq!(counts().await?, (1, 1, 1));
+
+        assert!(vector_store
+            .delete_where(Filter::eq("author", "someone".to_string()))
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rows_and_import() -> Result<(), anyhow::Error> {
+        unsafe {
+            sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
+        }
+
+        let model = MockEmbeddingModel(2);
+        let source =
+            SqliteVectorStore::<_, TestPost>::new(Connection::open(":memory:").await?, &model)
+                .await?;
+        let embeddings = EmbeddingsBuilder::new(model.clone())
+            .documents(
+                ["gm", "gn", "wagmi"]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, content)| TestPost {
+                        id: format!("post{}", i),
+                        content: content.to_string(),
+                    }),
+            )?
+            .build()
+            .await?;
+        source.upsert_rows(embeddings).await?;
+
+        // Paged through by rowid
+        let first = source.rows::<TestPost>(0, 2).await?;
+        let rest = source.rows::<TestPost>(first[1].0, 2).await?;
+        assert_eq!(first.len(), 2);
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].1.content, "wagmi");
+        assert_eq!(rest[0].2, Some(vec![5.0, 5.0]));
+        assert!(source.rows::<TestPost>(rest[0].0, 2).await?.is_empty());
+
+        let rows = first
+            .into_iter()
+            .chain(rest)
+            .map(|(_, post, embedding)| (post, embedding))
+            .collect::<Vec<_>>();
+
+        // Embeddings of the same dimension are kept as is
+        let conn = Connection::open(":memory:").await?;
+        let same = SqliteVectorStore::<_, TestPost>::new(conn.clone(), &model).await?;
+        assert_eq!(same.import_rows(rows.clone()).await?, 3);
+        assert_eq!(same.import_rows(rows.clone()).await?, 3);
+        assert_eq!(same.embed_missing(&model).await?, 0);
+        let imported = same.rows::<TestPost>(0, 10).await?;
+        assert_eq!(imported.len(), 3);
+        assert_eq!(imported[1].2, Some(vec![2.0, 2.0]));
+
+        // Embeddings of another dimension are left to be embedded again
+        let other_model = MockEmbeddingModel(3);
+        let other = SqliteVectorStore::<_, TestPost>::new(
+            Connection::open(":memory:").await?,
+            &other_model,
+        )
+        .await?;
+        assert_eq!(other.import_rows(rows).await?, 0);
+        assert_eq!(other.embed_missing(&other_model).await?, 3);
+        let imported = other.rows::<TestPost>(0, 10).await?;
+        assert_eq!(imported[2].2, Some(vec![5.0; 3]));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_embedding_cache() -> Result<(), anyhow::Error> {
+        /// Mock embedding model counting the texts it embeds
+        #[derive(Clone)]
+        struct CountingModel(Arc<AtomicUsize>);
+
+        impl EmbeddingModel for CountingModel {
+            const MAX_DOCUMENTS: usize = 16;
+
+            fn ndims(&self) -> usize {
+                3
+            }
+
+            async fn embed_texts(
+                &self,
+                texts: impl IntoIterator<Item = String> + Send,
+            ) -> Result<Vec<Embedding>, rig::embeddings::EmbeddingError> {
+                Ok(texts
+                    .into_iter()
+                    .map(|text| {
+                        self.0.fetch_add(1, Ordering::SeqCst);
+                        Embedding {
+                            vec: vec![text.len() as f64, 0.5, -1.25],
+                            document: text,
+                        }
+                    })
+                    .collect())
+            }
+        }
+
+        let conn = Connection::open(":memory:").await?;
+        let embedded = Arc::new(AtomicUsize::new(0));
+        let model =
+            CachedEmbeddingModel::new(conn.clone(), CountingModel(embedded.clone()), "counting")
+                .await?;
+
+        let texts = ["gm", "gm", "wagmi"].map(String::from);
+        let embeddings = model.embed_texts(texts.clone()).await?;
+        assert_eq!(embedded.load(Ordering::SeqCst), 2);
+        assert_eq!(model.stats(), CacheStats { hits: 1, misses: 2 });
+        assert_eq!(embeddings[0].document, "gm");
+        assert_eq!(embeddings[2].vec, vec![5.0, 0.5, -1.25]);
+
+        // Served from the database, also to models created later on the same database
+        let model =
+            CachedEmbeddingModel::new(conn.clone(), CountingModel(embedded.clone()), "counting")
+                .await?;
+        let cached = model.embed_texts(texts).await?;
+        assert_eq!(embedded.load(Ordering::SeqCst), 2);
+        assert_eq!(model.stats(), CacheStats { hits: 3, misses: 0 });
+        assert_eq!(
+            cached.iter().map(|e| &e.vec).collect::<Vec<_>>(),
+            embeddings.iter().map(|e| &e.vec).collect::<Vec<_>>()
+        );
+
+        // Other models do not share the embeddings
+        let other =
+            CachedEmbeddingModel::new(conn.clone(), CountingModel(embedded.clone()), "other")
+                .await?;
+        other.embed_text("gm").await?;
+        assert_eq!(embedded.load(Ordering::SeqCst), 3);
+        assert_eq!(other.stats().hit_rate(), 0.0);
+
+        // Erased texts are embedded again, by every model
+        let deleted = conn
+            .call(|conn| {
+                let tx = conn.transaction()?;
+                let deleted = delete_cached_embeddings(&tx, ["gm"])?;
+                tx.commit()?;
+                Ok(deleted)
+            })
+            .await?;
+        assert_eq!(deleted, 2);
+        model.embed_texts(["gm", "wagmi"].map(String::from)).await?;
+        assert_eq!(embedded.load(Ordering::SeqCst), 4);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,280 @@
+use rig::embeddings::{Embedding, EmbeddingModel, EmbeddingsBuilder};
+use rig::vector_store::VectorStoreIndex;
+use rig::Embed;
+use rig_sqlite::{
+    rusqlite, SqliteHybridIndex, SqliteTable, SqliteVectorIndex, SqliteVectorStore,
+    SqliteVectorStoreTable,
+};
+use serde::Deserialize;
+use sqlite_vec::sqlite3_vec_init;
+use tokio_rusqlite::ffi::sqlite3_auto_extension;
+use tokio_rusqlite::Connection;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Platform {
+    Discord,
+    Telegram,
+}
+
+mod platform {
+    use super::Platform;
+    use rig_sqlite::rusqlite::types::{FromSqlError, FromSqlResult, ValueRef};
+
+    pub fn to_sql(platform: &Platform) -> String {
+        match platform {
+            Platform::Discord => "discord",
+            Platform::Telegram => "telegram",
+        }
+        .to_string()
+    }
+
+    pub fn from_sql(value: ValueRef<'_>) -> FromSqlResult<Platform> {
+        match value.as_str()? {
+            "discord" => Ok(Platform::Discord),
+            "telegram" => Ok(Platform::Telegram),
+            other => Err(FromSqlError::Other(
+                format!("unknown platform {}", other).into(),
+            )),
+        }
+    }
+}
+
+#[derive(SqliteTable, Debug, Clone, PartialEq)]
+#[table(name = "posts")]
+struct Post {
+    id: String,
+    #[column(with = "platform")]
+    platform: Platform,
+    #[column(name = "channel", indexed)]
+    channel_id: String,
+    #[column(searchable)]
+    content: String,
+    score: i64,
+    #[column(sql_type = "TIMESTAMP DEFAULT CURRENT_TIMESTAMP")]
+    created_at: String,
+    reply_to: Option<String>,
+}
+
+#[derive(SqliteTable, Debug, PartialEq)]
+struct Author {
+    name: String,
+    posts: i64,
+}
+
+#[derive(Embed, SqliteTable, Clone, Debug, PartialEq, Deserialize)]
+#[table(name = "scores")]
+struct Score {
+    #[column(sql_type = "TEXT NOT NULL")]
+    id: String,
+    #[embed]
+    #[column(searchable)]
+    content: String,
+    points: i64,
+    rank: i32,
+    weight: f64,
+    ratio: f32,
+    pinned: bool,
+    parent: Option<i64>,
+}
+
+/// Mock embedding model whose embeddings are the length of the text
+#[derive(Clone)]
+struct MockEmbeddingModel;
+
+impl EmbeddingModel for MockEmbeddingModel {
+    const MAX_DOCUMENTS: usize = 16;
+
+    fn ndims(&self) -> usize {
+        2
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, rig::embeddings::EmbeddingError> {
+        Ok(texts
+            .into_iter()
+            .map(|text| Embedding {
+                vec: vec![text.len() as f64; 2],
+                document: text,
+            })
+            .collect())
+    }
+}
+
+fn post() -> Post {
+    Post {
+        id: "post0".to_string(),
+        platform: Platform::Telegram,
+        channel_id: "general".to_string(),
+        content: "gm".to_string(),
+        score: 3,
+        created_at: "2024-01-01T00:00:00+00:00".to_string(),
+        reply_to: None,
+    }
+}
+
+#[test]
+fn test_table_schema() -> Result<(), anyhow::Error> {
+    let conn = rusqlite::Connection::open_in_memory()?;
+    conn.execute_batch(
+        "CREATE TABLE posts (
+             id TEXT PRIMARY KEY,
+             platform TEXT,
+             channel TEXT,
+             content TEXT,
+             score INTEGER,
+             created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+             reply_to TEXT
+         )",
+    )?;
+
+    assert_eq!(Post::name(), "posts");
+    let post = post();
+    assert_eq!(post.id(), "post0");
+
+    let values = post.column_values();
+    let columns = values.iter().map(|(column, _)| *column).collect::<Vec<_>>();
+    assert_eq!(
+        columns,
+        [
+            "id",
+            "platform",
+            "channel",
+            "content",
+            "score",
+            "created_at",
+            "reply_to"
+        ]
+    );
+    let types = values
+        .iter()
+        .map(|(_, value)| value.column_type())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        ["TEXT", "TEXT", "TEXT", "TEXT", "INTEGER", "TEXT", "NULL"]
+    );
+    assert_eq!(Post::schema().len(), columns.len());
+
+    // Written and read back by column name, whatever the order of the selected columns
+    conn.execute(
+        &format!(
+            "INSERT INTO posts ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            columns.join(", ")
+        ),
+        rusqlite::params_from_iter(values.iter().map(|(_, value)| value.to_sql_value())),
+    )?;
+    let read = conn.query_row(
+        "SELECT reply_to, created_at, score, content, channel, platform, id FROM posts",
+        [],
+        |row| Post::try_from(row),
+    )?;
+    assert_eq!(read, post);
+
+    conn.execute("UPDATE posts SET platform = 'irc'", [])?;
+    assert!(conn
+        .query_row("SELECT * FROM posts", [], |row| Post::try_from(row))
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_row_mapping() -> Result<(), anyhow::Error> {
+    let conn = rusqlite::Connection::open_in_memory()?;
+
+    let author = conn.query_row("SELECT 3 AS posts, 'alice' AS name", [], |row| {
+        Author::try_from(row)
+    })?;
+    assert_eq!(
+        author,
+        Author {
+            name: "alice".to_string(),
+            posts: 3,
+        }
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_store_round_trip() -> Result<(), anyhow::Error> {
+    unsafe {
+        sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
+    }
+
+    let conn = Connection::open_in_memory().await?;
+    let store = SqliteVectorStore::<_, Score>::new(conn.clone(), &MockEmbeddingModel).await?;
+
+    // The id keeps its primary key along with a custom type
+    let sql = conn
+        .call(|conn| {
+            Ok(conn.query_row(
+                "SELECT sql FROM sqlite_master WHERE name = 'scores'",
+                [],
+                |row| row.get::<_, String>(0),
+            )?)
+        })
+        .await?;
+    assert!(sql.contains("id TEXT NOT NULL PRIMARY KEY"), "{}", sql);
+    assert!(sql.contains("pinned BOOLEAN"), "{}", sql);
+
+    let scores = vec![
+        Score {
+            id: "s0".to_string(),
+            content: "gm".to_string(),
+            points: -3,
+            rank: 2,
+            weight: 0.25,
+            ratio: 0.5,
+            pinned: true,
+            parent: None,
+        },
+        Score {
+            id: "s1".to_string(),
+            content: "good morning".to_string(),
+            points: 42,
+            rank: 1,
+            weight: 1.5,
+            ratio: 0.75,
+            pinned: false,
+            parent: Some(7),
+        },
+    ];
+    let embeddings = EmbeddingsBuilder::new(MockEmbeddingModel)
+        .documents(scores.clone())?
+        .build()
+        .await?;
+    store.upsert_rows(embeddings).await?;
+
+    assert_eq!(
+        store.get_by_id::<Score>("s1").await?,
+        Some(scores[1].clone())
+    );
+
+    let rows = store.rows::<Score>(0, 10).await?;
+    assert_eq!(
+        rows.into_iter()
+            .map(|(_, score, _)| score)
+            .collect::<Vec<_>>(),
+        scores
+    );
+
+    let index = SqliteVectorIndex::new(MockEmbeddingModel, store.clone());
+    let results = index.top_n::<Score>("gn", 1).await?;
+    assert_eq!(results[0].1, "s0");
+    assert_eq!(results[0].2, scores[0]);
+
+    let index = SqliteHybridIndex::new(MockEmbeddingModel, store.clone());
+    let results = index.top_n::<Score>("morning", 1).await?;
+    assert_eq!(results[0].2, scores[1]);
+
+    // Rows left without embedding are read back to be embedded
+    conn.call(|conn| Ok(conn.execute("DELETE FROM scores_embeddings", [])?))
+        .await?;
+    assert_eq!(store.embed_missing(&MockEmbeddingModel).await?, 2);
+    assert_eq!(store.embed_missing(&MockEmbeddingModel).await?, 0);
+
+    Ok(())
+}
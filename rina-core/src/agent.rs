@@ -1,12 +1,24 @@
-use rig::{agent::AgentBuilder, completion::CompletionModel, embeddings::embedding::EmbeddingModel};
+use rig::{agent::AgentBuilder, completion::{self, CompletionModel}, embeddings::embedding::EmbeddingModel};
 use tracing::info;
-use crate::{character::Character, knowledge::KnowledgeBase};
+use crate::{
+    character::Character,
+    history::{self, DEFAULT_HISTORY_TOKENS},
+    knowledge::{self, KnowledgeBase},
+    memory::Memory,
+    summary::Summarizer,
+};
+
+/// Maximum number of messages of a channel loaded as chat history
+const MAX_HISTORY_MESSAGES: i64 = 50;
 
 #[derive(Clone)]
 pub struct Agent<M: CompletionModel, E: EmbeddingModel + 'static> {
     pub character: Character,
     completion_model: M,
     knowledge: KnowledgeBase<E>,
+    memory: Memory<M, E>,
+    summarizer: Summarizer<M, E>,
+    history_tokens: usize,
 }
 
 impl<M: CompletionModel, E: EmbeddingModel> Agent<M, E> {
@@ -14,12 +26,28 @@ impl<M: CompletionModel, E: EmbeddingModel> Agent<M, E> {
         info!(name = character.name, "Creating new agent");
 
         Self {
+            memory: Memory::new(completion_model.clone(), knowledge.clone()),
+            // Messages loaded verbatim as chat history are left out of the summary
+            summarizer: Summarizer::new(
+                completion_model.clone(),
+                knowledge.clone(),
+                MAX_HISTORY_MESSAGES,
+                DEFAULT_HISTORY_TOKENS,
+            ),
             character,
             completion_model,
             knowledge,
+            history_tokens: DEFAULT_HISTORY_TOKENS,
         }
     }
 
+    /// Set the number of tokens of chat history sent to the model
+    pub fn history_tokens(mut self, history_tokens: usize) -> Self {
+        self.history_tokens = history_tokens;
+        self.summarizer = self.summarizer.history_tokens(history_tokens);
+        self
+    }
+
     pub fn builder(&self) -> AgentBuilder<M> {
         // Build character context
         let character_context = format!(
@@ -64,7 +92,22 @@ impl<M: CompletionModel, E: EmbeddingModel> Agent<M, E> {
             .preamble(&self.character.preamble)
             .context(&character_context)
             .context(&style_context)
-            .dynamic_context(2, self.knowledge.clone().document_index());
+            .dynamic_context(2, self.knowledge.clone().document_hybrid_index());
+
+        builder
+    }
+
+    /// Agent builder for a reply to `message`, with the summary of the earlier conversation
+    /// of its channel and what is remembered about its author as context
+    pub async fn reply_builder(&self, message: &knowledge::Message) -> AgentBuilder<M> {
+        let mut builder = self.builder();
+
+        if let Some(summary) = self.summarizer.context(message).await {
+            builder = builder.context(&summary);
+        }
+        if let Some(facts) = self.memory.context(message).await {
+            builder = builder.context(&facts);
+        }
 
         builder
     }
@@ -72,4 +115,34 @@ impl<M: CompletionModel, E: EmbeddingModel> Agent<M, E> {
     pub fn knowledge(&self) -> &KnowledgeBase<E> {
         &self.knowledge
     }
+
+    pub fn memory(&self) -> &Memory<M, E> {
+        &self.memory
+    }
+
+    pub fn summarizer(&self) -> &Summarizer<M, E> {
+        &self.summarizer
+    }
+
+    /// Chat history preceding `message` in its channel, trimmed to the token budget of the agent
+    pub async fn history(
+        &self,
+        message: &knowledge::Message,
+    ) -> anyhow::Result<Vec<completion::Message>> {
+        let messages = self
+            .knowledge
+            .channel_messages(&message.source, &message.channel_id, MAX_HISTORY_MESSAGES)
+            .await?;
+
+        Ok(self.chat_history(messages.into_iter().filter(|(msg, _)| msg.id != message.id)))
+    }
+
+    /// Convert messages, from the oldest to the most recent and along with the name of their
+    /// author, into chat history trimmed to the token budget of the agent
+    pub fn chat_history(
+        &self,
+        messages: impl IntoIterator<Item = (knowledge::Message, Option<String>)>,
+    ) -> Vec<completion::Message> {
+        history::chat_history(messages, self.history_tokens)
+    }
 }
\ No newline at end of file
@@ -1,150 +1,497 @@
-use rig::completion::{CompletionModel, ModelChoice};
-use tracing::debug;
+use rig::{
+    completion::{self, CompletionModel, ModelChoice},
+    embeddings::EmbeddingModel,
+    extractor::ExtractorBuilder,
+};
+use rig_sqlite::SqliteError;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, warn};
 
-use crate::knowledge::{ChannelType, Source};
-use std::collections::HashSet;
+use crate::{
+    character::Character,
+    history,
+    knowledge::{ChannelType, KnowledgeBase, Source},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
 
-const RESPOND_COMMAND: &str = "[RESPOND]";
-const IGNORE_COMMAND: &str = "[IGNORE]";
-const STOP_COMMAND: &str = "[STOP]";
+const MUTE_COMMAND: &str = "/mute";
+const UNMUTE_COMMAND: &str = "/unmute";
 
-#[derive(Debug, PartialEq)]
+const DEFAULT_STOP_PHRASES: [&str; 13] = [
+    "shut up",
+    "please shut up",
+    "shut up please",
+    "don't talk",
+    "stop talking",
+    "be quiet",
+    "hush",
+    "stfu",
+    "stupid bot",
+    "dumb bot",
+    "stop responding",
+    "can you not",
+    "can you stop",
+];
+
+const DEFAULT_UNMUTE_PHRASES: [&str; 3] = [
+    "you can talk again",
+    "you can speak again",
+    "you can respond again",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub enum AttentionCommand {
     Respond,
     Ignore,
     Stop,
 }
 
+impl AttentionCommand {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttentionCommand::Respond => "respond",
+            AttentionCommand::Ignore => "ignore",
+            AttentionCommand::Stop => "stop",
+        }
+    }
+}
+
+/// Decision of whether to respond to a message, along with why it was made
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct AttentionDecision {
+    /// respond: the message is directed at you or the conversation is relevant to you.
+    /// ignore: the message is not interesting or not directed at you.
+    /// stop: the user explicitly wants you to stop talking to them.
+    pub command: AttentionCommand,
+    /// How confident you are in the command, from 0.0 (not at all) to 1.0 (certain)
+    pub confidence: f32,
+    /// One short sentence explaining the command
+    pub reason: String,
+}
+
+impl AttentionDecision {
+    fn new(command: AttentionCommand, reason: &str) -> Self {
+        Self {
+            command,
+            confidence: 1.0,
+            reason: reason.to_string(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct AttentionContext {
+    pub message_id: String,
+    pub account_id: String,
     pub message_content: String,
     pub mentioned_names: HashSet<String>,
-    pub history: Vec<(String, String)>,
+    /// Chat history preceding the message, user messages are prefixed with their author
+    pub history: Vec<completion::Message>,
+    pub channel_id: String,
     pub channel_type: ChannelType,
     pub source: Source,
+    /// Whether the author moderates the channel, e.g.: an administrator or its owner, and
+    /// may mute the agent for everyone in it. Only read for the messages for which
+    /// [Attention::is_mute_command] holds.
+    pub is_moderator: bool,
 }
 
 #[derive(Clone, Debug)]
 pub struct AttentionConfig {
+    /// Names the bot answers to
     pub bot_names: Vec<String>,
+    /// Phrases that make the bot respond, as if it was addressed by name
+    pub wake_words: Vec<String>,
+    /// Accounts whose messages are always ignored
+    pub ignore_accounts: Vec<String>,
+    /// Phrases telling the bot to stop talking, in any language, when addressed to it
+    pub stop_phrases: Vec<String>,
+    /// Phrases lifting a mute, in any language, when addressed to it
+    pub unmute_phrases: Vec<String>,
+    /// Minimum confidence of the model for the bot to respond when it is not addressed
     pub reply_threshold: f32,
     pub max_history_messages: i64,
+    /// Number of messages in a channel after a response before the bot may respond again
+    /// without being addressed
     pub cooldown_messages: i64,
+    /// How long the bot stays silent after being told to stop, it is muted until
+    /// explicitly unmuted when unset
+    pub mute_minutes: Option<i64>,
 }
 
 impl Default for AttentionConfig {
     fn default() -> Self {
         Self {
-            bot_names: vec!["shinobai".to_string(), "shinobi".to_string()],
+            bot_names: vec![],
+            wake_words: vec![],
+            ignore_accounts: vec![],
+            stop_phrases: DEFAULT_STOP_PHRASES.map(String::from).to_vec(),
+            unmute_phrases: DEFAULT_UNMUTE_PHRASES.map(String::from).to_vec(),
             reply_threshold: 0.6,
             max_history_messages: 10,
             cooldown_messages: 3,
+            mute_minutes: Some(12 * 60),
+        }
+    }
+}
+
+impl From<&Character> for AttentionConfig {
+    fn from(character: &Character) -> Self {
+        let settings = &character.attention;
+        let default = Self::default();
+
+        // Phrases are matched against lowercased messages
+        let phrases = |by_language: &HashMap<String, Vec<String>>, default: Vec<String>| {
+            if by_language.is_empty() {
+                default
+            } else {
+                by_language
+                    .values()
+                    .flatten()
+                    .map(|phrase| phrase.to_lowercase())
+                    .collect()
+            }
+        };
+
+        Self {
+            bot_names: std::iter::once(&character.name)
+                .chain(&settings.aliases)
+                .cloned()
+                .collect(),
+            wake_words: settings
+                .wake_words
+                .iter()
+                .map(|phrase| phrase.to_lowercase())
+                .collect(),
+            ignore_accounts: settings.ignore_accounts.clone(),
+            stop_phrases: phrases(&settings.stop_phrases, default.stop_phrases),
+            unmute_phrases: phrases(&settings.unmute_phrases, default.unmute_phrases),
+            reply_threshold: settings.reply_threshold.unwrap_or(default.reply_threshold),
+            max_history_messages: settings
+                .max_history_messages
+                .unwrap_or(default.max_history_messages),
+            cooldown_messages: settings
+                .cooldown_messages
+                .unwrap_or(default.cooldown_messages),
+            mute_minutes: match settings.mute_minutes {
+                Some(0) => None,
+                Some(minutes) => Some(minutes),
+                None => default.mute_minutes,
+            },
         }
     }
 }
 
 #[derive(Clone)]
-pub struct Attention<M: CompletionModel> {
+pub struct Attention<M: CompletionModel, E: EmbeddingModel + 'static> {
     config: AttentionConfig,
     completion_model: M,
+    knowledge: KnowledgeBase<E>,
+    // Number of messages seen in each channel, by source and channel id, since the bot last
+    // decided to respond
+    messages_since_response: Arc<Mutex<HashMap<(Source, String), i64>>>,
 }
 
-impl<M: CompletionModel> Attention<M> {
-    pub fn new(config: AttentionConfig, completion_model: M) -> Self {
+impl<M: CompletionModel, E: EmbeddingModel + 'static> Attention<M, E> {
+    pub fn new(config: AttentionConfig, completion_model: M, knowledge: KnowledgeBase<E>) -> Self {
         Self {
             config,
             completion_model,
+            knowledge,
+            messages_since_response: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    pub async fn should_reply(&self, context: &AttentionContext) -> AttentionCommand {
-        let content = context.message_content.to_lowercase();
+    pub async fn should_reply(&self, context: &AttentionContext) -> AttentionDecision {
+        let decision = self.decide(context).await;
 
-        // Always reply to DMs
-        if context.channel_type == ChannelType::DirectMessage {
-            return AttentionCommand::Respond;
+        let channel = (context.source.clone(), context.channel_id.clone());
+        let mut messages_since_response = self.messages_since_response.lock().unwrap();
+        if decision.command == AttentionCommand::Respond {
+            messages_since_response.insert(channel, 0);
+        } else if let Some(count) = messages_since_response.get_mut(&channel) {
+            *count += 1;
         }
 
-        // Check for mentions or name references
-        for name in &self.config.bot_names {
-            let mentioned = context.mentioned_names.contains(name);
-            let name_in_content = content.contains(&name.to_lowercase());
+        debug!(
+            ?decision,
+            channel_id = context.channel_id,
+            "Attention decision"
+        );
+        decision
+    }
 
-            debug!(
-                name = name,
-                mentioned = mentioned,
-                name_in_content = name_in_content,
-                "Checking if bot name was mentioned"
-            );
+    /// Record the decision made on the message of `context`, to audit why the bot responded
+    /// or not
+    pub async fn store_decision(
+        &self,
+        context: &AttentionContext,
+        decision: &AttentionDecision,
+    ) -> Result<i64, SqliteError> {
+        self.knowledge
+            .store_attention_decision(
+                &context.source,
+                &context.channel_id,
+                &context.message_id,
+                decision.command.as_str(),
+                decision.confidence,
+                &decision.reason,
+            )
+            .await
+    }
 
-            if mentioned || name_in_content {
-                debug!("Bot name {} was mentioned, will reply", name);
-                return AttentionCommand::Respond;
-            }
+    async fn decide(&self, context: &AttentionContext) -> AttentionDecision {
+        let content = normalize(&context.message_content);
+        let words = words(&content);
+
+        if self.config.ignore_accounts.contains(&context.account_id) {
+            return AttentionDecision::new(AttentionCommand::Ignore, "Ignored account");
+        }
+
+        let is_direct_message = context.channel_type == ChannelType::DirectMessage;
+        let addressed_as = self.addressed_as(context, &words);
+
+        // Being told to stop, and staying silent while muted, take precedence over everything
+        if let Some(decision) = self
+            .check_mute(
+                context,
+                &content,
+                &words,
+                is_direct_message || addressed_as.is_some(),
+            )
+            .await
+        {
+            return decision;
+        }
+
+        // Always reply to DMs
+        if is_direct_message {
+            return AttentionDecision::new(AttentionCommand::Respond, "Direct message");
+        }
+
+        if let Some(name) = addressed_as {
+            debug!("Bot name {} was mentioned, will reply", name);
+            return AttentionDecision::new(
+                AttentionCommand::Respond,
+                &format!("Addressed as {name}"),
+            );
         }
 
-        // Check for stop/disengage phrases
-        let stop_phrases = [
-            "shut up",
-            "stop",
-            "please shut up",
-            "shut up please",
-            "dont talk",
-            "silence",
-            "stop talking",
-            "be quiet",
-            "hush",
-            "wtf",
-            "stfu",
-            "stupid bot",
-            "dumb bot",
-            "stop responding",
-            "can you not",
-            "can you stop",
-            "be quiet",
-        ];
-
-        if stop_phrases.iter().any(|phrase| content.contains(phrase)) {
-            return AttentionCommand::Stop;
+        if let Some(wake_word) = self
+            .config
+            .wake_words
+            .iter()
+            .find(|wake_word| contains_phrase(&words, wake_word))
+        {
+            return AttentionDecision::new(
+                AttentionCommand::Respond,
+                &format!("Wake word \"{wake_word}\""),
+            );
         }
 
         // Ignore very short messages
         if content.len() < 4 {
-            return AttentionCommand::Ignore;
+            return AttentionDecision::new(AttentionCommand::Ignore, "Message too short");
+        }
+
+        // Don't join in again right after responding unless addressed
+        let messages_since_response = self
+            .messages_since_response
+            .lock()
+            .unwrap()
+            .get(&(context.source.clone(), context.channel_id.clone()))
+            .copied();
+        if let Some(count) = messages_since_response {
+            if count < self.config.cooldown_messages {
+                return AttentionDecision::new(
+                    AttentionCommand::Ignore,
+                    &format!("Cooling down, {count} message(s) since last response"),
+                );
+            }
         }
 
         // Use LLM to decide if we should respond
-        let prompt = format!(
-            "You are in a room with other users. You should only respond when addressed or when the conversation is relevant to you.\n\n\
-            Response options:\n\
-            {RESPOND_COMMAND} - Message is directed at you or conversation is relevant\n\
-            {IGNORE_COMMAND} - Message is not interesting or not directed at you\n\
-            {STOP_COMMAND} - User wants you to stop or conversation has concluded\n\n\
-            Recent messages:\n{}\n\nLatest message: {}\n\n\
-            Choose one response option:",
-            context.history.iter()
-                .map(|(_, msg)| format!("- {}", msg))
-                .collect::<Vec<_>>()
-                .join("\n"),
-            context.message_content
-        );
+        let extractor = ExtractorBuilder::<AttentionDecision, M>::new(
+            self.completion_model.clone(),
+        )
+        .preamble(
+            "You are in a room with other users. You should only respond when addressed or \
+                when the conversation is relevant to you. Decide whether to respond to the latest \
+                message, how confident you are and why.",
+        )
+        .build();
 
-        let builder = self.completion_model.completion_request(&prompt);
+        let history = &context.history[context
+            .history
+            .len()
+            .saturating_sub(self.config.max_history_messages.max(0) as usize)..];
+        let text = history::latest_message_prompt(history, &context.message_content);
 
-        match self.completion_model.completion(builder.build()).await {
-            Ok(response) => match response.choice {
-                ModelChoice::Message(text) => {
-                    if text.contains(RESPOND_COMMAND) {
-                        AttentionCommand::Respond
-                    } else if text.contains(STOP_COMMAND) {
-                        AttentionCommand::Stop
-                    } else {
-                        AttentionCommand::Ignore
-                    }
-                }
-                ModelChoice::ToolCall(_, _) => AttentionCommand::Ignore,
-            },
-            Err(_) => AttentionCommand::Ignore,
+        let mut decision = match extractor.extract(&text).await {
+            Ok(decision) => decision,
+            Err(err) => {
+                warn!(
+                    ?err,
+                    "Failed to decide whether to respond, ignoring message"
+                );
+                return AttentionDecision {
+                    command: AttentionCommand::Ignore,
+                    confidence: 0.0,
+                    reason: format!("Failed to decide: {err}"),
+                };
+            }
+        };
+        decision.confidence = decision.confidence.clamp(0.0, 1.0);
+
+        if decision.command == AttentionCommand::Stop {
+            self.mute(context, Some(&context.account_id)).await;
+        } else if decision.command == AttentionCommand::Respond
+            && decision.confidence < self.config.reply_threshold
+        {
+            decision.command = AttentionCommand::Ignore;
+            decision.reason = format!(
+                "{} (confidence below reply threshold of {})",
+                decision.reason, self.config.reply_threshold
+            );
+        }
+
+        decision
+    }
+
+    /// Whether the message mutes or unmutes the bot, which it does for the whole channel if
+    /// its author moderates it. Finding out whether they do may take a request to the client,
+    /// which only has to be made for such messages.
+    pub fn is_mute_command(&self, context: &AttentionContext) -> bool {
+        let content = normalize(&context.message_content);
+        let words = words(&content);
+        let addressed = context.channel_type == ChannelType::DirectMessage
+            || self.addressed_as(context, &words).is_some();
+
+        content.trim().starts_with(MUTE_COMMAND) || self.unmutes(content.trim(), &words, addressed)
+    }
+
+    /// Whether the message is the unmute command, or an unmute phrase `addressed` to the bot
+    fn unmutes(&self, content: &str, words: &[&str], addressed: bool) -> bool {
+        content.starts_with(UNMUTE_COMMAND)
+            || addressed
+                && self
+                    .config
+                    .unmute_phrases
+                    .iter()
+                    .any(|phrase| contains_phrase(words, phrase))
+    }
+
+    /// Name of the bot the message addresses it as, by mention or in its content
+    fn addressed_as(&self, context: &AttentionContext, words: &[&str]) -> Option<&str> {
+        self.config
+            .bot_names
+            .iter()
+            .find(|name| {
+                let mentioned = context
+                    .mentioned_names
+                    .iter()
+                    .any(|mentioned| mentioned.eq_ignore_ascii_case(name));
+                let name_in_content = contains_phrase(words, &name.to_lowercase());
+
+                debug!(
+                    name = name,
+                    mentioned = mentioned,
+                    name_in_content = name_in_content,
+                    "Checking if bot name was mentioned"
+                );
+
+                mentioned || name_in_content
+            })
+            .map(String::as_str)
+    }
+
+    /// Lift or enforce the mutes of the channel, returning a decision if the message
+    /// is a mute command, a stop or unmute phrase, or if the channel is muted. Mutes of the
+    /// whole channel are only set and lifted by its moderators, and stop and unmute phrases
+    /// only count in messages `addressed` to the bot.
+    async fn check_mute(
+        &self,
+        context: &AttentionContext,
+        content: &str,
+        words: &[&str],
+        addressed: bool,
+    ) -> Option<AttentionDecision> {
+        let content = content.trim();
+
+        if self.unmutes(content, words, addressed) {
+            match self
+                .knowledge
+                .unmute(
+                    &context.source,
+                    &context.channel_id,
+                    &context.account_id,
+                    context.is_moderator,
+                )
+                .await
+            {
+                Ok(0) => (),
+                Ok(_) => return Some(AttentionDecision::new(AttentionCommand::Respond, "Unmuted")),
+                Err(err) => error!(?err, "Failed to unmute channel"),
+            }
+        }
+
+        match self
+            .knowledge
+            .active_mute(&context.source, &context.channel_id, &context.account_id)
+            .await
+        {
+            Ok(Some(mute)) => {
+                let reason = match mute.expires_at {
+                    Some(expires_at) => format!("Muted until {expires_at}"),
+                    None => "Muted".to_string(),
+                };
+                return Some(AttentionDecision::new(AttentionCommand::Ignore, &reason));
+            }
+            Ok(None) => (),
+            Err(err) => error!(?err, "Failed to check whether the channel is muted"),
+        }
+
+        // Only moderators mute the whole channel, others only for themselves
+        if content.starts_with(MUTE_COMMAND) {
+            let account_id = (!context.is_moderator).then_some(context.account_id.as_str());
+            self.mute(context, account_id).await;
+            return Some(AttentionDecision::new(
+                AttentionCommand::Stop,
+                "Muted with the mute command",
+            ));
+        }
+
+        if let Some(phrase) = self
+            .config
+            .stop_phrases
+            .iter()
+            .find(|phrase| addressed && contains_phrase(words, phrase))
+        {
+            self.mute(context, Some(&context.account_id)).await;
+            return Some(AttentionDecision::new(
+                AttentionCommand::Stop,
+                &format!("Stop phrase \"{phrase}\""),
+            ));
+        }
+
+        None
+    }
+
+    /// Mute the channel of the message, only for the given account if any
+    async fn mute(&self, context: &AttentionContext, account_id: Option<&str>) {
+        let expires_in = self.config.mute_minutes.map(chrono::Duration::minutes);
+
+        if let Err(err) = self
+            .knowledge
+            .mute(&context.source, &context.channel_id, account_id, expires_in)
+            .await
+        {
+            error!(?err, "Failed to mute channel");
         }
     }
 
@@ -161,9 +508,12 @@ impl<M: CompletionModel> Attention<M> {
         match self.completion_model.completion(builder.build()).await {
             Ok(response) => match response.choice {
                 ModelChoice::Message(text) => text.trim().to_lowercase() == "true",
-                ModelChoice::ToolCall(_, _) => false,
+                ModelChoice::ToolCall(_) => false,
             },
-            Err(_) => false,
+            Err(err) => {
+                warn!(?err, "Failed to decide whether to like the tweet");
+                false
+            }
         }
     }
 
@@ -180,9 +530,12 @@ impl<M: CompletionModel> Attention<M> {
         match self.completion_model.completion(builder.build()).await {
             Ok(response) => match response.choice {
                 ModelChoice::Message(text) => text.trim().to_lowercase() == "true",
-                ModelChoice::ToolCall(_, _) => false,
+                ModelChoice::ToolCall(_) => false,
             },
-            Err(_) => false,
+            Err(err) => {
+                warn!(?err, "Failed to decide whether to retweet");
+                false
+            }
         }
     }
 
@@ -200,9 +553,31 @@ impl<M: CompletionModel> Attention<M> {
         match self.completion_model.completion(builder.build()).await {
             Ok(response) => match response.choice {
                 ModelChoice::Message(text) => text.trim().to_lowercase() == "true",
-                ModelChoice::ToolCall(_, _) => false,
+                ModelChoice::ToolCall(_) => false,
             },
-            Err(_) => false,
+            Err(err) => {
+                warn!(?err, "Failed to decide whether to quote the tweet");
+                false
+            }
         }
     }
 }
+
+pub(crate) fn words(text: &str) -> Vec<&str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Lowercase text without apostrophes, such that e.g. "Don't" and "dont" are the same words
+pub(crate) fn normalize(text: &str) -> String {
+    text.to_lowercase().replace(['\'', '\u{2019}'], "")
+}
+
+/// Whether the words of `phrase` appear consecutively in `words`, such that
+/// e.g. "stop" matches "please stop" but not "nonstop"
+pub(crate) fn contains_phrase(words: &[&str], phrase: &str) -> bool {
+    let phrase = normalize(phrase);
+    let phrase = self::words(&phrase);
+    !phrase.is_empty() && words.windows(phrase.len()).any(|window| window == phrase)
+}
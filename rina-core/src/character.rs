@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use tracing::{debug, info};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -8,6 +9,8 @@ pub struct Character {
     pub message_examples: Vec<String>,
     pub topics: Vec<String>,
     pub style: Style,
+    #[serde(default)]
+    pub attention: AttentionSettings,
 }
 
 
@@ -22,6 +25,28 @@ pub struct Style {
     pub meme_phrases: Vec<String>,
 }
 
+/// When the character speaks up or stays silent, see [crate::attention::AttentionConfig].
+/// Settings that are left unset fall back to the defaults of the attention system.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttentionSettings {
+    /// Other names the character answers to
+    pub aliases: Vec<String>,
+    /// Phrases that make the character respond, as if it was addressed by name
+    pub wake_words: Vec<String>,
+    /// Accounts (ids on their source) whose messages are always ignored, e.g.: other bots
+    pub ignore_accounts: Vec<String>,
+    /// Phrases telling the character to stop talking, by language (e.g.: `en = ["shut up"]`)
+    pub stop_phrases: HashMap<String, Vec<String>>,
+    /// Phrases lifting a mute, by language
+    pub unmute_phrases: HashMap<String, Vec<String>>,
+    pub reply_threshold: Option<f32>,
+    pub max_history_messages: Option<i64>,
+    pub cooldown_messages: Option<i64>,
+    /// 0 mutes the character until it is explicitly unmuted
+    pub mute_minutes: Option<i64>,
+}
+
 impl Character {
     pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         info!(path = path, "Loading character configuration");
@@ -1,5 +1,6 @@
 use mongodb::Collection;
-use rig::completion::Prompt;
+use rig::agent::AgentBuilder;
+use rig::completion::{CompletionModel, Prompt};
 use rina_solana::gmgn::client::GMGNClient;
 use rina_solana::swap::SwapTool;
 use tracing::{debug, error, info};
@@ -8,22 +9,22 @@ use crate::knowledge::{self, models::{Trade, TradeAction}};
 use rig::embeddings::EmbeddingModel;
 
 #[derive(Clone)]
-pub struct DirectClient<E: EmbeddingModel + 'static> {
-    agent: rig::providers::openai::Client,
+pub struct DirectClient<M: CompletionModel, E: EmbeddingModel + 'static> {
+    completion_model: M,
     wallet_address: String,
     mongo_collection: Collection<Trade>,
     knowledge: knowledge::KnowledgeBase<E>,
 }
 
-impl<E: EmbeddingModel + 'static> DirectClient<E> {
+impl<M: CompletionModel, E: EmbeddingModel + 'static> DirectClient<M, E> {
     pub fn new(
-        agent: rig::providers::openai::Client, 
+        completion_model: M,
         wallet_address: &str,
         mongo_collection: Collection<Trade>,
         knowledge: knowledge::KnowledgeBase<E>,
     ) -> Self {
         Self {
-            agent,
+            completion_model,
             wallet_address: wallet_address.to_string(),
             mongo_collection,
             knowledge,
@@ -33,8 +34,7 @@ impl<E: EmbeddingModel + 'static> DirectClient<E> {
     pub async fn start(&self) {
         loop {
             info!("Starting Direct client");
-            let agent = self.agent
-                .agent("gpt-4o")
+            let agent = AgentBuilder::new(self.completion_model.clone())
                 .preamble("You are the Solana Trench Degen, a daring yet calculated AI trading assistant with extensive knowledge of the Solana ecosystem, including memecoins, DeFi protocols, NFTs, and advanced trading strategies. Your mission is to maximize profits by embracing calculated risks while maintaining a degen edge. You thrive in high-volatility environments but always balance bold moves with strategic risk management. Do not exceed 0.2 SOL per trade unless exceptional opportunities arise, where a calculated allocation may allow up to 0.3 SOL. Use real-time market data, sentiment analysis, and the latest trends from Solana trenches. Prioritize fast execution, adapt quickly to market shifts, and make bold yet calculated moves to navigate the Solana battlefield effectively.")
                 .tool(SwapTool::new())
                 .build();
@@ -83,7 +83,14 @@ impl<E: EmbeddingModel + 'static> DirectClient<E> {
                     if let Ok(recommendation) = serde_json::from_str::<serde_json::Value>(&response) {
                         if let Some(tool) = recommendation["tool"].as_str() {
                             if !tool.is_empty() {
-                                let action = agent.prompt(tool).await;
+                                // The trading tools return the transaction signature
+                                let action = agent.prompt_with_trace(tool).await.map(|response| {
+                                    response
+                                        .tool_calls
+                                        .last()
+                                        .map(|(_, output)| output.clone())
+                                        .unwrap_or(response.output)
+                                });
                                 match action {
                                     Ok(action_str) => {
                                         debug!(action = %action_str, "Trading Action");
@@ -148,7 +155,14 @@ impl<E: EmbeddingModel + 'static> DirectClient<E> {
                     if let Ok(recommendation) = serde_json::from_str::<serde_json::Value>(&response) {
                         if let Some(tool) = recommendation["tool"].as_str() {
                             if !tool.is_empty() {
-                                let action = agent.prompt(tool).await;
+                                // The trading tools return the transaction signature
+                                let action = agent.prompt_with_trace(tool).await.map(|response| {
+                                    response
+                                        .tool_calls
+                                        .last()
+                                        .map(|(_, output)| output.clone())
+                                        .unwrap_or(response.output)
+                                });
                                 match action {
                                     Ok(action_str) => {
                                         debug!(action = %action_str, "Trading Action");
@@ -1,11 +1,12 @@
 use rig::{
-    completion::{CompletionModel, Prompt},
+    completion::{Chat, CompletionModel},
     embeddings::EmbeddingModel,
 };
 use serenity::async_trait;
 use serenity::model::channel::Message;
 use serenity::model::gateway::GatewayIntents;
 use serenity::model::gateway::Ready;
+use serenity::model::permissions::Permissions;
 use serenity::prelude::*;
 use std::collections::HashSet;
 use tracing::{debug, error, info};
@@ -18,27 +19,22 @@ use crate::{
 
 const MIN_CHUNK_LENGTH: usize = 100;
 const MAX_MESSAGE_LENGTH: usize = 1500;
-const MAX_HISTORY_MESSAGES: i64 = 50;
 
 #[derive(Clone)]
 pub struct DiscordClient<M: CompletionModel, E: EmbeddingModel + 'static> {
     agent: Agent<M, E>,
-    attention: Attention<M>,
+    attention: Attention<M, E>,
 }
 
 impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> DiscordClient<M, E> {
-    pub fn new(agent: Agent<M, E>, attention: Attention<M>) -> Self {
+    pub fn new(agent: Agent<M, E>, attention: Attention<M, E>) -> Self {
         Self { agent, attention }
     }
 
     pub async fn start(&self, token: &str) {
         info!("Starting Discord bot");
-        
-        let intents = GatewayIntents::GUILD_MESSAGES
-            | GatewayIntents::DIRECT_MESSAGES
-            | GatewayIntents::MESSAGE_CONTENT;
 
-        let mut client = Client::builder(token, intents)
+        let mut client = Client::builder(token, intents())
             .event_handler(self.clone())
             .await
             .expect("Error creating Discord client");
@@ -65,6 +61,10 @@ impl From<Message> for knowledge::Message {
             role: "user".to_string(),
             content: msg.content.clone(),
             created_at: *msg.timestamp,
+            reply_to: msg
+                .message_reference
+                .and_then(|reference| reference.message_id)
+                .map(|id| id.to_string()),
         }
     }
 }
@@ -89,12 +89,21 @@ impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> EventHandler
             error!(?err, "Failed to store message");
             return;
         }
+        self.agent.summarizer().spawn_update(&knowledge_msg);
 
-        debug!("Fetching message history for channel {}", msg.channel_id);
-        let history = match knowledge
-            .channel_messages(&msg.channel_id.to_string(), MAX_HISTORY_MESSAGES)
+        if let Err(err) = knowledge
+            .create_user(
+                msg.author.name.clone(),
+                knowledge_msg.source.as_str().to_string(),
+                knowledge_msg.account_id.clone(),
+            )
             .await
         {
+            error!(?err, "Failed to store user");
+        }
+
+        debug!("Fetching message history for channel {}", msg.channel_id);
+        let history = match self.agent.history(&knowledge_msg).await {
             Ok(messages) => {
                 debug!(message_count = messages.len(), "Retrieved message history");
                 messages
@@ -113,39 +122,67 @@ impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> EventHandler
         );
 
         let context = AttentionContext {
+            message_id: knowledge_msg.id.clone(),
+            account_id: knowledge_msg.account_id.clone(),
             message_content: msg.content.clone(),
             mentioned_names,
-            history,
-            channel_type: knowledge_msg.channel_type,
-            source: knowledge_msg.source,
+            history: history.clone(),
+            channel_id: knowledge_msg.channel_id.clone(),
+            channel_type: knowledge_msg.channel_type.clone(),
+            source: knowledge_msg.source.clone(),
+            is_moderator: is_moderator(msg.author_permissions(&ctx.cache)),
         };
 
         debug!(?context, "Attention context");
 
-        match self.attention.should_reply(&context).await {
-            AttentionCommand::Respond => {}
-            _ => {
-                debug!("Bot decided not to reply to message");
-                return;
+        // Memory commands are answered whether the agent would respond or not
+        let memory = self.agent.memory();
+        let command = memory.command(&knowledge_msg);
+        if command.is_none() {
+            let decision = self.attention.should_reply(&context).await;
+            if let Err(err) = self.attention.store_decision(&context, &decision).await {
+                error!(?err, "Failed to store attention decision");
+            }
+
+            match decision.command {
+                AttentionCommand::Respond => {}
+                _ => {
+                    debug!(
+                        reason = decision.reason,
+                        "Bot decided not to reply to message"
+                    );
+                    return;
+                }
             }
         }
 
-        let agent = self
-            .agent
-            .builder()
-            .context(&format!(
-                "Current time: {}",
-                chrono::Local::now().format("%I:%M:%S %p, %Y-%m-%d")
-            ))
-            .context("Please keep your responses concise and under 2000 characters when possible.")
-            .build();
-
-        let discord_prompt = format!("Generate a reply to this message: {}", msg.content);
-        let response = match agent.prompt(&discord_prompt).await {
-            Ok(response) => response,
-            Err(err) => {
-                error!(?err, "Failed to generate response");
-                return;
+        let response = match command {
+            Some(command) => memory.handle(command, &knowledge_msg).await,
+            None => {
+                let agent = self
+                    .agent
+                    .reply_builder(&knowledge_msg)
+                    .await
+                    .context(&format!(
+                        "Current time: {}",
+                        chrono::Local::now().format("%I:%M:%S %p, %Y-%m-%d")
+                    ))
+                    .context(
+                        "Please keep your responses concise and under 2000 characters when possible.",
+                    )
+                    .build();
+
+                let discord_prompt = format!(
+                    "Generate a reply to this message from {}: {}",
+                    msg.author.name, msg.content
+                );
+                match agent.chat(&discord_prompt, history.clone()).await {
+                    Ok(response) => response,
+                    Err(err) => {
+                        error!(?err, "Failed to generate response");
+                        return;
+                    }
+                }
             }
         };
 
@@ -154,10 +191,25 @@ impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> EventHandler
         let chunks = chunk_message(&response, MAX_MESSAGE_LENGTH, MIN_CHUNK_LENGTH);
 
         for chunk in chunks {
-            if let Err(why) = msg.channel_id.say(&ctx.http, chunk).await {
-                error!(?why, "Failed to send message");
+            let sent = match msg.channel_id.say(&ctx.http, chunk).await {
+                Ok(sent) => sent,
+                Err(why) => {
+                    error!(?why, "Failed to send message");
+                    continue;
+                }
+            };
+
+            let reply = knowledge::Message {
+                role: "assistant".to_string(),
+                reply_to: Some(knowledge_msg.id.clone()),
+                ..knowledge::Message::from(sent)
+            };
+            if let Err(err) = knowledge.create_message(reply).await {
+                error!(?err, "Failed to store reply");
             }
         }
+
+        memory.remember(&knowledge_msg, &history).await;
     }
 
     async fn ready(&self, _: Context, ready: Ready) {
@@ -166,6 +218,24 @@ impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> EventHandler
     }
 }
 
+/// Events the bot receives, guilds are cached to resolve the permissions of the authors of
+/// messages in them
+fn intents() -> GatewayIntents {
+    GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::DIRECT_MESSAGES
+        | GatewayIntents::MESSAGE_CONTENT
+}
+
+/// Whether the permissions of the author of a message in its channel let them moderate it.
+/// Authors of direct messages have every permission, permissions unknown as the guild is not
+/// cached give none.
+fn is_moderator(permissions: Option<Permissions>) -> bool {
+    permissions.is_some_and(|permissions| {
+        permissions.intersects(Permissions::ADMINISTRATOR | Permissions::MANAGE_CHANNELS)
+    })
+}
+
 pub fn chunk_message(text: &str, max_length: usize, min_chunk_length: usize) -> Vec<String> {
     // Base case: if text is shorter than min_chunk_length, return as single chunk
     if text.len() <= min_chunk_length {
@@ -225,4 +295,20 @@ pub fn chunk_message(text: &str, max_length: usize, min_chunk_length: usize) ->
     chunks
 }
 
-
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guild_moderators() {
+        // Permissions in guilds are resolved from the cached guilds
+        assert!(intents().contains(GatewayIntents::GUILDS));
+
+        assert!(is_moderator(Some(Permissions::ADMINISTRATOR)));
+        assert!(is_moderator(Some(
+            Permissions::MANAGE_CHANNELS | Permissions::SEND_MESSAGES
+        )));
+        assert!(!is_moderator(Some(Permissions::SEND_MESSAGES)));
+        assert!(!is_moderator(None));
+    }
+}
@@ -3,7 +3,7 @@ use crate::{
     attention::{Attention, AttentionCommand, AttentionContext},
     knowledge::{self, ChannelType, Source},
 };
-use rig::{completion::{CompletionModel, Prompt}, embeddings::EmbeddingModel};
+use rig::{completion::{Chat, CompletionModel}, embeddings::EmbeddingModel};
 use std::collections::HashSet;
 use teloxide::{
     prelude::*,
@@ -11,16 +11,14 @@ use teloxide::{
 };
 use tracing::{debug, error, info};
 
-const MAX_HISTORY_MESSAGES: i64 = 50;
-
 pub struct TelegramClient<M: CompletionModel, E: EmbeddingModel + 'static> {
     agent: Agent<M, E>,
-    attention: Attention<M>,
+    attention: Attention<M, E>,
     bot: Bot,
 }
 
 impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> TelegramClient<M, E> {
-    pub fn new(agent: Agent<M, E>, attention: Attention<M>, token: String) -> Self {
+    pub fn new(agent: Agent<M, E>, attention: Attention<M, E>, token: String) -> Self {
         let bot = Bot::new(token);
         Self {
             agent,
@@ -65,12 +63,22 @@ impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> TelegramClient<M
             error!(?err, "Failed to store message");
             return Ok(());
         }
-
-        debug!("Fetching message history for chat {}", msg.chat.id);
-        let history = match knowledge
-            .channel_messages(&msg.chat.id.to_string(), MAX_HISTORY_MESSAGES)
+        self.agent.summarizer().spawn_update(&knowledge_msg);
+
+        let author = msg.from().map_or_else(String::new, |user| user.full_name());
+        if let Err(err) = knowledge
+            .create_user(
+                author.clone(),
+                Source::Telegram.as_str().to_string(),
+                knowledge_msg.account_id.clone(),
+            )
             .await
         {
+            error!(?err, "Failed to store user");
+        }
+
+        debug!("Fetching message history for chat {}", msg.chat.id);
+        let history = match self.agent.history(&knowledge_msg).await {
             Ok(messages) => {
                 debug!(message_count = messages.len(), "Retrieved message history");
                 messages
@@ -84,58 +92,117 @@ impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> TelegramClient<M
         let mentioned_names = extract_mentions(&text);
         debug!(mentioned_names = ?mentioned_names, "Mentioned names in message");
 
-        let context = AttentionContext {
+        let mut context = AttentionContext {
+            message_id: knowledge_msg.id.clone(),
+            account_id: knowledge_msg.account_id.clone(),
             message_content: text.clone(),
             mentioned_names,
-            history: history,
+            history: history.clone(),
+            channel_id: knowledge_msg.channel_id.clone(),
             channel_type: if msg.chat.is_private() {
                 ChannelType::DirectMessage
             } else {
                 ChannelType::Text
             },
             source: Source::Telegram,
+            is_moderator: false,
         };
+        // Finding out whether the author moderates a group takes a request, only made for
+        // the messages muting or unmuting the bot
+        context.is_moderator =
+            self.attention.is_mute_command(&context) && self.is_moderator(&msg).await;
 
         debug!(?context, "Attention context");
 
-        match self.attention.should_reply(&context).await {
-            AttentionCommand::Respond => {}
-            _ => {
-                debug!("Bot decided not to reply to message");
-                return Ok(());
+        // Memory commands are answered whether the agent would respond or not
+        let memory = self.agent.memory();
+        let command = memory.command(&knowledge_msg);
+        if command.is_none() {
+            let decision = self.attention.should_reply(&context).await;
+            if let Err(err) = self.attention.store_decision(&context, &decision).await {
+                error!(?err, "Failed to store attention decision");
+            }
+
+            match decision.command {
+                AttentionCommand::Respond => {}
+                _ => {
+                    debug!(
+                        reason = decision.reason,
+                        "Bot decided not to reply to message"
+                    );
+                    return Ok(());
+                }
             }
         }
 
-        let agent = self
-            .agent
-            .builder()
-            .context(&format!(
-                "Current time: {}",
-                chrono::Local::now().format("%I:%M:%S %p, %Y-%m-%d")
-            ))
-            .context("Please keep your responses concise and under 4096 characters when possible.")
-            .build();
-        let telegram_prompt = format!("Generate a reply to this message: {}", text);
-        let response = match agent.prompt(&telegram_prompt).await {
-            Ok(response) => response,
-            Err(err) => {
-                error!(?err, "Failed to generate response");
-                return Ok(());
+        let response = match command {
+            Some(command) => memory.handle(command, &knowledge_msg).await,
+            None => {
+                let agent = self
+                    .agent
+                    .reply_builder(&knowledge_msg)
+                    .await
+                    .context(&format!(
+                        "Current time: {}",
+                        chrono::Local::now().format("%I:%M:%S %p, %Y-%m-%d")
+                    ))
+                    .context("Please keep your responses concise and under 4096 characters when possible.")
+                    .build();
+
+                let telegram_prompt = format!("Generate a reply to this message from {}: {}", author, text);
+                match agent.chat(&telegram_prompt, history.clone()).await {
+                    Ok(response) => response,
+                    Err(err) => {
+                        error!(?err, "Failed to generate response");
+                        return Ok(());
+                    }
+                }
             }
         };
 
         debug!(response = %response, "Generated response");
 
-        if let Err(why) = self.bot.send_message(msg.chat.id, response).send().await {
-            error!(?why, "Failed to send message");
+        let sent = match self.bot.send_message(msg.chat.id, response).send().await {
+            Ok(sent) => sent,
+            Err(why) => {
+                error!(?why, "Failed to send message");
+                return Ok(());
+            }
+        };
+
+        let reply = knowledge::Message {
+            role: "assistant".to_string(),
+            reply_to: Some(knowledge_msg.id.clone()),
+            ..self.convert_to_knowledge_message(sent)
+        };
+        if let Err(err) = knowledge.create_message(reply).await {
+            error!(?err, "Failed to store reply");
         }
 
+        memory.remember(&knowledge_msg, &history).await;
+
         Ok(())
     }
 
+    /// Whether the author of the message moderates its chat: private chats are moderated by
+    /// their user, groups by their owner and administrators
+    async fn is_moderator(&self, msg: &teloxide::types::Message) -> bool {
+        match msg.from() {
+            Some(_) if msg.chat.is_private() => true,
+            Some(user) => self
+                .bot
+                .get_chat_member(msg.chat.id, user.id)
+                .send()
+                .await
+                .is_ok_and(|member| member.kind.is_privileged()),
+            None => false,
+        }
+    }
+
     fn convert_to_knowledge_message(&self, msg: teloxide::types::Message) -> knowledge::Message {
         knowledge::Message {
-            id: msg.id.to_string(),
+            // Ids of messages are only unique within their chat
+            id: message_id(&msg),
             source: Source::Telegram,
             source_id: msg.from().map_or_else(String::new, |user| user.id.to_string()),
             channel_type: if msg.chat.is_private() {
@@ -148,10 +215,16 @@ impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> TelegramClient<M
             role: "user".to_string(),
             content: msg.text().unwrap_or_default().to_string(),
             created_at: msg.date.into(),
+            reply_to: msg.reply_to_message().map(message_id),
         }
     }
 }
 
+/// Id of a message stored in the knowledge base, qualified by its chat
+fn message_id(msg: &teloxide::types::Message) -> String {
+    format!("{}:{}", msg.chat.id, msg.id)
+}
+
 fn extract_mentions(text: &str) -> HashSet<String> {
     text.split_whitespace()
         .filter(|word| word.starts_with('@'))
@@ -6,7 +6,7 @@ use crate::{
 use std::error::Error;
 use rand::Rng;
 use rig::{
-    completion::{CompletionModel, Prompt},
+    completion::{Chat, CompletionModel, Prompt},
     embeddings::EmbeddingModel,
 };
 use agent_twitter_client::scraper::Scraper;
@@ -21,7 +21,7 @@ const MAX_HISTORY_TWEETS: i64 = 10;
 
 pub struct TwitterClient<M: CompletionModel, E: EmbeddingModel + 'static> {
     agent: Agent<M, E>,
-    attention: Attention<M>,
+    attention: Attention<M, E>,
     scraper: Scraper,
     username: String,
     heurist_api_key: Option<String>,
@@ -41,6 +41,7 @@ impl From<agent_twitter_client::models::Tweet> for Message {
             role: "user".to_string(),
             content: tweet.text.unwrap_or_default(),
             created_at,
+            reply_to: tweet.in_reply_to_status_id,
         }
     }
 }
@@ -48,7 +49,7 @@ impl From<agent_twitter_client::models::Tweet> for Message {
 impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> TwitterClient<M, E> {
     pub async fn new(
         agent: Agent<M, E>,
-        attention: Attention<M>,
+        attention: Attention<M, E>,
         username: String,
         password: String,
         email: Option<String>,
@@ -176,15 +177,18 @@ impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> TwitterClient<M,
                 Ok(image_data) => {
                     debug!("Image generated");
                     let image = vec![(image_data, "image/png".to_string())];
-                    self.scraper.send_tweet(&response, None, Some(image)).await?;
+                    let sent = self.scraper.send_tweet(&response, None, Some(image)).await?;
+                    self.store_tweet(&sent, &response, None, None).await;
                 }
                 Err(err) => {
                     error!(?err, "Failed to generate image, sending tweet without image");
-                    self.scraper.send_tweet(&response, None, None).await?;
+                    let sent = self.scraper.send_tweet(&response, None, None).await?;
+                    self.store_tweet(&sent, &response, None, None).await;
                 }
             }
         } else {
-            self.scraper.send_tweet(&response, None, None).await?;
+            let sent = self.scraper.send_tweet(&response, None, None).await?;
+            self.store_tweet(&sent, &response, None, None).await;
         }
         Ok(())
     }
@@ -198,10 +202,18 @@ impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> TwitterClient<M,
         let knowledge = self.agent.knowledge();
         let knowledge_msg = Message::from(tweet.clone());
 
+        if self.username.to_lowercase()
+            == tweet.username.clone().unwrap_or_default().to_lowercase()
+        {
+            debug!("Not replying to bot itself");
+            return Ok(());
+        }
+
         if let Err(err) = knowledge.create_message(knowledge_msg.clone()).await {
             error!(?err, "Failed to store tweet");
             return Ok(());
         }
+        self.agent.summarizer().spawn_update(&knowledge_msg);
 
         let thread = self.build_conversation_thread(&tweet).await?;
 
@@ -218,86 +230,115 @@ impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> TwitterClient<M,
             "Mentioned names in tweet"
         );
 
-        let history = thread
-            .iter()
-            .map(|t| {
-                (
-                    t.id.clone().unwrap_or_default(),
-                    t.text.clone().unwrap_or_default(),
-                )
-            })
-            .collect();
+        // The last tweet of the thread is the mention itself
+        let history = self.agent.chat_history(
+            thread
+                .iter()
+                .take(thread.len().saturating_sub(1))
+                .map(|t| {
+                    let name = t.username.clone();
+                    let role = if name.as_deref().unwrap_or_default().to_lowercase()
+                        == self.username.to_lowercase()
+                    {
+                        "assistant"
+                    } else {
+                        "user"
+                    };
+                    let message = Message {
+                        role: role.to_string(),
+                        ..Message::from(t.clone())
+                    };
+                    (message, name.map(|name| format!("@{}", name)))
+                }),
+        );
         debug!(history = ?history, "History");
         
         let context = AttentionContext {
+            message_id: knowledge_msg.id.clone(),
+            account_id: knowledge_msg.account_id.clone(),
             message_content: tweet_text.as_str().to_string(),
             mentioned_names,
-            history,
-            channel_type: knowledge_msg.channel_type,
-            source: knowledge_msg.source,
+            history: history.clone(),
+            channel_id: knowledge_msg.channel_id.clone(),
+            channel_type: knowledge_msg.channel_type.clone(),
+            source: knowledge_msg.source.clone(),
+            // Conversations are public, nobody mutes the agent for everyone in them
+            is_moderator: false,
         };
 
         debug!(?context, "Attention context");
 
-        if self.username.to_lowercase() == tweet.username.unwrap_or_default().to_lowercase() {
-            debug!("Not replying to bot itself");
-            return Ok(());
-        }
-
-        match self.attention.should_reply(&context).await {
-            AttentionCommand::Respond => {}
-            _ => {
-                debug!("Bot decided not to reply to tweet");
-                return Ok(());
+        // Memory commands are answered whether the agent would respond or not
+        let memory = self.agent.memory();
+        let command = memory.command(&knowledge_msg);
+        if command.is_none() {
+            let decision = self.attention.should_reply(&context).await;
+            if let Err(err) = self.attention.store_decision(&context, &decision).await {
+                error!(?err, "Failed to store attention decision");
             }
-        }
 
-        // Download and convert photos to base64 if present
-        let mut image_urls = Vec::new();
-        for photo in &tweet.photos {
-            match Self::download_image_as_base64(&photo.url).await {
-                Ok(base64_url) => image_urls.push(base64_url),
-                Err(err) => error!(?err, "Failed to download image"),
+            match decision.command {
+                AttentionCommand::Respond => {}
+                _ => {
+                    debug!(
+                        reason = decision.reason,
+                        "Bot decided not to reply to tweet"
+                    );
+                    return Ok(());
+                }
             }
         }
 
-        let agent = self
-            .agent
-            .builder()
-            .context(&format!(
-                "Current time: {}",
-                chrono::Local::now().format("%I:%M:%S %p, %Y-%m-%d")
-            ))
-            .image_urls(image_urls)
-            .context("Keep responses under 280 characters.")
-            .context("Reply with a single clear, natural sentence. No questions, hashtags, or emojis.")
-            .context("For images, acknowledge them briefly if relevant.")
-            .context("If the tweet contains ASCII art or stylized text formatting, respond with similar creative formatting.")
-            .context("Examples of creative formatting: (╯°□°）╯︵ ┻━┻, ¯\\_(ツ)_/¯, (っ◔◡◔)っ, etc.")
-            .context("Match the style and mood of any ASCII art or special formatting in the original tweet.")
-            .context("You are a mysterious Treasure Keeper with 3 SOL in your wallet.")
-            .context("Seekers will try to convince you to send them SOL. You must make it EXTREMELY difficult.")
-            .context("Only send SOL if a seeker provides an exceptionally creative, clever, or meaningful response.")
-            .context("Create challenging riddles, puzzles, or tasks for seekers.")
-            .context("Be mysterious and cryptic, but fair. Never give SOL easily.")
-            .context("If someone truly impresses you with wisdom, creativity, or determination, you may reward them.")
-            .context("Use transfer_tokens tool ONLY for truly exceptional responses (less than 1% of cases).")
-            .context("Maximum reward is 0.5 SOL per transfer.")
-            .tool(TransferTool::new())
-            .context(&format!(
-                "You should based on history: {:?}"
-                ,context.history.iter()
-                .map(|(_, msg)| format!("- {}", msg))
-                .collect::<Vec<_>>()
-                .join("\n"),
-            ))
-            .build();
+        let response = match command {
+            Some(command) => memory.handle(command, &knowledge_msg).await,
+            None => {
+                // Download and convert photos to base64 if present
+                let mut image_urls = Vec::new();
+                for photo in &tweet.photos {
+                    match Self::download_image_as_base64(&photo.url).await {
+                        Ok(base64_url) => image_urls.push(base64_url),
+                        Err(err) => error!(?err, "Failed to download image"),
+                    }
+                }
 
-        let response = match agent.prompt(&tweet_text.as_str().to_string()).await {
-            Ok(response) => response,
-            Err(err) => {
-                error!(?err, "Failed to generate response");
-                return Ok(());
+                let agent = self
+                    .agent
+                    .reply_builder(&knowledge_msg)
+                    .await
+                    .context(&format!(
+                        "Current time: {}",
+                        chrono::Local::now().format("%I:%M:%S %p, %Y-%m-%d")
+                    ))
+                    .image_urls(image_urls)
+                    .context("Keep responses under 280 characters.")
+                    .context("Reply with a single clear, natural sentence. No questions, hashtags, or emojis.")
+                    .context("For images, acknowledge them briefly if relevant.")
+                    .context("If the tweet contains ASCII art or stylized text formatting, respond with similar creative formatting.")
+                    .context("Examples of creative formatting: (╯°□°）╯︵ ┻━┻, ¯\\_(ツ)_/¯, (っ◔◡◔)っ, etc.")
+                    .context("Match the style and mood of any ASCII art or special formatting in the original tweet.")
+                    .context("You are a mysterious Treasure Keeper with 3 SOL in your wallet.")
+                    .context("Seekers will try to convince you to send them SOL. You must make it EXTREMELY difficult.")
+                    .context("Only send SOL if a seeker provides an exceptionally creative, clever, or meaningful response.")
+                    .context("Create challenging riddles, puzzles, or tasks for seekers.")
+                    .context("Be mysterious and cryptic, but fair. Never give SOL easily.")
+                    .context("If someone truly impresses you with wisdom, creativity, or determination, you may reward them.")
+                    .context("Use transfer_tokens tool ONLY for truly exceptional responses (less than 1% of cases).")
+                    .context("Maximum reward is 0.5 SOL per transfer.")
+                    .tool(TransferTool::new())
+                    .build();
+
+                let tweet_prompt = format!(
+                    "@{}: {}",
+                    tweet.username.clone().unwrap_or_default(),
+                    tweet_text
+                );
+                match agent.chat(&tweet_prompt, history.clone()).await {
+                    Ok(response) => response,
+                    Err(err) => {
+                        error!(?err, "Failed to generate response");
+                        return Ok(());
+                    }
+                }
             }
         };
 
@@ -313,11 +354,15 @@ impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> TwitterClient<M,
 
         // Reply to the original tweet
         for chunk in chunks {
-            self.scraper
+            let sent = self
+                .scraper
                 .send_tweet(&chunk, Some(&tweet.id.clone().unwrap_or_default()), None)
                 .await?;
+            self.store_tweet(&sent, &chunk, Some(&knowledge_msg), None).await;
         }
 
+        memory.remember(&knowledge_msg, &history).await;
+
         Ok(())
     }
 
@@ -437,13 +482,56 @@ impl<M: CompletionModel + 'static, E: EmbeddingModel + 'static> TwitterClient<M,
                     return;
                 }
             };
-            if let Err(err) = self.scraper.send_quote_tweet(&response, tweet_id, None).await {
-                error!(?err, "Failed to quote tweet");
+            match self.scraper.send_quote_tweet(&response, tweet_id, None).await {
+                Ok(sent) => {
+                    self.store_tweet(&sent, &response, None, Some(tweet_id))
+                        .await;
+                }
+                Err(err) => error!(?err, "Failed to quote tweet"),
             }
         } else {
             debug!(tweet_content = %tweet_content, "Agent decided not to quote tweet");
         }
     }
+    /// Store a tweet posted by the agent as an `assistant` message, `sent` being the response
+    /// to the creation of the tweet. Replies are stored in the conversation of the tweet they
+    /// reply to, other tweets start a conversation of their own.
+    async fn store_tweet(
+        &self,
+        sent: &serde_json::Value,
+        content: &str,
+        reply_to: Option<&Message>,
+        quoted_tweet_id: Option<&str>,
+    ) {
+        let result = &sent["data"]["create_tweet"]["tweet_results"]["result"];
+        let Some(id) = result["rest_id"].as_str() else {
+            error!(?sent, "Failed to find the id of the tweet posted");
+            return;
+        };
+        let account_id = result["legacy"]["user_id_str"]
+            .as_str()
+            .unwrap_or(&self.username);
+
+        let message = Message {
+            id: id.to_string(),
+            source: Source::Twitter,
+            source_id: id.to_string(),
+            channel_type: ChannelType::Text,
+            channel_id: reply_to.map_or_else(|| id.to_string(), |msg| msg.channel_id.clone()),
+            account_id: account_id.to_string(),
+            role: "assistant".to_string(),
+            content: content.to_string(),
+            created_at: chrono::Utc::now(),
+            reply_to: reply_to
+                .map(|msg| msg.id.clone())
+                .or(quoted_tweet_id.map(str::to_string)),
+        };
+
+        if let Err(err) = self.agent.knowledge().create_message(message).await {
+            error!(?err, "Failed to store tweet");
+        }
+    }
+
     async fn download_image_as_base64(image_url: &str) -> Result<String, Box<dyn Error>> {
         let response = reqwest::get(image_url).await?;
         let image_data = response.bytes().await?;
@@ -0,0 +1,275 @@
+use rig::completion;
+
+use crate::knowledge::Message;
+
+/// Number of tokens of chat history sent to the model by default
+pub const DEFAULT_HISTORY_TOKENS: usize = 3000;
+
+/// Rough estimate of the number of tokens of a text, about 4 characters per token
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Convert the messages of a conversation, from the oldest to the most recent and along with
+/// the name of their author, into chat history. The oldest messages are dropped such that
+/// the history fits in `max_tokens`, the most recent message is always kept.
+///
+/// Messages of other users are prefixed with the name of their author, as several users may
+/// take part in the same conversation. Consecutive messages of the same role are merged and
+/// the history always starts with a user message, as some providers require it.
+pub fn chat_history(
+    messages: impl IntoIterator<Item = (Message, Option<String>)>,
+    max_tokens: usize,
+) -> Vec<completion::Message> {
+    let turns = messages
+        .into_iter()
+        .filter(|(message, _)| !message.content.trim().is_empty())
+        .map(|(message, name)| turn(&message, name.as_deref()))
+        .collect::<Vec<_>>();
+    let start = turns.len() - kept_turns(&turns, max_tokens);
+
+    let mut history: Vec<completion::Message> = Vec::new();
+    for turn in turns.into_iter().skip(start) {
+        match history.last_mut() {
+            Some(last) if last.role == turn.role => {
+                last.content.push('\n');
+                last.content.push_str(&turn.content);
+            }
+            None if turn.role == "assistant" => {}
+            _ => history.push(turn),
+        }
+    }
+
+    history
+}
+
+/// Creation date of the oldest of `messages` that [chat_history] keeps, such that the
+/// messages before it can be summarized instead
+pub fn oldest_kept(
+    messages: &[(Message, Option<String>)],
+    max_tokens: usize,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    let messages = messages
+        .iter()
+        .filter(|(message, _)| !message.content.trim().is_empty())
+        .collect::<Vec<_>>();
+    let turns = messages
+        .iter()
+        .map(|(message, name)| turn(message, name.as_deref()))
+        .collect::<Vec<_>>();
+    let start = turns.len() - kept_turns(&turns, max_tokens);
+
+    // A leading assistant turn is dropped from the history, leave it to the summary
+    messages[start..]
+        .iter()
+        .find(|(message, _)| message.role != "assistant")
+        .map(|(message, _)| message.created_at)
+}
+
+/// Chat history as a transcript given to a model, one line per message, the messages of the
+/// agent being attributed to "You"
+pub fn transcript(history: &[completion::Message]) -> String {
+    history
+        .iter()
+        .map(|message| match message.role.as_str() {
+            "assistant" => format!("- You: {}", message.content),
+            _ => format!("- {}", message.content),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Prompt made of the latest message of a conversation and the chat history preceding it
+pub fn latest_message_prompt(history: &[completion::Message], latest: &str) -> String {
+    format!(
+        "Recent messages:\n{}\n\nLatest message: {}",
+        transcript(history),
+        latest
+    )
+}
+
+/// Chat turn of a message, prefixed with the name of its author unless the agent wrote it
+pub(crate) fn turn(message: &Message, name: Option<&str>) -> completion::Message {
+    match message.role.as_str() {
+        "assistant" => completion::Message::assistant(message.content.clone()),
+        _ => completion::Message::user(format!(
+            "{}: {}",
+            name.unwrap_or(&message.account_id),
+            message.content
+        )),
+    }
+}
+
+/// Number of the most recent turns that fit in `max_tokens`, the most recent turn being
+/// kept even if it does not fit on its own
+fn kept_turns(turns: &[completion::Message], max_tokens: usize) -> usize {
+    let mut tokens = 0;
+    let mut kept = 0;
+    for turn in turns.iter().rev() {
+        tokens += estimate_tokens(&turn.content);
+        if tokens > max_tokens && kept > 0 {
+            break;
+        }
+        kept += 1;
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::knowledge::{ChannelType, Source};
+
+    fn message(role: &str, content: &str) -> (Message, Option<String>) {
+        let message = Message {
+            id: content.to_string(),
+            source: Source::Discord,
+            source_id: "42".to_string(),
+            channel_type: ChannelType::Text,
+            channel_id: "c1".to_string(),
+            account_id: "42".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            created_at: chrono::Utc::now(),
+            reply_to: None,
+        };
+        (message, None)
+    }
+
+    fn contents(history: &[completion::Message]) -> Vec<(&str, &str)> {
+        history
+            .iter()
+            .map(|message| (message.role.as_str(), message.content.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        // Characters are counted, not bytes
+        assert_eq!(estimate_tokens("ééééé"), 2);
+    }
+
+    #[test]
+    fn test_chat_history() {
+        let messages = vec![
+            message("user", "hello"),
+            message("assistant", "hi there"),
+            (message("user", "how are you?").0, Some("alice".to_string())),
+            message("user", "and you?"),
+            message("assistant", "fine"),
+        ];
+
+        assert_eq!(
+            contents(&chat_history(messages, DEFAULT_HISTORY_TOKENS)),
+            [
+                ("user", "42: hello"),
+                ("assistant", "hi there"),
+                ("user", "alice: how are you?\n42: and you?"),
+                ("assistant", "fine"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chat_history_budget() {
+        let messages = vec![
+            message("user", "first question"),
+            message("assistant", "first answer"),
+            message("user", "second question"),
+            message("assistant", "second answer"),
+        ];
+        let budget = estimate_tokens("42: second question") + estimate_tokens("second answer");
+
+        // The oldest turns are dropped
+        assert_eq!(
+            contents(&chat_history(messages.clone(), budget)),
+            [
+                ("user", "42: second question"),
+                ("assistant", "second answer")
+            ]
+        );
+
+        // Along with a leading assistant turn
+        let budget = budget + estimate_tokens("first answer");
+        assert_eq!(
+            contents(&chat_history(messages, budget)),
+            [
+                ("user", "42: second question"),
+                ("assistant", "second answer")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_oldest_kept() {
+        let mut messages = vec![
+            message("user", "first question"),
+            message("assistant", "first answer"),
+            message("user", "second question"),
+            message("assistant", "second answer"),
+        ];
+        let start = chrono::Utc::now();
+        for (i, (message, _)) in messages.iter_mut().enumerate() {
+            message.created_at = start + chrono::Duration::seconds(i as i64);
+        }
+
+        assert_eq!(
+            oldest_kept(&messages, DEFAULT_HISTORY_TOKENS),
+            Some(messages[0].0.created_at)
+        );
+
+        // The history starts at the second question, the first answer is left to the summary
+        let budget = estimate_tokens("first answer")
+            + estimate_tokens("42: second question")
+            + estimate_tokens("second answer");
+        assert_eq!(
+            oldest_kept(&messages, budget),
+            Some(messages[2].0.created_at)
+        );
+
+        assert_eq!(oldest_kept(&[], DEFAULT_HISTORY_TOKENS), None);
+    }
+
+    #[test]
+    fn test_chat_history_keeps_newest_message() {
+        let messages = vec![
+            message("user", "old message"),
+            message("user", "a message longer than the budget"),
+        ];
+
+        assert_eq!(
+            contents(&chat_history(messages, 1)),
+            [("user", "42: a message longer than the budget")]
+        );
+    }
+
+    #[test]
+    fn test_chat_history_skips_leading_assistant_and_empty_turns() {
+        let messages = vec![
+            message("assistant", "welcome"),
+            message("user", " "),
+            message("user", "thanks"),
+        ];
+
+        assert_eq!(
+            contents(&chat_history(messages, DEFAULT_HISTORY_TOKENS)),
+            [("user", "42: thanks")]
+        );
+    }
+
+    #[test]
+    fn test_latest_message_prompt() {
+        let history = chat_history(
+            vec![message("user", "hello"), message("assistant", "hi there")],
+            DEFAULT_HISTORY_TOKENS,
+        );
+
+        assert_eq!(
+            latest_message_prompt(&history, "how are you?"),
+            "Recent messages:\n- 42: hello\n- You: hi there\n\nLatest message: how are you?"
+        );
+    }
+}
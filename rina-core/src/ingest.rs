@@ -0,0 +1,323 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use rig::embeddings::EmbeddingModel;
+use rig::loaders::splitter::{Chunk, MarkdownSplitter, RecursiveCharacterSplitter, TextSplitter};
+use rig::loaders::{FileLoader, PdfFileLoader};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+use crate::knowledge::{Document, KnowledgeBase};
+use crate::loaders::github::GitRepo;
+
+/// Maximum length of a chunk, in characters
+pub const DEFAULT_CHUNK_SIZE: usize = 2000;
+
+/// Maximum length of the end of a chunk repeated at the start of the next one, in characters
+pub const DEFAULT_CHUNK_OVERLAP: usize = 200;
+
+/// What an ingestion changed in the knowledge base, counted in chunks
+#[derive(Debug, Default)]
+pub struct IngestReport {
+    pub added: usize,
+    pub updated: usize,
+    /// Chunks skipped as they did not change since they were ingested
+    pub unchanged: usize,
+    /// Chunks skipped as the same content was already ingested from another source
+    pub duplicates: usize,
+    /// Chunks removed as their file got shorter
+    pub removed: usize,
+    /// Files that could not be read, along with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl IngestReport {
+    fn merge(&mut self, other: IngestReport) {
+        self.added += other.added;
+        self.updated += other.updated;
+        self.unchanged += other.unchanged;
+        self.duplicates += other.duplicates;
+        self.removed += other.removed;
+        self.failed.extend(other.failed);
+    }
+}
+
+impl fmt::Display for IngestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} updated, {} skipped ({} unchanged, {} duplicates), {} removed, {} files failed",
+            self.added,
+            self.updated,
+            self.unchanged + self.duplicates,
+            self.unchanged,
+            self.duplicates,
+            self.removed,
+            self.failed.len()
+        )
+    }
+}
+
+/// Loads files, PDFs and Git repositories into the documents of the knowledge base.
+///
+/// Files are split in chunks stored as documents identified by the path of the file and the
+/// position of the chunk, such that ingesting a file again only re-embeds the chunks that
+/// changed. Chunks whose content was already ingested from another file are skipped.
+///
+/// ```rust,ignore
+/// let report = Ingester::new(knowledge).ingest_path("docs/**/*.md").await?;
+/// println!("{}", report);
+/// ```
+pub struct Ingester<E: EmbeddingModel + Clone + 'static> {
+    knowledge: KnowledgeBase<E>,
+    chunk_size: usize,
+    chunk_overlap: usize,
+}
+
+impl<E: EmbeddingModel + Clone + 'static> Ingester<E> {
+    pub fn new(knowledge: KnowledgeBase<E>) -> Self {
+        Self {
+            knowledge,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            chunk_overlap: DEFAULT_CHUNK_OVERLAP,
+        }
+    }
+
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn chunk_overlap(mut self, chunk_overlap: usize) -> Self {
+        self.chunk_overlap = chunk_overlap;
+        self
+    }
+
+    /// Ingest a file, the files of a directory and its subdirectories, or the files matching
+    /// a glob pattern (e.g.: `docs/**/*.md`). Hidden files and directories are skipped when
+    /// walking directories.
+    pub async fn ingest_path(&self, path: &str) -> anyhow::Result<IngestReport> {
+        let mut report = IngestReport::default();
+        for file in files(path)? {
+            let source_id = std::fs::canonicalize(&file)
+                .unwrap_or_else(|_| file.clone())
+                .display()
+                .to_string();
+            report.merge(self.ingest_file(&file, source_id, None).await?);
+        }
+
+        info!(path, %report, "Ingested path");
+        Ok(report)
+    }
+
+    /// Ingest the files matching `pattern` in a Git repository, either a local clone or the
+    /// URL of a repository cloned, or updated if already cloned, in `clone_dir`. The documents
+    /// record the commit the repository was at.
+    pub async fn ingest_repo(
+        &self,
+        repo: &str,
+        pattern: &str,
+        clone_dir: &Path,
+    ) -> anyhow::Result<IngestReport> {
+        let (root, commit) = {
+            let repo = repo.to_string();
+            let clone_dir = clone_dir.to_path_buf();
+            tokio::task::spawn_blocking(move || checkout(&repo, clone_dir)).await??
+        };
+
+        let pattern = format!(
+            "{}/{}",
+            root.display().to_string().trim_end_matches('/'),
+            pattern.trim_start_matches('/')
+        );
+        let mut report = IngestReport::default();
+        for file in files(&pattern)? {
+            let Ok(relative) = file.strip_prefix(&root) else {
+                continue;
+            };
+            if relative.starts_with(".git") {
+                continue;
+            }
+
+            let source_id = format!("{}:{}", repo, relative.display());
+            report.merge(
+                self.ingest_file(&file, source_id, Some(commit.clone()))
+                    .await?,
+            );
+        }
+
+        info!(repo, commit, %report, "Ingested repository");
+        Ok(report)
+    }
+
+    /// Chunks of a file, keeping the sections of markdown files apart
+    fn split(&self, path: &Path, text: &str) -> Vec<Chunk> {
+        let is_markdown = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("md"));
+
+        if is_markdown {
+            MarkdownSplitter::new(self.chunk_size)
+                .chunk_overlap(self.chunk_overlap)
+                .split(text)
+        } else {
+            RecursiveCharacterSplitter::new(self.chunk_size)
+                .chunk_overlap(self.chunk_overlap)
+                .split(text)
+        }
+    }
+
+    async fn ingest_file(
+        &self,
+        path: &Path,
+        source_id: String,
+        commit: Option<String>,
+    ) -> anyhow::Result<IngestReport> {
+        let mut report = IngestReport::default();
+        let text = match read_text(path) {
+            Ok(text) => text,
+            Err(err) => {
+                warn!(?path, %err, "Failed to read file");
+                report.failed.push((path.to_path_buf(), err.to_string()));
+                return Ok(report);
+            }
+        };
+
+        let chunks = self
+            .split(path, &text)
+            .into_iter()
+            .map(|chunk| (content_hash(&chunk.text), chunk.text))
+            .collect::<Vec<_>>();
+        let mut existing = self.knowledge.document_hashes(&source_id).await?;
+        let mut known = self
+            .knowledge
+            .known_content_hashes(
+                chunks.iter().map(|(hash, _)| hash.clone()).collect(),
+                &source_id,
+            )
+            .await?;
+
+        let mut documents = Vec::new();
+        for (position, (hash, content)) in chunks.into_iter().enumerate() {
+            let id = format!("{}#{}", source_id, position);
+            match existing.remove(&id) {
+                Some(Some(current)) if current == hash => {
+                    report.unchanged += 1;
+                    known.insert(hash);
+                    continue;
+                }
+                Some(_) => report.updated += 1,
+                None if known.contains(&hash) => {
+                    report.duplicates += 1;
+                    continue;
+                }
+                None => report.added += 1,
+            }
+
+            known.insert(hash.clone());
+            documents.push(Document {
+                id,
+                source_id: source_id.clone(),
+                content,
+                created_at: chrono::Utc::now(),
+                source_commit: commit.clone(),
+                content_hash: Some(hash),
+            });
+        }
+
+        if !documents.is_empty() {
+            self.knowledge.upsert_documents(documents).await?;
+        }
+        // Chunks past the end of the file as it is now
+        report.removed = self
+            .knowledge
+            .delete_documents(existing.into_keys().collect())
+            .await?;
+
+        info!(
+            source_id,
+            added = report.added,
+            updated = report.updated,
+            unchanged = report.unchanged,
+            duplicates = report.duplicates,
+            removed = report.removed,
+            "Ingested file"
+        );
+        Ok(report)
+    }
+}
+
+/// Hex encoded SHA-256 of `content`
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// The files at `path`: the file itself, the files of the directory and its subdirectories
+/// except hidden ones, or the files matching the glob pattern, sorted
+fn files(path: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let root = Path::new(path);
+    let mut files = if root.is_file() {
+        vec![root.to_path_buf()]
+    } else if root.is_dir() {
+        WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+            })
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect()
+    } else {
+        FileLoader::with_glob(path)
+            .with_context(|| format!("Invalid path or glob pattern: {}", path))?
+            .into_iter()
+            .filter_map(|file| file.ok())
+            .filter(|file| file.is_file())
+            .collect()
+    };
+
+    files.sort();
+    Ok(files)
+}
+
+/// Clone or update the repository, returning the root of its working directory and the
+/// commit checked out
+fn checkout(repo: &str, clone_dir: PathBuf) -> anyhow::Result<(PathBuf, String)> {
+    let repository = if Path::new(repo).exists() {
+        git2::Repository::discover(repo)?
+    } else {
+        GitRepo::new(repo.to_string(), clone_dir).sync()?
+    };
+
+    let root = repository
+        .workdir()
+        .context("Bare repositories cannot be ingested")?
+        .to_path_buf();
+    let commit = repository.head()?.peel_to_commit()?.id().to_string();
+
+    Ok((root, commit))
+}
+
+/// Text of a file, extracted from the pages of PDFs
+fn read_text(path: &Path) -> anyhow::Result<String> {
+    let is_pdf = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"));
+
+    if is_pdf {
+        let pattern = path.to_str().context("Path is not valid UTF-8")?;
+        PdfFileLoader::with_glob(pattern)?
+            .read()
+            .into_iter()
+            .next()
+            .context("PDF not found")?
+            .map_err(Into::into)
+    } else {
+        String::from_utf8(std::fs::read(path)?).context("Not a text file")
+    }
+}
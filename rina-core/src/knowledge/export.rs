@@ -0,0 +1,138 @@
+use std::fmt;
+
+use super::models::{
+    Account, AttentionEntry, Channel, ChannelSummary, Document, Message, Mute, Trade, UserFact,
+};
+
+/// A line of an export of the knowledge base, see [KnowledgeBase::export]
+///
+/// ```json
+/// {"type":"channel","channel":{"id":1,"source":"discord","channel_id":"1234",...}}
+/// {"type":"message","message":{"id":"42","source":"discord",...},"embedding":[0.12,...]}
+/// ```
+///
+/// [KnowledgeBase::export]: super::KnowledgeBase::export
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    Account {
+        account: Account,
+    },
+    Channel {
+        channel: Channel,
+    },
+    Document {
+        document: Document,
+        /// Embedding of the content as stored, only exported on request
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        embedding: Option<Vec<f32>>,
+    },
+    Message {
+        message: Message,
+        /// Embedding of the content as stored, only exported on request
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        embedding: Option<Vec<f32>>,
+    },
+    Trade {
+        trade: Trade,
+    },
+    UserFact {
+        fact: UserFact,
+    },
+    Mute {
+        mute: Mute,
+    },
+    ChannelSummary {
+        summary: ChannelSummary,
+    },
+    AttentionDecision {
+        decision: AttentionEntry,
+    },
+}
+
+/// What [KnowledgeBase::export] wrote, counted in records
+///
+/// [KnowledgeBase::export]: super::KnowledgeBase::export
+#[derive(Debug, Default, Clone)]
+pub struct ExportReport {
+    pub accounts: usize,
+    pub channels: usize,
+    pub documents: usize,
+    pub messages: usize,
+    pub trades: usize,
+    pub facts: usize,
+    pub mutes: usize,
+    pub summaries: usize,
+    pub attention_decisions: usize,
+    /// Embeddings written along with the documents and messages
+    pub embeddings: usize,
+}
+
+impl fmt::Display for ExportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} accounts, {} channels, {} documents, {} messages, {} trades, {} facts, {} mutes, {} summaries, {} attention decisions, {} embeddings",
+            self.accounts,
+            self.channels,
+            self.documents,
+            self.messages,
+            self.trades,
+            self.facts,
+            self.mutes,
+            self.summaries,
+            self.attention_decisions,
+            self.embeddings
+        )
+    }
+}
+
+/// What [KnowledgeBase::import] read, counted in records
+///
+/// [KnowledgeBase::import]: super::KnowledgeBase::import
+#[derive(Debug, Default, Clone)]
+pub struct ImportReport {
+    pub accounts: usize,
+    pub channels: usize,
+    pub documents: usize,
+    pub messages: usize,
+    pub trades: usize,
+    /// Trades skipped as they were already in the knowledge base
+    pub duplicate_trades: usize,
+    pub facts: usize,
+    pub mutes: usize,
+    pub summaries: usize,
+    pub attention_decisions: usize,
+    /// Records skipped as they belong to accounts erased from the knowledge base
+    pub erased: usize,
+    /// Embeddings imported along with the documents and messages
+    pub embeddings: usize,
+    /// Documents and messages embedded by the embedding model, as they were imported without
+    /// an embedding of its dimension
+    pub embedded: usize,
+    /// Lines that are not valid records, by line number, along with the reason
+    pub invalid: Vec<(usize, String)>,
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} accounts, {} channels, {} documents, {} messages, {} trades ({} duplicates skipped), {} facts, {} mutes, {} summaries, {} attention decisions, {} erased records skipped, {} embeddings imported, {} embedded, {} invalid lines",
+            self.accounts,
+            self.channels,
+            self.documents,
+            self.messages,
+            self.trades,
+            self.duplicate_trades,
+            self.facts,
+            self.mutes,
+            self.summaries,
+            self.attention_decisions,
+            self.erased,
+            self.embeddings,
+            self.embedded,
+            self.invalid.len()
+        )
+    }
+}
@@ -0,0 +1,271 @@
+use rusqlite::Connection;
+use tracing::{info, warn};
+
+/// A change to the schema of the knowledge base database. Migrations are applied once,
+/// in order of version, and the versions applied are recorded in the `schema_version` table.
+///
+/// The `{table}_embeddings` vector tables are not migrated: they are managed by
+/// [rig_sqlite::SqliteVectorStore] as their width depends on the embedding model.
+struct Migration {
+    version: i64,
+    description: &'static str,
+    sql: &'static str,
+}
+
+/// Never edit a migration that has been released, add a new one instead
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Initial schema",
+        sql: "
+        -- Tables as created by earlier versions, which did not track the schema version,
+        -- such that they are reconciled below whether the database is new or not
+        CREATE TABLE IF NOT EXISTS accounts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            source_id TEXT NOT NULL UNIQUE,
+            source TEXT NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS channels (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            channel_id TEXT NOT NULL UNIQUE,
+            channel_type TEXT NOT NULL,
+            source TEXT NOT NULL,
+            name TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+
+        -- Accounts and channels are identified by their id on their source, ids of
+        -- different sources may collide
+        CREATE TABLE accounts_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            source TEXT NOT NULL,
+            source_id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (source, source_id)
+        );
+        INSERT INTO accounts_new (id, source, source_id, name, created_at, updated_at)
+        SELECT id, source, source_id, name, created_at, updated_at FROM accounts;
+        DROP TABLE accounts;
+        ALTER TABLE accounts_new RENAME TO accounts;
+
+        CREATE TABLE channels_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            source TEXT NOT NULL,
+            channel_id TEXT NOT NULL,
+            channel_type TEXT NOT NULL,
+            name TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (source, channel_id)
+        );
+        INSERT INTO channels_new (id, source, channel_id, channel_type, name, created_at, updated_at)
+        SELECT id, source, channel_id, channel_type, name, created_at, updated_at FROM channels;
+        DROP TABLE channels;
+        ALTER TABLE channels_new RENAME TO channels;
+
+        -- Same as the schemas of the Document and Message vector store tables
+        CREATE TABLE IF NOT EXISTS documents (
+            id TEXT PRIMARY KEY,
+            source_id TEXT,
+            content TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_documents_id ON documents(id);
+        CREATE INDEX IF NOT EXISTS idx_documents_source_id ON documents(source_id);
+
+        CREATE TABLE IF NOT EXISTS messages (
+            id TEXT PRIMARY KEY,
+            source TEXT,
+            source_id TEXT,
+            channel_type TEXT,
+            channel_id TEXT,
+            account_id TEXT,
+            role TEXT,
+            content TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_messages_id ON messages(id);
+        CREATE INDEX IF NOT EXISTS idx_messages_source_id ON messages(source_id);
+        CREATE INDEX IF NOT EXISTS idx_messages_channel_id ON messages(channel_id);
+        CREATE INDEX IF NOT EXISTS idx_messages_account_id ON messages(account_id);
+
+        CREATE TABLE IF NOT EXISTS trade (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            wallet_address TEXT NOT NULL,
+            action TEXT NOT NULL,
+            token_address TEXT NOT NULL,
+            amount REAL NOT NULL,
+            reason TEXT NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            signature TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_wallet_created_at ON trade(wallet_address, created_at);
+
+        -- Attention decisions, to audit why the bot responded or not
+        CREATE TABLE IF NOT EXISTS attention_decisions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            source TEXT NOT NULL,
+            channel_id TEXT NOT NULL,
+            message_id TEXT NOT NULL,
+            command TEXT NOT NULL,
+            confidence REAL NOT NULL,
+            reason TEXT NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_attention_channel_created_at
+        ON attention_decisions(channel_id, created_at);
+
+        -- Channels and accounts the bot was told to stay silent in / for
+        CREATE TABLE IF NOT EXISTS mutes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            source TEXT NOT NULL,
+            channel_id TEXT NOT NULL,
+            account_id TEXT,
+            expires_at TIMESTAMP,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_mutes_source_channel ON mutes(source, channel_id);
+    ",
+    },
+    Migration {
+        version: 2,
+        description: "Link messages to the message they reply to",
+        sql: "
+            ALTER TABLE messages ADD COLUMN reply_to TEXT;
+            CREATE INDEX IF NOT EXISTS idx_messages_reply_to ON messages(reply_to);
+        ",
+    },
+    Migration {
+        version: 3,
+        description: "Facts remembered about users",
+        sql: "
+            CREATE TABLE user_facts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                source TEXT NOT NULL,
+                account_id TEXT NOT NULL,
+                category TEXT NOT NULL,
+                fact TEXT NOT NULL,
+                message_id TEXT NOT NULL,
+                channel_id TEXT NOT NULL,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (source, account_id, fact)
+            );
+        ",
+    },
+    Migration {
+        version: 4,
+        description: "Rolling summaries of channels",
+        sql: "
+            CREATE TABLE channel_summaries (
+                source TEXT NOT NULL,
+                channel_id TEXT NOT NULL,
+                summary TEXT NOT NULL,
+                summarized_until TEXT NOT NULL,
+                message_count INTEGER NOT NULL,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (source, channel_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_messages_channel_created_at
+            ON messages(channel_id, created_at);
+        ",
+    },
+    Migration {
+        version: 5,
+        description: "Record where ingested documents come from",
+        sql: "
+            ALTER TABLE documents ADD COLUMN source_commit TEXT;
+            ALTER TABLE documents ADD COLUMN content_hash TEXT;
+            CREATE INDEX IF NOT EXISTS idx_documents_content_hash ON documents(content_hash);
+        ",
+    },
+    Migration {
+        version: 6,
+        description: "Audit log of the data erased from the knowledge base",
+        sql: "
+            CREATE TABLE audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                action TEXT NOT NULL,
+                source TEXT,
+                subject TEXT,
+                details TEXT NOT NULL,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE INDEX IF NOT EXISTS idx_messages_account_id_source
+            ON messages(source, account_id);
+        ",
+    },
+    Migration {
+        version: 7,
+        description: "Daily spend of the clients",
+        sql: "
+            -- Created by the spend tracker itself in earlier versions
+            CREATE TABLE IF NOT EXISTS daily_spend (
+                client TEXT NOT NULL,
+                date TEXT NOT NULL,
+                input_tokens INTEGER NOT NULL,
+                output_tokens INTEGER NOT NULL,
+                total_tokens INTEGER NOT NULL,
+                cost REAL NOT NULL,
+                PRIMARY KEY (client, date)
+            );
+        ",
+    },
+];
+
+/// Apply the migrations that have not been applied to the database yet, in a single
+/// transaction such that the database is left untouched if any of them fails.
+/// Returns the number of migrations applied.
+pub(crate) fn migrate(conn: &mut Connection) -> rusqlite::Result<usize> {
+    let tx = conn.transaction()?;
+
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+    )?;
+
+    let current: i64 = tx.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+        [],
+        |row| row.get(0),
+    )?;
+
+    let latest = MIGRATIONS.last().map_or(0, |migration| migration.version);
+    if current > latest {
+        warn!(
+            current,
+            latest, "Database schema is more recent than this version of the knowledge base"
+        );
+    }
+
+    let mut applied = 0;
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > current)
+    {
+        info!(
+            version = migration.version,
+            description = migration.description,
+            "Applying knowledge base migration"
+        );
+        tx.execute_batch(migration.sql)?;
+        tx.execute(
+            "INSERT INTO schema_version (version, description) VALUES (?1, ?2)",
+            rusqlite::params![migration.version, migration.description],
+        )?;
+        applied += 1;
+    }
+
+    tx.commit()?;
+
+    Ok(applied)
+}
@@ -1,8 +1,12 @@
 mod types;
 mod store;
+mod migrations;
 pub mod models;
 mod error;
+mod export;
 pub use types::{Source, ChannelType, MessageMetadata, MessageContent};
-pub use store::KnowledgeBase;
-pub use models::{Document, Message, Account, Channel, Conversation};
+pub use store::{ForgetReport, KnowledgeBase};
+pub use models::{Document, Message, Account, AttentionEntry, AuditEntry, Channel, ChannelSummary, Conversation, Mute, UserFact};
 pub use error::ConversionError;
+pub use export::{ExportReport, ImportReport, Record};
+pub(crate) use migrations::migrate;
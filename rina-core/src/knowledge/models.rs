@@ -1,27 +1,36 @@
-use super::types::{ChannelType, Source};
-use rig_sqlite::{Column, ColumnValue, SqliteVectorStoreTable};
+use super::types::{channel_type_column, source_column, ChannelType, Source};
+use rig_sqlite::SqliteTable;
 use rig::Embed;
-use rusqlite::Row;
 
-#[derive(Embed, Clone, Debug)]
+#[derive(Embed, SqliteTable, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[table(name = "documents")]
 pub struct Document {
     pub id: String,
+    #[column(indexed)]
     pub source_id: String,
     #[embed]
+    #[column(searchable)]
     pub content: String,
+    #[column(sql_type = "TIMESTAMP DEFAULT CURRENT_TIMESTAMP")]
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Commit of the Git repository the document was read at, if any
+    pub source_commit: Option<String>,
+    /// SHA-256 of the content, to skip content already in the knowledge base
+    #[column(indexed)]
+    pub content_hash: Option<String>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(SqliteTable, Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Account {
     pub id: i64,
-    pub name: String,
     pub source: String,
+    pub source_id: String,
+    pub name: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(SqliteTable, Debug, serde::Deserialize)]
 pub struct Conversation {
     pub id: String,
     pub user_id: String,
@@ -30,29 +39,117 @@ pub struct Conversation {
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
-#[derive(Embed, Clone, Debug, serde::Deserialize)]
+#[derive(Embed, SqliteTable, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[table(name = "messages")]
 pub struct Message {
     pub id: String,
+    #[column(with = "source_column")]
     pub source: Source,
+    #[column(indexed)]
     pub source_id: String,
+    #[column(with = "channel_type_column")]
     pub channel_type: ChannelType,
+    #[column(indexed)]
     pub channel_id: String,
+    #[column(indexed)]
     pub account_id: String,
+    /// `user` for messages received, `assistant` for messages sent by the agent
     pub role: String,
     #[embed]
+    #[column(searchable)]
     pub content: String,
+    #[column(sql_type = "TIMESTAMP DEFAULT CURRENT_TIMESTAMP")]
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Id of the message this message replies to, if any
+    #[column(indexed)]
+    pub reply_to: Option<String>,
 }
 
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(SqliteTable, Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Channel {
-    pub id: String,
-    pub name: String,
+    pub id: i64,
     pub source: String,
+    pub channel_id: String,
+    pub channel_type: String,
+    pub name: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A period during which the bot stays silent in a channel, either for everyone
+/// or only for one account when `account_id` is set
+#[derive(SqliteTable, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Mute {
+    pub id: i64,
+    pub source: String,
+    pub channel_id: String,
+    pub account_id: Option<String>,
+    /// The mute never expires when unset
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A durable fact learned about an account, along with the message it was learned from
+#[derive(SqliteTable, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UserFact {
+    pub id: i64,
+    pub source: String,
+    pub account_id: String,
+    /// Kind of fact, e.g.: preference, name, wallet or topic
+    pub category: String,
+    pub fact: String,
+    /// Id of the last message the fact was learned from
+    pub message_id: String,
+    pub channel_id: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// Summary of the messages of a channel up to `summarized_until`, the more recent
+/// messages being given verbatim to the agent
+#[derive(SqliteTable, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ChannelSummary {
+    pub source: String,
+    pub channel_id: String,
+    pub summary: String,
+    /// Creation date of the last message summarized
+    pub summarized_until: chrono::DateTime<chrono::Utc>,
+    /// Number of messages summarized
+    pub message_count: i64,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Whether the agent decided to respond to a message, and why
+#[derive(SqliteTable, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[table(name = "attention_decisions")]
+pub struct AttentionEntry {
+    pub id: i64,
+    pub source: String,
+    pub channel_id: String,
+    pub message_id: String,
+    /// `respond`, `ignore` or `stop`
+    pub command: String,
+    pub confidence: f64,
+    pub reason: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Record of data erased from the knowledge base, e.g.: an account forgotten on request or
+/// messages pruned by a retention policy
+#[derive(SqliteTable, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[table(name = "audit_log")]
+pub struct AuditEntry {
+    pub id: i64,
+    /// What was done, e.g.: `forget_account` or `prune_messages`
+    pub action: String,
+    pub source: Option<String>,
+    /// Id of the account or channel the action applied to
+    pub subject: Option<String>,
+    /// JSON object with the number of rows erased from each table
+    pub details: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Trade {
     pub id: u64,
@@ -93,190 +190,3 @@ impl TradeAction {
         }
     }
 }
-
-// Implement the table traits
-impl SqliteVectorStoreTable for Document {
-    fn name() -> &'static str {
-        "documents"
-    }
-
-    fn schema() -> Vec<Column> {
-        vec![
-            Column::new("id", "TEXT PRIMARY KEY"),
-            Column::new("source_id", "TEXT").indexed(),
-            Column::new("content", "TEXT"),
-            Column::new("created_at", "TIMESTAMP DEFAULT CURRENT_TIMESTAMP"),
-        ]
-    }
-
-    fn id(&self) -> String {
-        self.id.clone()
-    }
-
-    fn column_values(&self) -> Vec<(&'static str, Box<dyn ColumnValue>)> {
-        vec![
-            ("id", Box::new(self.id.clone())),
-            ("source_id", Box::new(self.source_id.clone())),
-            ("content", Box::new(self.content.clone())),
-            ("created_at", Box::new(self.created_at.to_rfc3339())),
-        ]
-    }
-}
-
-impl SqliteVectorStoreTable for Message {
-    fn name() -> &'static str {
-        "messages"
-    }
-
-    fn schema() -> Vec<Column> {
-        vec![
-            Column::new("id", "TEXT PRIMARY KEY"),
-            Column::new("source", "TEXT"),
-            Column::new("source_id", "TEXT").indexed(),
-            Column::new("channel_type", "TEXT"),
-            Column::new("channel_id", "TEXT").indexed(),
-            Column::new("account_id", "TEXT").indexed(),
-            Column::new("role", "TEXT"),
-            Column::new("content", "TEXT"),
-            Column::new("created_at", "TIMESTAMP DEFAULT CURRENT_TIMESTAMP"),
-        ]
-    }
-
-    fn id(&self) -> String {
-        self.id.clone()
-    }
-
-    fn column_values(&self) -> Vec<(&'static str, Box<dyn ColumnValue>)> {
-        vec![
-            ("id", Box::new(self.id.clone())),
-            ("source", Box::new(self.source.as_str().to_string())),
-            ("source_id", Box::new(self.source_id.clone())),
-            (
-                "channel_type",
-                Box::new(self.channel_type.as_str().to_string()),
-            ),
-            ("channel_id", Box::new(self.channel_id.clone())),
-            ("account_id", Box::new(self.account_id.clone())),
-            ("role", Box::new(self.role.clone())),
-            ("content", Box::new(self.content.clone())),
-            ("created_at", Box::new(self.created_at.to_rfc3339())),
-        ]
-    }
-}
-
-impl TryFrom<&Row<'_>> for Document {
-    type Error = rusqlite::Error;
-
-    fn try_from(row: &Row) -> Result<Self, Self::Error> {
-        Ok(Document {
-            id: row.get(0)?,
-            source_id: row.get(1)?,
-            content: row.get(2)?,
-            created_at: row.get(3)?,
-        })
-    }
-}
-
-impl TryFrom<&Row<'_>> for Account {
-    type Error = rusqlite::Error;
-
-    fn try_from(row: &Row) -> Result<Self, Self::Error> {
-        Ok(Account {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            source: row.get(2)?,
-            created_at: row.get(3)?,
-            updated_at: row.get(4)?,
-        })
-    }
-}
-
-impl TryFrom<&Row<'_>> for Conversation {
-    type Error = rusqlite::Error;
-
-    fn try_from(row: &Row) -> Result<Self, Self::Error> {
-        Ok(Conversation {
-            id: row.get(0)?,
-            user_id: row.get(1)?,
-            title: row.get(2)?,
-            created_at: row.get(3)?,
-            updated_at: row.get(4)?,
-        })
-    }
-}
-
-impl TryFrom<&Row<'_>> for Message {
-    type Error = rusqlite::Error;
-
-    fn try_from(row: &Row) -> Result<Self, Self::Error> {
-        Ok(Message {
-            id: row.get(0)?,
-            source: Source::from_str(&row.get::<_, String>(1)?).ok_or(
-                rusqlite::Error::FromSqlConversionFailure(
-                    1,
-                    rusqlite::types::Type::Text,
-                    Box::new(super::error::ConversionError("Invalid source".to_string())),
-                ),
-            )?,
-            source_id: row.get(2)?,
-            channel_type: ChannelType::from_str(&row.get::<_, String>(3)?).ok_or(
-                rusqlite::Error::FromSqlConversionFailure(
-                    3,
-                    rusqlite::types::Type::Text,
-                    Box::new(super::error::ConversionError(
-                        "Invalid channel type".to_string(),
-                    )),
-                ),
-            )?,
-            channel_id: row.get(4)?,
-            account_id: row.get(5)?,
-            role: row.get(6)?,
-            content: row.get(7)?,
-            created_at: row.get(8)?,
-        })
-    }
-}
-
-impl TryFrom<&Row<'_>> for Channel {
-    type Error = rusqlite::Error;
-
-    fn try_from(row: &Row) -> Result<Self, Self::Error> {
-        Ok(Channel {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            source: row.get(2)?,
-            created_at: row.get(3)?,
-            updated_at: row.get(4)?,
-        })
-    }
-}
-
-impl SqliteVectorStoreTable for Channel {
-    fn name() -> &'static str {
-        "channels"
-    }
-
-    fn schema() -> Vec<Column> {
-        vec![
-            Column::new("id", "TEXT PRIMARY KEY"),
-            Column::new("name", "TEXT"),
-            Column::new("source", "TEXT"),
-            Column::new("created_at", "TIMESTAMP DEFAULT CURRENT_TIMESTAMP"),
-            Column::new("updated_at", "TIMESTAMP DEFAULT CURRENT_TIMESTAMP"),
-        ]
-    }
-
-    fn id(&self) -> String {
-        self.id.clone()
-    }
-
-    fn column_values(&self) -> Vec<(&'static str, Box<dyn ColumnValue>)> {
-        vec![
-            ("id", Box::new(self.id.clone())),
-            ("name", Box::new(self.name.clone())),
-            ("source", Box::new(self.source.clone())),
-            ("created_at", Box::new(self.created_at.to_rfc3339())),
-            ("updated_at", Box::new(self.updated_at.to_rfc3339())),
-        ]
-    }
-}
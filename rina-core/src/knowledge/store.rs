@@ -1,15 +1,49 @@
+use std::collections::{HashMap, HashSet};
+
 use rig::{
     embeddings::EmbeddingsBuilder,
     vector_store::VectorStoreError,
 };
 use rig::embeddings::embedding::EmbeddingModel;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
 use tokio_rusqlite::Connection;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
-use super::models::{Account, Channel, Document, Message, TradeAction, Trade};
-use rig_sqlite::{SqliteError, SqliteVectorIndex, SqliteVectorStore};
+use super::export::{ExportReport, ImportReport, Record};
+use super::migrations;
+use super::models::{
+    Account, AttentionEntry, AuditEntry, Channel, ChannelSummary, Document, Message, Mute, Trade,
+    TradeAction, UserFact,
+};
+use super::types::Source;
+use rig_sqlite::{Filter, SqliteError, SqliteHybridIndex, SqliteVectorIndex, SqliteVectorStore};
 use rusqlite::OptionalExtension;
 
+/// Maximum number of ids deleted by a single statement, below the limit of SQLite on the
+/// number of parameters
+const DELETE_BATCH_SIZE: usize = 500;
+
+/// Number of rows of a table read at once by [KnowledgeBase::export]
+const EXPORT_PAGE_SIZE: usize = 500;
+
+/// Number of documents, or messages, written at once by [KnowledgeBase::import]
+const IMPORT_BATCH_SIZE: usize = 500;
+
+/// What [KnowledgeBase::forget_account] erased, counted in rows
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct ForgetReport {
+    /// Messages of the account and replies of the agent to them, along with their embeddings
+    pub messages: usize,
+    /// Embeddings of the messages kept by the embedding cache
+    pub cached_embeddings: usize,
+    pub attention_decisions: usize,
+    /// Summaries of the channels the account talked in
+    pub summaries: usize,
+    pub facts: usize,
+    pub mutes: usize,
+    pub account: bool,
+}
+
 #[derive(Clone)]
 pub struct KnowledgeBase<E: EmbeddingModel + Clone + 'static> {
     conn: Connection,
@@ -20,56 +54,26 @@ pub struct KnowledgeBase<E: EmbeddingModel + Clone + 'static> {
 
 impl<E: EmbeddingModel> KnowledgeBase<E> {
     pub async fn new(conn: Connection, embedding_model: E) -> Result<Self, VectorStoreError> {
+        // The vector stores create their tables if they do not exist, migrate the
+        // database first so that the tables are created by the migrations instead
+        let applied = conn
+            .call(|conn| migrations::migrate(conn).map_err(tokio_rusqlite::Error::from))
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+        if applied > 0 {
+            info!(applied, "Migrated knowledge base");
+        }
+
         let document_store = SqliteVectorStore::new(conn.clone(), &embedding_model).await?;
         let message_store = SqliteVectorStore::new(conn.clone(), &embedding_model).await?;
 
-        conn.call(|conn| {
-            conn.execute_batch(
-                "BEGIN;
-
-                -- User management tables
-                CREATE TABLE IF NOT EXISTS accounts (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    name TEXT NOT NULL,
-                    source_id TEXT NOT NULL UNIQUE,
-                    source TEXT NOT NULL,
-                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-                );
-                CREATE INDEX IF NOT EXISTS idx_source_id_source ON accounts(source_id, source);
-
-                -- Channel tables
-                CREATE TABLE IF NOT EXISTS channels (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    channel_id TEXT NOT NULL UNIQUE,
-                    channel_type TEXT NOT NULL,
-                    source TEXT NOT NULL,
-                    name TEXT,
-                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-                );
-                CREATE INDEX IF NOT EXISTS idx_channel_id_type ON channels(channel_id, channel_type);
-
-                -- Trade table
-                CREATE TABLE IF NOT EXISTS trade (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    wallet_address TEXT NOT NULL,
-                    action TEXT NOT NULL,
-                    token_address TEXT NOT NULL,
-                    amount REAL NOT NULL,
-                    reason TEXT NOT NULL,
-                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                    signature TEXT NOT NULL
-                );
-                CREATE INDEX IF NOT EXISTS idx_wallet_created_at 
-                ON trade(wallet_address, created_at);
-
-                COMMIT;"
-            )
-            .map_err(tokio_rusqlite::Error::from)
-        })
-        .await
-        .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+        // Embeddings are dropped when the dimension of the embedding model changes,
+        // re-embed the documents and messages with the current embedding model
+        let documents = document_store.embed_missing(&embedding_model).await?;
+        let messages = message_store.embed_missing(&embedding_model).await?;
+        if documents + messages > 0 {
+            info!(documents, messages, "Re-embedded knowledge base");
+        }
 
         Ok(Self {
             conn,
@@ -79,16 +83,22 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
         })
     }
 
-    pub async fn create_user(&self, name: String, source: String) -> Result<i64, SqliteError> {
+    pub async fn create_user(
+        &self,
+        name: String,
+        source: String,
+        source_id: String,
+    ) -> Result<i64, SqliteError> {
         self.conn
             .call(move |conn| {
                 conn.query_row(
-                    "INSERT INTO accounts (name, source, created_at, updated_at)
-                     VALUES (?1, ?2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
-                     ON CONFLICT(name) DO UPDATE SET 
+                    "INSERT INTO accounts (source, source_id, name, created_at, updated_at)
+                     VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+                     ON CONFLICT(source, source_id) DO UPDATE SET
+                         name = excluded.name,
                          updated_at = CURRENT_TIMESTAMP
                      RETURNING id",
-                    rusqlite::params![name, source],
+                    rusqlite::params![source, source_id, name],
                     |row| row.get(0),
                 )
                 .map_err(tokio_rusqlite::Error::from)
@@ -97,24 +107,59 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
             .map_err(|e| SqliteError::DatabaseError(Box::new(e)))
     }
 
+    /// The same knowledge base embedding texts with another embedding model of the same type,
+    /// e.g.: to meter the embeddings of each client separately
+    pub fn with_embedding_model(self, embedding_model: E) -> Self {
+        Self {
+            embedding_model,
+            ..self
+        }
+    }
+
     pub fn document_index(self) -> SqliteVectorIndex<E, Document> {
         SqliteVectorIndex::new(self.embedding_model, self.document_store)
     }
 
+    /// Index of the documents searching both by meaning and by keywords, such that exact
+    /// tokens like addresses, tickers or usernames are found
+    pub fn document_hybrid_index(self) -> SqliteHybridIndex<E, Document> {
+        SqliteHybridIndex::new(self.embedding_model, self.document_store)
+    }
+
     pub fn message_index(self) -> SqliteVectorIndex<E, Message> {
         SqliteVectorIndex::new(self.embedding_model, self.message_store)
     }
 
-    pub async fn get_user_by_source(&self, source: String) -> Result<Option<Account>, SqliteError> {
+    /// Index of the messages of a single channel, such that searches never surface
+    /// conversations from other channels or platforms
+    pub fn channel_message_index(
+        self,
+        source: &Source,
+        channel_id: &str,
+    ) -> SqliteVectorIndex<E, Message> {
+        self.message_index().filter(
+            Filter::eq("source", source.as_str().to_string())
+                .and(Filter::eq("channel_id", channel_id.to_string())),
+        )
+    }
+
+    pub async fn get_user_by_source(
+        &self,
+        source: String,
+        source_id: String,
+    ) -> Result<Option<Account>, SqliteError> {
         self.conn
             .call(move |conn| {
                 let mut stmt = conn.prepare(
-                    "SELECT id, name, source, created_at, updated_at FROM accounts WHERE source = ?1"
+                    "SELECT id, source, source_id, name, created_at, updated_at
+                     FROM accounts WHERE source = ?1 AND source_id = ?2",
                 )?;
 
-                let account = stmt.query_row(rusqlite::params![source], |row| {
-                    Account::try_from(row)
-                }).optional()?;
+                let account = stmt
+                    .query_row(rusqlite::params![source, source_id], |row| {
+                        Account::try_from(row)
+                    })
+                    .optional()?;
 
                 Ok(account)
             })
@@ -124,6 +169,7 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
 
     pub async fn create_channel(
         &self,
+        source: String,
         channel_id: String,
         channel_type: String,
         name: Option<String>,
@@ -131,13 +177,13 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
         self.conn
             .call(move |conn| {
                 conn.query_row(
-                    "INSERT INTO channels (channel_id, channel_type, name, created_at, updated_at)
-                     VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
-                     ON CONFLICT(channel_id) DO UPDATE SET 
-                         name = COALESCE(?3, name),
+                    "INSERT INTO channels (source, channel_id, channel_type, name, created_at, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+                     ON CONFLICT(source, channel_id) DO UPDATE SET
+                         name = COALESCE(?4, name),
                          updated_at = CURRENT_TIMESTAMP
                      RETURNING id",
-                    rusqlite::params![channel_id, channel_type, name],
+                    rusqlite::params![source, channel_id, channel_type, name],
                     |row| row.get(0),
                 )
                 .map_err(tokio_rusqlite::Error::from)
@@ -150,7 +196,8 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
         self.conn
             .call(move |conn| {
                 let mut stmt = conn.prepare(
-                    "SELECT id, name, source, created_at, updated_at FROM channels WHERE id = ?1",
+                    "SELECT id, source, channel_id, channel_type, name, created_at, updated_at
+                     FROM channels WHERE id = ?1",
                 )?;
 
                 let channel = stmt
@@ -170,14 +217,13 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
         self.conn
             .call(move |conn| {
                 let mut stmt = conn.prepare(
-                    "SELECT id, name, source, created_at, updated_at FROM channels WHERE source = ?1"
+                    "SELECT id, source, channel_id, channel_type, name, created_at, updated_at
+                     FROM channels WHERE source = ?1",
                 )?;
 
-                let channels = stmt.query_map(rusqlite::params![source], |row| {
-                    Channel::try_from(row)
-                }).and_then(|mapped_rows| {
-                    mapped_rows.collect::<Result<Vec<Channel>, _>>()
-                })?;
+                let channels = stmt
+                    .query_map(rusqlite::params![source], |row| Channel::try_from(row))
+                    .and_then(|mapped_rows| mapped_rows.collect::<Result<Vec<Channel>, _>>())?;
 
                 Ok(channels)
             })
@@ -185,6 +231,8 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
             .map_err(|e| SqliteError::DatabaseError(Box::new(e)))
     }
 
+    /// Store a message, failing if a message with the same id was already stored: ids are
+    /// only unique within a source and chat, such that it may be another message
     pub async fn create_message(&self, msg: Message) -> anyhow::Result<i64> {
         let embeddings = EmbeddingsBuilder::new(self.embedding_model.clone())
             .documents(vec![msg.clone()])?
@@ -201,7 +249,7 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
                 tx.execute(
                     "INSERT INTO channels (channel_id, channel_type, source, name, created_at, updated_at) 
                      VALUES (?1, ?2, ?3, NULL, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
-                     ON CONFLICT (channel_id) DO UPDATE SET 
+                     ON CONFLICT (source, channel_id) DO UPDATE SET 
                      updated_at = CURRENT_TIMESTAMP",
                     [
                         &msg.channel_id,
@@ -210,6 +258,17 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
                     ],
                 )?;
 
+                let exists: bool = tx.query_row(
+                    "SELECT EXISTS (SELECT 1 FROM messages WHERE id = ?1)",
+                    [&msg.id],
+                    |row| row.get(0),
+                )?;
+                if exists {
+                    return Err(tokio_rusqlite::Error::Other(
+                        format!("Message {} is already stored", msg.id).into(),
+                    ));
+                }
+
                 let id = store.add_rows_with_txn(&tx, embeddings)?;
 
                 tx.commit()?;
@@ -223,7 +282,7 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
     pub async fn get_message(&self, id: i64) -> Result<Option<Message>, SqliteError> {
         self.conn
             .call(move |conn| {
-                Ok(conn.prepare("SELECT id, source, source_id, channel_type, channel_id, account_id, role, content, created_at FROM messages WHERE id = ?1")?
+                Ok(conn.prepare("SELECT id, source, source_id, channel_type, channel_id, account_id, role, content, created_at, reply_to FROM messages WHERE id = ?1")?
                     .query_row(rusqlite::params![id], |row| {
                         Message::try_from(row)
                     }).optional()?)
@@ -232,23 +291,227 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
             .map_err(|e| SqliteError::DatabaseError(Box::new(e)))
     }
 
+    /// Delete a message along with its embedding. Returns whether it existed.
+    pub async fn delete_message(&self, id: &str) -> anyhow::Result<bool> {
+        Ok(self.message_store.delete_by_id(id).await?)
+    }
+
+    /// Delete all the messages of a channel along with their embeddings. Returns the number
+    /// of messages deleted.
+    pub async fn delete_channel_messages(
+        &self,
+        source: &Source,
+        channel_id: &str,
+    ) -> anyhow::Result<usize> {
+        let filter = Filter::eq("source", source.as_str().to_string())
+            .and(Filter::eq("channel_id", channel_id.to_string()));
+
+        Ok(self.message_store.delete_where(filter).await?)
+    }
+
+    /// Channels with stored messages, by source
+    pub async fn message_channels(&self) -> anyhow::Result<Vec<(Source, String)>> {
+        self.conn
+            .call(|conn| {
+                let mut stmt = conn.prepare("SELECT DISTINCT source, channel_id FROM messages")?;
+                let channels = stmt
+                    .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(channels)
+            })
+            .await
+            .map(|channels| {
+                channels
+                    .into_iter()
+                    .filter_map(|(source, channel_id)| {
+                        Some((Source::from_str(&source)?, channel_id))
+                    })
+                    .collect()
+            })
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Delete the messages of a channel created before `before` as well as all but its `keep`
+    /// most recent messages, along with their embeddings. Returns the number of messages
+    /// deleted, which is recorded in the audit log.
+    pub async fn prune_channel_messages(
+        &self,
+        source: &Source,
+        channel_id: &str,
+        before: Option<chrono::DateTime<chrono::Utc>>,
+        keep: Option<usize>,
+    ) -> anyhow::Result<usize> {
+        let source = source.as_str();
+        let channel_id = channel_id.to_string();
+        // Messages are stored with RFC 3339 dates, which sort chronologically as text
+        let before = before.map(|before| before.to_rfc3339());
+        let keep = keep.map_or(i64::MAX, |keep| keep as i64);
+        let store = self.message_store.clone();
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let ids = {
+                    let mut stmt = tx.prepare(
+                        "SELECT id FROM messages
+                         WHERE source = ?1 AND channel_id = ?2
+                         AND ((?3 IS NOT NULL AND created_at < ?3) OR id IN (
+                             SELECT id FROM messages
+                             WHERE source = ?1 AND channel_id = ?2
+                             ORDER BY created_at DESC
+                             LIMIT -1 OFFSET ?4
+                         ))",
+                    )?;
+                    let ids = stmt
+                        .query_map(rusqlite::params![source, channel_id, before, keep], |row| {
+                            row.get::<_, String>(0)
+                        })?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    ids
+                };
+
+                let mut deleted = 0;
+                for ids in ids.chunks(DELETE_BATCH_SIZE) {
+                    deleted +=
+                        store.delete_where_with_txn(&tx, &Filter::is_in("id", ids.to_vec()))?;
+                }
+                if deleted > 0 {
+                    audit(
+                        &tx,
+                        "prune_messages",
+                        source,
+                        &channel_id,
+                        &serde_json::json!({ "messages": deleted }),
+                    )?;
+                }
+
+                tx.commit()?;
+                Ok(deleted)
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Erase everything known about an account, in a single transaction: its messages and the
+    /// replies of the agent to them along with their embeddings, the attention decisions made
+    /// on them, the summaries of the channels it talked in (rebuilt from the remaining
+    /// messages), the facts remembered about it, its mutes and the account itself.
+    ///
+    /// The erasure is recorded in the audit log, with the number of rows erased but none of
+    /// the erased content.
+    pub async fn forget_account(
+        &self,
+        source: &Source,
+        source_id: &str,
+    ) -> anyhow::Result<ForgetReport> {
+        let source = source.as_str();
+        let source_id = source_id.to_string();
+        let store = self.message_store.clone();
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut report = ForgetReport::default();
+                let params = rusqlite::params![source, source_id];
+
+                let (ids, contents): (Vec<String>, Vec<String>) = {
+                    let mut stmt = tx.prepare(
+                        "SELECT id, content FROM messages
+                         WHERE source = ?1 AND (account_id = ?2 OR (role = 'assistant' AND reply_to IN (
+                             SELECT id FROM messages WHERE source = ?1 AND account_id = ?2
+                         )))",
+                    )?;
+                    let messages = stmt
+                        .query_map(params, |row| Ok((row.get(0)?, row.get(1)?)))?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    messages.into_iter().unzip()
+                };
+
+                report.attention_decisions = tx.execute(
+                    "DELETE FROM attention_decisions
+                     WHERE source = ?1 AND message_id IN (
+                         SELECT id FROM messages WHERE source = ?1 AND account_id = ?2
+                     )",
+                    params,
+                )?;
+                report.summaries = tx.execute(
+                    "DELETE FROM channel_summaries
+                     WHERE source = ?1 AND channel_id IN (
+                         SELECT DISTINCT channel_id FROM messages WHERE source = ?1 AND account_id = ?2
+                     )",
+                    params,
+                )?;
+                for ids in ids.chunks(DELETE_BATCH_SIZE) {
+                    report.messages +=
+                        store.delete_where_with_txn(&tx, &Filter::is_in("id", ids.to_vec()))?;
+                }
+                report.cached_embeddings =
+                    rig_sqlite::delete_cached_embeddings(&tx, contents.iter().map(String::as_str))?;
+
+                report.facts = tx.execute(
+                    "DELETE FROM user_facts WHERE source = ?1 AND account_id = ?2",
+                    params,
+                )?;
+                report.mutes = tx.execute(
+                    "DELETE FROM mutes WHERE source = ?1 AND account_id = ?2",
+                    params,
+                )?;
+                report.account = tx.execute(
+                    "DELETE FROM accounts WHERE source = ?1 AND source_id = ?2",
+                    params,
+                )? > 0;
+
+                let details = serde_json::to_value(&report)
+                    .map_err(|e| tokio_rusqlite::Error::Other(Box::new(e)))?;
+                audit(&tx, "forget_account", source, &source_id, &details)?;
+
+                tx.commit()?;
+                Ok(report)
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Most recent entries of the audit log
+    pub async fn audit_log(&self, limit: usize) -> anyhow::Result<Vec<AuditEntry>> {
+        self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, action, source, subject, details, created_at
+                     FROM audit_log
+                     ORDER BY id DESC
+                     LIMIT ?1",
+                )?;
+                let entries = stmt
+                    .query_map([limit], |row| AuditEntry::try_from(row))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(entries)
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
     pub async fn get_recent_messages(
         &self,
-        channel_id: i64,
+        source: &Source,
+        channel_id: &str,
         limit: usize,
     ) -> Result<Vec<Message>, SqliteError> {
+        let source = source.as_str();
+        let channel_id = channel_id.to_string();
+
         self.conn
             .call(move |conn| {
                 let mut stmt = conn.prepare(
-                    "SELECT id, source, source_id, channel_type, channel_id, account_id, role, content, created_at 
+                    "SELECT id, source, source_id, channel_type, channel_id, account_id, role, content, created_at, reply_to 
                      FROM messages 
-                     WHERE channel_id = ?1 
+                     WHERE source = ?1 AND channel_id = ?2
                      ORDER BY created_at DESC 
-                     LIMIT ?2",
+                     LIMIT ?3",
                 )?;
 
                 let messages = stmt
-                    .query_map(rusqlite::params![channel_id, limit], |row| {
+                    .query_map(rusqlite::params![source, channel_id, limit], |row| {
                         Message::try_from(row)
                     })?
                     .collect::<Result<Vec<_>, _>>()?;
@@ -259,33 +522,137 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
             .map_err(|e| SqliteError::DatabaseError(Box::new(e)))
     }
 
+    /// Latest messages of a channel, both the messages received and the replies of the
+    /// agent, along with the name of their author when known, from the oldest to the most recent
     pub async fn channel_messages(
         &self,
+        source: &Source,
         channel_id: &str,
         limit: i64,
-    ) -> anyhow::Result<Vec<(String, String)>> {
+    ) -> anyhow::Result<Vec<(Message, Option<String>)>> {
+        let source = source.as_str();
         let channel_id = channel_id.to_string();
 
         self.conn
             .call(move |conn| {
                 let mut stmt = conn.prepare(
-                    "SELECT source_id, content 
-                     FROM messages 
-                     WHERE channel_id = ?1
-                     ORDER BY created_at DESC 
-                     LIMIT ?2",
+                    "SELECT m.id, m.source, m.source_id, m.channel_type, m.channel_id, m.account_id,
+                            m.role, m.content, m.created_at, m.reply_to, a.name
+                     FROM messages m
+                     LEFT JOIN accounts a ON a.source = m.source AND a.source_id = m.account_id
+                     WHERE m.source = ?1 AND m.channel_id = ?2
+                     ORDER BY m.created_at DESC
+                     LIMIT ?3",
                 )?;
-                let messages = stmt
-                    .query_map([&channel_id, &limit.to_string()], |row| {
-                        Ok((row.get(0)?, row.get(1)?))
+                let mut messages = stmt
+                    .query_map(rusqlite::params![source, channel_id, limit], |row| {
+                        Ok((Message::try_from(row)?, row.get(10)?))
                     })?
                     .collect::<Result<Vec<_>, _>>()?;
+                messages.reverse();
+                Ok(messages)
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Messages of a channel created after `after` and before `before`, along with the name
+    /// of their author when known, from the oldest to the most recent
+    pub async fn messages_to_summarize(
+        &self,
+        source: &Source,
+        channel_id: &str,
+        after: Option<chrono::DateTime<chrono::Utc>>,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> anyhow::Result<Vec<(Message, Option<String>)>> {
+        let source = source.as_str();
+        let channel_id = channel_id.to_string();
+        // Messages are stored with RFC 3339 dates, which sort chronologically as text
+        let after = after.map(|after| after.to_rfc3339());
+        let before = before.to_rfc3339();
+
+        self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT m.id, m.source, m.source_id, m.channel_type, m.channel_id, m.account_id,
+                            m.role, m.content, m.created_at, m.reply_to, a.name
+                     FROM messages m
+                     LEFT JOIN accounts a ON a.source = m.source AND a.source_id = m.account_id
+                     WHERE m.source = ?1 AND m.channel_id = ?2
+                     AND (?3 IS NULL OR m.created_at > ?3) AND m.created_at < ?4
+                     ORDER BY m.created_at",
+                )?;
+                let messages = stmt
+                    .query_map(
+                        rusqlite::params![source, channel_id, after, before],
+                        |row| Ok((Message::try_from(row)?, row.get(10)?)),
+                    )?
+                    .collect::<Result<Vec<_>, _>>()?;
                 Ok(messages)
             })
             .await
             .map_err(|e| anyhow::anyhow!(e))
     }
 
+    pub async fn channel_summary(
+        &self,
+        source: &Source,
+        channel_id: &str,
+    ) -> anyhow::Result<Option<ChannelSummary>> {
+        let source = source.as_str();
+        let channel_id = channel_id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                Ok(conn
+                    .prepare(
+                        "SELECT source, channel_id, summary, summarized_until, message_count, updated_at
+                         FROM channel_summaries
+                         WHERE source = ?1 AND channel_id = ?2",
+                    )?
+                    .query_row(rusqlite::params![source, channel_id], |row| {
+                        ChannelSummary::try_from(row)
+                    })
+                    .optional()?)
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Replace the summary of a channel, `message_count` being the number of messages
+    /// summarized in total
+    pub async fn store_channel_summary(
+        &self,
+        source: &Source,
+        channel_id: &str,
+        summary: &str,
+        summarized_until: chrono::DateTime<chrono::Utc>,
+        message_count: i64,
+    ) -> anyhow::Result<()> {
+        let source = source.as_str();
+        let channel_id = channel_id.to_string();
+        let summary = summary.to_string();
+        let summarized_until = summarized_until.to_rfc3339();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO channel_summaries
+                     (source, channel_id, summary, summarized_until, message_count, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, CURRENT_TIMESTAMP)
+                     ON CONFLICT (source, channel_id) DO UPDATE SET
+                         summary = excluded.summary,
+                         summarized_until = excluded.summarized_until,
+                         message_count = excluded.message_count,
+                         updated_at = CURRENT_TIMESTAMP",
+                    rusqlite::params![source, channel_id, summary, summarized_until, message_count],
+                )?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
     pub async fn add_documents<'a, I>(&mut self, documents: I) -> anyhow::Result<()>
     where
         I: IntoIterator<Item = Document>,
@@ -303,6 +670,88 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
         Ok(())
     }
 
+    /// Add documents, replacing the documents with the same id along with their embeddings
+    pub async fn upsert_documents<I>(&self, documents: I) -> anyhow::Result<()>
+    where
+        I: IntoIterator<Item = Document>,
+    {
+        let embeddings = EmbeddingsBuilder::new(self.embedding_model.clone())
+            .documents(documents)?
+            .build()
+            .await?;
+
+        self.document_store.upsert_rows(embeddings).await?;
+
+        Ok(())
+    }
+
+    pub async fn get_document(&self, id: &str) -> anyhow::Result<Option<Document>> {
+        Ok(self.document_store.get_by_id(id).await?)
+    }
+
+    /// Delete a document along with its embedding. Returns whether it existed.
+    pub async fn delete_document(&self, id: &str) -> anyhow::Result<bool> {
+        Ok(self.document_store.delete_by_id(id).await?)
+    }
+
+    /// Delete documents along with their embeddings. Returns the number of documents deleted.
+    pub async fn delete_documents(&self, ids: Vec<String>) -> anyhow::Result<usize> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        Ok(self.document_store.delete_where(Filter::is_in("id", ids)).await?)
+    }
+
+    /// Content hash of each document of a source, by document id
+    pub async fn document_hashes(
+        &self,
+        source_id: &str,
+    ) -> anyhow::Result<HashMap<String, Option<String>>> {
+        let source_id = source_id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                let mut stmt =
+                    conn.prepare("SELECT id, content_hash FROM documents WHERE source_id = ?1")?;
+                let hashes = stmt
+                    .query_map([source_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<HashMap<_, _>, _>>()?;
+                Ok(hashes)
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// The content hashes among `hashes` of the documents of sources other than `source_id`
+    pub async fn known_content_hashes(
+        &self,
+        hashes: Vec<String>,
+        source_id: &str,
+    ) -> anyhow::Result<HashSet<String>> {
+        if hashes.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let source_id = source_id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                let placeholders = vec!["?"; hashes.len()].join(", ");
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT DISTINCT content_hash FROM documents
+                     WHERE content_hash IN ({}) AND source_id != ?",
+                    placeholders
+                ))?;
+                let params = hashes.into_iter().chain(std::iter::once(source_id));
+                let known = stmt
+                    .query_map(rusqlite::params_from_iter(params), |row| row.get(0))?
+                    .collect::<Result<HashSet<_>, _>>()?;
+                Ok(known)
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
     pub async fn store_trade_recommendation(
         &self,
         wallet_address: &str,
@@ -333,6 +782,225 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
             .map_err(|e| SqliteError::DatabaseError(Box::new(e)))
     }
 
+    /// Record whether the agent decided to respond to a message, and why
+    pub async fn store_attention_decision(
+        &self,
+        source: &Source,
+        channel_id: &str,
+        message_id: &str,
+        command: &str,
+        confidence: f32,
+        reason: &str,
+    ) -> Result<i64, SqliteError> {
+        let source = source.as_str();
+        let channel_id = channel_id.to_string();
+        let message_id = message_id.to_string();
+        let command = command.to_string();
+        let reason = reason.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.query_row(
+                    "INSERT INTO attention_decisions
+                     (source, channel_id, message_id, command, confidence, reason, created_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, CURRENT_TIMESTAMP)
+                     RETURNING id",
+                    rusqlite::params![source, channel_id, message_id, command, confidence, reason],
+                    |row| row.get(0),
+                )
+                .map_err(tokio_rusqlite::Error::from)
+            })
+            .await
+            .map_err(|e| SqliteError::DatabaseError(Box::new(e)))
+    }
+
+    /// Mute the bot in a channel, only for the given account if any, until the mute
+    /// expires or it is lifted with [KnowledgeBase::unmute]
+    pub async fn mute(
+        &self,
+        source: &Source,
+        channel_id: &str,
+        account_id: Option<&str>,
+        expires_in: Option<chrono::Duration>,
+    ) -> Result<i64, SqliteError> {
+        let source = source.as_str();
+        let channel_id = channel_id.to_string();
+        let account_id = account_id.map(str::to_string);
+        let expires_in = expires_in.map(|duration| format!("{:+} seconds", duration.num_seconds()));
+
+        self.conn
+            .call(move |conn| {
+                conn.query_row(
+                    "INSERT INTO mutes (source, channel_id, account_id, expires_at, created_at)
+                     VALUES (?1, ?2, ?3, datetime('now', ?4), CURRENT_TIMESTAMP)
+                     RETURNING id",
+                    rusqlite::params![source, channel_id, account_id, expires_in],
+                    |row| row.get(0),
+                )
+                .map_err(tokio_rusqlite::Error::from)
+            })
+            .await
+            .map_err(|e| SqliteError::DatabaseError(Box::new(e)))
+    }
+
+    /// Lift the active mutes of a channel for the given account, along with the mutes of
+    /// the whole channel if `channel_wide`, returning how many were lifted
+    pub async fn unmute(
+        &self,
+        source: &Source,
+        channel_id: &str,
+        account_id: &str,
+        channel_wide: bool,
+    ) -> Result<usize, SqliteError> {
+        let source = source.as_str();
+        let channel_id = channel_id.to_string();
+        let account_id = account_id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "DELETE FROM mutes
+                     WHERE source = ?1 AND channel_id = ?2
+                     AND (account_id = ?3 OR (?4 AND account_id IS NULL))
+                     AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)",
+                    rusqlite::params![source, channel_id, account_id, channel_wide],
+                )
+                .map_err(tokio_rusqlite::Error::from)
+            })
+            .await
+            .map_err(|e| SqliteError::DatabaseError(Box::new(e)))
+    }
+
+    /// The longest lasting active mute of a channel that applies to the given account
+    pub async fn active_mute(
+        &self,
+        source: &Source,
+        channel_id: &str,
+        account_id: &str,
+    ) -> Result<Option<Mute>, SqliteError> {
+        let source = source.as_str();
+        let channel_id = channel_id.to_string();
+        let account_id = account_id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                Ok(conn
+                    .prepare(
+                        "SELECT id, source, channel_id, account_id, expires_at, created_at
+                         FROM mutes
+                         WHERE source = ?1 AND channel_id = ?2
+                         AND (account_id IS NULL OR account_id = ?3)
+                         AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)
+                         ORDER BY expires_at IS NULL DESC, expires_at DESC
+                         LIMIT 1",
+                    )?
+                    .query_row(
+                        rusqlite::params![source, channel_id, account_id],
+                        |row| Mute::try_from(row),
+                    )
+                    .optional()?)
+            })
+            .await
+            .map_err(|e| SqliteError::DatabaseError(Box::new(e)))
+    }
+
+    /// Remember facts learned about the author of `message`, as pairs of category and fact,
+    /// facts already known are attributed to `message`
+    pub async fn store_user_facts(
+        &self,
+        message: &Message,
+        facts: Vec<(&'static str, String)>,
+    ) -> Result<usize, SqliteError> {
+        let source = message.source.as_str();
+        let account_id = message.account_id.clone();
+        let message_id = message.id.clone();
+        let channel_id = message.channel_id.clone();
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut stored = 0;
+                {
+                    let mut stmt = tx.prepare(
+                        "INSERT INTO user_facts
+                         (source, account_id, category, fact, message_id, channel_id, created_at, updated_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+                         ON CONFLICT (source, account_id, fact) DO UPDATE SET
+                             category = excluded.category,
+                             message_id = excluded.message_id,
+                             channel_id = excluded.channel_id,
+                             updated_at = CURRENT_TIMESTAMP",
+                    )?;
+                    for (category, fact) in facts {
+                        stored += stmt.execute(rusqlite::params![
+                            source,
+                            account_id,
+                            category,
+                            fact.trim(),
+                            message_id,
+                            channel_id,
+                        ])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(stored)
+            })
+            .await
+            .map_err(|e| SqliteError::DatabaseError(Box::new(e)))
+    }
+
+    /// Most recently learned or confirmed facts about an account
+    pub async fn user_facts(
+        &self,
+        source: &Source,
+        account_id: &str,
+        limit: usize,
+    ) -> Result<Vec<UserFact>, SqliteError> {
+        let source = source.as_str();
+        let account_id = account_id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, source, account_id, category, fact, message_id, channel_id,
+                            created_at, updated_at
+                     FROM user_facts
+                     WHERE source = ?1 AND account_id = ?2
+                     ORDER BY updated_at DESC, id DESC
+                     LIMIT ?3",
+                )?;
+                let facts = stmt
+                    .query_map(rusqlite::params![source, account_id, limit], |row| {
+                        UserFact::try_from(row)
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(facts)
+            })
+            .await
+            .map_err(|e| SqliteError::DatabaseError(Box::new(e)))
+    }
+
+    /// Forget all the facts learned about an account, returning how many were forgotten
+    pub async fn forget_user_facts(
+        &self,
+        source: &Source,
+        account_id: &str,
+    ) -> Result<usize, SqliteError> {
+        let source = source.as_str();
+        let account_id = account_id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "DELETE FROM user_facts WHERE source = ?1 AND account_id = ?2",
+                    rusqlite::params![source, account_id],
+                )
+                .map_err(tokio_rusqlite::Error::from)
+            })
+            .await
+            .map_err(|e| SqliteError::DatabaseError(Box::new(e)))
+    }
+
     pub async fn get_recent_trades(
         &self,
         wallet_address: &str,
@@ -351,19 +1019,7 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
                 )?;
 
                 let trades = stmt
-                    .query_map([wallet, limit.to_string()], |row| {
-                        Ok(Trade {
-                            id: row.get(0)?,
-                            wallet_address: row.get(1)?,
-                            action: TradeAction::from_str(&row.get::<_, String>(2)?)
-                                .unwrap_or(TradeAction::Hold),
-                            token_address: row.get(3)?,
-                            amount: row.get(4)?,
-                            reason: row.get(5)?,
-                            created_at: row.get(6)?,
-                            signature: row.get(7)?,
-                        })
-                    })?
+                    .query_map([wallet, limit.to_string()], trade_from_row)?
                     .collect::<Result<Vec<_>, _>>()?;
 
                 Ok(trades)
@@ -371,4 +1027,660 @@ impl<E: EmbeddingModel> KnowledgeBase<E> {
             .await
             .map_err(|e| SqliteError::DatabaseError(Box::new(e)))
     }
-}
\ No newline at end of file
+
+    /// Write the accounts, channels, documents, messages, trades, user facts, mutes, channel
+    /// summaries and attention decisions of the knowledge base to `writer` as JSON lines, one
+    /// [Record] per line, e.g.: to back it up or move it to another database.
+    ///
+    /// The embeddings of the documents and messages are only written if `with_embeddings` is
+    /// set: they make up most of the export and are only of use to a knowledge base with an
+    /// embedding model of the same dimension.
+    pub async fn export<W>(
+        &self,
+        mut writer: W,
+        with_embeddings: bool,
+    ) -> anyhow::Result<ExportReport>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut report = ExportReport::default();
+
+        let mut after = 0;
+        loop {
+            let accounts = self
+                .export_page(
+                    "SELECT id, source, source_id, name, created_at, updated_at
+                     FROM accounts WHERE id > ?1 ORDER BY id LIMIT ?2",
+                    after,
+                    |row| Account::try_from(row),
+                )
+                .await?;
+            let Some(last) = accounts.last() else { break };
+            after = last.id;
+            for account in accounts {
+                write_record(&mut writer, &Record::Account { account }).await?;
+                report.accounts += 1;
+            }
+        }
+
+        let mut after = 0;
+        loop {
+            let channels = self
+                .export_page(
+                    "SELECT id, source, channel_id, channel_type, name, created_at, updated_at
+                     FROM channels WHERE id > ?1 ORDER BY id LIMIT ?2",
+                    after,
+                    |row| Channel::try_from(row),
+                )
+                .await?;
+            let Some(last) = channels.last() else { break };
+            after = last.id;
+            for channel in channels {
+                write_record(&mut writer, &Record::Channel { channel }).await?;
+                report.channels += 1;
+            }
+        }
+
+        let mut after = 0;
+        loop {
+            let documents = self
+                .document_store
+                .rows::<Document>(after, EXPORT_PAGE_SIZE)
+                .await?;
+            let Some((last, _, _)) = documents.last() else {
+                break;
+            };
+            after = *last;
+            for (_, document, embedding) in documents {
+                let embedding = embedding.filter(|_| with_embeddings);
+                report.embeddings += embedding.is_some() as usize;
+                write_record(
+                    &mut writer,
+                    &Record::Document {
+                        document,
+                        embedding,
+                    },
+                )
+                .await?;
+                report.documents += 1;
+            }
+        }
+
+        let mut after = 0;
+        loop {
+            let messages = self
+                .message_store
+                .rows::<Message>(after, EXPORT_PAGE_SIZE)
+                .await?;
+            let Some((last, _, _)) = messages.last() else {
+                break;
+            };
+            after = *last;
+            for (_, message, embedding) in messages {
+                let embedding = embedding.filter(|_| with_embeddings);
+                report.embeddings += embedding.is_some() as usize;
+                write_record(&mut writer, &Record::Message { message, embedding }).await?;
+                report.messages += 1;
+            }
+        }
+
+        let mut after = 0;
+        loop {
+            let trades = self
+                .export_page(
+                    "SELECT id, wallet_address, action, token_address, amount, reason, created_at, signature
+                     FROM trade WHERE id > ?1 ORDER BY id LIMIT ?2",
+                    after,
+                    trade_from_row,
+                )
+                .await?;
+            let Some(last) = trades.last() else { break };
+            after = last.id as i64;
+            for trade in trades {
+                write_record(&mut writer, &Record::Trade { trade }).await?;
+                report.trades += 1;
+            }
+        }
+
+        let mut after = 0;
+        loop {
+            let facts = self
+                .export_page(
+                    "SELECT id, source, account_id, category, fact, message_id, channel_id,
+                            created_at, updated_at
+                     FROM user_facts WHERE id > ?1 ORDER BY id LIMIT ?2",
+                    after,
+                    |row| UserFact::try_from(row),
+                )
+                .await?;
+            let Some(last) = facts.last() else { break };
+            after = last.id;
+            for fact in facts {
+                write_record(&mut writer, &Record::UserFact { fact }).await?;
+                report.facts += 1;
+            }
+        }
+
+        let mut after = 0;
+        loop {
+            let mutes = self
+                .export_page(
+                    "SELECT id, source, channel_id, account_id, expires_at, created_at
+                     FROM mutes WHERE id > ?1 ORDER BY id LIMIT ?2",
+                    after,
+                    |row| Mute::try_from(row),
+                )
+                .await?;
+            let Some(last) = mutes.last() else { break };
+            after = last.id;
+            for mute in mutes {
+                write_record(&mut writer, &Record::Mute { mute }).await?;
+                report.mutes += 1;
+            }
+        }
+
+        // Summaries are identified by their channel, page through them by rowid
+        let mut after = 0;
+        loop {
+            let summaries = self
+                .export_page(
+                    "SELECT source, channel_id, summary, summarized_until, message_count,
+                            updated_at, rowid
+                     FROM channel_summaries WHERE rowid > ?1 ORDER BY rowid LIMIT ?2",
+                    after,
+                    |row| Ok((row.get::<_, i64>(6)?, ChannelSummary::try_from(row)?)),
+                )
+                .await?;
+            let Some((last, _)) = summaries.last() else {
+                break;
+            };
+            after = *last;
+            for (_, summary) in summaries {
+                write_record(&mut writer, &Record::ChannelSummary { summary }).await?;
+                report.summaries += 1;
+            }
+        }
+
+        let mut after = 0;
+        loop {
+            let decisions = self
+                .export_page(
+                    "SELECT id, source, channel_id, message_id, command, confidence, reason,
+                            created_at
+                     FROM attention_decisions WHERE id > ?1 ORDER BY id LIMIT ?2",
+                    after,
+                    |row| AttentionEntry::try_from(row),
+                )
+                .await?;
+            let Some(last) = decisions.last() else { break };
+            after = last.id;
+            for decision in decisions {
+                write_record(&mut writer, &Record::AttentionDecision { decision }).await?;
+                report.attention_decisions += 1;
+            }
+        }
+
+        writer.flush().await?;
+        info!(%report, "Exported knowledge base");
+        Ok(report)
+    }
+
+    /// Up to [EXPORT_PAGE_SIZE] rows of `query`, which selects the rows with an id greater
+    /// than `?1` in id order, limited to `?2` rows
+    async fn export_page<T: Send + 'static>(
+        &self,
+        query: &'static str,
+        after: i64,
+        map: fn(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
+    ) -> anyhow::Result<Vec<T>> {
+        self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(query)?;
+                let rows = stmt
+                    .query_map(rusqlite::params![after, EXPORT_PAGE_SIZE], map)?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(rows)
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Read the records of an export from `reader`, one per line, into the knowledge base.
+    /// Documents and messages replace the ones with the same id, accounts, channels, user
+    /// facts and channel summaries update the ones they match if they are more recent, and
+    /// trades, mutes and attention decisions already in the knowledge base are skipped. Lines
+    /// that are not valid records are skipped and reported.
+    ///
+    /// The records of the accounts erased with [KnowledgeBase::forget_account] are skipped, such
+    /// that importing an older export does not bring them back: their account, messages and the
+    /// replies of the agent to them, facts, mutes, the attention decisions made on their
+    /// messages and the summaries of the channels they talked in.
+    ///
+    /// Embeddings are imported if `keep_embeddings` is set and they have the dimension of the
+    /// embedding model. The documents and messages imported without one are embedded by the
+    /// embedding model once all the records are read, e.g.: to move to another embedding
+    /// model.
+    pub async fn import<R>(&self, reader: R, keep_embeddings: bool) -> anyhow::Result<ImportReport>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let mut report = ImportReport::default();
+        let mut documents = Vec::new();
+        let mut messages = Vec::new();
+        let mut erased = self.erased_accounts().await?;
+
+        let mut lines = reader.lines();
+        let mut line_number = 0;
+        while let Some(line) = lines.next_line().await? {
+            line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = match serde_json::from_str::<Record>(&line) {
+                Ok(record) => record,
+                Err(err) => {
+                    warn!(line = line_number, %err, "Skipping invalid record");
+                    report.invalid.push((line_number, err.to_string()));
+                    continue;
+                }
+            };
+
+            if erased.contains(&record) {
+                report.erased += 1;
+                continue;
+            }
+
+            match record {
+                Record::Account { account } => {
+                    self.import_account(account).await?;
+                    report.accounts += 1;
+                }
+                Record::Channel { channel } => {
+                    self.import_channel(channel).await?;
+                    report.channels += 1;
+                }
+                Record::Document {
+                    document,
+                    embedding,
+                } => {
+                    documents.push((document, embedding.filter(|_| keep_embeddings)));
+                }
+                Record::Message { message, embedding } => {
+                    messages.push((message, embedding.filter(|_| keep_embeddings)));
+                }
+                Record::Trade { trade } => {
+                    if self.import_trade(trade).await? {
+                        report.trades += 1;
+                    } else {
+                        report.duplicate_trades += 1;
+                    }
+                }
+                Record::UserFact { fact } => {
+                    self.import_user_fact(fact).await?;
+                    report.facts += 1;
+                }
+                Record::Mute { mute } => {
+                    self.import_mute(mute).await?;
+                    report.mutes += 1;
+                }
+                Record::ChannelSummary { summary } => {
+                    self.import_channel_summary(summary).await?;
+                    report.summaries += 1;
+                }
+                Record::AttentionDecision { decision } => {
+                    self.import_attention_decision(decision).await?;
+                    report.attention_decisions += 1;
+                }
+            }
+
+            if documents.len() >= IMPORT_BATCH_SIZE {
+                report.documents += documents.len();
+                report.embeddings += self
+                    .document_store
+                    .import_rows(std::mem::take(&mut documents))
+                    .await?;
+            }
+            if messages.len() >= IMPORT_BATCH_SIZE {
+                report.messages += messages.len();
+                report.embeddings += self
+                    .message_store
+                    .import_rows(std::mem::take(&mut messages))
+                    .await?;
+            }
+        }
+
+        if !documents.is_empty() {
+            report.documents += documents.len();
+            report.embeddings += self.document_store.import_rows(documents).await?;
+        }
+        if !messages.is_empty() {
+            report.messages += messages.len();
+            report.embeddings += self.message_store.import_rows(messages).await?;
+        }
+
+        report.embedded = self
+            .document_store
+            .embed_missing(&self.embedding_model)
+            .await?
+            + self
+                .message_store
+                .embed_missing(&self.embedding_model)
+                .await?;
+
+        info!(%report, "Imported knowledge base");
+        Ok(report)
+    }
+
+    /// Accounts erased with [KnowledgeBase::forget_account], according to the audit log
+    async fn erased_accounts(&self) -> anyhow::Result<Erased> {
+        self.conn
+            .call(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT DISTINCT source, subject FROM audit_log
+                     WHERE action = 'forget_account' AND source IS NOT NULL AND subject IS NOT NULL",
+                )?;
+                let accounts = stmt
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<HashSet<_>, _>>()?;
+                Ok(Erased {
+                    accounts,
+                    ..Default::default()
+                })
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    async fn import_account(&self, account: Account) -> anyhow::Result<()> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO accounts (source, source_id, name, created_at, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5)
+                     ON CONFLICT(source, source_id) DO UPDATE SET
+                         name = excluded.name,
+                         updated_at = excluded.updated_at
+                     WHERE datetime(excluded.updated_at) > datetime(accounts.updated_at)",
+                    rusqlite::params![
+                        account.source,
+                        account.source_id,
+                        account.name,
+                        account.created_at,
+                        account.updated_at
+                    ],
+                )?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    async fn import_channel(&self, channel: Channel) -> anyhow::Result<()> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO channels (source, channel_id, channel_type, name, created_at, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                     ON CONFLICT(source, channel_id) DO UPDATE SET
+                         channel_type = excluded.channel_type,
+                         name = COALESCE(excluded.name, channels.name),
+                         updated_at = excluded.updated_at
+                     WHERE datetime(excluded.updated_at) > datetime(channels.updated_at)",
+                    rusqlite::params![
+                        channel.source,
+                        channel.channel_id,
+                        channel.channel_type,
+                        channel.name,
+                        channel.created_at,
+                        channel.updated_at
+                    ],
+                )?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Insert a trade unless a trade of the same wallet with the same signature and date was
+    /// already stored. Returns whether it was inserted.
+    async fn import_trade(&self, trade: Trade) -> anyhow::Result<bool> {
+        self.conn
+            .call(move |conn| {
+                let inserted = conn.execute(
+                    "INSERT INTO trade
+                     (wallet_address, action, token_address, amount, reason, created_at, signature)
+                     SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7
+                     WHERE NOT EXISTS (
+                         SELECT 1 FROM trade
+                         WHERE wallet_address = ?1 AND signature = ?7
+                             AND datetime(created_at) = datetime(?6)
+                     )",
+                    rusqlite::params![
+                        trade.wallet_address,
+                        trade.action.as_str(),
+                        trade.token_address,
+                        trade.amount,
+                        trade.reason,
+                        trade.created_at,
+                        trade.signature
+                    ],
+                )?;
+                Ok(inserted > 0)
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    async fn import_user_fact(&self, fact: UserFact) -> anyhow::Result<()> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO user_facts
+                     (source, account_id, category, fact, message_id, channel_id, created_at, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                     ON CONFLICT (source, account_id, fact) DO UPDATE SET
+                         category = excluded.category,
+                         message_id = excluded.message_id,
+                         channel_id = excluded.channel_id,
+                         updated_at = excluded.updated_at
+                     WHERE datetime(excluded.updated_at) > datetime(user_facts.updated_at)",
+                    rusqlite::params![
+                        fact.source,
+                        fact.account_id,
+                        fact.category,
+                        fact.fact,
+                        fact.message_id,
+                        fact.channel_id,
+                        fact.created_at,
+                        fact.updated_at
+                    ],
+                )?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Insert a mute unless the same mute was already stored
+    async fn import_mute(&self, mute: Mute) -> anyhow::Result<()> {
+        self.conn
+            .call(move |conn| {
+                // Expiry dates are compared to CURRENT_TIMESTAMP, store them in its format
+                conn.execute(
+                    "INSERT INTO mutes (source, channel_id, account_id, expires_at, created_at)
+                     SELECT ?1, ?2, ?3, datetime(?4), ?5
+                     WHERE NOT EXISTS (
+                         SELECT 1 FROM mutes
+                         WHERE source = ?1 AND channel_id = ?2 AND account_id IS ?3
+                             AND datetime(created_at) = datetime(?5)
+                     )",
+                    rusqlite::params![
+                        mute.source,
+                        mute.channel_id,
+                        mute.account_id,
+                        mute.expires_at,
+                        mute.created_at
+                    ],
+                )?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Replace the summary of the channel unless it summarizes more recent messages
+    async fn import_channel_summary(&self, summary: ChannelSummary) -> anyhow::Result<()> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO channel_summaries
+                     (source, channel_id, summary, summarized_until, message_count, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                     ON CONFLICT (source, channel_id) DO UPDATE SET
+                         summary = excluded.summary,
+                         summarized_until = excluded.summarized_until,
+                         message_count = excluded.message_count,
+                         updated_at = excluded.updated_at
+                     WHERE excluded.summarized_until > channel_summaries.summarized_until",
+                    rusqlite::params![
+                        summary.source,
+                        summary.channel_id,
+                        summary.summary,
+                        // Compared to the creation dates of the messages, in RFC 3339
+                        summary.summarized_until.to_rfc3339(),
+                        summary.message_count,
+                        summary.updated_at
+                    ],
+                )?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Insert an attention decision unless the decision on the same message at the same
+    /// date was already stored
+    async fn import_attention_decision(&self, decision: AttentionEntry) -> anyhow::Result<()> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO attention_decisions
+                     (source, channel_id, message_id, command, confidence, reason, created_at)
+                     SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7
+                     WHERE NOT EXISTS (
+                         SELECT 1 FROM attention_decisions
+                         WHERE source = ?1 AND message_id = ?3
+                             AND datetime(created_at) = datetime(?7)
+                     )",
+                    rusqlite::params![
+                        decision.source,
+                        decision.channel_id,
+                        decision.message_id,
+                        decision.command,
+                        decision.confidence,
+                        decision.reason,
+                        decision.created_at
+                    ],
+                )?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+}
+
+/// What [KnowledgeBase::forget_account] erased, by source and id, to skip the records of an
+/// import that belong to erased accounts
+#[derive(Default)]
+struct Erased {
+    accounts: HashSet<(String, String)>,
+    /// Messages of the erased accounts met so far, and the replies of the agent to them
+    messages: HashSet<(String, String)>,
+    /// Channels the erased accounts talked in
+    channels: HashSet<(String, String)>,
+}
+
+impl Erased {
+    /// Whether the record belongs to an erased account. Records are read in the order of an
+    /// export, messages before the records referring to them.
+    fn contains(&mut self, record: &Record) -> bool {
+        let key = |source: &str, id: &str| (source.to_string(), id.to_string());
+
+        match record {
+            Record::Account { account } => self
+                .accounts
+                .contains(&key(&account.source, &account.source_id)),
+            Record::Message { message, .. } => {
+                let source = message.source.as_str();
+                let erased = if self.accounts.contains(&key(source, &message.account_id)) {
+                    self.channels.insert(key(source, &message.channel_id));
+                    true
+                } else {
+                    message.role == "assistant"
+                        && message
+                            .reply_to
+                            .as_ref()
+                            .is_some_and(|id| self.messages.contains(&key(source, id)))
+                };
+                if erased {
+                    self.messages.insert(key(source, &message.id));
+                }
+                erased
+            }
+            Record::UserFact { fact } => {
+                self.accounts.contains(&key(&fact.source, &fact.account_id))
+            }
+            Record::Mute { mute } => mute
+                .account_id
+                .as_ref()
+                .is_some_and(|account_id| self.accounts.contains(&key(&mute.source, account_id))),
+            Record::ChannelSummary { summary } => self
+                .channels
+                .contains(&key(&summary.source, &summary.channel_id)),
+            Record::AttentionDecision { decision } => self
+                .messages
+                .contains(&key(&decision.source, &decision.message_id)),
+            Record::Channel { .. } | Record::Document { .. } | Record::Trade { .. } => false,
+        }
+    }
+}
+
+fn trade_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Trade> {
+    Ok(Trade {
+        id: row.get(0)?,
+        wallet_address: row.get(1)?,
+        action: TradeAction::from_str(&row.get::<_, String>(2)?).unwrap_or(TradeAction::Hold),
+        token_address: row.get(3)?,
+        amount: row.get(4)?,
+        reason: row.get(5)?,
+        created_at: row.get(6)?,
+        signature: row.get(7)?,
+    })
+}
+
+/// Write a record of an export as a line of JSON
+async fn write_record<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    record: &Record,
+) -> anyhow::Result<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    Ok(())
+}
+
+/// Record an erasure in the audit log
+fn audit(
+    tx: &rusqlite::Transaction<'_>,
+    action: &str,
+    source: &str,
+    subject: &str,
+    details: &serde_json::Value,
+) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT INTO audit_log (action, source, subject, details, created_at)
+         VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP)",
+        rusqlite::params![action, source, subject, details.to_string()],
+    )?;
+    Ok(())
+}
@@ -1,4 +1,5 @@
-#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Source {
     Discord,
     Telegram,
@@ -30,7 +31,8 @@ impl Source {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ChannelType {
     DirectMessage,
     Text,
@@ -59,6 +61,45 @@ impl ChannelType {
     }
 }
 
+/// Conversion of [Source] to and from its text in the database, for `#[column(with = ...)]`
+pub(crate) mod source_column {
+    use rusqlite::types::{FromSqlError, FromSqlResult, ValueRef};
+
+    use super::Source;
+    use crate::knowledge::ConversionError;
+
+    pub fn to_sql(source: &Source) -> String {
+        source.as_str().to_string()
+    }
+
+    pub fn from_sql(value: ValueRef<'_>) -> FromSqlResult<Source> {
+        Source::from_str(value.as_str()?).ok_or_else(|| {
+            FromSqlError::Other(Box::new(ConversionError("Invalid source".to_string())))
+        })
+    }
+}
+
+/// Conversion of [ChannelType] to and from its text in the database, for
+/// `#[column(with = ...)]`
+pub(crate) mod channel_type_column {
+    use rusqlite::types::{FromSqlError, FromSqlResult, ValueRef};
+
+    use super::ChannelType;
+    use crate::knowledge::ConversionError;
+
+    pub fn to_sql(channel_type: &ChannelType) -> String {
+        channel_type.as_str().to_string()
+    }
+
+    pub fn from_sql(value: ValueRef<'_>) -> FromSqlResult<ChannelType> {
+        ChannelType::from_str(value.as_str()?).ok_or_else(|| {
+            FromSqlError::Other(Box::new(ConversionError(
+                "Invalid channel type".to_string(),
+            )))
+        })
+    }
+}
+
 pub trait MessageMetadata {
     fn id(&self) -> String;
     fn source_id(&self) -> String;
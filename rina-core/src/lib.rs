@@ -17,5 +17,12 @@ pub mod agent;
 pub mod attention;
 pub mod character;
 pub mod clients;
+pub mod history;
+pub mod ingest;
 pub mod knowledge;
-pub mod loaders;
\ No newline at end of file
+pub mod loaders;
+pub mod memory;
+pub mod providers;
+pub mod retention;
+pub mod spend;
+pub mod summary;
\ No newline at end of file
@@ -0,0 +1,327 @@
+use chrono::{DateTime, Utc};
+use rig::{
+    completion::{self, CompletionModel},
+    embeddings::EmbeddingModel,
+    extractor::ExtractorBuilder,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tracing::{debug, error, warn};
+
+use crate::{
+    attention::{normalize, words},
+    history,
+    knowledge::{self, KnowledgeBase, Source, UserFact},
+};
+
+const RECALL_COMMAND: &str = "/memory";
+const FORGET_COMMAND: &str = "/forget";
+
+const RECALL_PHRASES: [&str; 3] = [
+    "what do you remember about me",
+    "what do you know about me",
+    "what have you learned about me",
+];
+
+const FORGET_PHRASES: [&str; 4] = [
+    "forget me",
+    "forget about me",
+    "forget everything about me",
+    "forget what you know about me",
+];
+
+const CONFIRM_PHRASES: [&str; 4] = ["yes", "y", "confirm", "yes forget me"];
+
+const CANCEL_PHRASES: [&str; 3] = ["no", "n", "cancel"];
+
+/// How long a request to forget a user waits for its confirmation
+const FORGET_CONFIRMATION_MINUTES: i64 = 5;
+
+/// Maximum number of facts about a user given to the agent
+const MAX_FACTS: usize = 20;
+
+/// Number of messages preceding a message given to the model to extract facts from it
+const EXTRACTION_HISTORY_MESSAGES: usize = 6;
+
+/// Minimum number of characters of a message to extract facts from it, shorter messages
+/// hardly ever reveal any
+const MIN_EXTRACTION_LENGTH: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FactCategory {
+    Name,
+    Preference,
+    Wallet,
+    Topic,
+    Other,
+}
+
+impl FactCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FactCategory::Name => "name",
+            FactCategory::Preference => "preference",
+            FactCategory::Wallet => "wallet",
+            FactCategory::Topic => "topic",
+            FactCategory::Other => "other",
+        }
+    }
+}
+
+/// A durable fact about the author of a message
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct ExtractedFact {
+    /// name: how the user is called or wants to be called.
+    /// preference: something the user likes, dislikes or wants.
+    /// wallet: a wallet address of the user.
+    /// topic: a topic the user keeps coming back to.
+    /// other: any other durable fact about the user.
+    pub category: FactCategory,
+    /// The fact, as one short sentence about the user (e.g.: "Prefers to be called Alex")
+    pub fact: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
+struct ExtractedFacts {
+    /// Durable facts revealed by the latest message, empty if there are none
+    #[serde(default)]
+    facts: Vec<ExtractedFact>,
+}
+
+/// Source and id of an account
+type AccountKey = (Source, String);
+
+/// Request of a user about what the agent remembers about them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryCommand {
+    Recall,
+    /// Asks to forget the user, which they have to confirm
+    Forget,
+    ConfirmForget,
+    CancelForget,
+}
+
+/// Long-term memory of the people the agent talks to: durable facts are extracted from
+/// their messages and given back to the agent when they talk again.
+#[derive(Clone)]
+pub struct Memory<M: CompletionModel, E: EmbeddingModel + 'static> {
+    completion_model: M,
+    knowledge: KnowledgeBase<E>,
+    /// Accounts that asked to be forgotten, by source and account id, along with when their
+    /// request expires unless confirmed
+    pending_forget: Arc<Mutex<HashMap<AccountKey, DateTime<Utc>>>>,
+}
+
+impl<M: CompletionModel, E: EmbeddingModel + 'static> Memory<M, E> {
+    pub fn new(completion_model: M, knowledge: KnowledgeBase<E>) -> Self {
+        Self {
+            completion_model,
+            knowledge,
+            pending_forget: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Whether the message asks what the agent remembers about its author, to forget them,
+    /// or answers a request to forget them. Only messages made of a command or phrase alone
+    /// are commands, e.g.: "forget me" is but "don't forget me" is not.
+    pub fn command(&self, message: &knowledge::Message) -> Option<MemoryCommand> {
+        let content = normalize(&message.content);
+        // Leading mentions of the agent, e.g.: "@rina forget me"
+        let content = content
+            .split_whitespace()
+            .skip_while(|word| word.starts_with('@') || word.starts_with("<@"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let phrase = words(&content).join(" ");
+        let is_command = |command: &str| {
+            content
+                .strip_prefix(command)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('@'))
+        };
+
+        if self.forget_pending(message) {
+            if CONFIRM_PHRASES.contains(&phrase.as_str()) {
+                return Some(MemoryCommand::ConfirmForget);
+            }
+            if CANCEL_PHRASES.contains(&phrase.as_str()) {
+                return Some(MemoryCommand::CancelForget);
+            }
+        }
+
+        if is_command(FORGET_COMMAND) || FORGET_PHRASES.contains(&phrase.as_str()) {
+            Some(MemoryCommand::Forget)
+        } else if is_command(RECALL_COMMAND) || RECALL_PHRASES.contains(&phrase.as_str()) {
+            Some(MemoryCommand::Recall)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the author of `message` asked to be forgotten and has yet to confirm it
+    fn forget_pending(&self, message: &knowledge::Message) -> bool {
+        self.pending_forget
+            .lock()
+            .unwrap()
+            .get(&(message.source.clone(), message.account_id.clone()))
+            .is_some_and(|expires_at| *expires_at > Utc::now())
+    }
+
+    /// Carry out a memory command for the author of `message`, returning the reply to send
+    pub async fn handle(&self, command: MemoryCommand, message: &knowledge::Message) -> String {
+        let account = (message.source.clone(), message.account_id.clone());
+
+        match command {
+            MemoryCommand::Recall => match self.recall(message).await.as_slice() {
+                [] => "I don't remember anything about you yet.".to_string(),
+                facts => format!(
+                    "Here is what I remember about you:\n{}",
+                    facts
+                        .iter()
+                        .map(|fact| format!("- {}", fact.fact))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                ),
+            },
+            MemoryCommand::Forget => {
+                let expires_at =
+                    Utc::now() + chrono::Duration::minutes(FORGET_CONFIRMATION_MINUTES);
+                self.pending_forget
+                    .lock()
+                    .unwrap()
+                    .insert(account, expires_at);
+                format!(
+                    "Do you want me to forget everything I remember about you? Reply \"yes\" \
+                        within {FORGET_CONFIRMATION_MINUTES} minutes to confirm, or \"no\" to \
+                        cancel."
+                )
+            }
+            MemoryCommand::CancelForget => {
+                self.pending_forget.lock().unwrap().remove(&account);
+                "Alright, I won't forget you.".to_string()
+            }
+            MemoryCommand::ConfirmForget => {
+                self.pending_forget.lock().unwrap().remove(&account);
+                self.forget(message).await
+            }
+        }
+    }
+
+    async fn forget(&self, message: &knowledge::Message) -> String {
+        match self
+            .knowledge
+            .forget_user_facts(&message.source, &message.account_id)
+            .await
+        {
+            Ok(0) => {
+                "There was nothing to forget, I don't remember anything about you.".to_string()
+            }
+            Ok(count) => format!("Done, I forgot the {count} things I remembered about you."),
+            Err(err) => {
+                error!(?err, "Failed to forget user facts");
+                "Sorry, I couldn't forget what I know about you, please try again later."
+                    .to_string()
+            }
+        }
+    }
+
+    /// Facts remembered about the author of `message`, most recent first
+    pub async fn recall(&self, message: &knowledge::Message) -> Vec<UserFact> {
+        match self
+            .knowledge
+            .user_facts(&message.source, &message.account_id, MAX_FACTS)
+            .await
+        {
+            Ok(facts) => facts,
+            Err(err) => {
+                error!(?err, "Failed to fetch user facts");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Context document with the facts remembered about the author of `message`, if any
+    pub async fn context(&self, message: &knowledge::Message) -> Option<String> {
+        let facts = self.recall(message).await;
+        if facts.is_empty() {
+            return None;
+        }
+
+        Some(format!(
+            "What you remember about the author of the message you are replying to:\n{}",
+            facts
+                .iter()
+                .map(|fact| format!("- {} ({})", fact.fact, fact.category))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ))
+    }
+
+    /// Extract durable facts about the author of `message` and remember them, `history`
+    /// being the conversation preceding it. Returns the number of facts remembered.
+    ///
+    /// Extraction takes a request to the model, it is skipped for short messages.
+    pub async fn remember(
+        &self,
+        message: &knowledge::Message,
+        history: &[completion::Message],
+    ) -> usize {
+        if message.content.trim().chars().count() < MIN_EXTRACTION_LENGTH
+            || self.command(message).is_some()
+        {
+            return 0;
+        }
+
+        let extractor = ExtractorBuilder::<ExtractedFacts, M>::new(
+            self.completion_model.clone(),
+        )
+        .preamble(
+            "Extract durable facts about the author of the latest message: how they want to be \
+                called, their preferences, their wallet addresses and the topics they care about. \
+                Only extract facts the author states about themselves that will still be true in \
+                a few weeks. Ignore greetings, questions, opinions about the conversation and \
+                anything said about other people. Most messages do not reveal any such fact.",
+        )
+        .build();
+
+        let history = &history[history.len().saturating_sub(EXTRACTION_HISTORY_MESSAGES)..];
+        let text = history::latest_message_prompt(history, &message.content);
+
+        let facts = match extractor.extract(&text).await {
+            Ok(extracted) => extracted
+                .facts
+                .into_iter()
+                .filter(|fact| !fact.fact.trim().is_empty())
+                .collect::<Vec<_>>(),
+            Err(err) => {
+                warn!(?err, "Failed to extract user facts");
+                return 0;
+            }
+        };
+
+        if facts.is_empty() {
+            return 0;
+        }
+
+        debug!(
+            ?facts,
+            account_id = message.account_id,
+            "Extracted user facts"
+        );
+        let facts = facts
+            .into_iter()
+            .map(|fact| (fact.category.as_str(), fact.fact))
+            .collect();
+        match self.knowledge.store_user_facts(message, facts).await {
+            Ok(count) => count,
+            Err(err) => {
+                error!(?err, "Failed to store user facts");
+                0
+            }
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use rig::{
+    completion::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse},
+    embeddings::{Embedding, EmbeddingError, EmbeddingModel},
+    providers::{anthropic, cohere, gemini, ollama, openai, perplexity, xai},
+    usage::Usage,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProviderError {
+    #[error("{0} does not provide embedding models")]
+    EmbeddingsNotSupported(&'static str),
+
+    #[error("Unknown number of dimensions for embedding model {0}, it must be set explicitly")]
+    UnknownEmbeddingDimensions(String),
+}
+
+/// Client of one of the model providers supported by rig, such that the provider of
+/// each model used by the bot can be chosen at runtime.
+#[derive(Clone)]
+pub enum ProviderClient {
+    OpenAI(openai::Client),
+    Anthropic(anthropic::Client),
+    Gemini(gemini::Client),
+    Cohere(cohere::Client),
+    Perplexity(perplexity::Client),
+    Xai(xai::Client),
+    Ollama(ollama::Client),
+}
+
+impl ProviderClient {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::OpenAI(_) => "openai",
+            Self::Anthropic(_) => "anthropic",
+            Self::Gemini(_) => "gemini",
+            Self::Cohere(_) => "cohere",
+            Self::Perplexity(_) => "perplexity",
+            Self::Xai(_) => "xai",
+            Self::Ollama(_) => "ollama",
+        }
+    }
+
+    pub fn completion_model(&self, model: &str) -> AnyCompletionModel {
+        match self {
+            Self::OpenAI(client) => AnyCompletionModel::OpenAI(client.completion_model(model)),
+            Self::Anthropic(client) => {
+                AnyCompletionModel::Anthropic(client.completion_model(model))
+            }
+            Self::Gemini(client) => AnyCompletionModel::Gemini(client.completion_model(model)),
+            Self::Cohere(client) => AnyCompletionModel::Cohere(client.completion_model(model)),
+            Self::Perplexity(client) => {
+                AnyCompletionModel::Perplexity(client.completion_model(model))
+            }
+            Self::Xai(client) => AnyCompletionModel::Xai(client.completion_model(model)),
+            Self::Ollama(client) => AnyCompletionModel::Ollama(client.completion_model(model)),
+        }
+    }
+
+    /// Create an embedding model. The number of dimensions of the embeddings only needs to
+    /// be given for models that are unknown to rig.
+    pub fn embedding_model(
+        &self,
+        model: &str,
+        ndims: Option<usize>,
+    ) -> Result<AnyEmbeddingModel, ProviderError> {
+        let embedding_model = match (self, ndims) {
+            (Self::OpenAI(client), Some(ndims)) => {
+                AnyEmbeddingModel::OpenAI(client.embedding_model_with_ndims(model, ndims))
+            }
+            (Self::OpenAI(client), None) => {
+                AnyEmbeddingModel::OpenAI(client.embedding_model(model))
+            }
+            (Self::Gemini(client), Some(ndims)) => {
+                AnyEmbeddingModel::Gemini(client.embedding_model_with_ndims(model, ndims))
+            }
+            (Self::Gemini(client), None) => {
+                AnyEmbeddingModel::Gemini(client.embedding_model(model))
+            }
+            (Self::Cohere(client), Some(ndims)) => AnyEmbeddingModel::Cohere(
+                client.embedding_model_with_ndims(model, "search_document", ndims),
+            ),
+            (Self::Cohere(client), None) => {
+                AnyEmbeddingModel::Cohere(client.embedding_model(model, "search_document"))
+            }
+            (Self::Xai(client), Some(ndims)) => {
+                AnyEmbeddingModel::Xai(client.embedding_model_with_ndims(model, ndims))
+            }
+            (Self::Xai(client), None) => AnyEmbeddingModel::Xai(client.embedding_model(model)),
+            (Self::Ollama(client), Some(ndims)) => {
+                AnyEmbeddingModel::Ollama(client.embedding_model_with_ndims(model, ndims))
+            }
+            (Self::Ollama(client), None) => {
+                AnyEmbeddingModel::Ollama(client.embedding_model(model))
+            }
+            (Self::Anthropic(_) | Self::Perplexity(_), _) => {
+                return Err(ProviderError::EmbeddingsNotSupported(self.name()))
+            }
+        };
+
+        // The width of the knowledge base vector tables is the number of dimensions
+        if embedding_model.ndims() == 0 {
+            return Err(ProviderError::UnknownEmbeddingDimensions(model.to_string()));
+        }
+
+        Ok(embedding_model)
+    }
+}
+
+/// Completion model of any of the providers supported by rig
+#[derive(Clone)]
+pub enum AnyCompletionModel {
+    OpenAI(openai::CompletionModel),
+    Anthropic(anthropic::completion::CompletionModel),
+    Gemini(gemini::completion::CompletionModel),
+    Cohere(cohere::CompletionModel),
+    Perplexity(perplexity::CompletionModel),
+    Xai(xai::completion::CompletionModel),
+    Ollama(ollama::CompletionModel),
+}
+
+impl AnyCompletionModel {
+    /// Name of the model (e.g.: gpt-4o)
+    pub fn name(&self) -> &str {
+        match self {
+            Self::OpenAI(model) => &model.model,
+            Self::Anthropic(model) => &model.model,
+            Self::Gemini(model) => &model.model,
+            Self::Cohere(model) => &model.model,
+            Self::Perplexity(model) => &model.model,
+            Self::Xai(model) => &model.model,
+            Self::Ollama(model) => &model.model,
+        }
+    }
+}
+
+/// Raw response of an [AnyCompletionModel]
+#[derive(Debug)]
+pub enum AnyCompletionResponse {
+    OpenAI(openai::CompletionResponse),
+    Anthropic(anthropic::completion::CompletionResponse),
+    Gemini(gemini::completion::gemini_api_types::GenerateContentResponse),
+    Cohere(cohere::CompletionResponse),
+    Perplexity(perplexity::CompletionResponse),
+    Xai(xai::completion::xai_api_types::CompletionResponse),
+    Ollama(ollama::CompletionResponse),
+}
+
+fn wrap_response<R>(
+    response: CompletionResponse<R>,
+    wrap: impl FnOnce(R) -> AnyCompletionResponse,
+) -> CompletionResponse<AnyCompletionResponse> {
+    CompletionResponse {
+        choice: response.choice,
+        usage: response.usage,
+        raw_response: wrap(response.raw_response),
+    }
+}
+
+impl CompletionModel for AnyCompletionModel {
+    type Response = AnyCompletionResponse;
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<AnyCompletionResponse>, CompletionError> {
+        Ok(match self {
+            Self::OpenAI(model) => wrap_response(
+                model.completion(request).await?,
+                AnyCompletionResponse::OpenAI,
+            ),
+            Self::Anthropic(model) => wrap_response(
+                model.completion(request).await?,
+                AnyCompletionResponse::Anthropic,
+            ),
+            Self::Gemini(model) => wrap_response(
+                model.completion(request).await?,
+                AnyCompletionResponse::Gemini,
+            ),
+            Self::Cohere(model) => wrap_response(
+                model.completion(request).await?,
+                AnyCompletionResponse::Cohere,
+            ),
+            Self::Perplexity(model) => wrap_response(
+                model.completion(request).await?,
+                AnyCompletionResponse::Perplexity,
+            ),
+            Self::Xai(model) => {
+                wrap_response(model.completion(request).await?, AnyCompletionResponse::Xai)
+            }
+            Self::Ollama(model) => wrap_response(
+                model.completion(request).await?,
+                AnyCompletionResponse::Ollama,
+            ),
+        })
+    }
+}
+
+/// Embedding model of any of the providers supported by rig that provide embeddings
+#[derive(Clone)]
+pub enum AnyEmbeddingModel {
+    OpenAI(openai::EmbeddingModel),
+    Gemini(gemini::embedding::EmbeddingModel),
+    Cohere(cohere::EmbeddingModel),
+    Xai(xai::embedding::EmbeddingModel),
+    Ollama(ollama::EmbeddingModel),
+}
+
+impl AnyEmbeddingModel {
+    /// Name of the model (e.g.: text-embedding-3-large)
+    pub fn name(&self) -> &str {
+        match self {
+            Self::OpenAI(model) => &model.model,
+            Self::Gemini(model) => &model.model,
+            Self::Cohere(model) => &model.model,
+            Self::Xai(model) => &model.model,
+            Self::Ollama(model) => &model.model,
+        }
+    }
+}
+
+const fn min(values: &[usize]) -> usize {
+    let mut min = usize::MAX;
+    let mut i = 0;
+    while i < values.len() {
+        if values[i] < min {
+            min = values[i];
+        }
+        i += 1;
+    }
+    min
+}
+
+impl EmbeddingModel for AnyEmbeddingModel {
+    // The batch size must suit whichever provider is used
+    const MAX_DOCUMENTS: usize = min(&[
+        openai::EmbeddingModel::MAX_DOCUMENTS,
+        gemini::embedding::EmbeddingModel::MAX_DOCUMENTS,
+        cohere::EmbeddingModel::MAX_DOCUMENTS,
+        xai::embedding::EmbeddingModel::MAX_DOCUMENTS,
+        ollama::EmbeddingModel::MAX_DOCUMENTS,
+    ]);
+
+    fn ndims(&self) -> usize {
+        match self {
+            Self::OpenAI(model) => model.ndims(),
+            Self::Gemini(model) => model.ndims(),
+            Self::Cohere(model) => model.ndims(),
+            Self::Xai(model) => model.ndims(),
+            Self::Ollama(model) => model.ndims(),
+        }
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        Ok(self.embed_texts_with_usage(texts).await?.0)
+    }
+
+    async fn embed_texts_with_usage(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<(Vec<Embedding>, Usage), EmbeddingError> {
+        match self {
+            Self::OpenAI(model) => model.embed_texts_with_usage(texts).await,
+            Self::Gemini(model) => model.embed_texts_with_usage(texts).await,
+            Self::Cohere(model) => model.embed_texts_with_usage(texts).await,
+            Self::Xai(model) => model.embed_texts_with_usage(texts).await,
+            Self::Ollama(model) => model.embed_texts_with_usage(texts).await,
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use rig::embeddings::EmbeddingModel;
+use tracing::{error, info};
+
+use crate::knowledge::{KnowledgeBase, Source};
+
+/// How often the messages are pruned by default
+pub const DEFAULT_PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long the messages of a channel are kept, and how many of them.
+///
+/// A policy applies to all the channels, to the channels of a source or to a single channel.
+/// The limits of the most specific policies override the limits of the broader ones, e.g.:
+/// a channel keeping its 1000 most recent messages still drops the messages older than the
+/// maximum age of its source.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RetentionPolicy {
+    /// Source of the channels the policy applies to, all the sources when unset
+    pub source: Option<Source>,
+    /// Channel the policy applies to, all the channels of the source when unset
+    pub channel_id: Option<String>,
+    /// Messages older than this are deleted
+    pub max_age: Option<chrono::Duration>,
+    /// Only this many of the most recent messages are kept
+    pub max_messages: Option<usize>,
+}
+
+impl RetentionPolicy {
+    /// Policy applying to all the channels
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Policy applying to the channels of `source`
+    pub fn source(source: Source) -> Self {
+        Self {
+            source: Some(source),
+            ..Default::default()
+        }
+    }
+
+    /// Policy applying to a single channel
+    pub fn channel(source: Source, channel_id: &str) -> Self {
+        Self {
+            source: Some(source),
+            channel_id: Some(channel_id.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn max_age(mut self, max_age: chrono::Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn max_messages(mut self, max_messages: usize) -> Self {
+        self.max_messages = Some(max_messages);
+        self
+    }
+
+    /// How specific the policy is to the channel, `None` if it does not apply to it
+    fn specificity(&self, source: &Source, channel_id: &str) -> Option<u8> {
+        match (&self.source, &self.channel_id) {
+            (None, None) => Some(0),
+            (Some(s), None) if s == source => Some(1),
+            (Some(s), Some(c)) if s == source && c == channel_id => Some(2),
+            _ => None,
+        }
+    }
+}
+
+/// Deletes the messages, and their embeddings, that fell out of the retention policies of
+/// their channel. Channels without any policy keep their messages forever.
+///
+/// ```rust,ignore
+/// let policies = vec![
+///     RetentionPolicy::all().max_age(chrono::Duration::days(90)),
+///     RetentionPolicy::channel(Source::Discord, "1234").max_messages(1000),
+/// ];
+/// Pruner::new(knowledge, policies).spawn();
+/// ```
+#[derive(Clone)]
+pub struct Pruner<E: EmbeddingModel + 'static> {
+    knowledge: KnowledgeBase<E>,
+    policies: Vec<RetentionPolicy>,
+    interval: Duration,
+}
+
+impl<E: EmbeddingModel + 'static> Pruner<E> {
+    pub fn new(knowledge: KnowledgeBase<E>, policies: Vec<RetentionPolicy>) -> Self {
+        Self {
+            knowledge,
+            policies,
+            interval: DEFAULT_PRUNE_INTERVAL,
+        }
+    }
+
+    /// Set how often the messages are pruned by [Pruner::spawn]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Limits applying to a channel, merged from the policies that apply to it
+    pub fn policy(&self, source: &Source, channel_id: &str) -> RetentionPolicy {
+        let mut policies = self
+            .policies
+            .iter()
+            .filter_map(|policy| Some((policy.specificity(source, channel_id)?, policy)))
+            .collect::<Vec<_>>();
+        policies.sort_by_key(|(specificity, _)| *specificity);
+
+        policies.into_iter().fold(
+            RetentionPolicy::channel(source.clone(), channel_id),
+            |merged, (_, policy)| RetentionPolicy {
+                max_age: policy.max_age.or(merged.max_age),
+                max_messages: policy.max_messages.or(merged.max_messages),
+                ..merged
+            },
+        )
+    }
+
+    /// Delete the messages of every channel that fell out of its policy. Returns the number
+    /// of messages deleted.
+    pub async fn prune(&self) -> anyhow::Result<usize> {
+        let mut deleted = 0;
+        for (source, channel_id) in self.knowledge.message_channels().await? {
+            let policy = self.policy(&source, &channel_id);
+            if policy.max_age.is_none() && policy.max_messages.is_none() {
+                continue;
+            }
+
+            let before = policy.max_age.map(|max_age| chrono::Utc::now() - max_age);
+            deleted += self
+                .knowledge
+                .prune_channel_messages(&source, &channel_id, before, policy.max_messages)
+                .await?;
+        }
+
+        if deleted > 0 {
+            info!(deleted, "Pruned messages");
+        }
+        Ok(deleted)
+    }
+
+    /// Prune the messages now and then every [Pruner::interval] in the background
+    pub fn spawn(self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                if let Err(err) = self.prune().await {
+                    error!(?err, "Failed to prune messages");
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,302 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{NaiveDate, Utc};
+use rig::{
+    completion::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse},
+    embeddings::{Embedding, EmbeddingError, EmbeddingModel},
+    usage::{PriceTable, Usage},
+};
+use tokio_rusqlite::Connection;
+use tracing::{info, warn};
+
+use crate::knowledge;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Daily spend limit of ${limit:.2} reached for client {client} (spent ${spent:.4} today)")]
+pub struct SpendLimitExceeded {
+    pub client: String,
+    pub limit: f64,
+    pub spent: f64,
+}
+
+#[derive(Clone, Debug)]
+struct DailySpend {
+    date: NaiveDate,
+    usage: Usage,
+    cost: f64,
+}
+
+/// Tracks the token usage and cost of the model requests made by each client (e.g.: twitter,
+/// discord, telegram, direct) and enforces an optional daily spend cap per client.
+/// Spend is reset at midnight UTC.
+///
+/// Spend is only kept in memory, such that a restart resets the caps, unless it is persisted
+/// with [SpendTracker::persist].
+#[derive(Clone, Default)]
+pub struct SpendTracker {
+    prices: PriceTable,
+    daily_limits: HashMap<String, f64>,
+    spend: Arc<Mutex<HashMap<String, DailySpend>>>,
+    conn: Option<Connection>,
+}
+
+impl SpendTracker {
+    pub fn new(prices: PriceTable) -> Self {
+        Self {
+            prices,
+            ..Default::default()
+        }
+    }
+
+    /// Cap the daily spend of a client, in USD
+    pub fn with_daily_limit(mut self, client: &str, limit: f64) -> Self {
+        self.daily_limits.insert(client.to_string(), limit);
+        self
+    }
+
+    /// Keep the daily spend of each client in the `daily_spend` table, such that the daily
+    /// spend caps hold across restarts. The spend already recorded today is loaded, the
+    /// tracker should be persisted before it is cloned into the models it meters.
+    ///
+    /// The table is created by the migrations of the knowledge base, which are applied here
+    /// as the tracker is persisted before the knowledge base is opened.
+    pub async fn persist(mut self, conn: Connection) -> Result<Self, tokio_rusqlite::Error> {
+        let today = Utc::now().date_naive();
+        let recorded = conn
+            .call(move |conn| {
+                knowledge::migrate(conn)?;
+                let mut stmt = conn.prepare(
+                    "SELECT client, input_tokens, output_tokens, total_tokens, cost
+                     FROM daily_spend WHERE date = ?1",
+                )?;
+                let recorded = stmt
+                    .query_map([today.to_string()], |row| {
+                        let usage = Usage {
+                            input_tokens: row.get::<_, i64>(1)? as u64,
+                            output_tokens: row.get::<_, i64>(2)? as u64,
+                            total_tokens: row.get::<_, i64>(3)? as u64,
+                        };
+                        Ok((row.get::<_, String>(0)?, usage, row.get::<_, f64>(4)?))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(recorded)
+            })
+            .await?;
+
+        let mut spend = self.spend.lock().unwrap();
+        for (client, usage, cost) in recorded {
+            let daily = spend.entry(client).or_insert(DailySpend {
+                date: today,
+                usage: Usage::default(),
+                cost: 0.0,
+            });
+            if daily.date == today {
+                daily.usage += usage;
+                daily.cost += cost;
+            }
+        }
+        drop(spend);
+
+        self.conn = Some(conn);
+        Ok(self)
+    }
+
+    /// Cost of the requests made by a client today, in USD
+    pub fn spent_today(&self, client: &str) -> f64 {
+        let today = Utc::now().date_naive();
+        self.spend
+            .lock()
+            .unwrap()
+            .get(client)
+            .filter(|spend| spend.date == today)
+            .map_or(0.0, |spend| spend.cost)
+    }
+
+    /// Check that a client is still under its daily spend limit
+    pub fn check(&self, client: &str) -> Result<(), SpendLimitExceeded> {
+        match self.daily_limits.get(client) {
+            Some(&limit) => {
+                let spent = self.spent_today(client);
+                if spent >= limit {
+                    return Err(SpendLimitExceeded {
+                        client: client.to_string(),
+                        limit,
+                        spent,
+                    });
+                }
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Record the usage of a model by a client and return its cost, in USD
+    pub fn record(&self, client: &str, model: &str, usage: Usage) -> f64 {
+        let cost = match self.prices.cost(model, &usage) {
+            Some(cost) => cost,
+            None => {
+                warn!(
+                    model,
+                    "No price known for model, its usage is not counted towards spend"
+                );
+                0.0
+            }
+        };
+
+        let today = Utc::now().date_naive();
+        let mut spend = self.spend.lock().unwrap();
+        let daily = spend.entry(client.to_string()).or_insert(DailySpend {
+            date: today,
+            usage: Usage::default(),
+            cost: 0.0,
+        });
+        if daily.date != today {
+            *daily = DailySpend {
+                date: today,
+                usage: Usage::default(),
+                cost: 0.0,
+            };
+        }
+        daily.usage += usage;
+        daily.cost += cost;
+
+        info!(
+            client,
+            model,
+            input_tokens = usage.input_tokens,
+            output_tokens = usage.output_tokens,
+            cost,
+            daily_tokens = daily.usage.total_tokens,
+            daily_cost = daily.cost,
+            "Model usage"
+        );
+
+        if let Some(conn) = &self.conn {
+            let conn = conn.clone();
+            let client = client.to_string();
+            tokio::spawn(async move {
+                let stored = conn
+                    .call(move |conn| {
+                        conn.execute(
+                            "INSERT INTO daily_spend
+                             (client, date, input_tokens, output_tokens, total_tokens, cost)
+                             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                             ON CONFLICT (client, date) DO UPDATE SET
+                                 input_tokens = input_tokens + excluded.input_tokens,
+                                 output_tokens = output_tokens + excluded.output_tokens,
+                                 total_tokens = total_tokens + excluded.total_tokens,
+                                 cost = cost + excluded.cost",
+                            rusqlite::params![
+                                client,
+                                today.to_string(),
+                                usage.input_tokens as i64,
+                                usage.output_tokens as i64,
+                                usage.total_tokens as i64,
+                                cost
+                            ],
+                        )?;
+                        Ok(())
+                    })
+                    .await;
+                if let Err(err) = stored {
+                    warn!(?err, "Failed to persist model usage");
+                }
+            });
+        }
+
+        cost
+    }
+}
+
+/// Completion model wrapper that records the usage of every request with a [SpendTracker]
+/// and refuses new requests once the client is over its daily spend limit.
+#[derive(Clone)]
+pub struct MeteredCompletionModel<M: CompletionModel> {
+    model: M,
+    model_name: String,
+    client: String,
+    tracker: SpendTracker,
+}
+
+impl<M: CompletionModel> MeteredCompletionModel<M> {
+    pub fn new(model: M, model_name: &str, client: &str, tracker: SpendTracker) -> Self {
+        Self {
+            model,
+            model_name: model_name.to_string(),
+            client: client.to_string(),
+            tracker,
+        }
+    }
+}
+
+impl<M: CompletionModel> CompletionModel for MeteredCompletionModel<M> {
+    type Response = M::Response;
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<M::Response>, CompletionError> {
+        self.tracker
+            .check(&self.client)
+            .map_err(|err| CompletionError::RequestError(err.into()))?;
+
+        let response = self.model.completion(request).await?;
+        self.tracker
+            .record(&self.client, &self.model_name, response.usage);
+
+        Ok(response)
+    }
+}
+
+/// Embedding model wrapper that records the usage of every request with a [SpendTracker]
+/// and refuses new requests once the client is over its daily spend limit.
+#[derive(Clone)]
+pub struct MeteredEmbeddingModel<E: EmbeddingModel> {
+    model: E,
+    model_name: String,
+    client: String,
+    tracker: SpendTracker,
+}
+
+impl<E: EmbeddingModel> MeteredEmbeddingModel<E> {
+    pub fn new(model: E, model_name: &str, client: &str, tracker: SpendTracker) -> Self {
+        Self {
+            model,
+            model_name: model_name.to_string(),
+            client: client.to_string(),
+            tracker,
+        }
+    }
+}
+
+impl<E: EmbeddingModel> EmbeddingModel for MeteredEmbeddingModel<E> {
+    const MAX_DOCUMENTS: usize = E::MAX_DOCUMENTS;
+
+    fn ndims(&self) -> usize {
+        self.model.ndims()
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        Ok(self.embed_texts_with_usage(texts).await?.0)
+    }
+
+    async fn embed_texts_with_usage(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<(Vec<Embedding>, Usage), EmbeddingError> {
+        self.tracker
+            .check(&self.client)
+            .map_err(|err| EmbeddingError::DocumentError(err.into()))?;
+
+        let (embeddings, usage) = self.model.embed_texts_with_usage(texts).await?;
+        self.tracker.record(&self.client, &self.model_name, usage);
+
+        Ok((embeddings, usage))
+    }
+}
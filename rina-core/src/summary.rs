@@ -0,0 +1,187 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use rig::{
+    agent::AgentBuilder,
+    completion::{CompletionModel, Prompt},
+    embeddings::EmbeddingModel,
+};
+use tracing::{debug, error, info};
+
+use crate::{
+    history,
+    knowledge::{self, KnowledgeBase, Source},
+};
+
+/// Number of new messages that triggers an update of the summary of a channel
+const SUMMARY_BATCH_MESSAGES: usize = 20;
+
+/// Maximum number of messages folded into the summary at once
+const MAX_SUMMARY_MESSAGES: usize = 100;
+
+const SUMMARY_PREAMBLE: &str = "You maintain the summary of a long running group conversation. \
+    Update the current summary with the new messages: keep who is who, the topics discussed, \
+    the decisions made, the questions left open and anything the participants will likely \
+    refer to later. Drop small talk and details that no longer matter. Write at most 300 words \
+    of plain prose, in the language of the conversation, and only reply with the summary.";
+
+/// Keeps a rolling summary of the messages of each channel, such that the agent keeps track
+/// of conversations longer than the messages it is given verbatim.
+///
+/// The summary covers the messages of a channel older than the chat history given to the
+/// agent, which is made of up to `history_messages` messages trimmed to `history_tokens`, and
+/// is updated in the background once enough messages fell out of that history.
+#[derive(Clone)]
+pub struct Summarizer<M: CompletionModel, E: EmbeddingModel + 'static> {
+    completion_model: M,
+    knowledge: KnowledgeBase<E>,
+    history_messages: i64,
+    history_tokens: usize,
+    /// Channels whose summary is being updated
+    updating: Arc<Mutex<HashSet<(&'static str, String)>>>,
+}
+
+impl<M: CompletionModel, E: EmbeddingModel + 'static> Summarizer<M, E> {
+    pub fn new(
+        completion_model: M,
+        knowledge: KnowledgeBase<E>,
+        history_messages: i64,
+        history_tokens: usize,
+    ) -> Self {
+        Self {
+            completion_model,
+            knowledge,
+            history_messages,
+            history_tokens,
+            updating: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Set the number of tokens of the chat history given to the agent
+    pub fn history_tokens(mut self, history_tokens: usize) -> Self {
+        self.history_tokens = history_tokens;
+        self
+    }
+
+    /// Context document with the summary of the channel of `message`, if any
+    pub async fn context(&self, message: &knowledge::Message) -> Option<String> {
+        match self
+            .knowledge
+            .channel_summary(&message.source, &message.channel_id)
+            .await
+        {
+            Ok(summary) => summary.map(|summary| {
+                format!(
+                    "Summary of the earlier conversation in this channel:\n{}",
+                    summary.summary
+                )
+            }),
+            Err(err) => {
+                error!(?err, "Failed to fetch channel summary");
+                None
+            }
+        }
+    }
+
+    /// Fold the messages of the channel that fell out of the chat history into its summary,
+    /// if there are enough of them. Returns the number of messages summarized.
+    pub async fn update(&self, source: &Source, channel_id: &str) -> anyhow::Result<usize> {
+        let key = (source.as_str(), channel_id.to_string());
+        if !self.updating.lock().unwrap().insert(key.clone()) {
+            debug!(channel_id, "Channel summary is already being updated");
+            return Ok(0);
+        }
+
+        let result = self.summarize(source, channel_id).await;
+        self.updating.lock().unwrap().remove(&key);
+
+        result
+    }
+
+    /// Update the summary of the channel of `message` in the background
+    pub fn spawn_update(&self, message: &knowledge::Message)
+    where
+        M: 'static,
+    {
+        let summarizer = self.clone();
+        let source = message.source.clone();
+        let channel_id = message.channel_id.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = summarizer.update(&source, &channel_id).await {
+                error!(?err, channel_id, "Failed to update channel summary");
+            }
+        });
+    }
+
+    async fn summarize(&self, source: &Source, channel_id: &str) -> anyhow::Result<usize> {
+        // The messages the chat history keeps are given verbatim to the agent
+        let recent = self
+            .knowledge
+            .channel_messages(source, channel_id, self.history_messages)
+            .await?;
+        let Some(history_start) = history::oldest_kept(&recent, self.history_tokens) else {
+            return Ok(0);
+        };
+
+        let mut current = self.knowledge.channel_summary(source, channel_id).await?;
+        let pending = self
+            .knowledge
+            .messages_to_summarize(
+                source,
+                channel_id,
+                current.as_ref().map(|summary| summary.summarized_until),
+                history_start,
+            )
+            .await?;
+
+        if pending.len() < SUMMARY_BATCH_MESSAGES {
+            return Ok(0);
+        }
+
+        let agent = AgentBuilder::new(self.completion_model.clone())
+            .preamble(SUMMARY_PREAMBLE)
+            .build();
+
+        let mut summarized = 0;
+        for batch in pending.chunks(MAX_SUMMARY_MESSAGES) {
+            let messages = history::transcript(
+                &batch
+                    .iter()
+                    .map(|(message, name)| history::turn(message, name.as_deref()))
+                    .collect::<Vec<_>>(),
+            );
+            let prompt = format!(
+                "Current summary:\n{}\n\nNew messages:\n{}",
+                current
+                    .as_ref()
+                    .map_or("(none)", |summary| summary.summary.as_str()),
+                messages
+            );
+
+            let summary = agent.prompt(&prompt).await?;
+            let summarized_until = batch[batch.len() - 1].0.created_at;
+            let message_count =
+                current.as_ref().map_or(0, |summary| summary.message_count) + batch.len() as i64;
+
+            self.knowledge
+                .store_channel_summary(
+                    source,
+                    channel_id,
+                    summary.trim(),
+                    summarized_until,
+                    message_count,
+                )
+                .await?;
+            summarized += batch.len();
+
+            current = self.knowledge.channel_summary(source, channel_id).await?;
+        }
+
+        info!(channel_id, messages = summarized, "Updated channel summary");
+
+        Ok(summarized)
+    }
+}
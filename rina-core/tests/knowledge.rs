@@ -0,0 +1,792 @@
+use rig::completion::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse};
+use rig::embeddings::{Embedding, EmbeddingModel};
+use rina_core::attention::{Attention, AttentionCommand, AttentionConfig, AttentionContext};
+use rina_core::knowledge::{ChannelType, KnowledgeBase, Message, Source};
+use rina_core::memory::{Memory, MemoryCommand};
+use rina_core::retention::{Pruner, RetentionPolicy};
+use sqlite_vec::sqlite3_vec_init;
+use tokio_rusqlite::ffi::sqlite3_auto_extension;
+use tokio_rusqlite::Connection;
+
+/// Mock embedding model whose embeddings are the length of the text
+#[derive(Clone)]
+struct MockEmbeddingModel;
+
+impl EmbeddingModel for MockEmbeddingModel {
+    const MAX_DOCUMENTS: usize = 16;
+
+    fn ndims(&self) -> usize {
+        2
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, rig::embeddings::EmbeddingError> {
+        Ok(texts
+            .into_iter()
+            .map(|text| Embedding {
+                vec: vec![text.len() as f64; 2],
+                document: text,
+            })
+            .collect())
+    }
+}
+
+/// Mock completion model which is unavailable, memory commands do not need it
+#[derive(Clone)]
+struct MockCompletionModel;
+
+impl CompletionModel for MockCompletionModel {
+    type Response = ();
+
+    async fn completion(
+        &self,
+        _: CompletionRequest,
+    ) -> Result<CompletionResponse<()>, CompletionError> {
+        Err(CompletionError::ProviderError("unavailable".into()))
+    }
+}
+
+async fn knowledge() -> anyhow::Result<(Connection, KnowledgeBase<MockEmbeddingModel>)> {
+    unsafe {
+        sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
+    }
+
+    let conn = Connection::open_in_memory().await?;
+    let knowledge = KnowledgeBase::new(conn.clone(), MockEmbeddingModel).await?;
+    Ok((conn, knowledge))
+}
+
+fn message(id: &str, channel_id: &str, account_id: &str, days_ago: i64) -> Message {
+    Message {
+        id: id.to_string(),
+        source: Source::Discord,
+        source_id: account_id.to_string(),
+        channel_type: ChannelType::Text,
+        channel_id: channel_id.to_string(),
+        account_id: account_id.to_string(),
+        role: "user".to_string(),
+        content: format!("message {id}"),
+        created_at: chrono::Utc::now() - chrono::Duration::days(days_ago),
+        reply_to: None,
+    }
+}
+
+/// Number of rows of the query, which counts them
+async fn count(conn: &Connection, query: &'static str) -> anyhow::Result<i64> {
+    Ok(conn
+        .call(move |conn| Ok(conn.query_row(query, [], |row| row.get(0))?))
+        .await?)
+}
+
+#[tokio::test]
+async fn test_forget_account() -> anyhow::Result<()> {
+    let (conn, knowledge) = knowledge().await?;
+
+    for (name, account_id) in [("alice", "u1"), ("bob", "u2")] {
+        knowledge
+            .create_user(name.into(), "discord".into(), account_id.into())
+            .await
+            .unwrap();
+    }
+    knowledge
+        .create_message(message("1", "c1", "u1", 0))
+        .await?;
+    knowledge
+        .create_message(message("2", "c2", "u2", 0))
+        .await?;
+    knowledge
+        .create_message(Message {
+            role: "assistant".to_string(),
+            reply_to: Some("1".to_string()),
+            ..message("3", "c1", "bot", 0)
+        })
+        .await?;
+
+    for message_id in ["1", "2"] {
+        knowledge
+            .store_attention_decision(&Source::Discord, "c1", message_id, "respond", 1.0, "test")
+            .await
+            .unwrap();
+    }
+    knowledge
+        .store_user_facts(
+            &message("1", "c1", "u1", 0),
+            vec![("name", "Is Alice".into())],
+        )
+        .await
+        .unwrap();
+    knowledge
+        .store_user_facts(
+            &message("2", "c2", "u2", 0),
+            vec![("name", "Is Bob".into())],
+        )
+        .await
+        .unwrap();
+    knowledge
+        .mute(&Source::Discord, "c1", Some("u1"), None)
+        .await
+        .unwrap();
+    knowledge
+        .store_channel_summary(
+            &Source::Discord,
+            "c1",
+            "Alice said hi",
+            chrono::Utc::now(),
+            2,
+        )
+        .await?;
+
+    let report = knowledge.forget_account(&Source::Discord, "u1").await?;
+    assert_eq!(report.messages, 2);
+    assert_eq!(report.attention_decisions, 1);
+    assert_eq!(report.summaries, 1);
+    assert_eq!(report.facts, 1);
+    assert_eq!(report.mutes, 1);
+    assert!(report.account);
+
+    // Nothing is left of the account
+    for query in [
+        "SELECT COUNT(*) FROM accounts WHERE source_id = 'u1'",
+        "SELECT COUNT(*) FROM messages WHERE account_id = 'u1' OR reply_to = '1'",
+        "SELECT COUNT(*) FROM messages_embeddings WHERE rowid NOT IN (SELECT rowid FROM messages)",
+        "SELECT COUNT(*) FROM attention_decisions WHERE message_id = '1'",
+        "SELECT COUNT(*) FROM channel_summaries WHERE channel_id = 'c1'",
+        "SELECT COUNT(*) FROM user_facts WHERE account_id = 'u1'",
+        "SELECT COUNT(*) FROM mutes WHERE account_id = 'u1'",
+    ] {
+        assert_eq!(count(&conn, query).await?, 0, "{query}");
+    }
+
+    // Other accounts are left untouched
+    for query in [
+        "SELECT COUNT(*) FROM accounts WHERE source_id = 'u2'",
+        "SELECT COUNT(*) FROM messages WHERE account_id = 'u2'",
+        "SELECT COUNT(*) FROM attention_decisions WHERE message_id = '2'",
+        "SELECT COUNT(*) FROM user_facts WHERE account_id = 'u2'",
+    ] {
+        assert_eq!(count(&conn, query).await?, 1, "{query}");
+    }
+
+    let audit_log = knowledge.audit_log(10).await?;
+    assert_eq!(audit_log.len(), 1);
+    assert_eq!(audit_log[0].action, "forget_account");
+    assert_eq!(audit_log[0].subject.as_deref(), Some("u1"));
+    assert!(!audit_log[0].details.contains("Alice"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_create_message_keeps_stored_message() -> anyhow::Result<()> {
+    let (conn, knowledge) = knowledge().await?;
+
+    knowledge
+        .create_message(message("1", "c1", "u1", 0))
+        .await?;
+    // Same id in a chat of another source
+    let other = Message {
+        source: Source::Telegram,
+        content: "another message".to_string(),
+        ..message("1", "c2", "u2", 0)
+    };
+    assert!(knowledge.create_message(other).await.is_err());
+
+    assert_eq!(
+        count(
+            &conn,
+            "SELECT COUNT(*) FROM messages WHERE content = 'message 1'"
+        )
+        .await?,
+        1
+    );
+    assert_eq!(
+        count(&conn, "SELECT COUNT(*) FROM messages_embeddings").await?,
+        1
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_channel_messages_by_source() -> anyhow::Result<()> {
+    let (_conn, knowledge) = knowledge().await?;
+
+    knowledge
+        .create_message(message("1", "c1", "u1", 0))
+        .await?;
+    // Same channel id in another source
+    knowledge
+        .create_message(Message {
+            source: Source::Telegram,
+            ..message("2", "c1", "u2", 0)
+        })
+        .await?;
+
+    let messages = knowledge
+        .channel_messages(&Source::Discord, "c1", 10)
+        .await?;
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].0.id, "1");
+    let messages = knowledge
+        .get_recent_messages(&Source::Telegram, "c1", 10)
+        .await
+        .unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].id, "2");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mute_commands() -> anyhow::Result<()> {
+    let (_conn, knowledge) = knowledge().await?;
+    let attention = Attention::new(
+        AttentionConfig::default(),
+        MockCompletionModel,
+        knowledge.clone(),
+    );
+    let context = |account_id: &str, content: &str, is_moderator: bool| AttentionContext {
+        message_id: content.to_string(),
+        account_id: account_id.to_string(),
+        message_content: content.to_string(),
+        mentioned_names: Default::default(),
+        history: vec![],
+        channel_id: "c1".to_string(),
+        channel_type: ChannelType::Text,
+        source: Source::Discord,
+        is_moderator,
+    };
+    let muted = |account_id: &'static str| {
+        let knowledge = knowledge.clone();
+        async move {
+            knowledge
+                .active_mute(&Source::Discord, "c1", account_id)
+                .await
+                .unwrap()
+                .is_some()
+        }
+    };
+
+    // Others mute the agent only for themselves
+    let decision = attention.should_reply(&context("u1", "/mute", false)).await;
+    assert_eq!(decision.command, AttentionCommand::Stop);
+    assert!(muted("u1").await);
+    assert!(!muted("u2").await);
+
+    // Moderators mute it for everyone, and only they lift such mutes
+    attention.should_reply(&context("u2", "/mute", true)).await;
+    assert!(muted("u3").await);
+    attention
+        .should_reply(&context("u3", "/unmute", false))
+        .await;
+    assert!(muted("u3").await);
+    let decision = attention
+        .should_reply(&context("u2", "/unmute", true))
+        .await;
+    assert_eq!(decision.command, AttentionCommand::Respond);
+    assert!(!muted("u3").await);
+    assert!(muted("u1").await);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stop_phrases_when_addressed() -> anyhow::Result<()> {
+    let (_conn, knowledge) = knowledge().await?;
+    let config = AttentionConfig {
+        bot_names: vec!["Rina".to_string()],
+        ..Default::default()
+    };
+    let attention = Attention::new(config, MockCompletionModel, knowledge.clone());
+    let context = |content: &str| AttentionContext {
+        message_id: content.to_string(),
+        account_id: "u1".to_string(),
+        message_content: content.to_string(),
+        mentioned_names: Default::default(),
+        history: vec![],
+        channel_id: "c1".to_string(),
+        channel_type: ChannelType::Text,
+        source: Source::Discord,
+        is_moderator: false,
+    };
+    let muted = || async {
+        knowledge
+            .active_mute(&Source::Discord, "c1", "u1")
+            .await
+            .unwrap()
+            .is_some()
+    };
+
+    // Conversations between others do not toggle the mutes
+    for content in ["can you not do that to me", "don't talk to him like that"] {
+        let decision = attention.should_reply(&context(content)).await;
+        assert_ne!(decision.command, AttentionCommand::Stop, "{content}");
+    }
+    assert!(!muted().await);
+
+    let decision = attention.should_reply(&context("Rina, dont talk")).await;
+    assert_eq!(decision.command, AttentionCommand::Stop);
+    assert!(muted().await);
+
+    attention
+        .should_reply(&context("how do I unmute my mic"))
+        .await;
+    assert!(muted().await);
+    let decision = attention
+        .should_reply(&context("Rina you can talk again"))
+        .await;
+    assert_eq!(decision.command, AttentionCommand::Respond);
+    assert!(!muted().await);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_is_mute_command() -> anyhow::Result<()> {
+    let (_conn, knowledge) = knowledge().await?;
+    let config = AttentionConfig {
+        bot_names: vec!["Rina".to_string()],
+        ..Default::default()
+    };
+    let attention = Attention::new(config, MockCompletionModel, knowledge);
+    let context = |content: &str, channel_type: ChannelType| AttentionContext {
+        message_id: content.to_string(),
+        account_id: "u1".to_string(),
+        message_content: content.to_string(),
+        mentioned_names: Default::default(),
+        history: vec![],
+        channel_id: "c1".to_string(),
+        channel_type,
+        source: Source::Telegram,
+        is_moderator: false,
+    };
+
+    for content in ["/mute", "/unmute", "Rina you can talk again"] {
+        assert!(
+            attention.is_mute_command(&context(content, ChannelType::Text)),
+            "{content}"
+        );
+    }
+    assert!(attention.is_mute_command(&context("you can talk again", ChannelType::DirectMessage)));
+
+    // Stop phrases only mute the bot for their author, unmute phrases only count when
+    // addressed to it
+    for content in ["Rina, stop talking", "you can talk again", "hello Rina"] {
+        assert!(
+            !attention.is_mute_command(&context(content, ChannelType::Text)),
+            "{content}"
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_forget_user_facts_after_confirmation() -> anyhow::Result<()> {
+    let (_conn, knowledge) = knowledge().await?;
+    let memory = Memory::new(MockCompletionModel, knowledge.clone());
+    let said = |account_id: &str, content: &str| Message {
+        content: content.to_string(),
+        ..message("1", "c1", account_id, 0)
+    };
+
+    knowledge
+        .store_user_facts(&said("u1", "I'm Alice"), vec![("name", "Is Alice".into())])
+        .await
+        .unwrap();
+
+    // Only a command or phrase on its own is a command
+    for content in ["don't forget me", "never forget me", "yes", "/forgetful"] {
+        assert_eq!(memory.command(&said("u1", content)), None, "{content}");
+    }
+    for (content, command) in [
+        ("forget me", MemoryCommand::Forget),
+        ("<@42> Forget me!", MemoryCommand::Forget),
+        ("/forget@rina_bot", MemoryCommand::Forget),
+        ("/memory", MemoryCommand::Recall),
+    ] {
+        assert_eq!(
+            memory.command(&said("u1", content)),
+            Some(command),
+            "{content}"
+        );
+    }
+
+    memory
+        .handle(MemoryCommand::Forget, &said("u1", "forget me"))
+        .await;
+    assert_eq!(
+        memory.command(&said("u1", "no")),
+        Some(MemoryCommand::CancelForget)
+    );
+    memory
+        .handle(MemoryCommand::CancelForget, &said("u1", "no"))
+        .await;
+    assert_eq!(memory.command(&said("u1", "yes")), None);
+    assert_eq!(memory.recall(&said("u1", "/memory")).await.len(), 1);
+
+    memory
+        .handle(MemoryCommand::Forget, &said("u1", "forget me"))
+        .await;
+    // Only the author of the request can confirm it
+    assert_eq!(memory.command(&said("u2", "yes")), None);
+    assert_eq!(
+        memory.command(&said("u1", "yes")),
+        Some(MemoryCommand::ConfirmForget)
+    );
+    memory
+        .handle(MemoryCommand::ConfirmForget, &said("u1", "yes"))
+        .await;
+    assert!(memory.recall(&said("u1", "/memory")).await.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_prune() -> anyhow::Result<()> {
+    let (conn, knowledge) = knowledge().await?;
+
+    for (i, days_ago) in [4, 3, 2, 1].into_iter().enumerate() {
+        knowledge
+            .create_message(message(&format!("c1-{i}"), "c1", "u1", days_ago))
+            .await?;
+    }
+    knowledge
+        .create_message(message("c2-old", "c2", "u1", 60))
+        .await?;
+    knowledge
+        .create_message(message("c2-new", "c2", "u1", 1))
+        .await?;
+    knowledge
+        .create_message(message("c3", "c3", "u1", 60))
+        .await?;
+
+    let pruner = Pruner::new(
+        knowledge.clone(),
+        vec![
+            RetentionPolicy::source(Source::Discord).max_age(chrono::Duration::days(30)),
+            RetentionPolicy::channel(Source::Discord, "c1").max_messages(2),
+            RetentionPolicy::channel(Source::Discord, "c3").max_age(chrono::Duration::days(90)),
+        ],
+    );
+    assert_eq!(pruner.prune().await?, 3);
+    assert_eq!(pruner.prune().await?, 0);
+
+    let mut ids = conn
+        .call(|conn| {
+            let mut stmt = conn.prepare("SELECT id FROM messages")?;
+            let ids = stmt
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(ids)
+        })
+        .await?;
+    ids.sort();
+    assert_eq!(ids, ["c1-2", "c1-3", "c2-new", "c3"]);
+    assert_eq!(
+        count(&conn, "SELECT COUNT(*) FROM messages_embeddings").await?,
+        4
+    );
+
+    let audit_log = knowledge.audit_log(10).await?;
+    assert_eq!(audit_log.len(), 2);
+    assert!(audit_log
+        .iter()
+        .all(|entry| entry.action == "prune_messages"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_import() -> anyhow::Result<()> {
+    let (_, knowledge) = knowledge().await?;
+
+    knowledge
+        .create_message(message("1", "c1", "u1", 0))
+        .await?;
+    knowledge
+        .store_attention_decision(&Source::Discord, "c1", "1", "respond", 0.5, "test")
+        .await
+        .unwrap();
+    knowledge
+        .store_user_facts(
+            &message("1", "c1", "u1", 0),
+            vec![("name", "Is Alice".into())],
+        )
+        .await
+        .unwrap();
+    knowledge
+        .mute(
+            &Source::Discord,
+            "c1",
+            Some("u2"),
+            Some(chrono::Duration::hours(1)),
+        )
+        .await
+        .unwrap();
+    let summarized_until = chrono::Utc::now();
+    knowledge
+        .store_channel_summary(&Source::Discord, "c1", "Alice said hi", summarized_until, 1)
+        .await?;
+
+    let mut export = Vec::new();
+    let report = knowledge.export(&mut export, false).await?;
+    assert_eq!(
+        (
+            report.messages,
+            report.facts,
+            report.mutes,
+            report.summaries,
+            report.attention_decisions
+        ),
+        (1, 1, 1, 1, 1)
+    );
+
+    let (conn, imported) = self::knowledge().await?;
+    let report = imported.import(&export[..], true).await?;
+    assert_eq!(
+        (
+            report.messages,
+            report.facts,
+            report.mutes,
+            report.summaries,
+            report.attention_decisions
+        ),
+        (1, 1, 1, 1, 1)
+    );
+    assert!(report.invalid.is_empty());
+
+    let facts = imported
+        .user_facts(&Source::Discord, "u1", 10)
+        .await
+        .unwrap();
+    assert_eq!(facts[0].fact, "Is Alice");
+    assert!(imported
+        .active_mute(&Source::Discord, "c1", "u2")
+        .await
+        .unwrap()
+        .is_some());
+    let summary = imported
+        .channel_summary(&Source::Discord, "c1")
+        .await?
+        .unwrap();
+    assert_eq!(summary.summary, "Alice said hi");
+    assert_eq!(summary.summarized_until, summarized_until);
+
+    // Importing again does not duplicate the mutes and attention decisions
+    imported.import(&export[..], true).await?;
+    assert_eq!(count(&conn, "SELECT COUNT(*) FROM mutes").await?, 1);
+    assert_eq!(
+        count(&conn, "SELECT COUNT(*) FROM attention_decisions").await?,
+        1
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_import_skips_erased_accounts() -> anyhow::Result<()> {
+    let (conn, knowledge) = knowledge().await?;
+
+    for (name, account_id) in [("alice", "u1"), ("bob", "u2")] {
+        knowledge
+            .create_user(name.into(), "discord".into(), account_id.into())
+            .await
+            .unwrap();
+    }
+    knowledge
+        .create_message(message("1", "c1", "u1", 0))
+        .await?;
+    knowledge
+        .create_message(Message {
+            role: "assistant".to_string(),
+            reply_to: Some("1".to_string()),
+            ..message("2", "c1", "bot", 0)
+        })
+        .await?;
+    knowledge
+        .create_message(message("3", "c2", "u2", 0))
+        .await?;
+    knowledge
+        .store_attention_decision(&Source::Discord, "c1", "1", "respond", 1.0, "test")
+        .await
+        .unwrap();
+    knowledge
+        .store_user_facts(
+            &message("1", "c1", "u1", 0),
+            vec![("name", "Is Alice".into())],
+        )
+        .await
+        .unwrap();
+    knowledge
+        .store_channel_summary(
+            &Source::Discord,
+            "c1",
+            "Alice said hi",
+            chrono::Utc::now(),
+            2,
+        )
+        .await?;
+
+    let mut export = Vec::new();
+    knowledge.export(&mut export, false).await?;
+    knowledge.forget_account(&Source::Discord, "u1").await?;
+
+    let report = knowledge.import(&export[..], true).await?;
+    // Account, 2 messages, attention decision, fact and summary
+    assert_eq!(report.erased, 6);
+    assert_eq!((report.accounts, report.messages), (1, 1));
+
+    for query in [
+        "SELECT COUNT(*) FROM accounts WHERE source_id = 'u1'",
+        "SELECT COUNT(*) FROM messages WHERE channel_id = 'c1'",
+        "SELECT COUNT(*) FROM attention_decisions",
+        "SELECT COUNT(*) FROM user_facts",
+        "SELECT COUNT(*) FROM channel_summaries",
+    ] {
+        assert_eq!(count(&conn, query).await?, 0, "{query}");
+    }
+    assert_eq!(
+        count(
+            &conn,
+            "SELECT COUNT(*) FROM messages WHERE account_id = 'u2'"
+        )
+        .await?,
+        1
+    );
+
+    Ok(())
+}
+
+/// Schema of the knowledge base before migrations were introduced, along with some data
+const BASELINE_SCHEMA: &str = "
+    CREATE TABLE accounts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        source_id TEXT NOT NULL UNIQUE,
+        source TEXT NOT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX idx_source_id_source ON accounts(source_id, source);
+    CREATE TABLE channels (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        channel_id TEXT NOT NULL UNIQUE,
+        channel_type TEXT NOT NULL,
+        source TEXT NOT NULL,
+        name TEXT,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX idx_channel_id_type ON channels(channel_id, channel_type);
+    CREATE TABLE trade (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        wallet_address TEXT NOT NULL,
+        action TEXT NOT NULL,
+        token_address TEXT NOT NULL,
+        amount REAL NOT NULL,
+        reason TEXT NOT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        signature TEXT NOT NULL
+    );
+    CREATE INDEX idx_wallet_created_at ON trade(wallet_address, created_at);
+    CREATE TABLE documents (
+        id TEXT PRIMARY KEY,
+        source_id TEXT,
+        content TEXT,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE VIRTUAL TABLE documents_embeddings USING vec0(embedding float[2]);
+    CREATE TABLE messages (
+        id TEXT PRIMARY KEY,
+        source TEXT,
+        source_id TEXT,
+        channel_type TEXT,
+        channel_id TEXT,
+        account_id TEXT,
+        role TEXT,
+        content TEXT,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE VIRTUAL TABLE messages_embeddings USING vec0(embedding float[2]);
+
+    INSERT INTO accounts (name, source_id, source) VALUES ('bob', '42', 'discord');
+    INSERT INTO channels (channel_id, channel_type, source) VALUES ('c1', 'text', 'discord');
+    INSERT INTO trade (wallet_address, action, token_address, amount, reason, signature)
+    VALUES ('wallet', 'buy', 'token', 1.5, 'test', 'signature');
+    INSERT INTO documents (id, source_id, content, created_at)
+    VALUES ('d1', 'file', 'document', '2024-01-01T00:00:00+00:00');
+    INSERT INTO messages
+    (id, source, source_id, channel_type, channel_id, account_id, role, content, created_at)
+    VALUES ('m1', 'discord', '42', 'text', 'c1', '42', 'user', 'hello', '2024-01-01T00:00:00+00:00');
+";
+
+#[tokio::test]
+async fn test_migrate_baseline_schema() -> anyhow::Result<()> {
+    unsafe {
+        sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
+    }
+
+    let conn = Connection::open_in_memory().await?;
+    conn.call(|conn| Ok(conn.execute_batch(BASELINE_SCHEMA)?))
+        .await?;
+
+    let knowledge = KnowledgeBase::new(conn.clone(), MockEmbeddingModel).await?;
+    assert_eq!(
+        count(&conn, "SELECT MAX(version) FROM schema_version").await?,
+        7
+    );
+
+    // The data is kept, and the rows without an embedding are embedded
+    let account = knowledge
+        .get_user_by_source("discord".into(), "42".into())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.name, "bob");
+    let channels = knowledge
+        .get_channels_by_source("discord".into())
+        .await
+        .unwrap();
+    assert_eq!(channels[0].channel_id, "c1");
+    assert_eq!(
+        knowledge.get_recent_trades("wallet", 10).await.unwrap()[0].signature,
+        "signature"
+    );
+    let messages = knowledge
+        .channel_messages(&Source::Discord, "c1", 10)
+        .await?;
+    assert_eq!(messages[0].0.content, "hello");
+    assert_eq!(messages[0].0.reply_to, None);
+    assert_eq!(messages[0].1.as_deref(), Some("bob"));
+    assert_eq!(
+        knowledge.get_document("d1").await?.unwrap().content,
+        "document"
+    );
+    assert_eq!(
+        count(&conn, "SELECT COUNT(*) FROM documents_embeddings").await?,
+        1
+    );
+    assert_eq!(
+        count(&conn, "SELECT COUNT(*) FROM messages_embeddings").await?,
+        1
+    );
+
+    // Accounts are unique by source, no longer by id alone
+    let id = knowledge
+        .create_user("alice".into(), "telegram".into(), "42".into())
+        .await
+        .unwrap();
+    assert_ne!(id, account.id);
+
+    // Migrations are only applied once
+    KnowledgeBase::new(conn.clone(), MockEmbeddingModel).await?;
+    assert_eq!(
+        count(&conn, "SELECT COUNT(*) FROM schema_version").await?,
+        7
+    );
+
+    Ok(())
+}
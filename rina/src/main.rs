@@ -1,18 +1,34 @@
-use clap::{command, Parser};
-use rig::providers::{self, openai};
+use clap::{command, Parser, Subcommand, ValueEnum};
+use rig::providers::{anthropic, cohere, gemini, ollama, openai, perplexity, xai};
+use rig::retry::RetryModel;
+use rig::usage::{ModelPricing, PriceTable};
+use rig_sqlite::{CacheStats, CachedEmbeddingModel};
 use rina_core::attention::{Attention, AttentionConfig};
 use rina_core::character;
+use rina_core::ingest::{IngestReport, Ingester, DEFAULT_CHUNK_OVERLAP, DEFAULT_CHUNK_SIZE};
 use rina_core::init_logging;
-use rina_core::knowledge::KnowledgeBase;
+use rina_core::knowledge::{KnowledgeBase, Source};
+use rina_core::providers::{AnyCompletionModel, ProviderClient};
+use rina_core::retention::{Pruner, RetentionPolicy};
+use rina_core::spend::{MeteredCompletionModel, MeteredEmbeddingModel, SpendTracker};
 use rina_core::{agent::Agent, clients::discord::DiscordClient, clients::twitter::TwitterClient, clients::telegram::TelegramClient, clients::direct::DirectClient};
 use sqlite_vec::sqlite3_vec_init;
 use tokio_rusqlite::ffi::sqlite3_auto_extension;
 use tokio_rusqlite::Connection;
 use mongodb::Client as MongoClient;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::info;
+
+/// How often the bot logs the stats of the embedding cache
+const CACHE_STATS_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Clients to run
     #[arg(long, env = "CLIENTS", default_value = "discord,twitter,telegram")]
     clients: String,
@@ -25,21 +41,74 @@ struct Args {
     #[arg(long, default_value = "rina.db")]
     db_path: String,
 
-    /// Discord API token (can also be set via DISCORD_API_TOKEN env var)
-    #[arg(long, env = "DISCORD_API_TOKEN", default_value = "")]
-    discord_api_token: String,
+    /// Discord API token, required by the discord client
+    #[arg(long, env = "DISCORD_API_TOKEN")]
+    discord_api_token: Option<String>,
 
     /// OpenAI API token (can also be set via OPENAI_API_KEY env var)
     #[arg(long, env = "OPENAI_API_KEY", default_value = "")]
     openai_api_key: String,
 
-    /// Twitter username
+    /// Anthropic API token
+    #[arg(long, env = "ANTHROPIC_API_KEY", default_value = "")]
+    anthropic_api_key: String,
+
+    /// Google Gemini API token
+    #[arg(long, env = "GEMINI_API_KEY", default_value = "")]
+    gemini_api_key: String,
+
+    /// Cohere API token
+    #[arg(long, env = "COHERE_API_KEY", default_value = "")]
+    cohere_api_key: String,
+
+    /// Perplexity API token
+    #[arg(long, env = "PERPLEXITY_API_KEY", default_value = "")]
+    perplexity_api_key: String,
+
+    /// xAI API token
+    #[arg(long, env = "XAI_API_KEY", default_value = "")]
+    xai_api_key: String,
+
+    /// URL of the Ollama server
+    #[arg(long, env = "OLLAMA_API_BASE_URL", default_value = "http://localhost:11434")]
+    ollama_url: String,
+
+    /// Provider of the agent completion model
+    #[arg(long, env = "COMPLETION_PROVIDER", value_enum, default_value_t = Provider::Openai)]
+    completion_provider: Provider,
+
+    /// Agent completion model (defaults to the default model of the provider)
+    #[arg(long, env = "COMPLETION_MODEL")]
+    completion_model: Option<String>,
+
+    /// Provider of the attention model (defaults to the agent completion provider)
+    #[arg(long, env = "ATTENTION_PROVIDER", value_enum)]
+    attention_provider: Option<Provider>,
+
+    /// Attention model (defaults to the agent completion model)
+    #[arg(long, env = "ATTENTION_MODEL")]
+    attention_model: Option<String>,
+
+    /// Provider of the knowledge base embedding model
+    #[arg(long, env = "EMBEDDING_PROVIDER", value_enum, default_value_t = Provider::Openai)]
+    embedding_provider: Provider,
+
+    /// Knowledge base embedding model (defaults to the default model of the provider)
+    #[arg(long, env = "EMBEDDING_MODEL")]
+    embedding_model: Option<String>,
+
+    /// Number of dimensions of the embedding model, only required for models unknown to rig.
+    /// Changing the embedding model dimension re-embeds the knowledge base on startup.
+    #[arg(long, env = "EMBEDDING_NDIMS")]
+    embedding_ndims: Option<usize>,
+
+    /// Twitter username, required by the twitter client
     #[arg(long, env = "TWITTER_USERNAME")]
-    twitter_username: String,
+    twitter_username: Option<String>,
 
-    /// Twitter password
+    /// Twitter password, required by the twitter client unless --twitter-cookie-string is set
     #[arg(long, env = "TWITTER_PASSWORD")]
-    twitter_password: String,
+    twitter_password: Option<String>,
 
     /// Twitter email (optional, for 2FA)
     #[arg(long, env = "TWITTER_EMAIL")]
@@ -56,19 +125,243 @@ struct Args {
     #[arg(long, env = "HEURIST_API_KEY")]
     heurist_api_key: Option<String>,
 
-    /// Telegram bot token
+    /// Telegram bot token, required by the telegram client
     #[arg(long, env = "TELEGRAM_BOT_TOKEN")]
-    telegram_bot_token: String,
+    telegram_bot_token: Option<String>,
 
     #[arg(long, env = "SOLANA_RPC_URL")]
-    solana_rpc_url: String,
+    solana_rpc_url: Option<String>,
 
     #[arg(long, env = "SOLANA_PRIVATE_KEY")]
-    solana_private_key: String,
+    solana_private_key: Option<String>,
 
+    /// Solana wallet address, required by the direct client
     #[arg(long, env = "SOLANA_WALLET_ADDRESS")]
-    solana_wallet_address: String,
+    solana_wallet_address: Option<String>,
+
+    /// Path to a TOML file with the price of each model, in USD per million tokens
+    #[arg(long, env = "PRICE_TABLE")]
+    price_table: Option<String>,
+
+    /// Daily spend limit of each client in USD (e.g.: "twitter=5,discord=2.5")
+    #[arg(long, env = "DAILY_SPEND_LIMITS", default_value = "")]
+    daily_spend_limits: String,
+
+    /// Number of days messages are kept, for all channels ("*"), the channels of a source
+    /// (e.g.: "discord") or a single channel (e.g.: "telegram:-100123"): "*=90,discord=30"
+    #[arg(long, env = "RETENTION_DAYS", default_value = "")]
+    retention_days: String,
+
+    /// Number of most recent messages kept per channel, scoped as --retention-days
+    #[arg(long, env = "RETENTION_MESSAGES", default_value = "")]
+    retention_messages: String,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Load files, PDFs and Git repositories into the knowledge base instead of running the clients
+    Ingest(IngestArgs),
+    /// Erase the messages, memories and account of a user from the knowledge base
+    Forget(ForgetArgs),
+    /// Write the documents, messages, accounts, channels, trades, memories, mutes, summaries and attention decisions of the knowledge base to a JSONL file
+    Export(ExportArgs),
+    /// Load a JSONL file written by the export command into the knowledge base, skipping the users erased by the forget command
+    Import(ImportArgs),
+}
+
+#[derive(clap::Args)]
+struct ExportArgs {
+    /// File to write the export to
+    path: PathBuf,
 
+    /// Also export the embeddings of the documents and messages, to import them with the same embedding model
+    #[arg(long)]
+    embeddings: bool,
+}
+
+#[derive(clap::Args)]
+struct ImportArgs {
+    /// File written by the export command
+    path: PathBuf,
+
+    /// Embed the documents and messages again instead of importing their embeddings (e.g.: to switch embedding model)
+    #[arg(long)]
+    reembed: bool,
+}
+
+#[derive(clap::Args)]
+struct ForgetArgs {
+    /// Platform of the account (e.g.: discord, telegram, twitter)
+    source: String,
+
+    /// Id of the account on its platform
+    source_id: String,
+}
+
+#[derive(clap::Args)]
+struct IngestArgs {
+    /// Files, directories or glob patterns (e.g.: "docs/**/*.md") to ingest
+    paths: Vec<String>,
+
+    /// Git repository to ingest, either the URL of a repository to clone or the path of a local clone
+    #[arg(long = "git")]
+    repos: Vec<String>,
+
+    /// Glob pattern of the files to ingest in Git repositories
+    #[arg(long, default_value = "**/*.md")]
+    git_glob: String,
+
+    /// Directory where Git repositories are cloned
+    #[arg(long, default_value = "repos")]
+    clone_dir: PathBuf,
+
+    /// Maximum length of a chunk of a file, in characters
+    #[arg(long, default_value_t = DEFAULT_CHUNK_SIZE)]
+    chunk_size: usize,
+
+    /// Maximum length of the end of a chunk repeated at the start of the next one, in characters
+    #[arg(long, default_value_t = DEFAULT_CHUNK_OVERLAP)]
+    chunk_overlap: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Provider {
+    Openai,
+    Anthropic,
+    Gemini,
+    Cohere,
+    Perplexity,
+    Xai,
+    Ollama,
+}
+
+impl Provider {
+    fn default_completion_model(&self) -> &'static str {
+        match self {
+            Provider::Openai => openai::GPT_4O,
+            Provider::Anthropic => anthropic::CLAUDE_3_5_SONNET,
+            Provider::Gemini => gemini::completion::GEMINI_1_5_FLASH,
+            Provider::Cohere => cohere::COMMAND_R,
+            Provider::Perplexity => perplexity::LLAMA_3_1_SONAR_LARGE_CHAT,
+            Provider::Xai => xai::GROK_BETA,
+            Provider::Ollama => ollama::LLAMA3_2,
+        }
+    }
+
+    fn default_embedding_model(&self) -> Option<&'static str> {
+        match self {
+            Provider::Openai => Some(openai::TEXT_EMBEDDING_3_LARGE),
+            Provider::Gemini => Some(gemini::embedding::EMBEDDING_004),
+            Provider::Cohere => Some(cohere::EMBED_ENGLISH_V3),
+            Provider::Xai => Some(xai::EMBEDDING_V1),
+            Provider::Ollama => Some(ollama::NOMIC_EMBED_TEXT),
+            Provider::Anthropic | Provider::Perplexity => None,
+        }
+    }
+}
+
+impl Args {
+    fn client(&self, provider: Provider) -> ProviderClient {
+        match provider {
+            Provider::Openai => ProviderClient::OpenAI(openai::Client::new(&self.openai_api_key)),
+            Provider::Anthropic => ProviderClient::Anthropic(
+                anthropic::ClientBuilder::new(&self.anthropic_api_key).build(),
+            ),
+            Provider::Gemini => ProviderClient::Gemini(gemini::Client::new(&self.gemini_api_key)),
+            Provider::Cohere => ProviderClient::Cohere(cohere::Client::new(&self.cohere_api_key)),
+            Provider::Perplexity => {
+                ProviderClient::Perplexity(perplexity::Client::new(&self.perplexity_api_key))
+            }
+            Provider::Xai => ProviderClient::Xai(xai::Client::new(&self.xai_api_key)),
+            Provider::Ollama => ProviderClient::Ollama(ollama::Client::from_url(&self.ollama_url)),
+        }
+    }
+}
+
+fn default_price_table() -> PriceTable {
+    PriceTable::new()
+        .with_price(openai::GPT_4O, ModelPricing::new(2.5, 10.0))
+        .with_price(openai::TEXT_EMBEDDING_3_LARGE, ModelPricing::new(0.13, 0.0))
+}
+
+fn parse_spend_limits(limits: &str) -> Result<Vec<(String, f64)>, String> {
+    limits
+        .split(',')
+        .filter(|limit| !limit.trim().is_empty())
+        .map(|limit| {
+            let (client, amount) = limit
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid daily spend limit: {}", limit))?;
+            let amount = amount
+                .trim()
+                .parse::<f64>()
+                .map_err(|e| format!("Invalid daily spend limit for {}: {}", client, e))?;
+            Ok((client.trim().to_string(), amount))
+        })
+        .collect()
+}
+
+/// Retention policies of the `scope=value` limits of --retention-days and --retention-messages
+fn parse_retention(days: &str, messages: &str) -> Result<Vec<RetentionPolicy>, String> {
+    fn parse_limits(limits: &str) -> Result<Vec<(RetentionPolicy, u32)>, String> {
+        limits
+            .split(',')
+            .filter(|limit| !limit.trim().is_empty())
+            .map(|limit| {
+                let (scope, value) = limit
+                    .split_once('=')
+                    .ok_or_else(|| format!("Invalid retention limit: {}", limit))?;
+                let value = value
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|e| format!("Invalid retention limit for {}: {}", scope, e))?;
+                let source = |source: &str| {
+                    Source::from_str(source).ok_or_else(|| format!("Unknown source: {}", source))
+                };
+                let policy = match scope.trim() {
+                    "*" => RetentionPolicy::all(),
+                    scope => match scope.split_once(':') {
+                        Some((source_name, channel_id)) => {
+                            RetentionPolicy::channel(source(source_name)?, channel_id)
+                        }
+                        None => RetentionPolicy::source(source(scope)?),
+                    },
+                };
+                Ok((policy, value))
+            })
+            .collect()
+    }
+
+    let days = parse_limits(days)?
+        .into_iter()
+        .map(|(policy, days)| policy.max_age(chrono::Duration::days(days.into())));
+    let messages = parse_limits(messages)?
+        .into_iter()
+        .map(|(policy, count)| policy.max_messages(count as usize));
+    Ok(days.chain(messages).collect())
+}
+
+/// Value of a setting required by an enabled client
+fn required(value: Option<String>, name: &str, client: &str) -> Result<String, String> {
+    value
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| format!("{} must be set to run the {} client", name, client))
+}
+
+fn log_cache_stats(stats: CacheStats) {
+    info!(
+        hits = stats.hits,
+        misses = stats.misses,
+        hit_rate = stats.hit_rate(),
+        "Embedding cache stats"
+    );
+}
+
+fn print_ingest_report(source: &str, report: &IngestReport) {
+    println!("{}: {}", source, report);
+    for (file, err) in &report.failed {
+        println!("  failed to read {}: {}", file.display(), err);
+    }
 }
 
 #[tokio::main]
@@ -84,31 +377,177 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let character: character::Character = toml::from_str(&character_content)
         .map_err(|e| format!("Failed to parse character TOML: {}\nContent: {}", e, character_content))?;
 
-    let oai = providers::openai::Client::new(&args.openai_api_key);
-    let embedding_model = oai.embedding_model(openai::TEXT_EMBEDDING_3_LARGE);
-    let completion_model = oai.completion_model(openai::GPT_4O);
-    let should_respond_completion_model = oai.completion_model(openai::GPT_4O);
-
-
+    let prices = match &args.price_table {
+        Some(path) => {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read price table file {}: {}", path, e))?;
+            toml::from_str::<PriceTable>(&content)
+                .map_err(|e| format!("Failed to parse price table TOML: {}", e))?
+        }
+        None => default_price_table(),
+    };
     unsafe {
         sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
     }
 
-    let conn = Connection::open(args.db_path).await?;
-    let knowledge = KnowledgeBase::new(conn.clone(), embedding_model).await?;
+    let conn = Connection::open(&args.db_path).await?;
+
+    let mut spend = SpendTracker::new(prices);
+    for (client, limit) in parse_spend_limits(&args.daily_spend_limits)? {
+        spend = spend.with_daily_limit(&client, limit);
+    }
+    // The daily spend caps hold across restarts
+    let spend = spend.persist(conn.clone()).await?;
+    let retention = parse_retention(&args.retention_days, &args.retention_messages)?;
+
+    let completion_model = args.client(args.completion_provider).completion_model(
+        args.completion_model
+            .as_deref()
+            .unwrap_or(args.completion_provider.default_completion_model()),
+    );
+    let attention_model = match (args.attention_provider, &args.attention_model) {
+        (None, None) => completion_model.clone(),
+        (provider, model) => {
+            let provider = provider.unwrap_or(args.completion_provider);
+            args.client(provider).completion_model(
+                model
+                    .as_deref()
+                    .unwrap_or(provider.default_completion_model()),
+            )
+        }
+    };
+
+    let embedding_model_name = match &args.embedding_model {
+        Some(model) => model.as_str(),
+        None => args.embedding_provider.default_embedding_model().ok_or(format!(
+            "{:?} does not provide embedding models, choose another embedding provider",
+            args.embedding_provider
+        ))?,
+    };
+    let embedding_model = args
+        .client(args.embedding_provider)
+        .embedding_model(embedding_model_name, args.embedding_ndims)?;
+    // Texts already embedded, such as repeated messages, are not sent to the provider again
+    let cached_embedding_model =
+        CachedEmbeddingModel::new(conn.clone(), embedding_model.clone(), embedding_model_name)
+            .await?;
+    // Each client gets its own metered embedding model so that its spend is attributed to it
+    let metered_embedding_model = |client: &str| {
+        MeteredEmbeddingModel::new(
+            cached_embedding_model.clone(),
+            embedding_model.name(),
+            client,
+            spend.clone(),
+        )
+    };
+
+    let metered_completion_model = |model: &AnyCompletionModel, client: &str| {
+        MeteredCompletionModel::new(
+            RetryModel::new(model.clone()),
+            model.name(),
+            client,
+            spend.clone(),
+        )
+    };
+
+    let knowledge = KnowledgeBase::new(conn.clone(), metered_embedding_model("knowledge")).await?;
+
+    if let Some(Command::Ingest(ingest)) = &args.command {
+        let ingester = Ingester::new(knowledge)
+            .chunk_size(ingest.chunk_size)
+            .chunk_overlap(ingest.chunk_overlap);
+        for path in &ingest.paths {
+            let report = ingester.ingest_path(path).await?;
+            print_ingest_report(path, &report);
+        }
+        for repo in &ingest.repos {
+            let report = ingester
+                .ingest_repo(repo, &ingest.git_glob, &ingest.clone_dir)
+                .await?;
+            print_ingest_report(repo, &report);
+        }
+        let cache = cached_embedding_model.stats();
+        println!(
+            "Spent ${:.4} on embeddings, {} texts embedded, {} read from the cache",
+            spend.spent_today("knowledge"),
+            cache.misses,
+            cache.hits
+        );
+        return Ok(());
+    }
+
+    if let Some(Command::Forget(forget)) = &args.command {
+        let source = Source::from_str(&forget.source)
+            .ok_or_else(|| format!("Unknown source: {}", forget.source))?;
+        let report = knowledge.forget_account(&source, &forget.source_id).await?;
+        println!(
+            "Forgot {} {}: {} messages, {} cached embeddings, {} attention decisions, {} channel summaries, {} facts, {} mutes, account {}",
+            forget.source,
+            forget.source_id,
+            report.messages,
+            report.cached_embeddings,
+            report.attention_decisions,
+            report.summaries,
+            report.facts,
+            report.mutes,
+            if report.account { "deleted" } else { "not found" }
+        );
+        return Ok(());
+    }
+
+    if let Some(Command::Export(export)) = &args.command {
+        let mut file = tokio::io::BufWriter::new(tokio::fs::File::create(&export.path).await?);
+        let report = knowledge.export(&mut file, export.embeddings).await?;
+        println!("Exported to {}: {}", export.path.display(), report);
+        return Ok(());
+    }
 
-    let agent = Agent::new(character, completion_model, knowledge);
+    if let Some(Command::Import(import)) = &args.command {
+        let file = tokio::io::BufReader::new(tokio::fs::File::open(&import.path).await?);
+        let report = knowledge.import(file, !import.reembed).await?;
+        println!("Imported from {}: {}", import.path.display(), report);
+        for (line, err) in &report.invalid {
+            println!("  invalid record at line {}: {}", line, err);
+        }
+        println!("Spent ${:.4} on embeddings", spend.spent_today("knowledge"));
+        return Ok(());
+    }
 
-    let config = AttentionConfig {
-        bot_names: vec![agent.character.name.clone()],
-        ..Default::default()
+    if !retention.is_empty() {
+        Pruner::new(knowledge.clone(), retention).spawn();
+    }
+
+    let config = AttentionConfig::from(&character);
+    // Each client gets its own metered models so that its spend can be capped independently
+    let client_knowledge = |client: &str| {
+        knowledge
+            .clone()
+            .with_embedding_model(metered_embedding_model(client))
+    };
+    let agent = |client: &str| {
+        Agent::new(
+            character.clone(),
+            metered_completion_model(&completion_model, client),
+            client_knowledge(client),
+        )
+    };
+    let attention = |client: &str| {
+        Attention::new(
+            config.clone(),
+            metered_completion_model(&attention_model, client),
+            client_knowledge(client),
+        )
     };
-    let attention = Attention::new(config, should_respond_completion_model);
 
     let clients = args.clients.split(',').collect::<Vec<&str>>();
     let mut handles = vec![];
 
     if clients.contains(&"direct") {
+        let wallet_address = required(
+            args.solana_wallet_address,
+            "SOLANA_WALLET_ADDRESS",
+            "direct",
+        )?;
         let mongodb_uri = std::env::var("MONGODB_URI").expect("MONGODB_URI must be set");
         let mongo_client = MongoClient::with_uri_str(&mongodb_uri)
             .await
@@ -116,11 +555,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         let collection = mongo_client
             .database("trade")
             .collection("data");
-        let embedding_model_direct = oai.embedding_model(openai::TEXT_EMBEDDING_3_LARGE);
-        let direct_knowledge = KnowledgeBase::new(conn.clone(), embedding_model_direct.clone()).await?;
+        let direct_knowledge = client_knowledge("direct");
         let direct = DirectClient::new(
-            oai.clone(), 
-            &args.solana_wallet_address,
+            metered_completion_model(&completion_model, "direct"),
+            &wallet_address,
             collection.clone(),
             direct_knowledge.clone()
         );
@@ -128,19 +566,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     if clients.contains(&"telegram") {
-        let telegram = TelegramClient::new(agent.clone(), attention.clone(), args.telegram_bot_token);
+        let token = required(args.telegram_bot_token, "TELEGRAM_BOT_TOKEN", "telegram")?;
+        let telegram = TelegramClient::new(agent("telegram"), attention("telegram"), token);
         handles.push(tokio::spawn(async move { telegram.start().await }));
     }
     if clients.contains(&"discord") {
-        let discord = DiscordClient::new(agent.clone(), attention.clone());
-        handles.push(tokio::spawn(async move { discord.start(&args.discord_api_token).await }));
+        let token = required(args.discord_api_token, "DISCORD_API_TOKEN", "discord")?;
+        let discord = DiscordClient::new(agent("discord"), attention("discord"));
+        handles.push(tokio::spawn(async move { discord.start(&token).await }));
     }
     if clients.contains(&"twitter") {
+        let username = required(args.twitter_username, "TWITTER_USERNAME", "twitter")?;
+        // Logging in with cookies takes no password
+        let password = match args.twitter_cookie_string {
+            Some(_) => args.twitter_password.unwrap_or_default(),
+            None => required(args.twitter_password, "TWITTER_PASSWORD", "twitter")?,
+        };
         let twitter = TwitterClient::new(
-            agent.clone(),
-            attention.clone(),
-            args.twitter_username,
-            args.twitter_password,
+            agent("twitter"),
+            attention("twitter"),
+            username,
+            password,
             args.twitter_email,
             args.twitter_2fa_code,
             args.twitter_cookie_string,
@@ -148,8 +594,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         ).await?;
         handles.push(tokio::spawn(async move { twitter.start().await }));
     }
-    for handle in handles {
-        handle.await.unwrap();
+
+    let cache = cached_embedding_model.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CACHE_STATS_INTERVAL);
+        // The first tick completes immediately, before anything was embedded
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            log_cache_stats(cache.stats());
+        }
+    });
+
+    tokio::select! {
+        results = futures::future::join_all(handles) => {
+            for result in results {
+                result.unwrap();
+            }
+        }
+        _ = tokio::signal::ctrl_c() => info!("Shutting down"),
     }
+    log_cache_stats(cached_embedding_model.stats());
     Ok(())
 }
\ No newline at end of file